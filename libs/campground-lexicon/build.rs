@@ -0,0 +1,44 @@
+use lexicon_codegen::Generator;
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// Refs that aren't generated from a document under gg.campground.
+const EXTERNAL_TYPES: &[(&str, &str)] = &[
+    ("blob", "::rsky_lexicon::com::atproto::repo::Blob"),
+    (
+        "com.atproto.label.defs#label",
+        "::rsky_lexicon::com::atproto::label::Label",
+    ),
+    (
+        "com.atproto.label.defs#selfLabels",
+        "::rsky_lexicon::com::atproto::label::SelfLabels",
+    ),
+    (
+        "app.bsky.graph.defs#listViewBasic",
+        "::rsky_lexicon::app::bsky::graph::ListViewBasic",
+    ),
+    // profileViewDetailed refers to the socials union by a name no document has
+    (
+        "gg.campground.actor.socialConnection",
+        "crate::gg::campground::socials::Socials",
+    ),
+];
+
+fn main() {
+    let lexicons = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("../../lexicons");
+    println!("cargo:rerun-if-changed={}", lexicons.display());
+
+    let mut generator = Generator::new();
+    generator
+        .load_dir(&lexicons)
+        .expect("Failed to load lexicons");
+    for (reference, path) in EXTERNAL_TYPES {
+        generator.external_type(reference, path);
+    }
+    let source = generator
+        .generate("gg.campground")
+        .unwrap_or_else(|error| panic!("Failed to generate lexicon types: {error}"));
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("lexicons.rs");
+    fs::write(out, source).expect("Failed to write generated lexicon types");
+}
@@ -1 +0,0 @@
-pub mod campground;
\ No newline at end of file
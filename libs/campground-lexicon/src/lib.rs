@@ -3,4 +3,35 @@ extern crate serde_derive;
 
 extern crate serde;
 
-pub mod gg;
+// Types are generated from the lexicon JSON by build.rs; edit the lexicons, not the output.
+include!(concat!(env!("OUT_DIR"), "/lexicons.rs"));
+
+#[cfg(test)]
+mod tests {
+    use crate::gg::campground::actor::{Activity, ActivityPlaying};
+    use serde_json::json;
+
+    #[test]
+    fn keeps_only_unlisted_union_members_as_json() {
+        let playing = json!({
+            "$type": "gg.campground.actor.defs#activityPlaying",
+            "name": "Chess",
+            "createdAt": "2024-05-01T12:00:00Z"
+        });
+        let activity: Activity = serde_json::from_value(playing.clone()).unwrap();
+        assert!(
+            matches!(&activity, Activity::Playing(ActivityPlaying { name, .. }) if name == "Chess")
+        );
+        assert_eq!(serde_json::to_value(&activity).unwrap(), playing);
+
+        // A member the lexicons list has to parse as that member
+        let mut invalid = playing.clone();
+        invalid.as_object_mut().unwrap().remove("createdAt");
+        assert!(serde_json::from_value::<Activity>(invalid).is_err());
+
+        let unlisted = json!({ "$type": "gg.example.activityDancing", "name": "Salsa" });
+        let activity: Activity = serde_json::from_value(unlisted.clone()).unwrap();
+        assert_eq!(activity, Activity::Unknown(unlisted.clone()));
+        assert_eq!(serde_json::to_value(&activity).unwrap(), unlisted);
+    }
+}
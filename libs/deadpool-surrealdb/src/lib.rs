@@ -157,6 +157,7 @@ mod rocket_pool {
         pub params: Option<serde_json::Value>,
     }
 
+    #[derive(Clone)]
     pub struct SurrealDBPool {
         pool: Pool<crate::Manager>,
     }
@@ -81,93 +81,75 @@ impl DIDAuditLogs {
             Some(cid) => cid,
             None => return Err(PLCError::MisorderedOperation),
         };
-        let index_of_prev = self.0.iter().position(|log| log.cid == cid.to_string());
-
-        if index_of_prev.is_none() {
-            return Err(PLCError::MisorderedOperation);
-        }
+        let index_of_prev = match self.0.iter().position(|log| log.cid == cid.to_string()) {
+            Some(index) => index,
+            None => return Err(PLCError::MisorderedOperation),
+        };
 
-        let ops_in_history = self.0[0..index_of_prev.unwrap()].to_vec();
-        let nullified = self.0[index_of_prev.unwrap()..self.0.len() - 1].to_vec();
+        // Everything after `prev` would be nullified by the proposed operation
+        let ops_in_history = self.0[0..=index_of_prev].to_vec();
+        let nullified = self.0[index_of_prev + 1..].to_vec();
 
-        let last_op = ops_in_history.last();
-        if last_op.is_none() {
-            return Err(PLCError::MisorderedOperation);
-        }
-        let last_op = last_op.unwrap();
+        let last_op = match ops_in_history.last() {
+            Some(op) => op,
+            None => return Err(PLCError::MisorderedOperation),
+        };
         if last_op.op_type() == PLCOperationType::Tombstone {
             return Err(PLCError::MisorderedOperation);
         }
 
         let last_op_normalized: SignedPLCOperation = match &last_op.operation {
-            PLCOperation::SignedGenesis(op) => op.normalize().unwrap().into(),
+            PLCOperation::SignedGenesis(op) => op.normalize()?.into(),
             PLCOperation::SignedPLC(op) => op.clone(),
             _ => {
                 unreachable!()
             }
         };
-
-        // No nullification is involved
-        let first_nullified = nullified.first();
-        if first_nullified.is_none() {
-            match last_op_normalized.verify_sig(None) {
-                Ok(_) => {
-                    return Ok(true);
-                }
-                Err(_) => {
-                    return Err(PLCError::InvalidSignature);
-                }
-            }
-        }
-        let first_nullified = first_nullified.unwrap();
-
-        let (_, disputed_key) = match last_op_normalized.verify_sig(None) {
-            Ok(result) => {
-                if !result.0 {
-                    return Err(PLCError::InvalidSignature);
-                }
-                result
-            }
-            Err(_) => {
-                return Err(PLCError::InvalidSignature);
+        let rotation_keys = last_op_normalized.unsigned.rotation_keys.clone();
+
+        // No nullification is involved, any of the current rotation keys may sign
+        let first_nullified = match nullified.first() {
+            Some(op) => op,
+            None => {
+                return match proposed.verify_sig(Some(rotation_keys)) {
+                    Ok((true, _)) => Ok(true),
+                    _ => Err(PLCError::InvalidSignature),
+                };
             }
         };
-        let disputed_key = disputed_key.unwrap();
 
-        let signer_index = last_op_normalized
-            .unsigned
-            .rotation_keys
-            .iter()
-            .position(|key| key == &disputed_key)
-            .unwrap();
-        let more_powerful_keys = last_op_normalized
-            .unsigned
-            .rotation_keys
-            .split_at(signer_index)
-            .1
-            .to_vec();
+        // Otherwise the proposed operation has to be signed by a key with higher priority than
+        // the one that signed the first operation it nullifies
+        let first_nullified_op: SignedPLCOperation = match &first_nullified.operation {
+            PLCOperation::SignedGenesis(op) => op.normalize()?.into(),
+            PLCOperation::SignedPLC(op) => op.clone(),
+            _ => {
+                unreachable!()
+            }
+        };
+        let disputed_key = match first_nullified_op.verify_sig(Some(rotation_keys.clone())) {
+            Ok((true, Some(key))) => key,
+            _ => return Err(PLCError::InvalidSignature),
+        };
+        let signer_index = match rotation_keys.iter().position(|key| key == &disputed_key) {
+            Some(index) => index,
+            None => return Err(PLCError::InvalidSignature),
+        };
+        let more_powerful_keys = rotation_keys[0..signer_index].to_vec();
 
         match proposed.verify_sig(Some(more_powerful_keys)) {
-            Ok(result) => {
-                if !result.0 {
-                    return Err(PLCError::InvalidSignature);
-                }
-            }
-            Err(_) => {
-                return Err(PLCError::InvalidSignature);
-            }
+            Ok((true, _)) => (),
+            _ => return Err(PLCError::InvalidSignature),
         }
 
-        if nullified.len() > 0 {
-            const RECOVERY_WINDOW: i64 = 72 * 60 * 60;
-            let local = Local::now().naive_utc();
-            let time_lapsed = local - first_nullified.created_at;
-            if time_lapsed.num_seconds() > RECOVERY_WINDOW {
-                return Err(PLCError::LateRecovery);
-            }
+        const RECOVERY_WINDOW: i64 = 72 * 60 * 60;
+        let local = Local::now().naive_utc();
+        let time_lapsed = local - first_nullified.created_at;
+        if time_lapsed.num_seconds() > RECOVERY_WINDOW {
+            return Err(PLCError::LateRecovery);
         }
 
-        return Ok(true);
+        Ok(true)
     }
 
     pub fn last(&self) -> Option<&AuditLog> {
@@ -203,4 +185,28 @@ mod tests {
         assert_eq!(audit_logs.last().unwrap().nullified, false);
         assert_eq!(audit_logs.last().unwrap().created_at, NaiveDateTime::parse_from_str("2023-11-09T21:49:10.793Z", "%Y-%m-%dT%H:%M:%S%.fZ").unwrap());
     }
+
+    #[test]
+    fn test_did_audit_log_assure_valid() {
+        let mut audit_logs = DIDAuditLogs::from_json(TEST_AUDIT_LOG).unwrap();
+        let proposed: SignedPLCOperation = match audit_logs.0.pop().unwrap().operation {
+            PLCOperation::SignedPLC(op) => op,
+            _ => unreachable!(),
+        };
+        assert!(audit_logs.assure_valid(proposed.clone()).unwrap());
+
+        let mut tampered = proposed.clone();
+        tampered.unsigned.also_known_as = vec!["at://example.com".to_string()];
+        assert!(matches!(
+            audit_logs.assure_valid(tampered),
+            Err(PLCError::InvalidSignature)
+        ));
+
+        let mut misordered = proposed;
+        misordered.unsigned.prev = Some("bafyreifn4pkect7nymne3sxkdg7tn7534msyxcjkshmzqtijmn3enyxm3q".to_string());
+        assert!(matches!(
+            audit_logs.assure_valid(misordered),
+            Err(PLCError::MisorderedOperation)
+        ));
+    }
 }
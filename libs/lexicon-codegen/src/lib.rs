@@ -0,0 +1,598 @@
+//! Generates serde types from AT Protocol lexicon documents.
+//!
+//! Every document under the requested NSID prefix becomes a Rust module named after its NSID
+//! authority (`gg.campground.actor.profile` lands in `gg::campground::actor`). Objects and
+//! records become structs, unions become `$type` tagged enums, closed string enums become unit
+//! enums and each record, query, procedure and subscription gets an `<NAME>_NSID` constant. Open
+//! unions get an `Unknown` variant that keeps members this version doesn't know about as JSON.
+//!
+//! Refs into documents outside the prefix are resolved through [`Generator::external_type`].
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+mod naming;
+
+use naming::{field_ident, pascal_case, snake_case, upper_snake_case};
+
+const DERIVES: &str = "#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]";
+
+#[derive(Default)]
+pub struct Generator {
+    documents: BTreeMap<String, Value>,
+    external: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct Module {
+    children: BTreeMap<String, Module>,
+    items: Vec<String>,
+    names: HashSet<String>,
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `.json` lexicon document under `dir`, recursively.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.load_dir(&path)?;
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                let document = serde_json::from_slice(&fs::read(&path)?)
+                    .map_err(|error| anyhow!("{}: {error}", path.display()))?;
+                self.add_document(document)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_document(&mut self, document: Value) -> Result<()> {
+        let id = match document.get("id").and_then(Value::as_str) {
+            Some(id) => id.to_string(),
+            None => bail!("Lexicon document is missing an id"),
+        };
+        if !document.get("defs").is_some_and(Value::is_object) {
+            bail!("Lexicon `{id}` has no defs");
+        }
+        if self.documents.insert(id.clone(), document).is_some() {
+            bail!("Lexicon `{id}` is defined twice");
+        }
+        Ok(())
+    }
+
+    /// Maps a ref (`nsid#def`, or `blob` for the blob type) that is not generated to an
+    /// existing Rust type path. Refs to documents that don't exist can be mapped the same way.
+    pub fn external_type(&mut self, reference: &str, path: &str) -> &mut Self {
+        self.external
+            .insert(reference.to_string(), path.to_string());
+        self
+    }
+
+    /// Renders every document whose NSID starts with `prefix` as one Rust source file of
+    /// nested modules, meant to be `include!`d at a crate root with serde_derive in scope.
+    pub fn generate(&self, prefix: &str) -> Result<String> {
+        let mut root = Module::default();
+        for (id, document) in &self.documents {
+            if !self.is_generated(id) || !in_prefix(id, prefix) {
+                continue;
+            }
+            let segments: Vec<&str> = id.split('.').collect();
+            let module = segments[..segments.len() - 1]
+                .iter()
+                .fold(&mut root, |module, segment| {
+                    module.children.entry(snake_case(segment)).or_default()
+                });
+            let mut emitter = Emitter {
+                generator: self,
+                prefix,
+                id,
+                module: &segments[..segments.len() - 1],
+                items: Vec::new(),
+                names: &mut module.names,
+            };
+            emitter.document(document)?;
+            let items = emitter.items;
+            module.items.extend(items);
+        }
+        if root.children.is_empty() {
+            bail!("No lexicons found under `{prefix}`");
+        }
+
+        let mut out = String::from(
+            "// @generated by lexicon-codegen from the lexicon JSON documents. Do not edit.\n",
+        );
+        for (name, module) in &root.children {
+            out.push('\n');
+            out.push_str("#[allow(clippy::large_enum_variant)]\n");
+            render_module(&mut out, name, module, 0);
+        }
+        Ok(out)
+    }
+
+    /// Only documents that weren't mapped onto external types get generated.
+    fn is_generated(&self, id: &str) -> bool {
+        !self
+            .external
+            .keys()
+            .any(|reference| reference.split('#').next() == Some(id))
+    }
+
+    fn def(&self, id: &str, name: &str) -> Option<&Value> {
+        self.documents.get(id)?.get("defs")?.get(name)
+    }
+}
+
+fn render_module(out: &mut String, name: &str, module: &Module, depth: usize) {
+    let indent = "    ".repeat(depth);
+    out.push_str(&format!("{indent}pub mod {name} {{\n"));
+    let mut first = true;
+    for item in &module.items {
+        if !first {
+            out.push('\n');
+        }
+        first = false;
+        for line in item.lines() {
+            if line.is_empty() {
+                out.push('\n');
+            } else {
+                out.push_str(&format!("{indent}    {line}\n"));
+            }
+        }
+    }
+    for (child_name, child) in &module.children {
+        if !first {
+            out.push('\n');
+        }
+        first = false;
+        render_module(out, child_name, child, depth + 1);
+    }
+    out.push_str(&format!("{indent}}}\n"));
+}
+
+/// Emits the items of a single lexicon document.
+struct Emitter<'a, 'm> {
+    generator: &'a Generator,
+    prefix: &'a str,
+    id: &'a str,
+    module: &'a [&'a str],
+    items: Vec<String>,
+    /// Type names already taken in the target module.
+    names: &'m mut HashSet<String>,
+}
+
+impl<'a> Emitter<'a, '_> {
+    fn document(&mut self, document: &Value) -> Result<()> {
+        let defs = document["defs"].as_object().unwrap();
+        if let Some(main) = defs.get("main") {
+            if matches!(
+                main["type"].as_str(),
+                Some("record" | "query" | "procedure" | "subscription")
+            ) {
+                let name = upper_snake_case(self.last_segment());
+                self.items
+                    .push(format!("pub const {name}_NSID: &str = \"{}\";\n", self.id));
+            }
+        }
+        // Emit main first, then the rest of the defs in declaration order.
+        let mut names: Vec<&String> = defs.keys().collect();
+        names.sort_by_key(|name| name.as_str() != "main");
+        for name in names {
+            self.def(name, &defs[name.as_str()])
+                .map_err(|error| anyhow!("{}#{name}: {error}", self.id))?;
+        }
+        Ok(())
+    }
+
+    fn last_segment(&self) -> &'a str {
+        self.id.rsplit('.').next().unwrap()
+    }
+
+    fn def(&mut self, name: &str, def: &Value) -> Result<()> {
+        let type_name = type_name(self.id, name);
+        match def["type"].as_str() {
+            Some("record") => {
+                let attrs = format!(
+                    "#[serde(tag = \"$type\")]\n#[serde(rename = \"{}\")]\n",
+                    self.id
+                );
+                self.object(&type_name, &def["record"], description(def), &attrs)?;
+            }
+            Some("object") => {
+                self.object(&type_name, def, description(def), "")?;
+            }
+            Some("query" | "procedure" | "subscription") => {
+                if def.get("parameters").is_some() {
+                    self.object(&format!("{type_name}Params"), &def["parameters"], None, "")?;
+                }
+                for (body, suffix) in [
+                    ("input", "Input"),
+                    ("output", "Output"),
+                    ("message", "Message"),
+                ] {
+                    if let Some(schema) = def.get(body).and_then(|body| body.get("schema")) {
+                        self.body(&format!("{type_name}{suffix}"), schema)?;
+                    }
+                }
+            }
+            Some("union") => self.union(&type_name, def)?,
+            Some("string") if def.get("enum").is_some() => self.string_enum(&type_name, def)?,
+            Some("token") => {
+                let value = format!("{}#{name}", self.id);
+                self.items.push(format!(
+                    "{}pub const {}: &str = \"{value}\";\n",
+                    doc_comment(description(def)),
+                    upper_snake_case(name)
+                ));
+            }
+            Some(_) => {
+                let rust_type = self.field_type(&type_name, "", def)?;
+                self.push_alias(&type_name, description(def), &rust_type)?;
+            }
+            None => bail!("Def has no type"),
+        }
+        Ok(())
+    }
+
+    /// Emits a query/procedure/subscription body schema.
+    fn body(&mut self, type_name: &str, schema: &Value) -> Result<()> {
+        match schema["type"].as_str() {
+            Some("object") => self.object(type_name, schema, description(schema), ""),
+            Some("union") => self.union(type_name, schema),
+            _ => {
+                let rust_type = self.field_type(type_name, "", schema)?;
+                self.push_alias(type_name, description(schema), &rust_type)
+            }
+        }
+    }
+
+    fn claim(&mut self, type_name: &str) -> Result<()> {
+        if !self.names.insert(type_name.to_string()) {
+            bail!("Generated type name `{type_name}` collides with another def");
+        }
+        Ok(())
+    }
+
+    fn push_alias(&mut self, type_name: &str, doc: Option<&str>, rust_type: &str) -> Result<()> {
+        self.claim(type_name)?;
+        self.items.push(format!(
+            "{}pub type {type_name} = {rust_type};\n",
+            doc_comment(doc)
+        ));
+        Ok(())
+    }
+
+    fn object(
+        &mut self,
+        type_name: &str,
+        schema: &Value,
+        doc: Option<&str>,
+        attrs: &str,
+    ) -> Result<()> {
+        self.claim(type_name)?;
+        let empty = Map::new();
+        let properties = schema["properties"].as_object().unwrap_or(&empty);
+        let required = string_list(&schema["required"]);
+
+        let slot = self.items.len();
+        self.items.push(String::new());
+        let mut fields = String::new();
+        for (name, property) in properties {
+            let mut rust_type = self.field_type(type_name, name, property)?;
+            let optional = !required.contains(&name.as_str());
+            if optional {
+                rust_type = format!("Option<{rust_type}>");
+            }
+            fields.push_str(&indent(&doc_comment(description(property))));
+            let ident = field_ident(name);
+            match (ident.trim_start_matches("r#") != name, optional) {
+                (true, true) => fields.push_str(&format!(
+                    "    #[serde(rename = \"{name}\", skip_serializing_if = \"Option::is_none\")]\n"
+                )),
+                (true, false) => fields.push_str(&format!("    #[serde(rename = \"{name}\")]\n")),
+                (false, true) => {
+                    fields.push_str("    #[serde(skip_serializing_if = \"Option::is_none\")]\n")
+                }
+                (false, false) => {}
+            }
+            fields.push_str(&format!("    pub {ident}: {rust_type},\n"));
+        }
+        self.items[slot] = format!(
+            "{}{DERIVES}\n{attrs}pub struct {type_name} {{\n{fields}}}\n",
+            doc_comment(doc)
+        );
+        Ok(())
+    }
+
+    fn union(&mut self, type_name: &str, schema: &Value) -> Result<()> {
+        self.claim(type_name)?;
+        let mut variants = String::new();
+        let mut arms = Vec::new();
+        let mut seen = HashSet::new();
+        for reference in string_list(&schema["refs"]) {
+            let (id, def) = resolve(self.id, reference);
+            let tag = if def == "main" {
+                id.to_string()
+            } else {
+                format!("{id}#{def}")
+            };
+            let mut variant = if def == "main" {
+                pascal_case(id.rsplit('.').next().unwrap())
+            } else {
+                pascal_case(def)
+            };
+            // `activity` -> `#activityCustom` becomes `Activity::Custom`.
+            if let Some(rest) = variant.strip_prefix(type_name) {
+                if rest.starts_with(|c: char| c.is_ascii_uppercase()) {
+                    variant = rest.to_string();
+                }
+            }
+            if !seen.insert(variant.clone()) {
+                bail!("Union `{type_name}` has two variants named `{variant}`");
+            }
+            let rust_type = self.ref_type(reference)?;
+            variants.push_str(&format!(
+                "    #[serde(rename = \"{tag}\")]\n    {variant}({rust_type}),\n"
+            ));
+            arms.push(format!(
+                "            Some(\"{tag}\") => ::serde_json::from_value(value).map(Self::{variant}),"
+            ));
+        }
+        let doc = doc_comment(description(schema));
+        if schema["closed"].as_bool() == Some(true) {
+            self.items.push(format!(
+                "{doc}{DERIVES}\n#[serde(tag = \"$type\")]\npub enum {type_name} {{\n{variants}}}\n"
+            ));
+            return Ok(());
+        }
+        if !seen.insert("Unknown".to_string()) {
+            bail!("Union `{type_name}` has a variant named `Unknown`");
+        }
+        variants.push_str("    #[serde(untagged)]\n    Unknown(::serde_json::Value),\n");
+        self.items.push(format!(
+            "{doc}{}\n#[serde(tag = \"$type\")]\n#[non_exhaustive]\npub enum {type_name} {{\n{variants}}}\n\n{}",
+            DERIVES.replace(", Deserialize", ""),
+            open_union_deserialize(type_name, &arms)
+        ));
+        Ok(())
+    }
+
+    fn string_enum(&mut self, type_name: &str, schema: &Value) -> Result<()> {
+        self.claim(type_name)?;
+        let default = schema["default"].as_str();
+        let mut variants = String::new();
+        for value in string_list(&schema["enum"]) {
+            if default == Some(value) {
+                variants.push_str("    #[default]\n");
+            }
+            variants.push_str(&format!(
+                "    #[serde(rename = \"{value}\")]\n    {},\n",
+                pascal_case(value)
+            ));
+        }
+        let derives = match default {
+            Some(_) => DERIVES.replace("PartialEq", "PartialEq, Eq, Hash, Default"),
+            None => DERIVES.replace("PartialEq", "PartialEq, Eq, Hash"),
+        };
+        self.items.push(format!(
+            "{}{derives}\npub enum {type_name} {{\n{variants}}}\n",
+            doc_comment(description(schema))
+        ));
+        Ok(())
+    }
+
+    /// Returns the Rust type of a property, emitting inline unions and objects as
+    /// `<Owner><Field>` items.
+    fn field_type(&mut self, owner: &str, field: &str, schema: &Value) -> Result<String> {
+        Ok(match schema["type"].as_str() {
+            Some("string") => match schema["format"].as_str() {
+                Some("datetime") => "::chrono::DateTime<::chrono::Utc>".to_string(),
+                _ => "String".to_string(),
+            },
+            Some("integer") => "i64".to_string(),
+            Some("boolean") => "bool".to_string(),
+            Some("unknown" | "bytes" | "cid-link") => "::serde_json::Value".to_string(),
+            Some("blob") => match self.generator.external.get("blob") {
+                Some(path) => path.clone(),
+                None => bail!("No Rust type registered for `blob`"),
+            },
+            Some("array") => {
+                format!("Vec<{}>", self.field_type(owner, field, &schema["items"])?)
+            }
+            Some("ref") => match schema["ref"].as_str() {
+                Some(reference) => self.ref_type(reference)?,
+                None => bail!("Ref without a target"),
+            },
+            Some("union") => {
+                let name = format!("{owner}{}", pascal_case(field));
+                self.union(&name, schema)?;
+                name
+            }
+            Some("object") => {
+                let name = format!("{owner}{}", pascal_case(field));
+                self.object(&name, schema, description(schema), "")?;
+                name
+            }
+            Some(other) => bail!("Unsupported type `{other}`"),
+            None => bail!("Property `{field}` has no type"),
+        })
+    }
+
+    fn ref_type(&self, reference: &str) -> Result<String> {
+        let (id, def) = resolve(self.id, reference);
+        let full = format!("{id}#{def}");
+        if let Some(path) = [reference, &full]
+            .iter()
+            .find_map(|key| self.generator.external.get(*key))
+        {
+            return Ok(path.clone());
+        }
+        let target = match self.generator.def(id, def) {
+            Some(target) if self.generator.is_generated(id) && in_prefix(id, self.prefix) => target,
+            _ => bail!("No Rust type for ref `{full}`"),
+        };
+        // Refs to tokens and plain strings (knownValues) stay strings.
+        match target["type"].as_str() {
+            Some("token") => return Ok("String".to_string()),
+            Some("string") if target.get("enum").is_none() => return Ok("String".to_string()),
+            _ => {}
+        }
+        let segments: Vec<&str> = id.split('.').collect();
+        let module = &segments[..segments.len() - 1];
+        let name = type_name(id, def);
+        if module == self.module {
+            Ok(name)
+        } else {
+            let path: Vec<String> = module.iter().map(|segment| snake_case(segment)).collect();
+            Ok(format!("crate::{}::{name}", path.join("::")))
+        }
+    }
+}
+
+/// `gg.campground.actor.defs#profileView` -> `ProfileView`,
+/// `gg.campground.actor.profile#main` -> `Profile`,
+/// `app.bsky.feed.post#replyRef` -> `PostReplyRef`.
+fn type_name(id: &str, def: &str) -> String {
+    let last = id.rsplit('.').next().unwrap();
+    match (last, def) {
+        ("defs", _) => pascal_case(def),
+        (_, "main") => pascal_case(last),
+        _ => format!("{}{}", pascal_case(last), pascal_case(def)),
+    }
+}
+
+fn in_prefix(id: &str, prefix: &str) -> bool {
+    id == prefix || id.starts_with(&format!("{prefix}."))
+}
+
+/// Splits a ref into its document id and def name, relative to the document `id`.
+fn resolve<'r>(id: &'r str, reference: &'r str) -> (&'r str, &'r str) {
+    match reference.split_once('#') {
+        Some(("", def)) => (id, def),
+        Some((other, def)) => (other, def),
+        None => (reference, "main"),
+    }
+}
+
+fn description(schema: &Value) -> Option<&str> {
+    schema["description"].as_str()
+}
+
+/// A derived `Deserialize` would also fall back to `Unknown` for a listed member that fails to
+/// parse, so open unions only keep a `$type` the lexicons don't list as JSON.
+fn open_union_deserialize(type_name: &str, arms: &[String]) -> String {
+    let mut lines = vec![
+        format!("impl<'de> ::serde::Deserialize<'de> for {type_name} {{"),
+        "    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {".to_string(),
+        "        let value = <::serde_json::Value as ::serde::Deserialize>::deserialize(deserializer)?;".to_string(),
+        "        let member = match value.get(\"$type\").and_then(::serde_json::Value::as_str) {".to_string(),
+    ];
+    lines.extend_from_slice(arms);
+    lines.extend(
+        [
+            "            _ => return Ok(Self::Unknown(value)),",
+            "        };",
+            "        member.map_err(::serde::de::Error::custom)",
+            "    }",
+            "}",
+        ]
+        .map(String::from),
+    );
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+fn doc_comment(doc: Option<&str>) -> String {
+    match doc {
+        Some(doc) => doc.lines().map(|line| format!("/// {line}\n")).collect(),
+        None => String::new(),
+    }
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("    {line}\n")).collect()
+}
+
+fn string_list(value: &Value) -> Vec<&str> {
+    value
+        .as_array()
+        .map(|values| values.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn generates_records_unions_and_enums() {
+        let mut generator = Generator::new();
+        generator
+            .add_document(json!({
+                "lexicon": 1,
+                "id": "gg.example.thing.defs",
+                "defs": {
+                    "mood": { "type": "string", "enum": ["happy", "sad"], "default": "happy" },
+                    "shape": { "type": "union", "refs": ["#shapeCircle"] },
+                    "shapeCircle": {
+                        "type": "object",
+                        "required": ["radius"],
+                        "properties": { "radius": { "type": "integer" } }
+                    }
+                }
+            }))
+            .unwrap();
+        generator
+            .add_document(json!({
+                "lexicon": 1,
+                "id": "gg.example.thing.post",
+                "defs": {
+                    "main": {
+                        "type": "record",
+                        "key": "tid",
+                        "record": {
+                            "type": "object",
+                            "required": ["createdAt"],
+                            "properties": {
+                                "mood": { "type": "ref", "ref": "gg.example.thing.defs#mood" },
+                                "labels": { "type": "union", "refs": ["com.atproto.label.defs#selfLabels"] },
+                                "createdAt": { "type": "string", "format": "datetime" }
+                            }
+                        }
+                    }
+                }
+            }))
+            .unwrap();
+        generator.external_type("com.atproto.label.defs#selfLabels", "::labels::SelfLabels");
+
+        let source = generator.generate("gg.example").unwrap();
+        assert!(source.contains("pub mod gg {\n    pub mod example {\n        pub mod thing {"));
+        assert!(source.contains("pub const POST_NSID: &str = \"gg.example.thing.post\";"));
+        assert!(source.contains(
+            "#[serde(rename = \"gg.example.thing.post\")]\n            pub struct Post {"
+        ));
+        assert!(source.contains("pub mood: Option<Mood>,"));
+        assert!(source.contains("pub created_at: ::chrono::DateTime<::chrono::Utc>,"));
+        assert!(source.contains("pub enum PostLabels {"));
+        assert!(source.contains("SelfLabels(::labels::SelfLabels),"));
+        assert!(source.contains("#[serde(rename = \"gg.example.thing.defs#shapeCircle\")]\n                Circle(ShapeCircle),"));
+        assert!(source.contains(
+            "#[serde(untagged)]\n                Unknown(::serde_json::Value),\n            }"
+        ));
+        assert!(source.contains("impl<'de> ::serde::Deserialize<'de> for PostLabels {"));
+        assert!(!source.contains(
+            "#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]\n            #[serde(tag = \"$type\")]\n            #[non_exhaustive]"
+        ));
+        assert!(source.contains(
+            "#[default]\n                #[serde(rename = \"happy\")]\n                Happy,"
+        ));
+
+        assert!(generator.generate("gg.missing").is_err());
+    }
+}
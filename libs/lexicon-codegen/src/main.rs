@@ -0,0 +1,51 @@
+use anyhow::{bail, Result};
+use lexicon_codegen::Generator;
+use std::path::PathBuf;
+use std::{env, fs};
+
+const USAGE: &str = "Usage: lexicon-codegen <LEXICON_DIR> <NSID_PREFIX> [--extern <REF>=<RUST_PATH>]... [-o <FILE>]";
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let mut positional = Vec::new();
+    let mut externals = Vec::new();
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extern" => match args
+                .next()
+                .as_deref()
+                .and_then(|value| value.split_once('='))
+            {
+                Some((reference, path)) => {
+                    externals.push((reference.to_string(), path.to_string()))
+                }
+                None => bail!("--extern expects <REF>=<RUST_PATH>\n{USAGE}"),
+            },
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => bail!("{arg} expects a file\n{USAGE}"),
+            },
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let [dir, prefix] = positional.as_slice() else {
+        bail!("{USAGE}");
+    };
+
+    let mut generator = Generator::new();
+    generator.load_dir(&PathBuf::from(dir))?;
+    for (reference, path) in &externals {
+        generator.external_type(reference, path);
+    }
+    let source = generator.generate(prefix)?;
+    match output {
+        Some(path) => fs::write(path, source)?,
+        None => print!("{source}"),
+    }
+    Ok(())
+}
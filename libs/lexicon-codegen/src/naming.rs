@@ -0,0 +1,94 @@
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "do", "dyn", "else", "enum",
+    "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static", "struct",
+    "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where",
+    "while", "yield",
+];
+
+/// Splits a lexicon name into words on punctuation and camelCase boundaries.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// `profileViewBasic` -> `ProfileViewBasic`
+pub fn pascal_case(name: &str) -> String {
+    let mut out: String = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, 'V');
+    }
+    out
+}
+
+/// `displayName` -> `display_name`
+pub fn snake_case(name: &str) -> String {
+    let out = words(name)
+        .iter()
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{out}")
+    } else {
+        out
+    }
+}
+
+/// `getProfiles` -> `GET_PROFILES`
+pub fn upper_snake_case(name: &str) -> String {
+    snake_case(name).to_ascii_uppercase()
+}
+
+/// Makes a snake_case name usable as a Rust field identifier.
+pub fn field_ident(name: &str) -> String {
+    let snake = snake_case(name);
+    match snake.as_str() {
+        "self" | "super" | "crate" | "Self" => format!("{snake}_"),
+        _ if KEYWORDS.contains(&snake.as_str()) => format!("r#{snake}"),
+        _ => snake,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_lexicon_names() {
+        assert_eq!(pascal_case("profileViewBasic"), "ProfileViewBasic");
+        assert_eq!(pascal_case("!no-unauthenticated"), "NoUnauthenticated");
+        assert_eq!(snake_case("displayName"), "display_name");
+        assert_eq!(snake_case("user_id"), "user_id");
+        assert_eq!(upper_snake_case("getProfiles"), "GET_PROFILES");
+        assert_eq!(field_ident("type"), "r#type");
+    }
+}
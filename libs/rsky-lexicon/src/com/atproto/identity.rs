@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ResolveHandleOutput {
@@ -12,3 +13,48 @@ pub struct UpdateHandleInput {
     /// The new handle.
     pub handle: String,
 }
+
+/// Signs a PLC operation to update some value(s) in the requesting DID's document.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SignPlcOperationInput {
+    /// A token received through com.atproto.identity.requestPlcOperationSignature
+    pub token: Option<String>,
+    #[serde(rename = "rotationKeys")]
+    pub rotation_keys: Option<Vec<String>>,
+    #[serde(rename = "alsoKnownAs")]
+    pub also_known_as: Option<Vec<String>>,
+    #[serde(rename = "verificationMethods")]
+    pub verification_methods: Option<Value>,
+    pub services: Option<Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SignPlcOperationOutput {
+    /// A signed DID PLC operation.
+    pub operation: Value,
+}
+
+/// Validates a PLC operation to ensure that it doesn't violate a service's constraints or get the
+/// identity into a bad state, then submits it to the PLC registry
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SubmitPlcOperationInput {
+    pub operation: Value,
+}
+
+/// Describe the credentials that should be included in the DID doc of an account that is
+/// migrating to this service.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GetRecommendedDidCredentialsOutput {
+    /// Recommended rotation keys for PLC dids. Should be undefined (or ignored) for did:webs.
+    #[serde(rename = "rotationKeys")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation_keys: Option<Vec<String>>,
+    #[serde(rename = "alsoKnownAs")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub also_known_as: Option<Vec<String>>,
+    #[serde(rename = "verificationMethods")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_methods: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub services: Option<Value>,
+}
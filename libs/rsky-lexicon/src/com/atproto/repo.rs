@@ -170,12 +170,49 @@ pub struct ListRecordsOutput {
 pub struct CreateRecordOutput {
     pub cid: String,
     pub uri: String,
+    /// "valid" or "unknown", absent when validation was skipped.
+    #[serde(rename = "validationStatus", skip_serializing_if = "Option::is_none")]
+    pub validation_status: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct PutRecordOutput {
     pub cid: String,
     pub uri: String,
+    /// "valid" or "unknown", absent when validation was skipped.
+    #[serde(rename = "validationStatus", skip_serializing_if = "Option::is_none")]
+    pub validation_status: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CommitMeta {
+    pub cid: String,
+    pub rev: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ApplyWritesOutput {
+    pub commit: CommitMeta,
+    pub results: Vec<ApplyWritesOutputRefResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+pub enum ApplyWritesOutputRefResult {
+    #[serde(rename = "com.atproto.repo.applyWrites#createResult")]
+    Create(RefWriteResult),
+    #[serde(rename = "com.atproto.repo.applyWrites#updateResult")]
+    Update(RefWriteResult),
+    #[serde(rename = "com.atproto.repo.applyWrites#deleteResult")]
+    Delete {},
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RefWriteResult {
+    pub uri: String,
+    pub cid: String,
+    #[serde(rename = "validationStatus", skip_serializing_if = "Option::is_none")]
+    pub validation_status: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -48,6 +48,20 @@ pub struct CreateAccountInput {
 pub struct CreateAppPasswordInput {
     /// A short name for the App Password, to help distinguish them.
     pub name: String,
+    /// If an app password has 'privileged' access to possibly sensitive account state, such as
+    /// chat. Meant for use with trusted clients.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privileged: Option<bool>,
+    /// Keep the app password from writing to the repo or making proxied procedure calls.
+    #[serde(rename = "readOnly", skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    /// NSIDs of the collections the app password may write records to. An NSID ending in `*`
+    /// matches every NSID that starts with what comes before it. Any collection if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+    /// When the app password stops working.
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
 }
 
 /// Create an authentication session.
@@ -56,6 +70,8 @@ pub struct CreateSessionInput {
     /// Handle or other identifier supported by the server for the authenticating user.
     pub identifier: String,
     pub password: String,
+    #[serde(rename = "authFactorToken", skip_serializing_if = "Option::is_none")]
+    pub auth_factor_token: Option<String>,
 }
 
 /// Delete an actor's account with a token and password. Can only be called after
@@ -104,6 +120,13 @@ pub struct RevokeAppPasswordInput {
     pub name: String,
 }
 
+/// Reserve a repo signing key, for use with account creation.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReserveSigningKeyInput {
+    /// The DID to reserve a key for.
+    pub did: Option<String>,
+}
+
 /// Update an account's email.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct UpdateEmailInput {
@@ -111,6 +134,8 @@ pub struct UpdateEmailInput {
     /// Requires a token from com.atproto.sever.requestEmailUpdate
     /// if the account's email has been confirmed.
     pub token: Option<String>,
+    #[serde(rename = "emailAuthFactor", skip_serializing_if = "Option::is_none")]
+    pub email_auth_factor: Option<bool>,
 }
 
 // Outputs
@@ -137,6 +162,14 @@ pub struct CreateAppPasswordOutput {
     pub password: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privileged: Option<bool>,
+    #[serde(rename = "readOnly", skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -164,6 +197,8 @@ pub struct CreateSessionOutput {
     pub email: Option<String>,
     #[serde(rename = "emailConfirmed", skip_serializing_if = "Option::is_none")]
     pub email_confirmed: Option<bool>,
+    #[serde(rename = "emailAuthFactor", skip_serializing_if = "Option::is_none")]
+    pub email_auth_factor: Option<bool>,
 }
 
 /// Get information about the current auth session. Requires auth.
@@ -176,6 +211,8 @@ pub struct GetSessionOutput {
     pub email_confirmed: Option<bool>,
     #[serde(rename = "didDoc", skip_serializing_if = "Option::is_none")]
     pub did_doc: Option<String>,
+    #[serde(rename = "emailAuthFactor", skip_serializing_if = "Option::is_none")]
+    pub email_auth_factor: Option<bool>,
 }
 
 /// Describes the server's account creation requirements and capabilities. Implemented by PDS.
@@ -200,6 +237,14 @@ pub struct DescribeServerOutput {
     pub did: String,
 }
 
+/// Reserve a repo signing key, for use with account creation.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReserveSigningKeyOutput {
+    /// The public key for the reserved signing key, in did:key serialization.
+    #[serde(rename(deserialize = "signingKey", serialize = "signingKey"))]
+    pub signing_key: String,
+}
+
 /// Get a signed token on behalf of the requesting DID for the requested service.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct GetServiceAuthOutput {
@@ -300,4 +345,15 @@ pub struct AppPassword {
     pub name: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privileged: Option<bool>,
+    #[serde(rename = "readOnly", skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// When the app password was last used to sign in or refresh a session.
+    #[serde(rename = "lastUsedAt", skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<String>,
 }
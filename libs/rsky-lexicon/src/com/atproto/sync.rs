@@ -13,6 +13,13 @@ pub struct SubscribeReposCommitOperation {
     pub path: String,
     pub action: String,
     pub cid: Option<Cid>,
+    /// For updates and deletes, the previous record CID. Required for inductive firehose.
+    #[serde(
+        default = "default_resource",
+        deserialize_with = "deserialize_option_cid_v1",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub prev: Option<Cid>,
 }
 
 /// Represents an update of repository state. Note that empty commits are allowed,
@@ -32,6 +39,15 @@ pub struct SubscribeReposCommit {
         deserialize_with = "deserialize_option_cid_v1"
     )]
     pub prev: Option<Cid>,
+    /// The root CID of the MST tree for the previous commit from this repo. Required for
+    /// inductive firehose.
+    #[serde(
+        rename = "prevData",
+        default = "default_resource",
+        deserialize_with = "deserialize_option_cid_v1",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub prev_data: Option<Cid>,
     pub rev: String,
     pub since: Option<String>,
     #[serde(with = "serde_bytes")]
@@ -40,6 +56,22 @@ pub struct SubscribeReposCommit {
     pub blobs: Vec<String>,
 }
 
+/// Updates the repo to a new state, without necessarily including that state on the firehose.
+/// Used to recover from broken commit streams, data loss incidents, or in situations where
+/// upstream host does not know recent state of the repository.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscribeReposSync {
+    pub seq: i64,
+    pub did: String,
+    /// CAR file containing the commit, as a block. The CAR header must include the commit block
+    /// CID as the first 'root'.
+    #[serde(with = "serde_bytes")]
+    pub blocks: Vec<u8>,
+    /// The rev of the commit. This value must match that in the commit object.
+    pub rev: String,
+    pub time: DateTime<Utc>,
+}
+
 /// Get the current commit CID & revision of the specified repo. Does not require auth.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GetLatestCommitOutput {
@@ -141,9 +173,19 @@ pub struct SubscribeReposTombstone {
     pub time: DateTime<Utc>,
 }
 
+/// An informational message from the server, such as `OutdatedCursor` when the requested cursor
+/// is older than the events it still has.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscribeReposInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
 #[derive(Debug)]
 pub enum SubscribeRepos {
     Commit(SubscribeReposCommit),
+    Sync(SubscribeReposSync),
     Identity(SubscribeReposIdentity),
     Account(SubscribeReposAccount),
     Handle(SubscribeReposHandle),
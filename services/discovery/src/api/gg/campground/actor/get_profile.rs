@@ -0,0 +1,48 @@
+use crate::api::gg::campground::actor::{get_actors, hydrate_profiles};
+use crate::api::{xrpc_error, XrpcError};
+use crate::database::DiscoveryDb;
+use anyhow::Result;
+use campground_lexicon::gg::campground::actor::ProfileViewDetailed;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+
+async fn inner_get_profile(
+    db: &Surreal<Client>,
+    actor: String,
+) -> Result<Option<ProfileViewDetailed>> {
+    let actors = get_actors(db, &vec![actor]).await?;
+    Ok(hydrate_profiles(db, actors).await?.pop())
+}
+
+#[rocket::get("/xrpc/gg.campground.actor.getProfile?<actor>")]
+pub async fn get_profile(
+    actor: String,
+    db: Connection<DiscoveryDb>,
+) -> Result<Json<ProfileViewDetailed>, XrpcError> {
+    if actor.is_empty() {
+        return Err(xrpc_error(
+            Status::BadRequest,
+            "InvalidRequest",
+            "Missing actor".to_string(),
+        ));
+    }
+    match inner_get_profile(&db, actor.clone()).await {
+        Ok(Some(profile)) => Ok(Json(profile)),
+        Ok(None) => Err(xrpc_error(
+            Status::BadRequest,
+            "InvalidRequest",
+            format!("Profile not found: `{actor}`"),
+        )),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            Err(xrpc_error(
+                Status::InternalServerError,
+                "InternalServerError",
+                "Internal error".to_string(),
+            ))
+        }
+    }
+}
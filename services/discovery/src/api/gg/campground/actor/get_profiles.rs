@@ -0,0 +1,48 @@
+use crate::api::gg::campground::actor::{get_actors, hydrate_profiles};
+use crate::api::{xrpc_error, XrpcError};
+use crate::database::DiscoveryDb;
+use anyhow::Result;
+use campground_lexicon::gg::campground::actor::GetProfilesOutput;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+
+const MAX_ACTORS: usize = 25;
+
+async fn inner_get_profiles(
+    db: &Surreal<Client>,
+    actors: Vec<String>,
+) -> Result<GetProfilesOutput> {
+    let actors = get_actors(db, &actors).await?;
+    Ok(GetProfilesOutput {
+        profiles: hydrate_profiles(db, actors).await?,
+    })
+}
+
+/// Unknown actors are left out of the output rather than failing the whole request.
+#[rocket::get("/xrpc/gg.campground.actor.getProfiles?<actors>")]
+pub async fn get_profiles(
+    actors: Vec<String>,
+    db: Connection<DiscoveryDb>,
+) -> Result<Json<GetProfilesOutput>, XrpcError> {
+    if actors.is_empty() || actors.len() > MAX_ACTORS {
+        return Err(xrpc_error(
+            Status::BadRequest,
+            "InvalidRequest",
+            format!("Between 1 and {MAX_ACTORS} actors are required"),
+        ));
+    }
+    match inner_get_profiles(&db, actors).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            Err(xrpc_error(
+                Status::InternalServerError,
+                "InternalServerError",
+                "Internal error".to_string(),
+            ))
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::REGISTRY_CONFIG;
+use crate::database::models;
+use anyhow::Result;
+use campground_lexicon::gg::campground::actor::ProfileViewDetailed;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::{RecordId, Surreal};
+
+pub mod get_profile;
+pub mod get_profiles;
+
+pub const INVALID_HANDLE: &str = "handle.invalid";
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![get_profile::get_profile, get_profiles::get_profiles]
+}
+
+/// Looks up indexed actors by DID or (lowercased) handle, preserving the order they were asked
+/// for in. Actors that haven't been indexed are left out.
+pub async fn get_actors(db: &Surreal<Client>, actors: &Vec<String>) -> Result<Vec<models::Actor>> {
+    let (dids, handles): (Vec<String>, Vec<String>) = actors
+        .iter()
+        .map(|actor| actor.to_lowercase())
+        .partition(|actor| actor.starts_with("did:"));
+    let found: Vec<models::Actor> = db
+        .query("SELECT * FROM actor WHERE string::lowercase(did) IN $dids OR handle IN $handles")
+        .bind(("dids", dids))
+        .bind(("handles", handles))
+        .await?
+        .take(0)?;
+
+    Ok(actors
+        .iter()
+        .filter_map(|actor| {
+            let actor = actor.to_lowercase();
+            found
+                .iter()
+                .find(|found| {
+                    found.did.to_lowercase() == actor || found.handle.as_ref() == Some(&actor)
+                })
+                .cloned()
+        })
+        .collect())
+}
+
+/// Builds profile views for `actors` out of their indexed profile and status records.
+pub async fn hydrate_profiles(
+    db: &Surreal<Client>,
+    actors: Vec<models::Actor>,
+) -> Result<Vec<ProfileViewDetailed>> {
+    let creators: Vec<RecordId> = actors
+        .iter()
+        .map(|actor| RecordId::from(("actor", actor.did.as_str())))
+        .collect();
+    let mut res = db
+        .query("SELECT * FROM profile WHERE creator IN $creators")
+        .query("SELECT * FROM status WHERE creator IN $creators")
+        .bind(("creators", creators.clone()))
+        .await?;
+    let profiles: Vec<models::Profile> = res.take(0)?;
+    let statuses: Vec<models::Status> = res.take(1)?;
+
+    Ok(actors
+        .into_iter()
+        .zip(creators)
+        .map(|(actor, creator)| {
+            let profile = profiles.iter().find(|profile| profile.creator == creator);
+            let status = statuses.iter().find(|status| status.creator == creator);
+            let avatar = profile
+                .and_then(|profile| profile.avatar_cid.as_ref())
+                .map(|cid| REGISTRY_CONFIG.blob_url(&actor.did, cid));
+            let banner = profile
+                .and_then(|profile| profile.banner_cid.as_ref())
+                .map(|cid| REGISTRY_CONFIG.blob_url(&actor.did, cid));
+            ProfileViewDetailed {
+                handle: actor.handle.unwrap_or(INVALID_HANDLE.to_string()),
+                status: status.and_then(|status| status.status_type.clone()),
+                activities: status.map(|status| status.activities.clone()),
+                display_name: profile.and_then(|p| p.display_name.clone()),
+                tagline: profile.and_then(|p| p.tagline.clone()),
+                description: profile.and_then(|p| p.description.clone()),
+                location: profile.and_then(|p| p.location.clone()),
+                social_connections: profile.and_then(|p| p.social_connections.clone()),
+                avatar,
+                banner,
+                viewer: None,
+                labels: None,
+                indexed_at: actor.indexed_at.map(|indexed_at| indexed_at.0),
+                created_at: profile
+                    .and_then(|profile| profile.created_at.clone().map(|created_at| created_at.0)),
+                did: actor.did,
+            }
+        })
+        .collect())
+}
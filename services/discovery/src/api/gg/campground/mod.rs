@@ -0,0 +1,7 @@
+pub mod actor;
+
+pub fn routes() -> Vec<rocket::Route> {
+    let mut routes = Vec::new();
+    routes.append(&mut actor::routes());
+    routes
+}
@@ -0,0 +1,7 @@
+pub mod campground;
+
+pub fn routes() -> Vec<rocket::Route> {
+    let mut routes = Vec::new();
+    routes.append(&mut campground::routes());
+    routes
+}
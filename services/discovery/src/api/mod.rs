@@ -0,0 +1,31 @@
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+
+pub mod gg;
+
+/// XRPC error body, `{"error": ..., "message": ...}`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ErrorMessageResponse {
+    pub error: String,
+    pub message: Option<String>,
+}
+
+pub type XrpcError = status::Custom<Json<ErrorMessageResponse>>;
+
+pub fn xrpc_error(status: Status, error: &str, message: String) -> XrpcError {
+    status::Custom(
+        status,
+        Json(ErrorMessageResponse {
+            error: error.to_string(),
+            message: Some(message),
+        }),
+    )
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    let mut routes = Vec::new();
+    routes.append(&mut gg::routes());
+    routes
+}
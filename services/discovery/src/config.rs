@@ -1,8 +1,41 @@
+use lazy_static::lazy_static;
 use rocket::serde::Deserialize;
+use rocket::Config;
 
+lazy_static! {
+    pub static ref REGISTRY_CONFIG: RegistryConfig = Config::figment()
+        .extract_inner("registry")
+        .expect("Failed to load registry configuration");
+}
+
+/// The registry whose firehose is indexed.
 #[derive(Debug, Deserialize, Clone)]
 #[serde(crate = "rocket::serde")]
-pub struct DatabaseConfig {
+pub struct RegistryConfig {
+    /// Base URL, e.g. `https://registry.example.com`
     pub url: String,
-    pub pool_size: u32,
-}
\ No newline at end of file
+}
+
+impl RegistryConfig {
+    pub fn hostname(&self) -> String {
+        let url = self.url.trim_end_matches('/');
+        url.split_once("://").map_or(url, |(_, host)| host).to_string()
+    }
+
+    pub fn firehose_url(&self) -> String {
+        let url = self.url.trim_end_matches('/');
+        let url = match url.split_once("://") {
+            Some(("http", host)) => format!("ws://{host}"),
+            Some((_, host)) => format!("wss://{host}"),
+            None => format!("wss://{url}"),
+        };
+        format!("{url}/xrpc/com.atproto.sync.subscribeRepos")
+    }
+
+    pub fn blob_url(&self, did: &str, cid: &str) -> String {
+        format!(
+            "{}/xrpc/com.atproto.sync.getBlob?did={did}&cid={cid}",
+            self.url.trim_end_matches('/')
+        )
+    }
+}
@@ -0,0 +1,132 @@
+//! Applies the SurrealQL scripts under `db/`, which are embedded into the binary at build time.
+//!
+//! `db/schemas` describe the current shape of the database and are reapplied on every start, so
+//! every definition in them must be idempotent (`DEFINE ... OVERWRITE`). They aren't recorded in
+//! `script_migration`: reapplying them is what brings an existing database in line when a schema
+//! file is edited, which a record of having run it once would prevent. `db/migrations` are
+//! one-off data changes: each runs once, in file name order, and is recorded in
+//! `script_migration` in the same transaction.
+//!
+//! Instances starting together take turns through `script_migration_lock`, so a migration can't
+//! run twice and schemas aren't redefined while another instance is migrating.
+use anyhow::{Context, Result};
+use include_dir::{include_dir, Dir};
+use std::time::{Duration, Instant};
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+
+static SCHEMAS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/db/schemas");
+static MIGRATIONS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/db/migrations");
+
+/// A lock older than this is taken to belong to an instance that died while migrating, and is
+/// broken. Waiting for the lock gives up a little after it.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The `.surql` files in `dir`, sorted by name.
+fn scripts(dir: &'static Dir<'static>) -> Vec<(&'static str, &'static str)> {
+    let mut scripts: Vec<(&'static str, &'static str)> = dir
+        .files()
+        .filter(|file| file.path().extension().is_some_and(|ext| ext == "surql"))
+        .filter_map(|file| {
+            let name = file.path().file_name()?.to_str()?;
+            Some((name, file.contents_utf8()?))
+        })
+        .collect();
+    scripts.sort_by_key(|(name, _)| *name);
+    scripts
+}
+
+/// Waits for and takes the migration lock. SurrealDB has no advisory locks, and a transaction
+/// can't span the reads and writes made from here, so the lock is a record: creating a record
+/// whose id is taken fails, so only one instance at a time succeeds.
+async fn lock(db: &Surreal<Client>) -> Result<()> {
+    let deadline = Instant::now() + LOCK_TIMEOUT + LOCK_TIMEOUT / 10;
+    loop {
+        let locked = db
+            .query(format!(
+                "DELETE script_migration_lock:migrations WHERE locked_at < time::now() - {}s",
+                LOCK_TIMEOUT.as_secs()
+            ))
+            .query("CREATE script_migration_lock:migrations SET locked_at = time::now()")
+            .await?
+            .check();
+        match locked {
+            Ok(_) => return Ok(()),
+            Err(error) if Instant::now() >= deadline => {
+                return Err(error).context("Timed out waiting for the migration lock")
+            }
+            Err(_) => tokio::time::sleep(LOCK_RETRY_INTERVAL).await,
+        }
+    }
+}
+
+async fn unlock(db: &Surreal<Client>) -> Result<()> {
+    db.query("DELETE script_migration_lock:migrations")
+        .await?
+        .check()?;
+    Ok(())
+}
+
+pub async fn run_migrations(db: &Surreal<Client>) -> Result<()> {
+    lock(db).await?;
+    let result = apply_scripts(db).await;
+    // The lock is released whether or not the scripts applied, so a fixed build can retry
+    // straight away rather than waiting for it to time out.
+    unlock(db).await?;
+    result
+}
+
+async fn apply_scripts(db: &Surreal<Client>) -> Result<()> {
+    for (name, script) in scripts(&SCHEMAS) {
+        db.query(script)
+            .await?
+            .check()
+            .with_context(|| format!("Failed to apply schema `{name}`"))?;
+    }
+
+    let applied: Vec<String> = db
+        .query("SELECT VALUE script_name FROM script_migration")
+        .await?
+        .take(0)?;
+    for (name, script) in scripts(&MIGRATIONS) {
+        if applied.iter().any(|applied| applied == name) {
+            continue;
+        }
+        db.query("BEGIN TRANSACTION")
+            .query(script)
+            .query("CREATE script_migration SET script_name = $script_name")
+            .query("COMMIT TRANSACTION")
+            .bind(("script_name", name))
+            .await?
+            .check()
+            .with_context(|| format!("Failed to apply migration `{name}`"))?;
+        println!("@LOG: applied migration {name}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemas_are_sorted_and_idempotent() {
+        let schemas = scripts(&SCHEMAS);
+        for expected in ["script_migration.surql", "script_migration_lock.surql"] {
+            assert!(
+                schemas.iter().any(|(name, _)| *name == expected),
+                "{expected}"
+            );
+        }
+        assert!(schemas.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for (name, script) in schemas {
+            for statement in script.lines().filter(|line| line.starts_with("DEFINE")) {
+                assert!(
+                    statement.contains(" OVERWRITE ") || statement.contains(" IF NOT EXISTS "),
+                    "`{name}` would fail on restart: {statement}"
+                );
+            }
+        }
+    }
+}
@@ -1,34 +1,21 @@
-use std::sync::{Mutex, OnceLock};
-
 use anyhow::Result;
-use rocket::Config;
-use crate::config::DatabaseConfig;
-use diesel::{pg::PgConnection, r2d2::{Pool, ConnectionManager, PooledConnection}};
-use lazy_static::lazy_static;
+use deadpool_surrealdb::SurrealDBPool;
+use rocket_db_pools::{Database, Pool};
 
-// pub mod models;
-// pub use self::models::*;
+pub mod migrations;
+pub mod models;
+pub use self::models::*;
 
-pub type DbPool = Pool<ConnectionManager<PgConnection>>;
-pub type DbConnection = PooledConnection<ConnectionManager<PgConnection>>;
+pub type DbConnection = <SurrealDBPool as Pool>::Connection;
 
-lazy_static! {
-    static ref CONFIG: DatabaseConfig = Config::figment()
-        .extract_inner("database")
-        .expect("Failed to load database configuration");
+/// The SurrealDB pool, configured under `[default.databases.discovery]`.
+#[derive(Database, Clone)]
+#[database("discovery")]
+pub struct DiscoveryDb(pub SurrealDBPool);
 
-    static ref POOL: OnceLock<Mutex<DbPool>> = OnceLock::new();
+impl DiscoveryDb {
+    /// Checks a connection out of the pool, for work done outside of a request.
+    pub async fn connection(&self) -> Result<DbConnection> {
+        Ok(self.0.get().await?)
+    }
 }
-
-pub fn establish_connection() -> Result<DbConnection> {
-    let pool = POOL.get_or_init(|| {
-        let manager = ConnectionManager::<PgConnection>::new(&CONFIG.url);
-        Mutex::new(
-            Pool::builder()
-                .max_size(CONFIG.pool_size.clone())
-                .build(manager)
-                .expect("Failed to create connection pool")
-            )
-    });
-    Ok(pool.lock().unwrap().get()?)
-}
\ No newline at end of file
@@ -0,0 +1,43 @@
+use campground_lexicon::gg::campground::actor::{Activity, ProfileStatus};
+use campground_lexicon::gg::campground::socials::Socials;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Datetime;
+use surrealdb::RecordId;
+
+/// `indexed_at` is set by the database on every write, so it's only ever read.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    pub did: String,
+    pub handle: Option<String>,
+    #[serde(skip_serializing)]
+    pub indexed_at: Option<Datetime>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub uri: String,
+    pub cid: String,
+    pub creator: RecordId,
+    pub display_name: Option<String>,
+    pub tagline: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub social_connections: Option<Vec<Socials>>,
+    pub avatar_cid: Option<String>,
+    pub banner_cid: Option<String>,
+    pub created_at: Option<Datetime>,
+    #[serde(skip_serializing)]
+    pub indexed_at: Option<Datetime>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub uri: String,
+    pub cid: String,
+    pub creator: RecordId,
+    pub status_type: Option<ProfileStatus>,
+    pub activities: Vec<Activity>,
+    pub updated_at: Option<Datetime>,
+    #[serde(skip_serializing)]
+    pub indexed_at: Option<Datetime>,
+}
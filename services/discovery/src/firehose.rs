@@ -0,0 +1,193 @@
+//! Client for the registry's `com.atproto.sync.subscribeRepos` firehose.
+use crate::config::REGISTRY_CONFIG;
+use crate::database::DiscoveryDb;
+use crate::indexer;
+use anyhow::{bail, Result};
+use futures::StreamExt;
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Decode;
+use libipld::{Cid, Ipld};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::time::Duration;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct CommitOp {
+    pub action: String,
+    pub path: String,
+    pub cid: Option<Cid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub repo: String,
+    pub too_big: bool,
+    pub ops: Vec<CommitOp>,
+    pub blocks: Vec<u8>,
+}
+
+/// The firehose events the indexer cares about. Everything else is skipped over.
+#[derive(Debug, Clone)]
+pub enum Event {
+    Commit(Commit),
+    Identity { did: String, handle: Option<String> },
+    Tombstone { did: String },
+    Other,
+}
+
+fn get<'a>(map: &'a BTreeMap<String, Ipld>, key: &str) -> Option<&'a Ipld> {
+    map.get(key).filter(|value| **value != Ipld::Null)
+}
+
+fn get_string(map: &BTreeMap<String, Ipld>, key: &str) -> Result<String> {
+    match get(map, key) {
+        Some(Ipld::String(value)) => Ok(value.clone()),
+        _ => bail!("Invalid frame: expected string at {key}"),
+    }
+}
+
+fn get_optional_string(map: &BTreeMap<String, Ipld>, key: &str) -> Option<String> {
+    match get(map, key) {
+        Some(Ipld::String(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Decodes a binary frame: a DAG-CBOR header followed by a DAG-CBOR body.
+/// Returns the event's sequence number along with the event.
+pub fn decode_frame(bytes: &[u8]) -> Result<(Option<i64>, Event)> {
+    let mut reader = Cursor::new(bytes);
+    let header = match Ipld::decode(DagCborCodec, &mut reader)? {
+        Ipld::Map(header) => header,
+        _ => bail!("Invalid frame: header is not a map"),
+    };
+    let body = match Ipld::decode(DagCborCodec, &mut reader)? {
+        Ipld::Map(body) => body,
+        _ => bail!("Invalid frame: body is not a map"),
+    };
+    match get(&header, "op") {
+        Some(Ipld::Integer(1)) => (),
+        Some(Ipld::Integer(-1)) => bail!(
+            "Error frame: {} {}",
+            get_optional_string(&body, "error").unwrap_or_default(),
+            get_optional_string(&body, "message").unwrap_or_default()
+        ),
+        _ => bail!("Invalid frame: unknown op"),
+    }
+    let seq = match get(&body, "seq") {
+        Some(Ipld::Integer(seq)) => Some(*seq as i64),
+        _ => None,
+    };
+
+    let event = match get_optional_string(&header, "t").as_deref() {
+        Some("#commit") => {
+            let ops = match get(&body, "ops") {
+                Some(Ipld::List(ops)) => ops
+                    .iter()
+                    .map(|op| match op {
+                        Ipld::Map(op) => Ok(CommitOp {
+                            action: get_string(op, "action")?,
+                            path: get_string(op, "path")?,
+                            cid: match get(op, "cid") {
+                                Some(Ipld::Link(cid)) => Some(*cid),
+                                _ => None,
+                            },
+                        }),
+                        _ => bail!("Invalid frame: op is not a map"),
+                    })
+                    .collect::<Result<Vec<CommitOp>>>()?,
+                _ => vec![],
+            };
+            Event::Commit(Commit {
+                repo: get_string(&body, "repo")?,
+                too_big: get(&body, "tooBig") == Some(&Ipld::Bool(true)),
+                ops,
+                blocks: match get(&body, "blocks") {
+                    Some(Ipld::Bytes(blocks)) => blocks.clone(),
+                    _ => vec![],
+                },
+            })
+        }
+        Some("#identity") | Some("#handle") => Event::Identity {
+            did: get_string(&body, "did")?,
+            handle: get_optional_string(&body, "handle"),
+        },
+        Some("#tombstone") => Event::Tombstone {
+            did: get_string(&body, "did")?,
+        },
+        _ => Event::Other,
+    };
+    Ok((seq, event))
+}
+
+async fn get_cursor(db: &Surreal<Client>, service: &String) -> Result<Option<i64>> {
+    let cursor: Option<i64> = db
+        .query("SELECT VALUE cursor FROM ONLY type::thing('subscription', $service)")
+        .bind(("service", service.clone()))
+        .await?
+        .take(0)?;
+    Ok(cursor)
+}
+
+async fn set_cursor(db: &Surreal<Client>, service: &String, cursor: i64) -> Result<()> {
+    db.query("UPSERT type::thing('subscription', $service) SET cursor = $cursor")
+        .bind(("service", service.clone()))
+        .bind(("cursor", cursor))
+        .await?
+        .check()?;
+    Ok(())
+}
+
+/// Reads the firehose until the connection drops, resuming from the stored cursor.
+async fn subscribe(db: &DiscoveryDb, service: &String) -> Result<()> {
+    let conn = db.connection().await?;
+    let mut url = REGISTRY_CONFIG.firehose_url();
+    if let Some(cursor) = get_cursor(&conn, service).await? {
+        url = format!("{url}?cursor={cursor}");
+    }
+    let (mut stream, _) = connect_async(url).await?;
+    println!("@LOG: connected to firehose of {service}");
+
+    while let Some(message) = stream.next().await {
+        let bytes = match message? {
+            Message::Binary(bytes) => bytes,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let (seq, event) = match decode_frame(&bytes) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                eprintln!("@LOG: ERROR: failed to decode frame: {error}");
+                continue;
+            }
+        };
+        // A failure to index one event shouldn't stall the whole stream
+        if let Err(error) = indexer::index_event(&conn, event).await {
+            eprintln!("@LOG: ERROR: failed to index event {seq:?}: {error}");
+        }
+        if let Some(seq) = seq {
+            set_cursor(&conn, service, seq).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the firehose subscription alive for the lifetime of the service.
+pub async fn run(db: DiscoveryDb) {
+    let service = REGISTRY_CONFIG.hostname();
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match subscribe(&db, &service).await {
+            Ok(()) => backoff = Duration::from_secs(1),
+            Err(error) => eprintln!("@LOG: ERROR: firehose subscription failed: {error}"),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
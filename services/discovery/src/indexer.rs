@@ -0,0 +1,190 @@
+//! Applies firehose events to the discovery database.
+use crate::config::REGISTRY_CONFIG;
+use crate::database::models;
+use crate::firehose::{Commit, Event};
+use crate::repo::{get_record, read_car};
+use anyhow::Result;
+use campground_lexicon::gg::campground::actor::{Profile, Status, PROFILE_NSID, STATUS_NSID};
+use serde::Deserialize;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::{RecordId, Surreal};
+
+pub async fn index_event(db: &Surreal<Client>, event: Event) -> Result<()> {
+    match event {
+        Event::Commit(commit) => index_commit(db, commit).await,
+        Event::Identity { did, handle } => {
+            let handle = match handle {
+                Some(handle) => Some(handle),
+                None => resolve_handle(&did).await,
+            };
+            upsert_actor(db, &did, handle).await
+        }
+        Event::Tombstone { did } => delete_actor(db, &did).await,
+        Event::Other => Ok(()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DescribeRepoOutput {
+    handle: String,
+}
+
+/// Asks the registry for the handle of an account that hasn't announced one on the firehose yet.
+async fn resolve_handle(did: &String) -> Option<String> {
+    let url = format!(
+        "{}/xrpc/com.atproto.repo.describeRepo?repo={did}",
+        REGISTRY_CONFIG.url.trim_end_matches('/')
+    );
+    let res = reqwest::get(url).await.ok()?.error_for_status().ok()?;
+    Some(res.json::<DescribeRepoOutput>().await.ok()?.handle)
+}
+
+async fn ensure_actor(db: &Surreal<Client>, did: &String) -> Result<()> {
+    let actor: Option<models::Actor> = db.select(("actor", did.as_str())).await?;
+    if actor.is_none() {
+        upsert_actor(db, did, resolve_handle(did).await).await?;
+    }
+    Ok(())
+}
+
+async fn upsert_actor(db: &Surreal<Client>, did: &String, handle: Option<String>) -> Result<()> {
+    // Handles are case-insensitive, so they're kept lowercased to keep lookups simple
+    let handle = handle.map(|handle| handle.to_lowercase());
+
+    // Handles are unique, so whoever held this one before has to let go of it
+    db.query("BEGIN TRANSACTION")
+        .query("IF $handle != NONE { UPDATE actor SET handle = NONE WHERE handle = $handle AND did != $did }")
+        .query("UPSERT type::thing('actor', $did) CONTENT $actor")
+        .query("COMMIT TRANSACTION")
+        .bind(("did", did.clone()))
+        .bind(("handle", handle.clone()))
+        .bind((
+            "actor",
+            models::Actor {
+                did: did.clone(),
+                handle,
+                indexed_at: None,
+            },
+        ))
+        .await?
+        .check()?;
+    Ok(())
+}
+
+async fn index_commit(db: &Surreal<Client>, commit: Commit) -> Result<()> {
+    let indexed = commit.ops.iter().any(|op| {
+        op.path.starts_with(&format!("{PROFILE_NSID}/"))
+            || op.path.starts_with(&format!("{STATUS_NSID}/"))
+    });
+    if !indexed {
+        return Ok(());
+    }
+    if commit.too_big {
+        eprintln!(
+            "@LOG: WARN: skipping tooBig commit from {}, records may be stale",
+            commit.repo
+        );
+        return Ok(());
+    }
+    ensure_actor(db, &commit.repo).await?;
+    let blocks = read_car(&commit.blocks)?;
+
+    for op in commit.ops {
+        // Both records are singletons keyed `self`, so they're stored under the creator's DID
+        let collection = match op.path.split_once('/') {
+            Some((collection, "self")) => collection,
+            _ => continue,
+        };
+        let uri = format!("at://{}/{}", commit.repo, op.path);
+        match (collection, op.action.as_str(), op.cid) {
+            (PROFILE_NSID, "create" | "update", Some(cid)) => {
+                let record: Profile = serde_json::from_value(get_record(&blocks, &cid)?)?;
+                upsert_profile(db, uri, cid.to_string(), &commit.repo, record).await?;
+            }
+            (STATUS_NSID, "create" | "update", Some(cid)) => {
+                let record: Status = serde_json::from_value(get_record(&blocks, &cid)?)?;
+                upsert_status(db, uri, cid.to_string(), &commit.repo, record).await?;
+            }
+            (PROFILE_NSID, "delete", _) => delete_record(db, "profile", &commit.repo).await?,
+            (STATUS_NSID, "delete", _) => delete_record(db, "status", &commit.repo).await?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+async fn upsert_profile(
+    db: &Surreal<Client>,
+    uri: String,
+    cid: String,
+    creator: &String,
+    record: Profile,
+) -> Result<()> {
+    let row = models::Profile {
+        uri,
+        cid,
+        creator: RecordId::from(("actor", creator.as_str())),
+        display_name: record.display_name,
+        tagline: record.tagline,
+        description: record.description,
+        location: record.location,
+        // Profiles hold one `socials` union while views list them
+        social_connections: record.social_connections.map(|socials| vec![socials]),
+        avatar_cid: record
+            .avatar
+            .and_then(|avatar| avatar.r#ref)
+            .map(|cid| cid.to_string()),
+        banner_cid: record
+            .banner
+            .and_then(|banner| banner.r#ref)
+            .map(|cid| cid.to_string()),
+        created_at: record.created_at.map(Into::into),
+        indexed_at: None,
+    };
+    let _: Option<models::Profile> = db
+        .upsert(("profile", creator.as_str()))
+        .content(row)
+        .await?;
+    Ok(())
+}
+
+async fn upsert_status(
+    db: &Surreal<Client>,
+    uri: String,
+    cid: String,
+    creator: &String,
+    record: Status,
+) -> Result<()> {
+    let row = models::Status {
+        uri,
+        cid,
+        creator: RecordId::from(("actor", creator.as_str())),
+        status_type: record.status_type,
+        activities: record.activities.unwrap_or_default(),
+        updated_at: record.updated_at.map(Into::into),
+        indexed_at: None,
+    };
+    let _: Option<models::Status> = db.upsert(("status", creator.as_str())).content(row).await?;
+    Ok(())
+}
+
+async fn delete_record(db: &Surreal<Client>, table: &str, creator: &String) -> Result<()> {
+    db.query("DELETE type::thing($table, $creator)")
+        .bind(("table", table.to_string()))
+        .bind(("creator", creator.clone()))
+        .await?
+        .check()?;
+    Ok(())
+}
+
+async fn delete_actor(db: &Surreal<Client>, did: &String) -> Result<()> {
+    db.query("BEGIN TRANSACTION")
+        .query("DELETE type::thing('profile', $did)")
+        .query("DELETE type::thing('status', $did)")
+        .query("DELETE type::thing('actor', $did)")
+        .query("COMMIT TRANSACTION")
+        .bind(("did", did.clone()))
+        .await?
+        .check()?;
+    Ok(())
+}
@@ -1,15 +1,32 @@
 use anyhow::Result;
+use database::migrations::run_migrations;
+use database::DiscoveryDb;
+use rocket_db_pools::Database;
 
 #[macro_use] extern crate rocket;
 extern crate thiserror;
 
 pub mod database;
 pub mod config;
+pub mod repo;
+pub mod firehose;
+pub mod indexer;
+pub mod api;
 
 #[rocket::main]
 async fn main() -> Result<()> {
     let rocket = rocket::build()
-        .mount("/", routes![]);
+        .attach(DiscoveryDb::init())
+        .mount("/", api::routes())
+        .ignite()
+        .await?;
+
+    // The schema has to be in place before the indexer starts writing to it
+    let db = DiscoveryDb::fetch(&rocket)
+        .expect("Database is not attached")
+        .clone();
+    run_migrations(&*db.connection().await?).await?;
+    tokio::spawn(firehose::run(db));
 
     rocket.launch().await?;
 
@@ -0,0 +1,100 @@
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD_NO_PAD as base64, Engine as _};
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::{Cid, Ipld};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// Parse a CARv1 archive, such as the `blocks` of a firehose commit, into its blocks.
+pub fn read_car(bytes: &[u8]) -> Result<HashMap<Cid, Vec<u8>>> {
+    let (header_len, rest) = unsigned_varint::decode::u64(bytes)?;
+    let header_len = header_len as usize;
+    if rest.len() < header_len {
+        bail!("Invalid CAR: truncated header");
+    }
+    match DagCborCodec.decode::<Ipld>(&rest[..header_len])? {
+        Ipld::Map(header) if header.get("version") == Some(&Ipld::Integer(1)) => (),
+        _ => bail!("Invalid CAR: unsupported header"),
+    }
+
+    let mut blocks = HashMap::new();
+    let mut remaining = &rest[header_len..];
+    while !remaining.is_empty() {
+        let (section_len, rest) = unsigned_varint::decode::u64(remaining)?;
+        let section_len = section_len as usize;
+        if rest.len() < section_len {
+            bail!("Invalid CAR: truncated block");
+        }
+        let section = &rest[..section_len];
+        let mut reader = Cursor::new(section);
+        let cid = Cid::read_bytes(&mut reader)?;
+        blocks.insert(cid, section[reader.position() as usize..].to_vec());
+        remaining = &rest[section_len..];
+    }
+    Ok(blocks)
+}
+
+/// Converts decoded DAG-CBOR to the atproto JSON representation, with links as `{"$link": cid}`
+/// and bytes as `{"$bytes": base64}`, so records can be deserialized into the lexicon types.
+pub fn ipld_to_json(ipld: Ipld) -> Value {
+    match ipld {
+        Ipld::Null => Value::Null,
+        Ipld::Bool(b) => Value::Bool(b),
+        Ipld::Integer(i) => match i64::try_from(i) {
+            Ok(i) => Value::Number(i.into()),
+            Err(_) => Value::String(i.to_string()),
+        },
+        Ipld::Float(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        Ipld::String(s) => Value::String(s),
+        Ipld::Bytes(bytes) => {
+            let mut map = Map::new();
+            map.insert("$bytes".to_string(), Value::String(base64.encode(bytes)));
+            Value::Object(map)
+        }
+        Ipld::List(list) => Value::Array(list.into_iter().map(ipld_to_json).collect()),
+        Ipld::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, ipld_to_json(value)))
+                .collect(),
+        ),
+        Ipld::Link(cid) => {
+            let mut map = Map::new();
+            map.insert("$link".to_string(), Value::String(cid.to_string()));
+            Value::Object(map)
+        }
+    }
+}
+
+/// Decodes the record stored under `cid` in a commit's blocks.
+pub fn get_record(blocks: &HashMap<Cid, Vec<u8>>, cid: &Cid) -> Result<Value> {
+    match blocks.get(cid) {
+        None => bail!("Record block {cid} missing from commit"),
+        Some(block) => Ok(ipld_to_json(DagCborCodec.decode::<Ipld>(block)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld::ipld;
+
+    #[test]
+    fn converts_links_and_bytes() {
+        let cid =
+            Cid::try_from("bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm").unwrap();
+        let json = ipld_to_json(ipld!({
+            "avatar": { "ref": cid, "size": 12 },
+            "data": Ipld::Bytes(vec![1, 2, 3]),
+        }));
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "avatar": { "ref": { "$link": cid.to_string() }, "size": 12 },
+                "data": { "$bytes": "AQID" },
+            })
+        );
+    }
+}
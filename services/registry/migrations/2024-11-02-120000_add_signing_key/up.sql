@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- Create Signing Key Table
+-- Keyed by the DID the key was reserved for, or by the key's own did:key
+-- when it was reserved without one.
+CREATE TABLE IF NOT EXISTS registry.signing_key (
+    did character varying PRIMARY KEY,
+    "keyDid" character varying NOT NULL,
+    "privateKey" character varying NOT NULL,
+    "createdAt" character varying NOT NULL
+);
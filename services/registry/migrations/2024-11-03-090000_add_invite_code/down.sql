@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE registry.account DROP COLUMN "inviteNote";
+ALTER TABLE registry.account DROP COLUMN "invitesDisabled";
+DROP TABLE registry.invite_code_use;
+DROP TABLE registry.invite_code;
\ No newline at end of file
@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- Create Invite Code Table
+CREATE TABLE IF NOT EXISTS registry.invite_code (
+    code character varying PRIMARY KEY,
+    "availableUses" integer NOT NULL,
+    disabled smallint NOT NULL DEFAULT 0,
+    "forAccount" character varying NOT NULL,
+    "createdBy" character varying NOT NULL,
+    "createdAt" character varying NOT NULL
+);
+CREATE INDEX invite_code_for_account_idx
+	ON registry.invite_code("forAccount");
+
+-- Create Invite Code Use Table
+CREATE TABLE IF NOT EXISTS registry.invite_code_use (
+    code character varying NOT NULL,
+    "usedBy" character varying NOT NULL,
+    "usedAt" character varying NOT NULL
+);
+ALTER TABLE ONLY registry.invite_code_use
+    DROP CONSTRAINT IF EXISTS invite_code_use_pkey;
+ALTER TABLE ONLY registry.invite_code_use
+    ADD CONSTRAINT invite_code_use_pkey PRIMARY KEY (code, "usedBy");
+
+-- Track accounts that may no longer receive invite codes
+ALTER TABLE registry.account
+    ADD COLUMN IF NOT EXISTS "invitesDisabled" smallint NOT NULL DEFAULT 0;
+ALTER TABLE registry.account
+    ADD COLUMN IF NOT EXISTS "inviteNote" character varying;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE registry.oauth_used_jti;
+DROP TABLE registry.oauth_token;
+DROP TABLE registry.oauth_request;
\ No newline at end of file
@@ -0,0 +1,42 @@
+-- Your SQL goes here
+-- Create OAuth Request Table
+-- Pushed authorization requests, which later hold the authorization code once the user consents
+CREATE TABLE IF NOT EXISTS registry.oauth_request (
+    id character varying PRIMARY KEY,
+    "clientId" character varying NOT NULL,
+    "clientAuth" character varying NOT NULL,
+    parameters text NOT NULL,
+    "dpopJkt" character varying NOT NULL,
+    did character varying,
+    code character varying,
+    "expiresAt" character varying NOT NULL,
+    "createdAt" character varying NOT NULL
+);
+CREATE UNIQUE INDEX oauth_request_code_idx
+	ON registry.oauth_request(code);
+
+-- Create OAuth Token Table
+CREATE TABLE IF NOT EXISTS registry.oauth_token (
+    id character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    "clientId" character varying NOT NULL,
+    "clientAuth" character varying NOT NULL,
+    scope character varying NOT NULL,
+    "dpopJkt" character varying NOT NULL,
+    "refreshToken" character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "expiresAt" character varying NOT NULL
+);
+CREATE UNIQUE INDEX oauth_token_refresh_token_idx
+	ON registry.oauth_token("refreshToken");
+CREATE INDEX oauth_token_did_idx
+	ON registry.oauth_token(did);
+
+-- Create OAuth Used JTI Table
+-- Guards against replay of DPoP proofs and client assertions
+CREATE TABLE IF NOT EXISTS registry.oauth_used_jti (
+    jti character varying PRIMARY KEY,
+    "expiresAt" character varying NOT NULL
+);
+CREATE INDEX oauth_used_jti_expires_at_idx
+	ON registry.oauth_used_jti("expiresAt");
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE registry.blob
+    DROP COLUMN duration,
+    DROP COLUMN container,
+    DROP COLUMN codec;
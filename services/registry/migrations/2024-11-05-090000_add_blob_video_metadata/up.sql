@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- Probed metadata of video blobs. Dimensions go in the existing width and height columns.
+ALTER TABLE registry.blob
+    ADD COLUMN duration integer, -- milliseconds
+    ADD COLUMN container character varying,
+    ADD COLUMN codec character varying;
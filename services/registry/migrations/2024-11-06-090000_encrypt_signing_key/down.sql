@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+-- Encrypted keys can't be read without their nonce, so this loses every key
+-- stored after the migration ran.
+ALTER TABLE registry.signing_key
+    DROP COLUMN "keyNonce";
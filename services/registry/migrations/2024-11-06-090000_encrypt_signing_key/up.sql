@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Private keys are encrypted with the keystore key from here on. Rows without a
+-- nonce still hold a plain hex key and are encrypted when the registry starts.
+ALTER TABLE registry.signing_key
+    ADD COLUMN "keyNonce" character varying;
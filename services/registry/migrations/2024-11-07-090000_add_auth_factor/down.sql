@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE registry.recovery_code;
+DROP TABLE registry.totp;
+ALTER TABLE registry.account
+    DROP COLUMN "emailAuthFactor";
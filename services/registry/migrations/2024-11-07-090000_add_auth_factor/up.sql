@@ -0,0 +1,25 @@
+-- Your SQL goes here
+-- Whether sign in with the account password also needs a code sent by email
+ALTER TABLE registry.account
+    ADD COLUMN "emailAuthFactor" smallint NOT NULL DEFAULT 0;
+
+-- Create TOTP Table
+-- The secret is encrypted with the keystore key like signing keys are. It
+-- isn't used for sign in until a code from it has been confirmed.
+CREATE TABLE IF NOT EXISTS registry.totp (
+    did character varying PRIMARY KEY,
+    secret character varying NOT NULL,
+    "secretNonce" character varying NOT NULL,
+    "lastUsedStep" bigint,
+    "confirmedAt" character varying,
+    "createdAt" character varying NOT NULL
+);
+
+-- Create Recovery Code Table
+-- Single use codes that stand in for a TOTP code, stored as SHA-256 hashes
+CREATE TABLE IF NOT EXISTS registry.recovery_code (
+    did character varying NOT NULL,
+    code character varying NOT NULL,
+    "usedAt" character varying,
+    PRIMARY KEY (did, code)
+);
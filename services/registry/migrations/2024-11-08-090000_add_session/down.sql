@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE registry.refresh_token
+    DROP COLUMN "sessionId";
+DROP TABLE registry.session;
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+-- Create Session Table
+-- A session lasts from sign in until it's revoked or its refresh tokens expire,
+-- across every refresh in between. Tokens issued for it carry its id, so
+-- revoking it also stops its access tokens.
+CREATE TABLE IF NOT EXISTS registry.session (
+    id character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    "appPasswordName" character varying,
+    "userAgent" character varying,
+    "ipAddress" character varying,
+    "createdAt" character varying NOT NULL,
+    "lastUsedAt" character varying NOT NULL
+);
+CREATE INDEX session_did_idx
+	ON registry.session(did);
+
+-- Refresh tokens issued before this migration have no session until they're
+-- next refreshed
+ALTER TABLE registry.refresh_token
+    ADD COLUMN "sessionId" character varying;
+CREATE INDEX refresh_token_session_id_idx
+	ON registry.refresh_token("sessionId");
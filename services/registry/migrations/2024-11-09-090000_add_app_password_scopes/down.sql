@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE registry.app_password
+    DROP COLUMN "lastUsedAt",
+    DROP COLUMN "expiresAt",
+    DROP COLUMN collections,
+    DROP COLUMN "readOnly",
+    DROP COLUMN privileged;
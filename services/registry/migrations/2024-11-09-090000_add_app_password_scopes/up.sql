@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- Limits on what an app password may do. "collections" holds space separated
+-- NSIDs that records may be written to, any of them ending in a `*` wildcard;
+-- NULL allows every collection.
+ALTER TABLE registry.app_password
+    ADD COLUMN privileged smallint NOT NULL DEFAULT 0,
+    ADD COLUMN "readOnly" smallint NOT NULL DEFAULT 0,
+    ADD COLUMN collections character varying,
+    ADD COLUMN "expiresAt" character varying,
+    ADD COLUMN "lastUsedAt" character varying;
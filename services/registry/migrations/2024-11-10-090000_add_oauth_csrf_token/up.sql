@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- One-time token for the sign-in form of a pending request, replaced every
+-- time the form is shown.
+ALTER TABLE registry.oauth_request
+    ADD COLUMN "csrfToken" character varying;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Set on a key staged by a rotation for the account it's going to replace the
+-- key of. The row stays keyed by its own did:key until the new key has been
+-- published and the account switches over to it.
+ALTER TABLE registry.signing_key
+    ADD COLUMN "pendingFor" character varying;
+CREATE INDEX signing_key_pending_for_idx
+	ON registry.signing_key("pendingFor");
@@ -5,7 +5,7 @@
  */
 use rsky_pds::common;
 use rsky_pds::common::RFC3339_VARIANT;
-use crate::database::establish_connection;
+use crate::database::{establish_connection, DbConnection};
 use crate::schema::registry::account::dsl as AccountSchema;
 use crate::schema::registry::account::table as AccountTable;
 use crate::schema::registry::actor::dsl as ActorSchema;
@@ -191,9 +191,12 @@ pub async fn get_account_by_email(
     Ok(found)
 }
 
-pub fn register_actor(did: String, handle: String, deactivated: Option<bool>) -> Result<()> {
-    let conn = &mut establish_connection()?;
-
+pub fn register_actor(
+    conn: &mut DbConnection,
+    did: String,
+    handle: String,
+    deactivated: Option<bool>,
+) -> Result<()> {
     let system_time = SystemTime::now();
     let dt: DateTime<UtcOffset> = system_time.into();
     let created_at = format!("{}", dt.format(RFC3339_VARIANT));
@@ -223,9 +226,12 @@ pub fn register_actor(did: String, handle: String, deactivated: Option<bool>) ->
     Ok(())
 }
 
-pub fn register_account(did: String, email: String, password: String) -> Result<()> {
-    let conn = &mut establish_connection()?;
-
+pub fn register_account(
+    conn: &mut DbConnection,
+    did: String,
+    email: String,
+    password: String,
+) -> Result<()> {
     let created_at = common::now();
 
     // @TODO record recovery key for bring your own recovery key
@@ -243,16 +249,28 @@ pub fn register_account(did: String, email: String, password: String) -> Result<
 }
 
 pub async fn delete_account(did: &String) -> Result<()> {
+    use crate::schema::registry::recovery_code::dsl as RecoveryCodeSchema;
     use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
     use crate::schema::registry::repo_root::dsl as RepoRootSchema;
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    use crate::schema::registry::totp::dsl as TotpSchema;
 
     let conn = &mut establish_connection()?;
     delete(RepoRootSchema::repo_root)
         .filter(RepoRootSchema::did.eq(did))
         .execute(conn)?;
+    delete(SigningKeySchema::signing_key)
+        .filter(SigningKeySchema::did.eq(did))
+        .execute(conn)?;
     delete(RefreshTokenSchema::refresh_token)
         .filter(RefreshTokenSchema::did.eq(did))
         .execute(conn)?;
+    delete(TotpSchema::totp)
+        .filter(TotpSchema::did.eq(did))
+        .execute(conn)?;
+    delete(RecoveryCodeSchema::recovery_code)
+        .filter(RecoveryCodeSchema::did.eq(did))
+        .execute(conn)?;
     delete(AccountSchema::account)
         .filter(AccountSchema::did.eq(did))
         .execute(conn)?;
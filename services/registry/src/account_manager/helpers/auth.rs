@@ -5,24 +5,35 @@
  */
 use rsky_pds::auth_verifier::AuthScope;
 use rsky_pds::common::time::{from_micros_to_utc, MINUTE};
-use rsky_pds::common::{get_random_str, json_to_b64url, RFC3339_VARIANT};
+use rsky_pds::common::{get_random_str, RFC3339_VARIANT};
+use crate::account_manager::helpers::password::AppPasswordScopes;
+use crate::account_manager::helpers::session;
 use crate::database::establish_connection;
 use crate::database::models;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use diesel::*;
 use jwt_simple::prelude::*;
-use secp256k1::{Keypair, Message, SecretKey};
-use sha2::{Digest, Sha256};
+use crate::signer::Signer;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use secp256k1::PublicKey;
+use std::sync::Arc;
 use std::time::SystemTime;
 use thiserror::Error;
 
 pub struct CreateTokensOpts {
     pub did: String,
-    pub jwt_key: Keypair,
+    pub signer: Arc<dyn Signer>,
     pub service_did: String,
     pub scope: Option<AuthScope>,
     pub jti: Option<String>,
     pub expires_in: Option<Duration>,
+    /// Session the tokens belong to, so revoking it stops them.
+    pub session_id: Option<String>,
+    /// Limits of the app password the session signed in with.
+    pub app_password_scopes: Option<AppPasswordScopes>,
+    /// Neither token outlives this, for app passwords that expire.
+    pub valid_until: Option<DateTime<Utc>>,
 }
 
 pub struct RefreshGracePeriodOpts {
@@ -42,6 +53,7 @@ pub struct RefreshToken {
     pub sub: String,
     pub exp: Duration,
     pub jti: String,
+    pub sid: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -65,12 +77,22 @@ pub struct ServiceJwtParams {
     pub exp: Option<u64>,
     pub lxm: Option<String>,
     pub jti: Option<String>,
-    pub keypair: SecretKey,
+    pub signer: Arc<dyn Signer>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct CustomClaimObj {
     pub scope: String,
+    /// Session id, on tokens issued since sessions were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    /// On access tokens for app passwords with limits.
+    #[serde(
+        rename = "appPassword",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub app_password: Option<AppPasswordScopes>,
 }
 
 #[derive(Error, Debug)]
@@ -79,88 +101,118 @@ pub enum AuthHelperError {
     ConcurrentRefresh,
 }
 
-pub fn create_tokens(opts: CreateTokensOpts) -> Result<(String, String)> {
+pub async fn create_tokens(opts: CreateTokensOpts) -> Result<(String, String)> {
     let CreateTokensOpts {
         did,
-        jwt_key,
+        signer,
         service_did,
         scope,
         jti,
         expires_in,
+        session_id,
+        app_password_scopes,
+        valid_until,
     } = opts;
     let access_jwt = create_access_token(CreateTokensOpts {
         did: did.clone(),
-        jwt_key,
+        signer: signer.clone(),
         service_did: service_did.clone(),
         scope,
         expires_in,
         jti: None,
-    })?;
+        session_id: session_id.clone(),
+        app_password_scopes,
+        valid_until,
+    })
+    .await?;
     let refresh_jwt = create_refresh_token(CreateTokensOpts {
         did,
-        jwt_key,
+        signer,
         service_did,
         jti,
         expires_in,
         scope: None,
-    })?;
+        session_id,
+        app_password_scopes: None,
+        valid_until,
+    })
+    .await?;
     Ok((access_jwt, refresh_jwt))
 }
 
-pub fn create_access_token(opts: CreateTokensOpts) -> Result<String> {
+pub async fn create_access_token(opts: CreateTokensOpts) -> Result<String> {
     let CreateTokensOpts {
         did,
-        jwt_key,
+        signer,
         service_did,
         scope,
         expires_in,
+        session_id,
+        app_password_scopes,
+        valid_until,
         ..
     } = opts;
     let scope = scope.unwrap_or_else(|| AuthScope::Access);
-    let expires_in = expires_in.unwrap_or_else(|| Duration::from_hours(2));
+    let expires_in = cap_lifetime(
+        expires_in.unwrap_or_else(|| Duration::from_hours(2)),
+        valid_until,
+    );
     let claims = Claims::with_custom_claims(
         CustomClaimObj {
             scope: scope.as_str().to_owned(),
+            sid: session_id,
+            app_password: app_password_scopes.filter(|scopes| !scopes.is_unlimited()),
         },
         expires_in,
     )
     .with_audience(service_did)
     .with_subject(did);
-    // alg ES256K
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let token = key.sign(claims)?;
-    Ok(token)
+    sign_jwt(&*signer, &claims).await
 }
 
-pub fn create_refresh_token(opts: CreateTokensOpts) -> Result<String> {
+pub async fn create_refresh_token(opts: CreateTokensOpts) -> Result<String> {
     let CreateTokensOpts {
         did,
-        jwt_key,
+        signer,
         service_did,
         jti,
         expires_in,
+        session_id,
+        valid_until,
         ..
     } = opts;
     let jti = jti.unwrap_or_else(|| get_random_str());
-    let expires_in = expires_in.unwrap_or_else(|| Duration::from_days(90));
+    let expires_in = cap_lifetime(
+        expires_in.unwrap_or_else(|| Duration::from_days(90)),
+        valid_until,
+    );
     let claims = Claims::with_custom_claims(
         CustomClaimObj {
             scope: AuthScope::Refresh.as_str().to_owned(),
+            sid: session_id,
+            app_password: None,
         },
         expires_in,
     )
     .with_audience(service_did)
     .with_subject(did)
     .with_jwt_id(jti);
-    // alg ES256K
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let token = key.sign(claims)?;
-    Ok(token)
+    sign_jwt(&*signer, &claims).await
+}
+
+fn cap_lifetime(expires_in: Duration, valid_until: Option<DateTime<Utc>>) -> Duration {
+    match valid_until {
+        None => expires_in,
+        Some(valid_until) => {
+            let remaining = (valid_until - Utc::now()).num_seconds().max(0) as u64;
+            expires_in.min(Duration::from_secs(remaining))
+        }
+    }
 }
 
 pub async fn create_service_jwt(params: ServiceJwtParams) -> Result<String> {
     let ServiceJwtParams {
-        iss, aud, keypair, ..
+        iss, aud, signer, ..
     } = params;
     let now = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -171,10 +223,6 @@ pub async fn create_service_jwt(params: ServiceJwtParams) -> Result<String> {
         .unwrap_or(((now + MINUTE as usize) / 1000) as u64);
     let lxm = params.lxm;
     let jti = get_random_str();
-    let header = ServiceJwtHeader {
-        typ: "JWT".to_string(),
-        alg: "ES256K".to_string(),
-    };
     let payload = ServiceJwtPayload {
         iss,
         aud,
@@ -182,18 +230,32 @@ pub async fn create_service_jwt(params: ServiceJwtParams) -> Result<String> {
         lxm,
         jti: Some(jti),
     };
+    sign_jwt(&*signer, &payload).await
+}
+
+pub async fn create_service_auth_headers(params: ServiceJwtParams) -> Result<HeaderMap> {
+    let jwt = create_service_jwt(params).await?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {jwt}"))?,
+    );
+    Ok(headers)
+}
+
+/// Encodes `payload` as an ES256K JWT signed by `signer`.
+pub async fn sign_jwt<T: Serialize>(signer: &dyn Signer, payload: &T) -> Result<String> {
+    let header = ServiceJwtHeader {
+        typ: "JWT".to_string(),
+        alg: "ES256K".to_string(),
+    };
+    // Unpadded, as jwt-simple won't verify tokens with padding in them
     let to_sign_str = format!(
         "{0}.{1}",
-        json_to_b64url(&header)?,
-        json_to_b64url(&payload)?
+        base64_url::encode(&serde_json::to_vec(&header)?).replace("=", ""),
+        base64_url::encode(&serde_json::to_vec(payload)?).replace("=", "")
     );
-    let hash = Sha256::digest(to_sign_str.clone());
-    let message = Message::from_digest_slice(hash.as_ref())?;
-    let mut sig = keypair.sign_ecdsa(message);
-    // Convert to low-s
-    sig.normalize_s();
-    // ASN.1 encoded per decode_dss_signature
-    let compact_sig = sig.serialize_compact();
+    let compact_sig = signer.sign(to_sign_str.as_bytes()).await?;
     Ok(format!(
         "{0}.{1}",
         to_sign_str,
@@ -202,9 +264,8 @@ pub async fn create_service_jwt(params: ServiceJwtParams) -> Result<String> {
 }
 
 // @NOTE unsafe for verification, should only be used w/ direct output from createRefreshToken() or createTokens()
-pub fn decode_refresh_token(jwt: String, jwt_key: Keypair) -> Result<RefreshToken> {
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let public_key = key.public_key();
+pub fn decode_refresh_token(jwt: String, jwt_key: &PublicKey) -> Result<RefreshToken> {
+    let public_key = ES256kPublicKey::from_bytes(&jwt_key.serialize())?;
     let claims = public_key.verify_token::<CustomClaimObj>(&jwt, None)?;
     assert_eq!(
         claims.custom.scope,
@@ -216,6 +277,7 @@ pub fn decode_refresh_token(jwt: String, jwt_key: Keypair) -> Result<RefreshToke
         sub: claims.subject.unwrap(),
         exp: claims.expires_at.unwrap(),
         jti: claims.jwt_id.unwrap(),
+        sid: claims.custom.sid,
     })
 }
 
@@ -234,6 +296,7 @@ pub async fn store_refresh_token(
             RefreshTokenSchema::id.eq(payload.jti),
             RefreshTokenSchema::did.eq(payload.sub),
             RefreshTokenSchema::appPasswordName.eq(app_password_name),
+            RefreshTokenSchema::sessionId.eq(payload.sid),
             RefreshTokenSchema::expiresAt.eq(format!("{}", exp.format(RFC3339_VARIANT))),
         ))
         .on_conflict_do_nothing() // E.g. when re-granting during a refresh grace period
@@ -241,6 +304,8 @@ pub async fn store_refresh_token(
     Ok(())
 }
 
+/// Revokes the session the refresh token belongs to, including the tokens it replaced that are
+/// still in their grace period. Tokens from before sessions were recorded are revoked alone.
 pub async fn revoke_refresh_token(id: String) -> Result<bool> {
     use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
     let conn = &mut establish_connection()?;
@@ -248,17 +313,26 @@ pub async fn revoke_refresh_token(id: String) -> Result<bool> {
     let deleted_rows = delete(RefreshTokenSchema::refresh_token)
         .filter(RefreshTokenSchema::id.eq(id))
         .get_results::<models::RefreshToken>(conn)?;
+    for row in &deleted_rows {
+        if let Some(session_id) = &row.session_id {
+            session::revoke_session(&row.did, session_id).await?;
+        }
+    }
 
     Ok(deleted_rows.len() > 0)
 }
 
 pub async fn revoke_refresh_tokens_by_did(did: &String) -> Result<bool> {
     use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::registry::session::dsl as SessionSchema;
     let conn = &mut establish_connection()?;
 
     let deleted_rows = delete(RefreshTokenSchema::refresh_token)
         .filter(RefreshTokenSchema::did.eq(did))
         .get_results::<models::RefreshToken>(conn)?;
+    delete(SessionSchema::session)
+        .filter(SessionSchema::did.eq(did))
+        .execute(conn)?;
 
     Ok(deleted_rows.len() > 0)
 }
@@ -268,16 +342,22 @@ pub async fn revoke_app_password_refresh_token(
     app_pass_name: &String,
 ) -> Result<bool> {
     use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::registry::session::dsl as SessionSchema;
     let conn = &mut establish_connection()?;
 
     let deleted_rows = delete(RefreshTokenSchema::refresh_token)
         .filter(RefreshTokenSchema::did.eq(did))
         .filter(RefreshTokenSchema::appPasswordName.eq(app_pass_name))
         .get_results::<models::RefreshToken>(conn)?;
+    delete(SessionSchema::session)
+        .filter(SessionSchema::did.eq(did))
+        .filter(SessionSchema::appPasswordName.eq(app_pass_name))
+        .execute(conn)?;
 
     Ok(deleted_rows.len() > 0)
 }
 
+/// Attaches a token issued before sessions were recorded to the session started for it.
 pub async fn get_refresh_token(id: &String) -> Result<Option<models::RefreshToken>> {
     use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
     let conn = &mut establish_connection()?;
@@ -299,6 +379,16 @@ pub async fn delete_expired_refresh_tokens(did: &String, now: String) -> Result<
     Ok(())
 }
 
+/// Removes expired refresh tokens for every account, for the janitor.
+pub async fn delete_all_expired_refresh_tokens(now: String) -> Result<usize> {
+    use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(delete(RefreshTokenSchema::refresh_token)
+        .filter(RefreshTokenSchema::expiresAt.le(now))
+        .execute(conn)?)
+}
+
 pub async fn add_refresh_grace_period(opts: RefreshGracePeriodOpts) -> Result<()> {
     let RefreshGracePeriodOpts {
         id,
@@ -327,4 +417,31 @@ pub async fn add_refresh_grace_period(opts: RefreshGracePeriodOpts) -> Result<()
 
 pub fn get_refresh_token_id() -> String {
     get_random_str()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::LocalSigner;
+
+    #[tokio::test]
+    async fn signed_tokens_verify_with_public_key() {
+        let signer = LocalSigner::new(secp256k1::SecretKey::new(&mut rand::thread_rng()));
+        let public_key = signer.public_key();
+        let refresh_jwt = create_refresh_token(CreateTokensOpts {
+            did: "did:example:alice".to_string(),
+            signer: Arc::new(signer),
+            service_did: "did:web:pds.example".to_string(),
+            scope: None,
+            jti: None,
+            expires_in: None,
+            session_id: Some("session".to_string()),
+            app_password_scopes: None,
+            valid_until: None,
+        })
+        .await
+        .unwrap();
+        let payload = decode_refresh_token(refresh_jwt, &public_key).unwrap();
+        assert_eq!(payload.sub, "did:example:alice");
+        assert_eq!(payload.sid, Some("session".to_string()));
+    }
+}
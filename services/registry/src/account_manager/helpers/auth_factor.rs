@@ -0,0 +1,307 @@
+use crate::account_manager::helpers::signing_key::keystore_cipher;
+use crate::api::com::atproto::server::get_random_token;
+use crate::database::establish_connection;
+use crate::database::models::{RecoveryCode, Totp};
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Result};
+use data_encoding::BASE32_NOPAD;
+use diesel::*;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use rsky_pds::common;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+
+/// TOTP as authenticator apps expect it by default (RFC 6238): HMAC-SHA1 over 30 second steps,
+/// truncated to 6 digits.
+const TOTP_STEP_SECS: u64 = 30;
+const TOTP_DIGITS: usize = 6;
+/// Codes from one step either side of now are accepted, for clocks that have drifted.
+const TOTP_SKEW_STEPS: i64 = 1;
+const TOTP_SECRET_LEN: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub fn totp_code(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        truncated % 10u32.pow(TOTP_DIGITS as u32),
+        width = TOTP_DIGITS
+    )
+}
+
+fn current_step() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("timestamp in secs since UNIX epoch")
+        .as_secs();
+    (now / TOTP_STEP_SECS) as i64
+}
+
+/// Returns the step `code` belongs to, if it's close enough to `now_step` and later than the
+/// last step a code was accepted for.
+fn find_totp_step(
+    secret: &[u8],
+    code: &str,
+    now_step: i64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    (now_step - TOTP_SKEW_STEPS..=now_step + TOTP_SKEW_STEPS)
+        .filter(|step| last_used_step.map_or(true, |last| *step > last))
+        .find(|step| totp_code(secret, *step) == code.trim())
+}
+
+/// The secret is bound to its DID as associated data, like signing keys are to their did:key.
+fn seal_secret(cipher: &Aes256Gcm, did: &String, secret: &[u8]) -> Result<(String, String)> {
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: secret,
+                aad: did.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("Failed to encrypt TOTP secret for `{did}`"))?;
+    Ok((hex::encode(nonce), hex::encode(ciphertext)))
+}
+
+fn open_secret(cipher: &Aes256Gcm, row: &Totp) -> Result<Vec<u8>> {
+    cipher
+        .decrypt(
+            Nonce::from_slice(&hex::decode(row.secret_nonce.as_bytes())?),
+            Payload {
+                msg: &hex::decode(row.secret.as_bytes())?,
+                aad: row.did.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("Failed to decrypt TOTP secret for `{0}`", row.did))
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().to_uppercase().as_bytes()))
+}
+
+async fn get_totp(did: &String) -> Result<Option<Totp>> {
+    use crate::schema::registry::totp::dsl as TotpSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(TotpSchema::totp
+        .filter(TotpSchema::did.eq(did))
+        .select(Totp::as_select())
+        .first(conn)
+        .optional()?)
+}
+
+/// Whether `did` has a confirmed authenticator app.
+pub async fn has_totp(did: &String) -> Result<bool> {
+    Ok(get_totp(did)
+        .await?
+        .is_some_and(|row| row.confirmed_at.is_some()))
+}
+
+/// Starts enrolling an authenticator app, replacing any enrolment that wasn't confirmed.
+/// Returns the base32 secret to show the user.
+pub async fn create_totp(did: &String) -> Result<String> {
+    use crate::schema::registry::totp::dsl as TotpSchema;
+    if has_totp(did).await? {
+        bail!("InvalidRequest: An authenticator app is already enabled for this account");
+    }
+    let conn = &mut establish_connection()?;
+
+    let mut secret = [0u8; TOTP_SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let (secret_nonce, sealed) = seal_secret(&keystore_cipher()?, did, &secret)?;
+    let row = Totp {
+        did: did.clone(),
+        secret: sealed,
+        secret_nonce,
+        last_used_step: None,
+        confirmed_at: None,
+        created_at: common::now(),
+    };
+    insert_into(TotpSchema::totp)
+        .values(&row)
+        .on_conflict(TotpSchema::did)
+        .do_update()
+        .set((
+            TotpSchema::secret.eq(&row.secret),
+            TotpSchema::secretNonce.eq(&row.secret_nonce),
+            TotpSchema::lastUsedStep.eq(&row.last_used_step),
+            TotpSchema::createdAt.eq(&row.created_at),
+        ))
+        .execute(conn)?;
+    Ok(BASE32_NOPAD.encode(&secret))
+}
+
+/// Accepts `code` if it's current for `row` and no code for the same or a later step has been
+/// accepted yet. Concurrent sign ins can't both use one code, since only one of them can move
+/// `lastUsedStep` forward.
+async fn accept_totp_code(row: &Totp, code: &String) -> Result<bool> {
+    use crate::schema::registry::totp::dsl as TotpSchema;
+    let secret = open_secret(&keystore_cipher()?, row)?;
+    let step = match find_totp_step(&secret, code, current_step(), row.last_used_step) {
+        None => return Ok(false),
+        Some(step) => step,
+    };
+    let conn = &mut establish_connection()?;
+
+    let updated = update(TotpSchema::totp)
+        .filter(TotpSchema::did.eq(&row.did))
+        .filter(
+            TotpSchema::lastUsedStep
+                .is_null()
+                .or(TotpSchema::lastUsedStep.lt(step)),
+        )
+        .set(TotpSchema::lastUsedStep.eq(step))
+        .execute(conn)?;
+    Ok(updated == 1)
+}
+
+/// Finishes enrolment once the user enters a code from their app. Returns the account's new
+/// recovery codes, which aren't stored in a form that can be shown again.
+pub async fn confirm_totp(did: &String, code: &String) -> Result<Vec<String>> {
+    use crate::schema::registry::totp::dsl as TotpSchema;
+    let row = match get_totp(did).await? {
+        None => bail!("InvalidRequest: No authenticator app is being enrolled for this account"),
+        Some(Totp {
+            confirmed_at: Some(_),
+            ..
+        }) => bail!("InvalidRequest: An authenticator app is already enabled for this account"),
+        Some(row) => row,
+    };
+    if !accept_totp_code(&row, code).await? {
+        bail!("InvalidToken: Code is invalid or expired");
+    }
+    let conn = &mut establish_connection()?;
+
+    update(TotpSchema::totp)
+        .filter(TotpSchema::did.eq(did))
+        .set(TotpSchema::confirmedAt.eq(common::now()))
+        .execute(conn)?;
+    create_recovery_codes(did).await
+}
+
+/// Checks a sign in code against the account's confirmed authenticator app.
+pub async fn verify_totp(did: &String, code: &String) -> Result<bool> {
+    match get_totp(did).await? {
+        Some(row) if row.confirmed_at.is_some() => accept_totp_code(&row, code).await,
+        _ => Ok(false),
+    }
+}
+
+/// Removes the authenticator app and the recovery codes that go with it.
+pub async fn delete_totp(did: &String) -> Result<()> {
+    use crate::schema::registry::recovery_code::dsl as RecoveryCodeSchema;
+    use crate::schema::registry::totp::dsl as TotpSchema;
+    let conn = &mut establish_connection()?;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        delete(RecoveryCodeSchema::recovery_code)
+            .filter(RecoveryCodeSchema::did.eq(did))
+            .execute(conn)?;
+        delete(TotpSchema::totp)
+            .filter(TotpSchema::did.eq(did))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Replaces the account's recovery codes, returning the new ones.
+pub async fn create_recovery_codes(did: &String) -> Result<Vec<String>> {
+    use crate::schema::registry::recovery_code::dsl as RecoveryCodeSchema;
+    let conn = &mut establish_connection()?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| get_random_token().to_uppercase())
+        .collect();
+    let rows: Vec<RecoveryCode> = codes
+        .iter()
+        .map(|code| RecoveryCode {
+            did: did.clone(),
+            code: hash_recovery_code(code),
+            used_at: None,
+        })
+        .collect();
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        delete(RecoveryCodeSchema::recovery_code)
+            .filter(RecoveryCodeSchema::did.eq(did))
+            .execute(conn)?;
+        insert_into(RecoveryCodeSchema::recovery_code)
+            .values(&rows)
+            .execute(conn)?;
+        Ok(())
+    })?;
+    Ok(codes)
+}
+
+/// Marks `code` used if it's one of the account's unused recovery codes.
+pub async fn use_recovery_code(did: &String, code: &String) -> Result<bool> {
+    use crate::schema::registry::recovery_code::dsl as RecoveryCodeSchema;
+    let conn = &mut establish_connection()?;
+
+    let updated = update(RecoveryCodeSchema::recovery_code)
+        .filter(RecoveryCodeSchema::did.eq(did))
+        .filter(RecoveryCodeSchema::code.eq(hash_recovery_code(code)))
+        .filter(RecoveryCodeSchema::usedAt.is_null())
+        .set(RecoveryCodeSchema::usedAt.eq(common::now()))
+        .execute(conn)?;
+    Ok(updated == 1)
+}
+
+pub async fn get_email_auth_factor(did: &String) -> Result<bool> {
+    use crate::schema::registry::account::dsl as AccountSchema;
+    let conn = &mut establish_connection()?;
+
+    let res = AccountSchema::account
+        .filter(AccountSchema::did.eq(did))
+        .select(AccountSchema::emailAuthFactor)
+        .first::<i16>(conn)
+        .optional()?;
+    Ok(res == Some(1))
+}
+
+pub async fn set_email_auth_factor(did: &String, enabled: bool) -> Result<()> {
+    use crate::schema::registry::account::dsl as AccountSchema;
+    let conn = &mut establish_connection()?;
+
+    update(AccountSchema::account)
+        .filter(AccountSchema::did.eq(did))
+        .set(AccountSchema::emailAuthFactor.eq(if enabled { 1 } else { 0 }))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        let secret = b"12345678901234567890";
+        assert_eq!(totp_code(secret, 59 / 30), "287082");
+        assert_eq!(totp_code(secret, 1111111109 / 30), "081804");
+        assert_eq!(totp_code(secret, 20000000000 / 30), "353130");
+    }
+
+    #[test]
+    fn rejects_reused_and_stale_codes() {
+        let secret = b"12345678901234567890";
+        let code = totp_code(secret, 100);
+        assert_eq!(find_totp_step(secret, &code, 101, None), Some(100));
+        assert_eq!(find_totp_step(secret, &code, 101, Some(100)), None);
+        assert_eq!(find_totp_step(secret, &code, 102, None), None);
+    }
+}
@@ -12,6 +12,15 @@ use crate::database::models::EmailTokenPurpose;
 use crate::database::models::EmailToken;
 use anyhow::{bail, Result};
 use diesel::*;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EmailTokenError {
+    #[error("Token is expired")]
+    Expired,
+    #[error("Token is invalid")]
+    Invalid,
+}
 
 pub async fn create_email_token(did: &String, purpose: EmailTokenPurpose) -> Result<String> {
     use crate::schema::registry::email_token::dsl as EmailTokenSchema;
@@ -58,11 +67,11 @@ pub async fn assert_valid_token(
         let now = Utc::now();
         let expired = now - requested_at > chrono::Duration::milliseconds(expiration_len as i64);
         if expired {
-            bail!("Token is expired")
+            bail!(EmailTokenError::Expired)
         }
         Ok(())
     } else {
-        bail!("Token is invalid")
+        bail!(EmailTokenError::Invalid)
     }
 }
 
@@ -86,11 +95,11 @@ pub async fn assert_valid_token_and_find_did(
         let now = Utc::now();
         let expired = now - requested_at > chrono::Duration::milliseconds(expiration_len as i64);
         if expired {
-            bail!("Token is expired")
+            bail!(EmailTokenError::Expired)
         }
         Ok(res.did)
     } else {
-        bail!("Token is invalid")
+        bail!(EmailTokenError::Invalid)
     }
 }
 
@@ -113,4 +122,14 @@ pub async fn delete_all_email_tokens(did: &String) -> Result<()> {
         .filter(EmailTokenSchema::did.eq(did))
         .execute(conn)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Removes tokens requested before `before`, which are long past any expiration length in use.
+pub async fn delete_email_tokens_requested_before(before: String) -> Result<usize> {
+    use crate::schema::registry::email_token::dsl as EmailTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(delete(EmailTokenSchema::email_token)
+        .filter(EmailTokenSchema::requestedAt.le(before))
+        .execute(conn)?)
+}
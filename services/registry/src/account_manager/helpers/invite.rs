@@ -0,0 +1,370 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::database::{establish_connection, DbConnection};
+use crate::database::models;
+use anyhow::{bail, Result};
+use diesel::*;
+use rsky_lexicon::com::atproto::server::{
+    AccountCodes, InviteCode as CodeDetail, InviteCodeUse as CodeUse,
+};
+use rsky_pds::common;
+use std::collections::BTreeMap;
+
+/// Codes handed out by an administrator are marked as created by `admin`.
+pub const ADMIN_CREATOR: &str = "admin";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InviteCodesSort {
+    Recent,
+    Usage,
+}
+
+pub async fn ensure_invite_is_available(invite_code: &String) -> Result<()> {
+    let conn = &mut establish_connection()?;
+    assert_invite_available(conn, invite_code)
+}
+
+/// Locks the code's row until the surrounding transaction ends, so sign ups racing for its last
+/// use are checked one after the other.
+fn assert_invite_available(conn: &mut DbConnection, invite_code: &String) -> Result<()> {
+    use crate::schema::registry::actor::dsl as ActorSchema;
+    use crate::schema::registry::invite_code::dsl as InviteCodeSchema;
+    use crate::schema::registry::invite_code_use::dsl as InviteCodeUseSchema;
+
+    let invite = InviteCodeSchema::invite_code
+        .filter(InviteCodeSchema::code.eq(invite_code))
+        .select(models::InviteCode::as_select())
+        .for_update()
+        .first(conn)
+        .optional()?;
+    let invite = match invite {
+        Some(invite) if invite.disabled == 0 => invite,
+        _ => bail!("InvalidInviteCode: Provided invite code not available"),
+    };
+    // Codes belonging to a taken down account can't be used
+    let taken_down = ActorSchema::actor
+        .filter(ActorSchema::did.eq(&invite.for_account))
+        .filter(ActorSchema::takedownRef.is_not_null())
+        .count()
+        .get_result::<i64>(conn)?;
+    if taken_down > 0 {
+        bail!("InvalidInviteCode: Provided invite code not available");
+    }
+
+    let uses = InviteCodeUseSchema::invite_code_use
+        .filter(InviteCodeUseSchema::code.eq(invite_code))
+        .count()
+        .get_result::<i64>(conn)?;
+    if invite.available_uses as i64 <= uses {
+        bail!("InvalidInviteCode: Provided invite code not available");
+    }
+    Ok(())
+}
+
+/// Checks the code again before using it, so call it in the transaction that creates the
+/// account.
+pub fn record_invite_use(
+    conn: &mut DbConnection,
+    did: &String,
+    invite_code: Option<String>,
+    now: &String,
+) -> Result<()> {
+    use crate::schema::registry::invite_code_use::dsl as InviteCodeUseSchema;
+
+    if let Some(invite_code) = invite_code {
+        assert_invite_available(conn, &invite_code)?;
+        insert_into(InviteCodeUseSchema::invite_code_use)
+            .values(models::InviteCodeUse {
+                code: invite_code,
+                used_by: did.clone(),
+                used_at: now.clone(),
+            })
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+pub async fn create_invite_codes(to_create: Vec<AccountCodes>, use_count: i32) -> Result<()> {
+    use crate::schema::registry::invite_code::dsl as InviteCodeSchema;
+    let conn = &mut establish_connection()?;
+
+    let created_at = common::now();
+    let rows = to_create
+        .into_iter()
+        .flat_map(|AccountCodes { account, codes }| {
+            let created_at = created_at.clone();
+            codes.into_iter().map(move |code| models::InviteCode {
+                code,
+                available_uses: use_count,
+                disabled: 0,
+                for_account: account.clone(),
+                created_by: ADMIN_CREATOR.to_string(),
+                created_at: created_at.clone(),
+            })
+        })
+        .collect::<Vec<models::InviteCode>>();
+    insert_into(InviteCodeSchema::invite_code)
+        .values(&rows)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Creates single use codes earned by `for_account`. `expected_total` is the number of routine
+/// codes the account should have afterwards, so concurrent requests can't both create codes.
+pub async fn create_account_invite_codes(
+    for_account: &String,
+    codes: Vec<String>,
+    expected_total: usize,
+    disabled: bool,
+) -> Result<Vec<CodeDetail>> {
+    use crate::schema::registry::invite_code::dsl as InviteCodeSchema;
+    let conn = &mut establish_connection()?;
+
+    let created_at = common::now();
+    let rows = codes
+        .into_iter()
+        .map(|code| models::InviteCode {
+            code,
+            available_uses: 1,
+            disabled: if disabled { 1 } else { 0 },
+            for_account: for_account.clone(),
+            created_by: for_account.clone(),
+            created_at: created_at.clone(),
+        })
+        .collect::<Vec<models::InviteCode>>();
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        insert_into(InviteCodeSchema::invite_code)
+            .values(&rows)
+            .execute(conn)?;
+        let final_routine_codes = InviteCodeSchema::invite_code
+            .filter(InviteCodeSchema::forAccount.eq(for_account))
+            .filter(InviteCodeSchema::createdBy.ne(ADMIN_CREATOR))
+            .count()
+            .get_result::<i64>(conn)?;
+        if final_routine_codes as usize > expected_total {
+            bail!("DuplicateCreate: attempted to create additional codes in another request");
+        }
+        Ok(())
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| CodeDetail {
+            code: row.code,
+            available: 1,
+            disabled: row.disabled == 1,
+            for_account: row.for_account,
+            created_by: row.created_by,
+            created_at: row.created_at,
+            uses: Vec::new(),
+        })
+        .collect())
+}
+
+pub async fn get_account_invite_codes(did: &String) -> Result<Vec<CodeDetail>> {
+    use crate::schema::registry::invite_code::dsl as InviteCodeSchema;
+    let conn = &mut establish_connection()?;
+
+    let res = InviteCodeSchema::invite_code
+        .filter(InviteCodeSchema::forAccount.eq(did))
+        .select(models::InviteCode::as_select())
+        .load(conn)?;
+    with_uses(res).await
+}
+
+pub async fn get_invite_codes_uses(codes: Vec<String>) -> Result<BTreeMap<String, Vec<CodeUse>>> {
+    use crate::schema::registry::invite_code_use::dsl as InviteCodeUseSchema;
+
+    let mut uses: BTreeMap<String, Vec<CodeUse>> = BTreeMap::new();
+    if codes.is_empty() {
+        return Ok(uses);
+    }
+    let conn = &mut establish_connection()?;
+    let res = InviteCodeUseSchema::invite_code_use
+        .filter(InviteCodeUseSchema::code.eq_any(codes))
+        .order_by(InviteCodeUseSchema::usedAt.desc())
+        .select(models::InviteCodeUse::as_select())
+        .load(conn)?;
+    for code_use in res {
+        uses.entry(code_use.code).or_default().push(CodeUse {
+            used_by: code_use.used_by,
+            used_at: code_use.used_at,
+        });
+    }
+    Ok(uses)
+}
+
+/// The code each of `dids` signed up with, if any.
+pub async fn get_invited_by_for_accounts(dids: Vec<String>) -> Result<BTreeMap<String, CodeDetail>> {
+    use crate::schema::registry::invite_code::dsl as InviteCodeSchema;
+    use crate::schema::registry::invite_code_use::dsl as InviteCodeUseSchema;
+
+    let mut invited_by: BTreeMap<String, CodeDetail> = BTreeMap::new();
+    if dids.is_empty() {
+        return Ok(invited_by);
+    }
+    let conn = &mut establish_connection()?;
+    let code_uses = InviteCodeUseSchema::invite_code_use
+        .filter(InviteCodeUseSchema::usedBy.eq_any(&dids))
+        .select(models::InviteCodeUse::as_select())
+        .load(conn)?;
+    let used_codes = code_uses
+        .iter()
+        .map(|code_use| code_use.code.clone())
+        .collect::<Vec<String>>();
+    let codes = InviteCodeSchema::invite_code
+        .filter(InviteCodeSchema::code.eq_any(used_codes))
+        .select(models::InviteCode::as_select())
+        .load(conn)?;
+    let details = with_uses(codes).await?;
+    for code_use in code_uses {
+        if let Some(detail) = details.iter().find(|detail| detail.code == code_use.code) {
+            invited_by.insert(code_use.used_by, detail.clone());
+        }
+    }
+    Ok(invited_by)
+}
+
+/// Whether the account may receive new invite codes, with the note left by the last moderator to
+/// change it.
+pub async fn get_account_invites_disabled(did: &String) -> Result<Option<(bool, Option<String>)>> {
+    use crate::schema::registry::account::dsl as AccountSchema;
+    let conn = &mut establish_connection()?;
+
+    let res = AccountSchema::account
+        .filter(AccountSchema::did.eq(did))
+        .select((AccountSchema::invitesDisabled, AccountSchema::inviteNote))
+        .first::<(i16, Option<String>)>(conn)
+        .optional()?;
+    Ok(res.map(|(invites_disabled, invite_note)| (invites_disabled == 1, invite_note)))
+}
+
+pub async fn set_account_invites_disabled(
+    did: &String,
+    disabled: bool,
+    note: Option<String>,
+) -> Result<()> {
+    use crate::schema::registry::account::dsl as AccountSchema;
+    let conn = &mut establish_connection()?;
+
+    update(AccountSchema::account)
+        .filter(AccountSchema::did.eq(did))
+        .set((
+            AccountSchema::invitesDisabled.eq(if disabled { 1 } else { 0 }),
+            AccountSchema::inviteNote.eq(note),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub async fn disable_invite_codes(codes: Vec<String>, accounts: Vec<String>) -> Result<()> {
+    use crate::schema::registry::invite_code::dsl as InviteCodeSchema;
+    let conn = &mut establish_connection()?;
+
+    if !codes.is_empty() {
+        update(InviteCodeSchema::invite_code)
+            .filter(InviteCodeSchema::code.eq_any(codes))
+            .set(InviteCodeSchema::disabled.eq(1))
+            .execute(conn)?;
+    }
+    if !accounts.is_empty() {
+        update(InviteCodeSchema::invite_code)
+            .filter(InviteCodeSchema::forAccount.eq_any(accounts))
+            .set(InviteCodeSchema::disabled.eq(1))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Lists every invite code on the server. Codes sorted by `Recent` page on a
+/// `createdAt::code` cursor; codes sorted by `Usage` page on an offset.
+pub async fn get_invite_codes(
+    sort: InviteCodesSort,
+    limit: i64,
+    cursor: Option<String>,
+) -> Result<(Vec<CodeDetail>, Option<String>)> {
+    use crate::schema::registry::invite_code::dsl as InviteCodeSchema;
+    let conn = &mut establish_connection()?;
+
+    match sort {
+        InviteCodesSort::Recent => {
+            let mut builder = InviteCodeSchema::invite_code.into_boxed();
+            if let Some(cursor) = cursor {
+                let (created_at, code) = match cursor.split_once("::") {
+                    Some(parts) => parts,
+                    None => bail!("InvalidRequest: Malformed cursor"),
+                };
+                builder = builder.filter(
+                    InviteCodeSchema::createdAt.lt(created_at.to_string()).or(
+                        InviteCodeSchema::createdAt
+                            .eq(created_at.to_string())
+                            .and(InviteCodeSchema::code.lt(code.to_string())),
+                    ),
+                );
+            }
+            let res = builder
+                .order_by((
+                    InviteCodeSchema::createdAt.desc(),
+                    InviteCodeSchema::code.desc(),
+                ))
+                .limit(limit)
+                .select(models::InviteCode::as_select())
+                .load(conn)?;
+            let cursor = res
+                .last()
+                .map(|last| format!("{}::{}", last.created_at, last.code));
+            Ok((with_uses(res).await?, cursor))
+        }
+        InviteCodesSort::Usage => {
+            let offset = match cursor {
+                None => 0,
+                Some(cursor) => match cursor.parse::<usize>() {
+                    Ok(offset) => offset,
+                    Err(_) => bail!("InvalidRequest: Malformed cursor"),
+                },
+            };
+            let res = InviteCodeSchema::invite_code
+                .select(models::InviteCode::as_select())
+                .load(conn)?;
+            let mut codes = with_uses(res).await?;
+            codes.sort_by(|a, b| {
+                b.uses
+                    .len()
+                    .cmp(&a.uses.len())
+                    .then_with(|| b.created_at.cmp(&a.created_at))
+                    .then_with(|| b.code.cmp(&a.code))
+            });
+            let page = codes
+                .into_iter()
+                .skip(offset)
+                .take(limit as usize)
+                .collect::<Vec<CodeDetail>>();
+            let cursor = if page.is_empty() {
+                None
+            } else {
+                Some((offset + page.len()).to_string())
+            };
+            Ok((page, cursor))
+        }
+    }
+}
+
+async fn with_uses(codes: Vec<models::InviteCode>) -> Result<Vec<CodeDetail>> {
+    let mut uses =
+        get_invite_codes_uses(codes.iter().map(|code| code.code.clone()).collect()).await?;
+    Ok(codes
+        .into_iter()
+        .map(|code| CodeDetail {
+            uses: uses.remove(&code.code).unwrap_or_default(),
+            code: code.code,
+            available: code.available_uses,
+            disabled: code.disabled == 1,
+            for_account: code.for_account,
+            created_by: code.created_by,
+            created_at: code.created_at,
+        })
+        .collect())
+}
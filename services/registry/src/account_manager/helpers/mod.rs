@@ -1,5 +1,10 @@
 pub mod account;
 pub mod auth;
+pub mod auth_factor;
 pub mod password;
 pub mod repo;
-pub mod email_token;
\ No newline at end of file
+pub mod session;
+pub mod email_token;
+pub mod invite;
+pub mod signing_key;
+
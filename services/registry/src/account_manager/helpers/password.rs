@@ -3,7 +3,8 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
-use rsky_pds::common::{get_random_str, now};
+use rsky_pds::auth_verifier::AuthScope;
+use rsky_pds::common::{get_random_str, now, RFC3339_VARIANT};
 use crate::database::establish_connection;
 use crate::database::models;
 use crate::database::models::AppPassword;
@@ -13,8 +14,10 @@ use argon2::{
     Argon2,
 };
 use base64ct::{Base64, Encoding};
+use chrono::{DateTime, Utc};
 use diesel::*;
-use rsky_lexicon::com::atproto::server::CreateAppPasswordOutput;
+use rsky_lexicon::com::atproto::server::{CreateAppPasswordInput, CreateAppPasswordOutput};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
 pub struct UpdateUserPasswordOpts {
@@ -22,6 +25,93 @@ pub struct UpdateUserPasswordOpts {
     pub password_encrypted: String,
 }
 
+/// Limits on what an app password may do, beyond what its `AuthScope` allows. They're carried in
+/// the claims of the tokens issued to the password's sessions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppPasswordScopes {
+    #[serde(
+        rename = "readOnly",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub read_only: bool,
+    /// Collections records may be written to, see `collection_matches`. Any if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+}
+
+impl AppPasswordScopes {
+    pub fn from_app_password(app_password: &AppPassword) -> Self {
+        AppPasswordScopes {
+            read_only: app_password.read_only == 1,
+            collections: app_password
+                .collections
+                .as_ref()
+                .map(|collections| collections.split(' ').map(str::to_string).collect()),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        !self.read_only && self.collections.is_none()
+    }
+
+    pub fn allows_collection(&self, collection: &str) -> bool {
+        if self.read_only {
+            return false;
+        }
+        match &self.collections {
+            None => true,
+            Some(patterns) => patterns
+                .iter()
+                .any(|pattern| collection_matches(pattern, collection)),
+        }
+    }
+}
+
+/// A pattern is an NSID, or the start of one followed by `*`: `gg.campground.*` matches every
+/// collection under `gg.campground`.
+fn collection_matches(pattern: &str, collection: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => collection.starts_with(prefix),
+        None => pattern == collection,
+    }
+}
+
+fn is_valid_collection_pattern(pattern: &str) -> bool {
+    let nsid = pattern.strip_suffix('*').unwrap_or(pattern);
+    // The longest an NSID can be
+    nsid.len() <= 317
+        && nsid
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        && !(pattern == nsid && nsid.is_empty())
+}
+
+/// What the tokens of a session signed in with `app_password` carry: its `AuthScope`, its limits
+/// and when it expires. Sessions signed in with the account password get full access.
+pub fn app_password_grant(
+    app_password: Option<&AppPassword>,
+) -> Result<(AuthScope, Option<AppPasswordScopes>, Option<DateTime<Utc>>)> {
+    let app_password = match app_password {
+        None => return Ok((AuthScope::Access, None, None)),
+        Some(app_password) => app_password,
+    };
+    let scope = if app_password.privileged == 1 {
+        AuthScope::AppPassPrivileged
+    } else {
+        AuthScope::AppPass
+    };
+    let expires_at = match &app_password.expires_at {
+        None => None,
+        Some(expires_at) => Some(DateTime::parse_from_rfc3339(expires_at)?.with_timezone(&Utc)),
+    };
+    Ok((
+        scope,
+        Some(AppPasswordScopes::from_app_password(app_password)),
+        expires_at,
+    ))
+}
+
 pub async fn verify_account_password(did: &String, password: &String) -> Result<bool> {
     use crate::schema::registry::account::dsl as AccountSchema;
     let conn = &mut establish_connection()?;
@@ -38,22 +128,54 @@ pub async fn verify_account_password(did: &String, password: &String) -> Result<
     }
 }
 
-pub async fn verify_app_password(did: &String, password: &String) -> Result<Option<String>> {
+/// Finds the app password of `did` that `password` belongs to, unless it has expired.
+pub async fn verify_app_password(did: &String, password: &String) -> Result<Option<AppPassword>> {
     use crate::schema::registry::app_password::dsl as AppPasswordSchema;
     let conn = &mut establish_connection()?;
 
     let password_encrypted = hash_app_password(did, password).await?;
-    let found = AppPasswordSchema::app_password
+    Ok(AppPasswordSchema::app_password
         .filter(AppPasswordSchema::did.eq(did))
         .filter(AppPasswordSchema::password.eq(password_encrypted))
+        .filter(
+            AppPasswordSchema::expiresAt
+                .is_null()
+                .or(AppPasswordSchema::expiresAt.gt(now())),
+        )
         .select(AppPassword::as_select())
         .first(conn)
-        .optional()?;
-    if let Some(found) = found {
-        Ok(Some(found.name))
-    } else {
-        Ok(None)
-    }
+        .optional()?)
+}
+
+/// Like `verify_app_password`, by name.
+pub async fn get_app_password(did: &String, name: &String) -> Result<Option<AppPassword>> {
+    use crate::schema::registry::app_password::dsl as AppPasswordSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(AppPasswordSchema::app_password
+        .filter(AppPasswordSchema::did.eq(did))
+        .filter(AppPasswordSchema::name.eq(name))
+        .filter(
+            AppPasswordSchema::expiresAt
+                .is_null()
+                .or(AppPasswordSchema::expiresAt.gt(now())),
+        )
+        .select(AppPassword::as_select())
+        .first(conn)
+        .optional()?)
+}
+
+/// Records that an app password was just used to sign in or refresh a session.
+pub async fn touch_app_password(did: &String, name: &String) -> Result<()> {
+    use crate::schema::registry::app_password::dsl as AppPasswordSchema;
+    let conn = &mut establish_connection()?;
+
+    update(AppPasswordSchema::app_password)
+        .filter(AppPasswordSchema::did.eq(did))
+        .filter(AppPasswordSchema::name.eq(name))
+        .set(AppPasswordSchema::lastUsedAt.eq(now()))
+        .execute(conn)?;
+    Ok(())
 }
 
 // We use Argon because it's 3x faster than scrypt.
@@ -93,7 +215,45 @@ pub async fn hash_app_password(did: &String, password: &String) -> Result<String
 
 /// create an app password with format:
 /// 1234-abcd-5678-efgh
-pub async fn create_app_password(did: String, name: String) -> Result<CreateAppPasswordOutput> {
+pub async fn create_app_password(
+    did: String,
+    input: CreateAppPasswordInput,
+) -> Result<CreateAppPasswordOutput> {
+    let CreateAppPasswordInput {
+        name,
+        privileged,
+        read_only,
+        collections,
+        expires_at,
+    } = input;
+    let privileged = privileged.unwrap_or(false);
+    let read_only = read_only.unwrap_or(false);
+    if let Some(collections) = &collections {
+        if collections.is_empty() {
+            bail!("InvalidRequest: `collections` can't be empty, leave it out to allow any");
+        }
+        if let Some(pattern) = collections
+            .iter()
+            .find(|pattern| !is_valid_collection_pattern(pattern))
+        {
+            bail!("InvalidRequest: `{pattern}` isn't an NSID or an NSID prefix ending in `*`");
+        }
+    }
+    // Stored like `now()`, so expiry can be checked by comparing strings
+    let expires_at = match expires_at {
+        None => None,
+        Some(expires_at) => {
+            let expires_at = match DateTime::parse_from_rfc3339(&expires_at) {
+                Ok(expires_at) => expires_at.with_timezone(&Utc),
+                Err(_) => bail!("InvalidRequest: `expiresAt` must be an RFC 3339 datetime"),
+            };
+            if expires_at <= Utc::now() {
+                bail!("InvalidRequest: `expiresAt` must be in the future");
+            }
+            Some(expires_at.format(RFC3339_VARIANT).to_string())
+        }
+    };
+
     let str = &get_random_str()[0..16].to_lowercase();
     let chunks = vec![&str[0..4], &str[4..8], &str[8..12], &str[12..16]];
     let password = chunks.join("-");
@@ -110,6 +270,10 @@ pub async fn create_app_password(did: String, name: String) -> Result<CreateAppP
             AppPasswordSchema::name.eq(&name),
             AppPasswordSchema::password.eq(password_encrypted),
             AppPasswordSchema::createdAt.eq(&created_at),
+            AppPasswordSchema::privileged.eq(if privileged { 1 } else { 0 }),
+            AppPasswordSchema::readOnly.eq(if read_only { 1 } else { 0 }),
+            AppPasswordSchema::collections.eq(collections.as_ref().map(|c| c.join(" "))),
+            AppPasswordSchema::expiresAt.eq(&expires_at),
         ))
         .returning(AppPassword::as_select())
         .get_result(conn)
@@ -119,19 +283,23 @@ pub async fn create_app_password(did: String, name: String) -> Result<CreateAppP
             name,
             password,
             created_at,
+            privileged: Some(privileged),
+            read_only: Some(read_only),
+            collections,
+            expires_at,
         })
     } else {
         bail!("could not create app-specific password")
     }
 }
 
-pub async fn list_app_passwords(did: &String) -> Result<Vec<(String, String)>> {
+pub async fn list_app_passwords(did: &String) -> Result<Vec<AppPassword>> {
     use crate::schema::registry::app_password::dsl as AppPasswordSchema;
     let conn = &mut establish_connection()?;
 
     Ok(AppPasswordSchema::app_password
         .filter(AppPasswordSchema::did.eq(did))
-        .select((AppPasswordSchema::name, AppPasswordSchema::createdAt))
+        .select(AppPassword::as_select())
         .get_results(conn)?)
 }
 
@@ -155,4 +323,34 @@ pub async fn delete_app_password(did: &String, name: &String) -> Result<()> {
         .filter(AppPasswordSchema::name.eq(name))
         .execute(conn)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_collection_patterns() {
+        let scopes = AppPasswordScopes {
+            read_only: false,
+            collections: Some(vec![
+                "gg.campground.*".to_string(),
+                "app.bsky.feed.like".to_string(),
+            ]),
+        };
+        assert!(scopes.allows_collection("gg.campground.site"));
+        assert!(scopes.allows_collection("gg.campground.site.page"));
+        assert!(scopes.allows_collection("app.bsky.feed.like"));
+        assert!(!scopes.allows_collection("app.bsky.feed.post"));
+        assert!(!scopes.allows_collection("gg.campgroundx.site"));
+        assert!(!AppPasswordScopes {
+            read_only: true,
+            collections: None,
+        }
+        .allows_collection("app.bsky.feed.like"));
+
+        assert!(is_valid_collection_pattern("gg.campground.*"));
+        assert!(!is_valid_collection_pattern("gg.*.site"));
+        assert!(!is_valid_collection_pattern(""));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::database::models::Session;
+use crate::database::{establish_connection, DbConnection};
+use anyhow::Result;
+use diesel::dsl::{exists, not};
+use diesel::*;
+use rsky_pds::common;
+
+/// Longest user agent kept for a session; anything past it is cut off.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Where a session is being used from, as recorded when it's created or refreshed.
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl SessionClient {
+    fn user_agent(&self) -> Option<String> {
+        self.user_agent
+            .as_ref()
+            .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LEN).collect())
+    }
+}
+
+/// Records a new session. Doesn't touch a session that already exists with `id`, which happens
+/// for tokens issued before sessions were recorded: their session takes the first token's id.
+pub async fn create_session(
+    id: &String,
+    did: &String,
+    app_password_name: Option<String>,
+    client: &SessionClient,
+) -> Result<()> {
+    let conn = &mut establish_connection()?;
+    insert_session(conn, id, did, app_password_name, client)
+}
+
+/// Starts a session for a refresh token issued before sessions were recorded, named after the
+/// token. The token is attached in the same transaction, so the janitor can't see the session
+/// without it and sweep it away.
+pub async fn create_session_for_refresh_token(
+    token_id: &String,
+    did: &String,
+    app_password_name: Option<String>,
+    client: &SessionClient,
+) -> Result<()> {
+    use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        update(RefreshTokenSchema::refresh_token)
+            .filter(RefreshTokenSchema::id.eq(token_id))
+            .set(RefreshTokenSchema::sessionId.eq(token_id))
+            .execute(conn)?;
+        insert_session(conn, token_id, did, app_password_name, client)
+    })
+}
+
+fn insert_session(
+    conn: &mut DbConnection,
+    id: &String,
+    did: &String,
+    app_password_name: Option<String>,
+    client: &SessionClient,
+) -> Result<()> {
+    use crate::schema::registry::session::dsl as SessionSchema;
+
+    let now = common::now();
+    insert_into(SessionSchema::session)
+        .values(Session {
+            id: id.clone(),
+            did: did.clone(),
+            app_password_name,
+            user_agent: client.user_agent(),
+            ip_address: client.ip_address.clone(),
+            created_at: now.clone(),
+            last_used_at: now,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Marks a session used by `client` now.
+pub async fn touch_session(id: &String, client: &SessionClient) -> Result<()> {
+    use crate::schema::registry::session::dsl as SessionSchema;
+    let conn = &mut establish_connection()?;
+
+    update(SessionSchema::session)
+        .filter(SessionSchema::id.eq(id))
+        .set((
+            SessionSchema::userAgent.eq(client.user_agent()),
+            SessionSchema::ipAddress.eq(&client.ip_address),
+            SessionSchema::lastUsedAt.eq(common::now()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub async fn session_exists(id: &String, did: &String) -> Result<bool> {
+    use crate::schema::registry::session::dsl as SessionSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(select(exists(
+        SessionSchema::session
+            .filter(SessionSchema::id.eq(id))
+            .filter(SessionSchema::did.eq(did)),
+    ))
+    .get_result(conn)?)
+}
+
+/// Sessions of `did` that can still be refreshed, most recently used first.
+pub async fn list_sessions(did: &String) -> Result<Vec<Session>> {
+    use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::registry::session::dsl as SessionSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(SessionSchema::session
+        .filter(SessionSchema::did.eq(did))
+        .filter(exists(
+            RefreshTokenSchema::refresh_token
+                .filter(RefreshTokenSchema::sessionId.eq(SessionSchema::id.nullable()))
+                .filter(RefreshTokenSchema::expiresAt.gt(common::now())),
+        ))
+        .order(SessionSchema::lastUsedAt.desc())
+        .select(Session::as_select())
+        .load(conn)?)
+}
+
+/// Deletes a session of `did` with its refresh tokens. Returns whether there was one.
+pub async fn revoke_session(did: &String, id: &String) -> Result<bool> {
+    use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::registry::session::dsl as SessionSchema;
+    let conn = &mut establish_connection()?;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        delete(RefreshTokenSchema::refresh_token)
+            .filter(RefreshTokenSchema::did.eq(did))
+            .filter(RefreshTokenSchema::sessionId.eq(id))
+            .execute(conn)?;
+        let deleted = delete(SessionSchema::session)
+            .filter(SessionSchema::did.eq(did))
+            .filter(SessionSchema::id.eq(id))
+            .execute(conn)?;
+        Ok(deleted > 0)
+    })
+}
+
+/// Removes sessions whose refresh tokens have all expired or been deleted, for the janitor.
+pub async fn delete_orphaned_sessions() -> Result<usize> {
+    use crate::schema::registry::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::registry::session::dsl as SessionSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(delete(SessionSchema::session)
+        .filter(not(exists(RefreshTokenSchema::refresh_token.filter(
+            RefreshTokenSchema::sessionId.eq(SessionSchema::id.nullable()),
+        ))))
+        .execute(conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_manager::helpers::auth::{self, CreateTokensOpts};
+    use crate::account_manager::AccountManager;
+    use crate::auth_verifier::verify_jwt;
+    use crate::config::SERVICE_CONFIG;
+    use crate::database::models::OAuthToken;
+    use crate::oauth::{self, store as oauth_store};
+    use crate::signer::{Signer, PDS_SIGNER};
+
+    fn new_did() -> String {
+        format!("did:example:{}", common::get_random_str())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn creates_lists_and_revokes_sessions() {
+        let did = new_did();
+        let client = SessionClient {
+            user_agent: Some("a".repeat(MAX_USER_AGENT_LEN * 2)),
+            ip_address: Some("192.0.2.1".to_string()),
+        };
+        let (access_jwt, _) = AccountManager::create_session(did.clone(), None, &client)
+            .await
+            .unwrap();
+        AccountManager::create_session(did.clone(), None, &SessionClient::default())
+            .await
+            .unwrap();
+
+        let sessions = list_sessions(&did).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        let session = sessions
+            .iter()
+            .find(|session| session.ip_address == client.ip_address)
+            .unwrap();
+        assert_eq!(
+            session.user_agent.as_ref().map(String::len),
+            Some(MAX_USER_AGENT_LEN)
+        );
+        assert!(session_exists(&session.id, &did).await.unwrap());
+        assert!(
+            verify_jwt(access_jwt.clone(), &PDS_SIGNER.public_key(), None)
+                .await
+                .is_ok()
+        );
+
+        // Only the account the session belongs to can revoke it
+        assert!(!revoke_session(&new_did(), &session.id).await.unwrap());
+        assert!(revoke_session(&did, &session.id).await.unwrap());
+        assert!(!revoke_session(&did, &session.id).await.unwrap());
+        assert_eq!(list_sessions(&did).await.unwrap().len(), 1);
+        assert!(!session_exists(&session.id, &did).await.unwrap());
+        // Its access token stops working before it expires
+        assert!(verify_jwt(access_jwt, &PDS_SIGNER.public_key(), None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn starts_sessions_for_tokens_issued_without_one() {
+        let did = new_did();
+        let (_, refresh_jwt) = auth::create_tokens(CreateTokensOpts {
+            did: did.clone(),
+            signer: PDS_SIGNER.clone(),
+            service_did: SERVICE_CONFIG.did.clone(),
+            scope: None,
+            jti: None,
+            expires_in: None,
+            session_id: None,
+            app_password_scopes: None,
+            valid_until: None,
+        })
+        .await
+        .unwrap();
+        let payload = auth::decode_refresh_token(refresh_jwt, &PDS_SIGNER.public_key()).unwrap();
+        let token_id = payload.jti.clone();
+        auth::store_refresh_token(payload, None).await.unwrap();
+
+        let client = SessionClient {
+            user_agent: Some("legacy".to_string()),
+            ip_address: None,
+        };
+        assert!(AccountManager::rotate_refresh_token(&token_id, &client)
+            .await
+            .unwrap()
+            .is_some());
+        let token = auth::get_refresh_token(&token_id).await.unwrap().unwrap();
+        assert_eq!(token.session_id.as_ref(), Some(&token_id));
+
+        delete_orphaned_sessions().await.unwrap();
+        let sessions = list_sessions(&did).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, token_id);
+        assert_eq!(sessions[0].user_agent.as_deref(), Some("legacy"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn lists_and_revokes_oauth_grants_with_sessions() {
+        let did = new_did();
+        let grant = |client_id: &str| OAuthToken {
+            id: oauth::random_id("tok"),
+            did: did.clone(),
+            client_id: client_id.to_string(),
+            client_auth: "none".to_string(),
+            scope: "atproto".to_string(),
+            dpop_jkt: "jkt".to_string(),
+            refresh_token: oauth::random_id("ref"),
+            created_at: common::now(),
+            expires_at: oauth_store::expires_in(60),
+        };
+        let (first, second) = (grant("https://a.example"), grant("https://b.example"));
+        oauth_store::create_token(first.clone()).await.unwrap();
+        oauth_store::create_token(second.clone()).await.unwrap();
+        AccountManager::create_session(did.clone(), None, &SessionClient::default())
+            .await
+            .unwrap();
+
+        let (sessions, grants) = AccountManager::list_sessions(&did).await.unwrap();
+        assert_eq!((sessions.len(), grants.len()), (1, 2));
+
+        // Grants are revoked by id like sessions, but only by the account that gave them
+        assert!(!AccountManager::revoke_session(&new_did(), &first.id)
+            .await
+            .unwrap());
+        assert!(AccountManager::revoke_session(&did, &first.id)
+            .await
+            .unwrap());
+        assert!(oauth_store::get_token(&first.id).await.unwrap().is_none());
+
+        assert!(AccountManager::revoke_all_sessions(&did).await.unwrap());
+        let (sessions, grants) = AccountManager::list_sessions(&did).await.unwrap();
+        assert!(sessions.is_empty() && grants.is_empty());
+        assert!(oauth_store::get_token(&second.id).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::api::com::atproto::server::encode_did_key;
+use crate::config::SECRET_CONFIG;
+use crate::database::establish_connection;
+use crate::database::models::SigningKey;
+use crate::signer::{LocalSigner, Signer, REPO_SIGNER};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Result};
+use diesel::*;
+use rand::RngCore;
+use rsky_pds::common;
+use secp256k1::{Secp256k1, SecretKey};
+use std::sync::Arc;
+
+/// Private keys are encrypted with AES-256-GCM under `secret.keystore_key`. The key's did:key
+/// is bound in as associated data, so a ciphertext can't be moved onto another key's row. TOTP
+/// secrets are kept under the same key.
+pub fn keystore_cipher() -> Result<Aes256Gcm> {
+    Aes256Gcm::new_from_slice(&hex::decode(SECRET_CONFIG.keystore_key.as_bytes())?)
+        .map_err(|_| anyhow!("Keystore key must be 32 hex-encoded bytes"))
+}
+
+/// Returns the hex-encoded nonce and ciphertext.
+fn seal(cipher: &Aes256Gcm, secret_key: &SecretKey, key_did: &String) -> Result<(String, String)> {
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &secret_key.secret_bytes(),
+                aad: key_did.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("Failed to encrypt signing key `{key_did}`"))?;
+    Ok((hex::encode(nonce), hex::encode(ciphertext)))
+}
+
+/// Decrypts a stored key. Rows without a nonce predate the keystore and hold the key in plain
+/// hex until `encrypt_plaintext_signing_keys` gets to them.
+fn open(cipher: &Aes256Gcm, row: &SigningKey) -> Result<SecretKey> {
+    let key_nonce = match &row.key_nonce {
+        None => {
+            return Ok(SecretKey::from_slice(&hex::decode(
+                row.private_key.as_bytes(),
+            )?)?)
+        }
+        Some(key_nonce) => key_nonce,
+    };
+    let secret_bytes = cipher
+        .decrypt(
+            Nonce::from_slice(&hex::decode(key_nonce.as_bytes())?),
+            Payload {
+                msg: &hex::decode(row.private_key.as_bytes())?,
+                aad: row.key_did.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("Failed to decrypt signing key `{0}`", row.key_did))?;
+    Ok(SecretKey::from_slice(&secret_bytes)?)
+}
+
+/// Without a DID, the key is stored under its own did:key, as staged keys are.
+fn signing_key_row(
+    cipher: &Aes256Gcm,
+    did: Option<String>,
+    secret_key: &SecretKey,
+) -> Result<SigningKey> {
+    let secp = Secp256k1::new();
+    let key_did = encode_did_key(&secret_key.public_key(&secp));
+    let (key_nonce, private_key) = seal(cipher, secret_key, &key_did)?;
+    Ok(SigningKey {
+        did: did.unwrap_or(key_did.clone()),
+        key_did,
+        private_key,
+        key_nonce: Some(key_nonce),
+        created_at: common::now(),
+        pending_for: None,
+    })
+}
+
+/// Generates a new repo signing key for `did`, which is migrating here, or returns the key
+/// already reserved for it. `createAccount` signs with whatever is reserved, so a DID that
+/// already has an account here can't reserve one: it would take over from the key the account
+/// signs with. A key reserved without a DID could never be claimed, so one is required.
+pub async fn reserve_signing_key(did: Option<String>) -> Result<String> {
+    use crate::schema::registry::actor::dsl as ActorSchema;
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    let Some(did) = did else {
+        bail!("InvalidRequest: A DID is required to reserve a signing key")
+    };
+    let has_account = select(exists(ActorSchema::actor.filter(ActorSchema::did.eq(&did))))
+        .get_result::<bool>(conn)?;
+    if has_account {
+        bail!("InvalidRequest: `{did}` already has an account on this server")
+    }
+    let existing = SigningKeySchema::signing_key
+        .filter(SigningKeySchema::did.eq(&did))
+        .select(SigningKey::as_select())
+        .first(conn)
+        .optional()?;
+    if let Some(existing) = existing {
+        return Ok(existing.key_did);
+    }
+
+    let secret_key = SecretKey::new(&mut rand::thread_rng());
+    let row = signing_key_row(&keystore_cipher()?, Some(did), &secret_key)?;
+    let key_did = row.key_did.clone();
+    insert_into(SigningKeySchema::signing_key)
+        .values(row)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(key_did)
+}
+
+/// Removes keys reserved before `cutoff` for DIDs that never created an account. A migration
+/// creates its account soon after reserving, so ones that have sat around this long never will.
+/// Staged keys are left alone.
+pub async fn delete_unclaimed_signing_keys(cutoff: &String) -> Result<usize> {
+    delete_unclaimed_signing_keys_for(cutoff, None)
+}
+
+fn delete_unclaimed_signing_keys_for(cutoff: &String, did: Option<&String>) -> Result<usize> {
+    use crate::schema::registry::actor::dsl as ActorSchema;
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    let mut builder = delete(SigningKeySchema::signing_key)
+        .filter(SigningKeySchema::pendingFor.is_null())
+        .filter(SigningKeySchema::createdAt.lt(cutoff))
+        .filter(not(exists(
+            ActorSchema::actor.filter(ActorSchema::did.eq(SigningKeySchema::did)),
+        )))
+        .into_boxed();
+    if let Some(did) = did {
+        builder = builder.filter(SigningKeySchema::did.eq(did));
+    }
+    Ok(builder.execute(conn)?)
+}
+
+/// Stores `secret_key` as the signing key for `did`, replacing any key it had. Returns the
+/// key's did:key.
+pub async fn store_signing_key(did: &String, secret_key: &SecretKey) -> Result<String> {
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    let row = signing_key_row(&keystore_cipher()?, Some(did.clone()), secret_key)?;
+    let key_did = row.key_did.clone();
+    insert_into(SigningKeySchema::signing_key)
+        .values(&row)
+        .on_conflict(SigningKeySchema::did)
+        .do_update()
+        .set((
+            SigningKeySchema::keyDid.eq(&row.key_did),
+            SigningKeySchema::privateKey.eq(&row.private_key),
+            SigningKeySchema::keyNonce.eq(&row.key_nonce),
+            SigningKeySchema::createdAt.eq(&row.created_at),
+        ))
+        .execute(conn)?;
+    Ok(key_did)
+}
+
+/// Saves a key a rotation is going to give `did`, before it's published anywhere, so it can't be
+/// lost whatever happens to the rotation. Until `activate_signing_key`, it's stored under its own
+/// did:key and `did` keeps signing with its current key.
+pub async fn stage_signing_key(did: &String, secret_key: &SecretKey) -> Result<String> {
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    let row = SigningKey {
+        pending_for: Some(did.clone()),
+        ..signing_key_row(&keystore_cipher()?, None, secret_key)?
+    };
+    let key_did = row.key_did.clone();
+    insert_into(SigningKeySchema::signing_key)
+        .values(row)
+        .execute(conn)?;
+    Ok(key_did)
+}
+
+/// The key staged for `did` by a rotation that hasn't switched over to it yet.
+pub async fn get_staged_signing_key(did: &String) -> Result<Option<SecretKey>> {
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    let found = SigningKeySchema::signing_key
+        .filter(SigningKeySchema::pendingFor.eq(did))
+        .order(SigningKeySchema::createdAt.desc())
+        .select(SigningKey::as_select())
+        .first(conn)
+        .optional()?;
+    match found {
+        None => Ok(None),
+        Some(found) => Ok(Some(open(&keystore_cipher()?, &found)?)),
+    }
+}
+
+/// Switches `did` over to the key staged for it as `key_did`, dropping the key it had.
+pub async fn activate_signing_key(did: &String, key_did: &String) -> Result<()> {
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        delete(SigningKeySchema::signing_key)
+            .filter(SigningKeySchema::did.eq(did))
+            .execute(conn)?;
+        let updated = update(SigningKeySchema::signing_key)
+            .filter(SigningKeySchema::did.eq(key_did))
+            .filter(SigningKeySchema::pendingFor.eq(did))
+            .set((
+                SigningKeySchema::did.eq(did),
+                SigningKeySchema::pendingFor.eq(None::<String>),
+            ))
+            .execute(conn)?;
+        if updated != 1 {
+            bail!("No key `{key_did}` is staged for `{did}`")
+        }
+        Ok(())
+    })
+}
+
+pub async fn get_signing_key(did: &String) -> Result<Option<SecretKey>> {
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    let found = SigningKeySchema::signing_key
+        .filter(SigningKeySchema::did.eq(did))
+        .select(SigningKey::as_select())
+        .first(conn)
+        .optional()?;
+    match found {
+        None => Ok(None),
+        Some(found) => Ok(Some(open(&keystore_cipher()?, &found)?)),
+    }
+}
+
+/// The key used to sign `did`'s commits and service auth: its own key if it has one, otherwise
+/// the server-wide repo signing key. Accounts created before per-account keys fall back to the
+/// latter until their key is rotated.
+pub async fn get_signer(did: &String) -> Result<Arc<dyn Signer>> {
+    match get_signing_key(did).await? {
+        Some(secret_key) => Ok(Arc::new(LocalSigner::new(secret_key))),
+        None => Ok(REPO_SIGNER.clone()),
+    }
+}
+
+/// Encrypts keys stored before the keystore existed. Returns how many were encrypted.
+pub async fn encrypt_plaintext_signing_keys() -> Result<usize> {
+    use crate::schema::registry::signing_key::dsl as SigningKeySchema;
+    let conn = &mut establish_connection()?;
+
+    let rows: Vec<SigningKey> = SigningKeySchema::signing_key
+        .filter(SigningKeySchema::keyNonce.is_null())
+        .select(SigningKey::as_select())
+        .load(conn)?;
+    let cipher = keystore_cipher()?;
+    for row in &rows {
+        let (key_nonce, private_key) = seal(&cipher, &open(&cipher, row)?, &row.key_did)?;
+        update(SigningKeySchema::signing_key)
+            .filter(SigningKeySchema::did.eq(&row.did))
+            .filter(SigningKeySchema::keyNonce.is_null())
+            .set((
+                SigningKeySchema::privateKey.eq(private_key),
+                SigningKeySchema::keyNonce.eq(key_nonce),
+            ))
+            .execute(conn)?;
+    }
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> Aes256Gcm {
+        Aes256Gcm::new(&Aes256Gcm::generate_key(&mut rand::thread_rng()))
+    }
+
+    fn row_for(cipher: &Aes256Gcm, secret_key: &SecretKey) -> SigningKey {
+        signing_key_row(cipher, Some("did:example:alice".to_string()), secret_key).unwrap()
+    }
+
+    #[test]
+    fn opens_sealed_keys() {
+        let cipher = cipher();
+        let secret_key = SecretKey::new(&mut rand::thread_rng());
+        let row = row_for(&cipher, &secret_key);
+        assert_ne!(row.private_key, hex::encode(secret_key.secret_bytes()));
+        assert_eq!(open(&cipher, &row).unwrap(), secret_key);
+
+        let legacy = SigningKey {
+            private_key: hex::encode(secret_key.secret_bytes()),
+            key_nonce: None,
+            ..row
+        };
+        assert_eq!(open(&cipher, &legacy).unwrap(), secret_key);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn signs_migrated_accounts_with_their_reserved_key() {
+        let did = format!("did:plc:{}", common::get_random_str());
+        let reserved = reserve_signing_key(Some(did.clone())).await.unwrap();
+        // createAccount reserves again for the DID, which hands back the same key
+        assert_eq!(
+            reserve_signing_key(Some(did.clone())).await.unwrap(),
+            reserved
+        );
+        assert_eq!(get_signer(&did).await.unwrap().did_key(), reserved);
+
+        assert!(reserve_signing_key(None).await.is_err());
+
+        let unclaimed = format!("did:plc:{}", common::get_random_str());
+        reserve_signing_key(Some(unclaimed.clone())).await.unwrap();
+        delete_unclaimed_signing_keys_for(&"9999".to_string(), Some(&unclaimed)).unwrap();
+        assert!(get_signing_key(&unclaimed).await.unwrap().is_none());
+
+        let staged = stage_signing_key(&did, &SecretKey::new(&mut rand::thread_rng()))
+            .await
+            .unwrap();
+        delete_unclaimed_signing_keys_for(&"9999".to_string(), Some(&did)).unwrap();
+        assert!(get_staged_signing_key(&did).await.unwrap().is_some());
+        // The migration never created its account, so its reservation went with the sweep
+        assert!(get_signing_key(&did).await.unwrap().is_none());
+
+        activate_signing_key(&did, &staged).await.unwrap();
+        assert_eq!(get_signer(&did).await.unwrap().did_key(), staged);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn refuses_reservations_for_existing_accounts() {
+        use crate::schema::registry::actor::dsl as ActorSchema;
+        let did = format!("did:plc:{}", common::get_random_str());
+        insert_into(ActorSchema::actor)
+            .values((
+                ActorSchema::did.eq(&did),
+                ActorSchema::createdAt.eq(common::now()),
+            ))
+            .execute(&mut establish_connection().unwrap())
+            .unwrap();
+
+        // The account signs with the repo signing key, which a reservation would take over from
+        assert!(reserve_signing_key(Some(did.clone())).await.is_err());
+        assert!(get_signing_key(&did).await.unwrap().is_none());
+        assert_eq!(
+            get_signer(&did).await.unwrap().did_key(),
+            REPO_SIGNER.did_key()
+        );
+    }
+
+    #[test]
+    fn rejects_keys_moved_between_rows() {
+        let cipher = cipher();
+        let row = row_for(&cipher, &SecretKey::new(&mut rand::thread_rng()));
+        let other = row_for(&cipher, &SecretKey::new(&mut rand::thread_rng()));
+        let moved = SigningKey {
+            private_key: row.private_key,
+            key_nonce: row.key_nonce,
+            ..other
+        };
+        assert!(open(&cipher, &moved).is_err());
+    }
+}
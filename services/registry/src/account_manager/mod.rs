@@ -12,21 +12,30 @@ use crate::account_manager::helpers::auth::{
 };
 use crate::account_manager::helpers::password::UpdateUserPasswordOpts;
 use crate::account_manager::helpers::repo;
-use crate::config::{SECRET_CONFIG, SERVICE_CONFIG};
+use crate::account_manager::helpers::session::SessionClient;
+use crate::config::SERVICE_CONFIG;
+use crate::database::establish_connection;
+use crate::oauth::store as oauth_store;
+use crate::signer::{Signer, PDS_SIGNER};
 use rsky_pds::auth_verifier::AuthScope;
 use rsky_pds::common;
 use rsky_pds::common::time::{from_millis_to_str, from_str_to_millis};
 use rsky_pds::common::RFC3339_VARIANT;
-use crate::database::models::EmailTokenPurpose;
+use crate::database::models::{AppPassword, EmailTokenPurpose, OAuthToken, Session};
 use anyhow::Result;
 use chrono::offset::Utc as UtcOffset;
 use chrono::DateTime;
+use diesel::Connection;
 use futures::try_join;
-use helpers::{account, auth, email_token, password};
+use helpers::{account, auth, auth_factor, email_token, invite, password, session, signing_key};
 use libipld::Cid;
 use rsky_lexicon::com::atproto::admin::StatusAttr;
-use rsky_lexicon::com::atproto::server::CreateAppPasswordOutput;
-use secp256k1::{Keypair, Secp256k1, SecretKey};
+use rsky_lexicon::com::atproto::server::{
+    AccountCodes, CreateAppPasswordInput, CreateAppPasswordOutput, InviteCode,
+};
+use secp256k1::SecretKey;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::SystemTime;
 
 /// Helps with readability when calling create_account()
@@ -38,7 +47,9 @@ pub struct CreateAccountOpts {
     pub password: Option<String>,
     pub repo_cid: Cid,
     pub repo_rev: String,
+    pub invite_code: Option<String>,
     pub deactivated: Option<bool>,
+    pub client: SessionClient,
 }
 
 #[derive(Debug, Clone)]
@@ -117,33 +128,42 @@ impl AccountManager {
             password,
             repo_cid,
             repo_rev,
+            invite_code,
             deactivated,
+            client,
         } = opts;
         let password_encrypted: Option<String> = match password {
             Some(password) => Some(password::gen_salt_and_hash(password)?),
             None => None,
         };
-        // Should be a global var so this only happens once
-        let secp = Secp256k1::new();
-        let private_key = SECRET_CONFIG.pds_private_key.clone();
-        let secret_key =
-            SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-        let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
+        let session_id = auth::get_refresh_token_id();
         let (access_jwt, refresh_jwt) = auth::create_tokens(auth::CreateTokensOpts {
             did: did.clone(),
-            jwt_key,
+            signer: PDS_SIGNER.clone(),
             service_did: SERVICE_CONFIG.did.clone(),
             scope: Some(AuthScope::Access),
             jti: None,
             expires_in: None,
+            session_id: Some(session_id.clone()),
+            app_password_scopes: None,
+            valid_until: None,
+        })
+        .await?;
+        let refresh_payload =
+            auth::decode_refresh_token(refresh_jwt.clone(), &PDS_SIGNER.public_key())?;
+
+        // The invite is checked again as it's used, so the account is only created if it's
+        // still available
+        let conn = &mut establish_connection()?;
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            account::register_actor(conn, did.clone(), handle, deactivated)?;
+            if let (Some(email), Some(password_encrypted)) = (email, password_encrypted) {
+                account::register_account(conn, did.clone(), email, password_encrypted)?;
+            }
+            invite::record_invite_use(conn, &did, invite_code, &common::now())
         })?;
-        let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), jwt_key)?;
-
-        account::register_actor(did.clone(), handle, deactivated)?;
-        if let (Some(email), Some(password_encrypted)) = (email, password_encrypted) {
-            account::register_account(did.clone(), email, password_encrypted)?;
-        }
         auth::store_refresh_token(refresh_payload, None).await?;
+        session::create_session(&session_id, &did, None, &client).await?;
         repo::update_root(did, repo_cid, repo_rev)?;
         Ok((access_jwt, refresh_jwt))
     }
@@ -163,9 +183,10 @@ impl AccountManager {
     }
 
     pub async fn takedown_account(did: &String, takedown: StatusAttr) -> Result<()> {
-        (_, _) = try_join!(
+        (_, _, _) = try_join!(
             account::update_account_takedown_status(did, takedown),
-            auth::revoke_refresh_tokens_by_did(did)
+            auth::revoke_refresh_tokens_by_did(did),
+            oauth_store::delete_tokens_by_did(did)
         )?;
         Ok(())
     }
@@ -203,32 +224,40 @@ impl AccountManager {
     // ----------
     pub async fn create_session(
         did: String,
-        app_password_name: Option<String>,
+        app_password: Option<AppPassword>,
+        client: &SessionClient,
     ) -> Result<(String, String)> {
-        let secp = Secp256k1::new();
-        let private_key = SECRET_CONFIG.pds_private_key.clone();
-        let secret_key =
-            SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-        let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
-        let scope = if app_password_name.is_none() {
-            AuthScope::Access
-        } else {
-            AuthScope::AppPass
-        };
+        let (scope, app_password_scopes, valid_until) =
+            password::app_password_grant(app_password.as_ref())?;
+        let app_password_name = app_password.map(|app_password| app_password.name);
+        let session_id = auth::get_refresh_token_id();
         let (access_jwt, refresh_jwt) = auth::create_tokens(CreateTokensOpts {
-            did,
-            jwt_key,
+            did: did.clone(),
+            signer: PDS_SIGNER.clone(),
             service_did: SERVICE_CONFIG.did.clone(),
             scope: Some(scope),
             jti: None,
             expires_in: None,
-        })?;
-        let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), jwt_key)?;
-        auth::store_refresh_token(refresh_payload, app_password_name).await?;
+            session_id: Some(session_id.clone()),
+            app_password_scopes,
+            valid_until,
+        })
+        .await?;
+        let refresh_payload =
+            auth::decode_refresh_token(refresh_jwt.clone(), &PDS_SIGNER.public_key())?;
+        // The refresh token goes in first, so the janitor never sees the session without one
+        auth::store_refresh_token(refresh_payload, app_password_name.clone()).await?;
+        session::create_session(&session_id, &did, app_password_name.clone(), client).await?;
+        if let Some(app_password_name) = app_password_name {
+            password::touch_app_password(&did, &app_password_name).await?;
+        }
         Ok((access_jwt, refresh_jwt))
     }
 
-    pub async fn rotate_refresh_token(id: &String) -> Result<Option<(String, String)>> {
+    pub async fn rotate_refresh_token(
+        id: &String,
+        client: &SessionClient,
+    ) -> Result<Option<(String, String)>> {
         let token = auth::get_refresh_token(id).await?;
         if let Some(token) = token {
             let system_time = SystemTime::now();
@@ -257,43 +286,71 @@ impl AccountManager {
                 return Ok(None);
             }
 
+            // Tokens are reissued with the app password's current limits. One that was revoked
+            // or has expired takes its sessions with it.
+            let app_password = match &token.app_password_name {
+                None => None,
+                Some(name) => match password::get_app_password(&token.did, name).await? {
+                    None => return Ok(None),
+                    Some(app_password) => Some(app_password),
+                },
+            };
+            let (scope, app_password_scopes, valid_until) =
+                password::app_password_grant(app_password.as_ref())?;
+
             // Determine the next refresh token id: upon refresh token
             // reuse you always receive a refresh token with the same id.
             let next_id = token
                 .next_id
                 .unwrap_or_else(|| auth::get_refresh_token_id());
 
-            let secp = Secp256k1::new();
-            let private_key = SECRET_CONFIG.pds_private_key.clone();
-            let secret_key =
-                SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-            let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
+            // Tokens from before sessions were recorded start one named after themselves
+            let session_id = match token.session_id {
+                Some(session_id) => session_id,
+                None => {
+                    session::create_session_for_refresh_token(
+                        &token.id,
+                        &token.did,
+                        token.app_password_name.clone(),
+                        client,
+                    )
+                    .await?;
+                    token.id.clone()
+                }
+            };
 
             let (access_jwt, refresh_jwt) = auth::create_tokens(CreateTokensOpts {
-                did: token.did,
-                jwt_key,
+                did: token.did.clone(),
+                signer: PDS_SIGNER.clone(),
                 service_did: SERVICE_CONFIG.did.clone(),
-                scope: Some(if token.app_password_name.is_none() {
-                    AuthScope::Access
-                } else {
-                    AuthScope::AppPass
-                }),
+                scope: Some(scope),
                 jti: Some(next_id.clone()),
                 expires_in: None,
-            })?;
-            let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), jwt_key)?;
+                session_id: Some(session_id.clone()),
+                app_password_scopes,
+                valid_until,
+            })
+            .await?;
+            let refresh_payload =
+                auth::decode_refresh_token(refresh_jwt.clone(), &PDS_SIGNER.public_key())?;
             match try_join!(
                 auth::add_refresh_grace_period(RefreshGracePeriodOpts {
                     id: id.clone(),
                     expires_at: from_millis_to_str(expires_at),
                     next_id
                 }),
-                auth::store_refresh_token(refresh_payload, token.app_password_name)
+                auth::store_refresh_token(refresh_payload, token.app_password_name.clone())
             ) {
-                Ok(_) => Ok(Some((access_jwt, refresh_jwt))),
+                Ok(_) => {
+                    session::touch_session(&session_id, client).await?;
+                    if let Some(app_password) = app_password {
+                        password::touch_app_password(&token.did, &app_password.name).await?;
+                    }
+                    Ok(Some((access_jwt, refresh_jwt)))
+                }
                 Err(e) => match e.downcast_ref() {
                     Some(AuthHelperError::ConcurrentRefresh) => {
-                        Box::pin(Self::rotate_refresh_token(id)).await
+                        Box::pin(Self::rotate_refresh_token(id, client)).await
                     }
                     _ => Err(e),
                 },
@@ -307,14 +364,47 @@ impl AccountManager {
         auth::revoke_refresh_token(id).await
     }
 
+    // Sessions
+    // ----------
+    pub async fn session_exists(id: &String, did: &String) -> Result<bool> {
+        session::session_exists(id, did).await
+    }
+
+    /// Sessions signed in to `did`, and the OAuth grants it has given clients. Both are signed
+    /// out by `revoke_session` with their id.
+    pub async fn list_sessions(did: &String) -> Result<(Vec<Session>, Vec<OAuthToken>)> {
+        try_join!(
+            session::list_sessions(did),
+            oauth_store::list_tokens_by_did(did)
+        )
+    }
+
+    pub async fn revoke_session(did: &String, id: &String) -> Result<bool> {
+        if session::revoke_session(did, id).await? {
+            return Ok(true);
+        }
+        oauth_store::delete_token_by_did(did, id).await
+    }
+
+    pub async fn revoke_all_sessions(did: &String) -> Result<bool> {
+        let (revoked, grants) = try_join!(
+            auth::revoke_refresh_tokens_by_did(did),
+            oauth_store::delete_tokens_by_did(did)
+        )?;
+        Ok(revoked || grants > 0)
+    }
+
     // Passwords
     // ----------
 
-    pub async fn create_app_password(did: String, name: String) -> Result<CreateAppPasswordOutput> {
-        password::create_app_password(did, name).await
+    pub async fn create_app_password(
+        did: String,
+        input: CreateAppPasswordInput,
+    ) -> Result<CreateAppPasswordOutput> {
+        password::create_app_password(did, input).await
     }
 
-    pub async fn list_app_passwords(did: &String) -> Result<Vec<(String, String)>> {
+    pub async fn list_app_passwords(did: &String) -> Result<Vec<AppPassword>> {
         password::list_app_passwords(did).await
     }
 
@@ -325,7 +415,7 @@ impl AccountManager {
     pub async fn verify_app_password(
         did: &String,
         password_str: &String,
-    ) -> Result<Option<String>> {
+    ) -> Result<Option<AppPassword>> {
         password::verify_app_password(did, password_str).await
     }
 
@@ -398,6 +488,134 @@ impl AccountManager {
     pub async fn create_email_token(did: &String, purpose: EmailTokenPurpose) -> Result<String> {
         email_token::create_email_token(did, purpose).await
     }
+
+    pub async fn delete_email_token(did: &String, purpose: EmailTokenPurpose) -> Result<()> {
+        email_token::delete_email_token(did, purpose).await
+    }
+
+    // Auth Factors
+    // ----------
+    pub async fn get_email_auth_factor(did: &String) -> Result<bool> {
+        auth_factor::get_email_auth_factor(did).await
+    }
+
+    pub async fn set_email_auth_factor(did: &String, enabled: bool) -> Result<()> {
+        auth_factor::set_email_auth_factor(did, enabled).await
+    }
+
+    pub async fn has_totp(did: &String) -> Result<bool> {
+        auth_factor::has_totp(did).await
+    }
+
+    pub async fn create_totp(did: &String) -> Result<String> {
+        auth_factor::create_totp(did).await
+    }
+
+    pub async fn confirm_totp(did: &String, code: &String) -> Result<Vec<String>> {
+        auth_factor::confirm_totp(did, code).await
+    }
+
+    pub async fn verify_totp(did: &String, code: &String) -> Result<bool> {
+        auth_factor::verify_totp(did, code).await
+    }
+
+    pub async fn delete_totp(did: &String) -> Result<()> {
+        auth_factor::delete_totp(did).await
+    }
+
+    pub async fn use_recovery_code(did: &String, code: &String) -> Result<bool> {
+        auth_factor::use_recovery_code(did, code).await
+    }
+
+    // Invites
+    // ----------
+
+    pub async fn ensure_invite_is_available(invite_code: &String) -> Result<()> {
+        invite::ensure_invite_is_available(invite_code).await
+    }
+
+    pub async fn create_invite_codes(to_create: Vec<AccountCodes>, use_count: i32) -> Result<()> {
+        invite::create_invite_codes(to_create, use_count).await
+    }
+
+    pub async fn create_account_invite_codes(
+        for_account: &String,
+        codes: Vec<String>,
+        expected_total: usize,
+        disabled: bool,
+    ) -> Result<Vec<InviteCode>> {
+        invite::create_account_invite_codes(for_account, codes, expected_total, disabled).await
+    }
+
+    pub async fn get_account_invite_codes(did: &String) -> Result<Vec<InviteCode>> {
+        invite::get_account_invite_codes(did).await
+    }
+
+    pub async fn get_invited_by_for_accounts(
+        dids: Vec<String>,
+    ) -> Result<BTreeMap<String, InviteCode>> {
+        invite::get_invited_by_for_accounts(dids).await
+    }
+
+    pub async fn get_account_invites_disabled(
+        did: &String,
+    ) -> Result<Option<(bool, Option<String>)>> {
+        invite::get_account_invites_disabled(did).await
+    }
+
+    pub async fn set_account_invites_disabled(
+        did: &String,
+        disabled: bool,
+        note: Option<String>,
+    ) -> Result<()> {
+        invite::set_account_invites_disabled(did, disabled, note).await
+    }
+
+    pub async fn disable_invite_codes(codes: Vec<String>, accounts: Vec<String>) -> Result<()> {
+        invite::disable_invite_codes(codes, accounts).await
+    }
+
+    pub async fn get_invite_codes(
+        sort: invite::InviteCodesSort,
+        limit: i64,
+        cursor: Option<String>,
+    ) -> Result<(Vec<InviteCode>, Option<String>)> {
+        invite::get_invite_codes(sort, limit, cursor).await
+    }
+
+    // Signing Keys
+    // ----------
+    pub async fn reserve_signing_key(did: Option<String>) -> Result<String> {
+        signing_key::reserve_signing_key(did).await
+    }
+
+    pub async fn delete_unclaimed_signing_keys(cutoff: &String) -> Result<usize> {
+        signing_key::delete_unclaimed_signing_keys(cutoff).await
+    }
+
+    pub async fn store_signing_key(did: &String, secret_key: &SecretKey) -> Result<String> {
+        signing_key::store_signing_key(did, secret_key).await
+    }
+
+    pub async fn stage_signing_key(did: &String, secret_key: &SecretKey) -> Result<String> {
+        signing_key::stage_signing_key(did, secret_key).await
+    }
+
+    pub async fn get_staged_signing_key(did: &String) -> Result<Option<SecretKey>> {
+        signing_key::get_staged_signing_key(did).await
+    }
+
+    pub async fn activate_signing_key(did: &String, key_did: &String) -> Result<()> {
+        signing_key::activate_signing_key(did, key_did).await
+    }
+
+    pub async fn get_signer(did: &String) -> Result<Arc<dyn Signer>> {
+        signing_key::get_signer(did).await
+    }
+
+    pub async fn encrypt_plaintext_signing_keys() -> Result<usize> {
+        signing_key::encrypt_plaintext_signing_keys().await
+    }
 }
 
 pub mod helpers;
\ No newline at end of file
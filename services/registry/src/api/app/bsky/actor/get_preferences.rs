@@ -4,11 +4,10 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::auth_verifier::AccessStandard;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -16,14 +15,14 @@ use rocket::State;
 use rsky_lexicon::app::bsky::actor::{RefPreferences, GetPreferencesOutput};
 
 async fn inner_get_preferences(
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: AccessStandard,
 ) -> Result<GetPreferencesOutput> {
     let auth = auth.access.credentials.unwrap();
     let requester = auth.did.unwrap().clone();
     let actor_store = ActorStore::new(
         requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config),
+        blobstore.create(requester.clone()),
     );
     let preferences: Vec<RefPreferences> = actor_store
         .pref
@@ -37,10 +36,10 @@ async fn inner_get_preferences(
 /// between multiple devices, and import/export during account migration. Requires auth.
 #[rocket::get("/xrpc/app.bsky.actor.getPreferences")]
 pub async fn get_preferences(
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: AccessStandard,
 ) -> Result<Json<GetPreferencesOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_preferences(s3_config, auth).await {
+    match inner_get_preferences(blobstore, auth).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
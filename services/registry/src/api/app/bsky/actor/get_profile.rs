@@ -12,7 +12,7 @@ use crate::read_after_write::util::{handle_read_after_write, ReadAfterWriteRespo
 use crate::read_after_write::viewer::LocalViewer;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -26,7 +26,7 @@ pub async fn inner_get_profile(
     _actor: String,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<ProfileViewDetailed>> {
     let requester: Option<String> = match auth.access.credentials {
@@ -41,7 +41,7 @@ pub async fn inner_get_profile(
                 requester,
                 res,
                 get_profile_munge,
-                s3_config,
+                blobstore,
                 state_local_viewer,
             )
             .await?;
@@ -58,7 +58,7 @@ pub async fn get_profile(
     actor: String,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<ProfileViewDetailed>, status::Custom<Json<ErrorMessageResponse>>>
 {
@@ -70,7 +70,7 @@ pub async fn get_profile(
             };
             return Err(status::Custom(Status::NotFound, Json(not_found)));
         }
-        Some(_) => match inner_get_profile(actor, auth, res, s3_config, state_local_viewer).await {
+        Some(_) => match inner_get_profile(actor, auth, res, blobstore, state_local_viewer).await {
             Ok(response) => Ok(response),
             Err(error) => {
                 let internal_error = ErrorMessageResponse {
@@ -12,7 +12,7 @@ use crate::SharedLocalViewer;
 use crate::config::BSKY_APP_VIEW_CONFIG;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -25,7 +25,7 @@ pub async fn inner_get_profiles(
     _actors: Vec<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<GetProfilesOutput>> {
     let requester: String = match auth.access.credentials {
@@ -37,7 +37,7 @@ pub async fn inner_get_profiles(
         requester,
         res,
         get_profiles_munge,
-        s3_config,
+        blobstore,
         state_local_viewer,
     )
     .await?;
@@ -50,7 +50,7 @@ pub async fn get_profiles(
     actors: Vec<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<GetProfilesOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     match &*BSKY_APP_VIEW_CONFIG {
@@ -61,7 +61,7 @@ pub async fn get_profiles(
             };
             return Err(status::Custom(Status::NotFound, Json(not_found)));
         }
-        Some(_) => match inner_get_profiles(actors, auth, res, s3_config, state_local_viewer).await
+        Some(_) => match inner_get_profiles(actors, auth, res, blobstore, state_local_viewer).await
         {
             Ok(response) => Ok(response),
             Err(error) => {
@@ -4,11 +4,10 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::auth_verifier::AccessStandard;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -17,7 +16,7 @@ use rsky_lexicon::app::bsky::actor::PutPreferencesInput;
 
 async fn inner_put_preferences(
     body: Json<PutPreferencesInput>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: AccessStandard,
 ) -> Result<()> {
     let PutPreferencesInput { preferences } = body.into_inner();
@@ -25,7 +24,7 @@ async fn inner_put_preferences(
     let requester = auth.did.unwrap().clone();
     let actor_store = ActorStore::new(
         requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config),
+        blobstore.create(requester.clone()),
     );
     actor_store
         .pref
@@ -40,10 +39,10 @@ async fn inner_put_preferences(
 )]
 pub async fn put_preferences(
     body: Json<PutPreferencesInput>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: AccessStandard,
 ) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_put_preferences(body, s3_config, auth).await {
+    match inner_put_preferences(body, blobstore, auth).await {
         Ok(_) => Ok(()),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
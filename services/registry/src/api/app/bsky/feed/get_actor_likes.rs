@@ -12,7 +12,7 @@ use crate::xrpc::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -27,7 +27,7 @@ pub async fn inner_get_actor_likes(
     _cursor: Option<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<AuthorFeed>> {
     let requester: Option<String> = match auth.access.credentials {
@@ -42,7 +42,7 @@ pub async fn inner_get_actor_likes(
                 requester,
                 res,
                 get_author_munge,
-                s3_config,
+                blobstore,
                 state_local_viewer,
             )
             .await?;
@@ -59,7 +59,7 @@ pub async fn get_actor_likes(
     cursor: Option<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<AuthorFeed>, status::Custom<Json<ErrorMessageResponse>>> {
     if let Some(limit) = limit {
@@ -85,7 +85,7 @@ pub async fn get_actor_likes(
             cursor,
             auth,
             res,
-            s3_config,
+            blobstore,
             state_local_viewer,
         )
         .await
@@ -12,7 +12,7 @@ use crate::xrpc::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::form::validate::Contains;
 use rocket::http::Status;
 use rocket::response::status;
@@ -29,7 +29,7 @@ pub async fn inner_get_author_feed(
     _filter: Option<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<AuthorFeed>> {
     let requester: Option<String> = match auth.access.credentials {
@@ -44,7 +44,7 @@ pub async fn inner_get_author_feed(
                 requester,
                 res,
                 get_author_munge,
-                s3_config,
+                blobstore,
                 state_local_viewer,
             )
             .await?;
@@ -62,7 +62,7 @@ pub async fn get_author_feed(
     filter: Option<String>, // Combinations of post/repost types to include in response.
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<AuthorFeed>, status::Custom<Json<ErrorMessageResponse>>> {
     if let Some(limit) = limit {
@@ -105,7 +105,7 @@ pub async fn get_author_feed(
             filter,
             auth,
             res,
-            s3_config,
+            blobstore,
             state_local_viewer,
         )
         .await
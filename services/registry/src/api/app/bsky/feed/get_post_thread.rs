@@ -11,7 +11,6 @@ use crate::read_after_write::util::{
     ReadAfterWriteResponse,
 };
 use crate::read_after_write::viewer::LocalViewer;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use crate::xrpc::types::{HandlerPipeThrough, InvalidRequestError, XRPCError};
 use crate::{SharedLocalViewer, APP_USER_AGENT};
@@ -25,7 +24,7 @@ use atrium_api::client::AtpServiceClient;
 use atrium_api::types::LimitedU16;
 use atrium_ipld::ipld::Ipld as AtriumIpld;
 use atrium_xrpc_client::reqwest::ReqwestClientBuilder;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use futures::stream::{self, StreamExt};
 use reqwest::header::HeaderMap;
 use rocket::http::Status;
@@ -55,7 +54,7 @@ pub async fn inner_get_post_thread(
     parentHeight: u16,
     auth: AccessStandard,
     res: Result<HandlerPipeThrough>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<GetPostThreadOutput>> {
     let requester: String = match auth.access.credentials {
@@ -69,7 +68,7 @@ pub async fn inner_get_post_thread(
                 requester,
                 res,
                 get_post_thread_munge,
-                s3_config,
+                blobstore,
                 state_local_viewer,
             )
             .await?;
@@ -88,7 +87,7 @@ pub async fn inner_get_post_thread(
                         Some(error) if error == "NotFound" => {
                             let actor_store = ActorStore::new(
                                 requester.clone(),
-                                S3BlobStore::new(requester.clone(), s3_config),
+                                blobstore.create(requester.clone()),
                             );
                             let local_viewer_lock = state_local_viewer.local_viewer.read().await;
                             let local_viewer = local_viewer_lock(actor_store);
@@ -129,7 +128,7 @@ pub async fn get_post_thread(
     parentHeight: Option<u16>, // How many levels of parent (and grandparent, etc.) post to include.
     auth: AccessStandard,
     res: Result<HandlerPipeThrough>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<GetPostThreadOutput>, status::Custom<Json<ErrorMessageResponse>>>
 {
@@ -156,7 +155,7 @@ pub async fn get_post_thread(
             parentHeight,
             auth,
             res,
-            s3_config,
+            blobstore,
             state_local_viewer,
         )
         .await
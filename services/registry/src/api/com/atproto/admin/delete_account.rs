@@ -6,12 +6,11 @@
 use crate::account_manager::helpers::account::AccountStatus;
 use crate::account_manager::AccountManager;
 use crate::auth_verifier::AdminToken;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use crate::{sequencer, SharedSequencer};
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -21,11 +20,11 @@ use rsky_lexicon::com::atproto::admin::DeleteAccountInput;
 async fn inner_delete_account(
     body: Json<DeleteAccountInput>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<()> {
     let DeleteAccountInput { did } = body.into_inner();
 
-    let mut actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+    let mut actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
     actor_store.destroy().await?;
     AccountManager::delete_account(&did).await?;
     let mut lock = sequencer.sequencer.write().await;
@@ -34,7 +33,7 @@ async fn inner_delete_account(
         .sequence_account_evt(did.clone(), AccountStatus::Deleted)
         .await?;
 
-    sequencer::delete_all_for_user(&did, Some(vec![account_seq, tombstone_seq])).await?;
+    sequencer::invalidate_all_for_user(&did, Some(vec![account_seq, tombstone_seq])).await?;
     Ok(())
 }
 
@@ -46,10 +45,10 @@ async fn inner_delete_account(
 pub async fn delete_account(
     body: Json<DeleteAccountInput>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     _auth: AdminToken,
 ) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_delete_account(body, sequencer, s3_config).await {
+    match inner_delete_account(body, sequencer, blobstore).await {
         Ok(_) => Ok(()),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
@@ -0,0 +1,44 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::Moderator;
+use anyhow::Result;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::admin::DisableAccountInvitesInput;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+
+async fn inner_disable_account_invites(body: DisableAccountInvitesInput) -> Result<()> {
+    let DisableAccountInvitesInput { account, note } = body;
+    AccountManager::set_account_invites_disabled(&account, true, note).await
+}
+
+/// Disable an account from receiving new invite codes, but does not invalidate existing codes.
+#[rocket::post(
+    "/xrpc/com.atproto.admin.disableAccountInvites",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn disable_account_invites(
+    body: Json<DisableAccountInvitesInput>,
+    _auth: Moderator,
+) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_disable_account_invites(body.into_inner()).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let internal_error = ErrorMessageResponse {
+                code: Some(ErrorCode::InternalServerError),
+                message: Some(error.to_string()),
+            };
+            return Err(status::Custom(
+                Status::InternalServerError,
+                Json(internal_error),
+            ));
+        }
+    }
+}
@@ -0,0 +1,54 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::invite::ADMIN_CREATOR;
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::Moderator;
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::admin::DisableInviteCodesInput;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+
+async fn inner_disable_invite_codes(body: DisableInviteCodesInput) -> Result<()> {
+    let DisableInviteCodesInput { codes, accounts } = body;
+    let codes = codes.unwrap_or_default();
+    let accounts = accounts.unwrap_or_default();
+    if accounts.iter().any(|account| account == ADMIN_CREATOR) {
+        bail!("InvalidRequest: cannot disable admin invite codes");
+    }
+    AccountManager::disable_invite_codes(codes, accounts).await
+}
+
+/// Disable some set of codes and/or all codes associated with a set of users.
+#[rocket::post(
+    "/xrpc/com.atproto.admin.disableInviteCodes",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn disable_invite_codes(
+    body: Json<DisableInviteCodesInput>,
+    _auth: Moderator,
+) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_disable_invite_codes(body.into_inner()).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
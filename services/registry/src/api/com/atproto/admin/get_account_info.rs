@@ -23,17 +23,25 @@ async fn inner_get_account_info(did: String) -> Result<AccountView> {
         })
     ).await?;
     if let Some(account) = account {
+        let invites = AccountManager::get_account_invite_codes(&account.did).await?;
+        let mut invited_by =
+            AccountManager::get_invited_by_for_accounts(vec![account.did.clone()]).await?;
+        let (invites_disabled, invite_note) =
+            match AccountManager::get_account_invites_disabled(&account.did).await? {
+                None => (None, None),
+                Some((invites_disabled, invite_note)) => (Some(invites_disabled), invite_note),
+            };
         Ok(AccountView {
+            invited_by: invited_by.remove(&account.did),
             did: account.did,
             handle: account.handle.unwrap_or(INVALID_HANDLE.to_string()),
             email: account.email,
             indexed_at: account.created_at,
             email_confirmed_at: account.email_confirmed_at,
-            invited_by: None,
-            invites: None,
-            invites_disabled: None,
+            invites: Some(invites),
+            invites_disabled,
             related_records: None,
-            invite_note: None,
+            invite_note,
         })
     } else {
         bail!("Account not found")
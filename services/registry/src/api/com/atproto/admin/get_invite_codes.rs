@@ -0,0 +1,59 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::invite::InviteCodesSort;
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::Moderator;
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::admin::GetInviteCodesOutput;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+
+async fn inner_get_invite_codes(
+    sort: String,
+    limit: i64,
+    cursor: Option<String>,
+) -> Result<GetInviteCodesOutput> {
+    let sort = match sort.as_str() {
+        "recent" => InviteCodesSort::Recent,
+        "usage" => InviteCodesSort::Usage,
+        _ => bail!("InvalidRequest: Unknown sort method: {sort}"),
+    };
+    let (codes, cursor) = AccountManager::get_invite_codes(sort, limit, cursor).await?;
+    Ok(GetInviteCodesOutput { cursor, codes })
+}
+
+/// Get an admin view of invite codes.
+#[rocket::get("/xrpc/com.atproto.admin.getInviteCodes?<sort>&<limit>&<cursor>")]
+pub async fn get_invite_codes(
+    sort: Option<String>,
+    limit: Option<u16>,
+    cursor: Option<String>,
+    _auth: Moderator,
+) -> Result<Json<GetInviteCodesOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    let sort = sort.unwrap_or("recent".to_string());
+    let limit = limit.unwrap_or(100).clamp(1, 500) as i64;
+
+    match inner_get_invite_codes(sort, limit, cursor).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
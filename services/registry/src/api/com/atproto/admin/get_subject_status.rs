@@ -5,11 +5,10 @@
  */
 use crate::account_manager::AccountManager;
 use crate::auth_verifier::Moderator;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use futures::try_join;
 use libipld::Cid;
 use rocket::http::Status;
@@ -24,7 +23,7 @@ async fn inner_get_subject_status(
     did: Option<String>,
     uri: Option<String>,
     blob: Option<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<SubjectStatus> {
     let mut body: Option<SubjectStatus> = None;
     if let Some(blob) = blob {
@@ -32,7 +31,7 @@ async fn inner_get_subject_status(
             None => bail!("Must provide a did to request blob state"),
             Some(did) => {
                 let actor_store =
-                    ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+                    ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
                 let takedown = actor_store
                     .blob
@@ -57,7 +56,7 @@ async fn inner_get_subject_status(
         if let (Some(uri_hostname), Some(_), Some(_)) = (parts.get(0), parts.get(1), parts.get(2)) {
             let actor_store = ActorStore::new(
                 uri_hostname.to_string(),
-                S3BlobStore::new(uri_hostname.to_string(), s3_config),
+                blobstore.create(uri_hostname.to_string()),
             );
             let (takedown, cid) = try_join!(
                 actor_store.record.get_record_takedown_status(uri.clone()),
@@ -97,10 +96,10 @@ pub async fn get_subject_status(
     did: Option<String>,
     uri: Option<String>,
     blob: Option<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     _auth: Moderator,
 ) -> Result<Json<SubjectStatus>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_subject_status(did, uri, blob, s3_config).await {
+    match inner_get_subject_status(did, uri, blob, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
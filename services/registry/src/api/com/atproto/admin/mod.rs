@@ -1,5 +1,9 @@
 pub mod delete_account;
+pub mod disable_account_invites;
+pub mod disable_invite_codes;
+pub mod enable_account_invites;
 pub mod get_account_info;
+pub mod get_invite_codes;
 pub mod get_subject_status;
 pub mod send_email;
 pub mod update_account_email;
@@ -10,7 +14,11 @@ pub mod update_subject_status;
 pub fn routes() -> Vec<rocket::Route> {
     routes![
         delete_account::delete_account,
+        disable_account_invites::disable_account_invites,
+        disable_invite_codes::disable_invite_codes,
+        enable_account_invites::enable_account_invites,
         get_account_info::get_account_info,
+        get_invite_codes::get_invite_codes,
         get_subject_status::get_subject_status,
         send_email::send_email,
         update_account_email::update_account_email,
@@ -5,12 +5,11 @@
  */
 use crate::account_manager::AccountManager;
 use crate::auth_verifier::Moderator;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use crate::SharedSequencer;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use libipld::Cid;
 use rocket::http::Status;
 use rocket::response::status;
@@ -22,7 +21,7 @@ use std::str::FromStr;
 async fn inner_update_subject_status(
     body: Json<SubjectStatus>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<UpdateSubjectStatusOutput> {
     let SubjectStatus {
         subject,
@@ -43,7 +42,7 @@ async fn inner_update_subject_status(
                 {
                     let actor_store = ActorStore::new(
                         uri_hostname.to_string(),
-                        S3BlobStore::new(uri_hostname.to_string(), s3_config),
+                        blobstore.create(uri_hostname.to_string()),
                     );
                     actor_store
                         .record
@@ -54,7 +53,7 @@ async fn inner_update_subject_status(
             Subject::RepoBlobRef(subject) => {
                 let actor_store = ActorStore::new(
                     subject.did.clone(),
-                    S3BlobStore::new(subject.did.clone(), s3_config),
+                    blobstore.create(subject.did.clone()),
                 );
                 actor_store
                     .blob
@@ -92,10 +91,10 @@ async fn inner_update_subject_status(
 pub async fn update_subject_status(
     body: Json<SubjectStatus>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     _auth: Moderator,
 ) -> Result<Json<UpdateSubjectStatusOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_update_subject_status(body, sequencer, s3_config).await {
+    match inner_update_subject_status(body, sequencer, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
@@ -0,0 +1,72 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use crate::config::CORE_CONFIG;
+use crate::signer::ROTATION_SIGNER;
+use anyhow::Result;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::identity::GetRecommendedDidCredentialsOutput;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use serde_json::json;
+
+async fn inner_get_recommended_did_credentials(
+    auth: AccessFull,
+) -> Result<GetRecommendedDidCredentialsOutput> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let account = AccountManager::get_account(
+        &did,
+        Some(AvailabilityFlags {
+            include_deactivated: Some(true),
+            include_taken_down: Some(true),
+        }),
+    )
+    .await?;
+    let also_known_as = match account.and_then(|account| account.handle) {
+        None => vec![],
+        Some(handle) => vec![format!("at://{handle}")],
+    };
+    let signer = AccountManager::get_signer(&did).await?;
+
+    Ok(GetRecommendedDidCredentialsOutput {
+        rotation_keys: Some(vec![ROTATION_SIGNER.did_key()]),
+        also_known_as: Some(also_known_as),
+        verification_methods: Some(json!({
+            "atproto": signer.did_key()
+        })),
+        services: Some(json!({
+            "atproto_pds": {
+                "type": "AtprotoPersonalDataServer",
+                "endpoint": CORE_CONFIG.public_url()
+            }
+        })),
+    })
+}
+
+/// Describe the credentials that should be included in the DID doc of an account that is
+/// migrating to this service.
+#[rocket::get("/xrpc/com.atproto.identity.getRecommendedDidCredentials")]
+pub async fn get_recommended_did_credentials(
+    auth: AccessFull,
+) -> Result<Json<GetRecommendedDidCredentialsOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_get_recommended_did_credentials(auth).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let internal_error = ErrorMessageResponse {
+                code: Some(ErrorCode::InternalServerError),
+                message: Some("Internal error".to_string()),
+            };
+            return Err(status::Custom(
+                Status::InternalServerError,
+                Json(internal_error),
+            ));
+        }
+    }
+}
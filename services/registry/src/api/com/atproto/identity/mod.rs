@@ -1,6 +1,17 @@
+pub mod get_recommended_did_credentials;
+pub mod request_plc_operation_signature;
 pub mod resolve_handle;
+pub mod sign_plc_operation;
+pub mod submit_plc_operation;
 pub mod update_handle;
 
 pub fn routes() -> Vec<rocket::Route> {
-    routes![resolve_handle::resolve_handle, update_handle::update_handle]
-}
\ No newline at end of file
+    routes![
+        get_recommended_did_credentials::get_recommended_did_credentials,
+        request_plc_operation_signature::request_plc_operation_signature,
+        resolve_handle::resolve_handle,
+        sign_plc_operation::sign_plc_operation,
+        submit_plc_operation::submit_plc_operation,
+        update_handle::update_handle
+    ]
+}
@@ -0,0 +1,107 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::email_token::EmailTokenError;
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use crate::config::IDENTITY_CONFIG;
+use crate::database::models::EmailTokenPurpose;
+use crate::plc;
+use crate::plc::operations::create_update_op;
+use crate::plc::types::{CompatibleOp, CompatibleOpOrTombstone, Operation, Service};
+use crate::signer::ROTATION_SIGNER;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::identity::{SignPlcOperationInput, SignPlcOperationOutput};
+use std::collections::BTreeMap;
+
+async fn inner_sign_plc_operation(
+    body: SignPlcOperationInput,
+    auth: AccessFull,
+) -> Result<SignPlcOperationOutput> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let SignPlcOperationInput {
+        token,
+        rotation_keys,
+        also_known_as,
+        verification_methods,
+        services,
+    } = body;
+
+    let token = match token {
+        None => bail!("InvalidRequest: email confirmation token required to sign PLC operations"),
+        Some(token) => token,
+    };
+    AccountManager::assert_valid_email_token(&did, EmailTokenPurpose::PlcOperation, &token)
+        .await?;
+
+    let verification_methods: Option<BTreeMap<String, String>> = match verification_methods {
+        None => None,
+        Some(verification_methods) => Some(serde_json::from_value(verification_methods)?),
+    };
+    let services: Option<BTreeMap<String, Service>> = match services {
+        None => None,
+        Some(services) => Some(serde_json::from_value(services)?),
+    };
+
+    let plc_client = plc::Client::new(IDENTITY_CONFIG.plc_url.clone());
+    let last_op: CompatibleOp = match plc_client.ensure_last_op(&did).await? {
+        CompatibleOpOrTombstone::CreateOpV1(last_op) => CompatibleOp::CreateOpV1(last_op),
+        CompatibleOpOrTombstone::Operation(last_op) => CompatibleOp::Operation(last_op),
+        CompatibleOpOrTombstone::Tombstone(_) => bail!("Did is tombstoned"),
+    };
+    let operation = create_update_op(last_op, &**ROTATION_SIGNER, |last_op: Operation| {
+        Operation {
+            rotation_keys: rotation_keys.clone().unwrap_or(last_op.rotation_keys),
+            also_known_as: also_known_as.clone().unwrap_or(last_op.also_known_as),
+            verification_methods: verification_methods
+                .clone()
+                .unwrap_or(last_op.verification_methods),
+            services: services.clone().unwrap_or(last_op.services),
+            ..last_op
+        }
+    })
+    .await?;
+    AccountManager::delete_email_token(&did, EmailTokenPurpose::PlcOperation).await?;
+
+    Ok(SignPlcOperationOutput {
+        operation: serde_json::to_value(operation)?,
+    })
+}
+
+/// Signs a PLC operation to update some value(s) in the requesting DID's document.
+#[rocket::post(
+    "/xrpc/com.atproto.identity.signPlcOperation",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn sign_plc_operation(
+    body: Json<SignPlcOperationInput>,
+    auth: AccessFull,
+) -> Result<Json<SignPlcOperationOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_sign_plc_operation(body.into_inner(), auth).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest")
+                || error.downcast_ref::<EmailTokenError>().is_some()
+            {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
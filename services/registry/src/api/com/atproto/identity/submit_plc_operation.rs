@@ -0,0 +1,121 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use crate::config::{CORE_CONFIG, IDENTITY_CONFIG};
+use crate::plc;
+use crate::plc::types::{OpOrTombstone, Operation};
+use crate::signer::ROTATION_SIGNER;
+use crate::SharedSequencer;
+use anyhow::{bail, Result};
+use did_method_plc::operation::SignedPLCOperation;
+use did_method_plc::DIDPLC;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::identity::SubmitPlcOperationInput;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+
+async fn inner_submit_plc_operation(
+    body: SubmitPlcOperationInput,
+    sequencer: &State<SharedSequencer>,
+    auth: AccessFull,
+) -> Result<()> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let json = serde_json::to_string(&body.operation)?;
+    let op: Operation = match serde_json::from_value(body.operation) {
+        Ok(op) => op,
+        Err(error) => bail!("InvalidRequest: Invalid PLC operation: {error}"),
+    };
+
+    if !op.rotation_keys.contains(&ROTATION_SIGNER.did_key()) {
+        bail!("InvalidRequest: Rotation keys do not include server's rotation key")
+    }
+    match op.services.get("atproto_pds") {
+        Some(service) if service.r#type == "AtprotoPersonalDataServer" => {
+            if service.endpoint != CORE_CONFIG.public_url() {
+                bail!("InvalidRequest: Incorrect endpoint on atproto_pds service")
+            }
+        }
+        Some(_) => bail!("InvalidRequest: Incorrect type on atproto_pds service"),
+        None => bail!("InvalidRequest: Missing atproto_pds service"),
+    }
+    let signer = AccountManager::get_signer(&did).await?;
+    if op.verification_methods.get("atproto") != Some(&signer.did_key()) {
+        bail!("InvalidRequest: Incorrect signing key")
+    }
+    let account = AccountManager::get_account(
+        &did,
+        Some(AvailabilityFlags {
+            include_deactivated: Some(true),
+            include_taken_down: Some(true),
+        }),
+    )
+    .await?;
+    let handle = match account {
+        None => bail!("Account not found"),
+        Some(account) => account.handle,
+    };
+    if let Some(ref handle) = handle {
+        if !op.also_known_as.contains(&format!("at://{handle}")) {
+            bail!("InvalidRequest: Incorrect handle in alsoKnownAs")
+        }
+    }
+
+    // Check the operation against the DID's history before handing it to the directory
+    let audit_log = DIDPLC::new(&IDENTITY_CONFIG.plc_url)
+        .get_audit_log(&did)
+        .await?;
+    let signed_op = match SignedPLCOperation::from_json(&json) {
+        Ok(signed_op) => signed_op,
+        Err(error) => bail!("InvalidRequest: Invalid PLC operation: {error}"),
+    };
+    if let Err(error) = audit_log.assure_valid(signed_op) {
+        bail!("InvalidRequest: Invalid PLC operation: {error}")
+    }
+
+    let plc_client = plc::Client::new(IDENTITY_CONFIG.plc_url.clone());
+    plc_client
+        .send_operation(&did, &OpOrTombstone::Operation(op))
+        .await?;
+    let mut lock = sequencer.sequencer.write().await;
+    lock.sequence_identity_evt(did, handle).await?;
+    Ok(())
+}
+
+/// Validates a PLC operation to ensure that it doesn't violate a service's constraints or get the
+/// identity into a bad state, then submits it to the PLC registry.
+#[rocket::post(
+    "/xrpc/com.atproto.identity.submitPlcOperation",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn submit_plc_operation(
+    body: Json<SubmitPlcOperationInput>,
+    sequencer: &State<SharedSequencer>,
+    auth: AccessFull,
+) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_submit_plc_operation(body.into_inner(), sequencer, auth).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
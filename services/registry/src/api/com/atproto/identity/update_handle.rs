@@ -5,15 +5,15 @@
  */
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::api::com::atproto::server::get_keys_from_private_key_str;
 use crate::auth_verifier::AccessStandardCheckTakedown;
-use crate::config::{IDENTITY_CONFIG, SECRET_CONFIG};
+use crate::config::IDENTITY_CONFIG;
 use crate::handle::explicit_slurs::has_explicit_slur;
 use crate::handle::normalize_and_validate_handle;
 use crate::handle::reserved::is_handle_reserved;
 use crate::SharedSequencer;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use crate::plc;
+use crate::signer::ROTATION_SIGNER;
 use anyhow::{bail, Result};
 use rocket::http::Status;
 use rocket::response::status;
@@ -53,10 +53,8 @@ async fn inner_update_handle(
         None => {
             let plc_url = IDENTITY_CONFIG.plc_url.clone();
             let plc_client = plc::Client::new(plc_url);
-            let private_key = SECRET_CONFIG.pds_rotation_key.clone();
-            let (signing_key, _) = get_keys_from_private_key_str(private_key)?;
             plc_client
-                .update_handle(&requester, &signing_key, &handle)
+                .update_handle(&requester, &**ROTATION_SIGNER, &handle)
                 .await?;
             AccountManager::update_handle(&requester, &handle).await?;
         }
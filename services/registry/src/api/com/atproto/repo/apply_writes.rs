@@ -5,33 +5,37 @@
  */
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::auth_verifier::AccessRepoWrite;
 use crate::repository::ActorStore;
+use crate::lexicon::{ValidationError, ValidationStatus, LEXICONS};
 use rsky_pds::repo::{
     prepare_create, prepare_delete, prepare_update, PrepareCreateOpts,
     PrepareDeleteOpts, PrepareUpdateOpts,
 };
 use rsky_pds::repo::types::PreparedWrite;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
 use crate::SharedSequencer;
+use crate::rate_limiter::RateLimit;
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use futures::stream::{self, StreamExt};
 use libipld::Cid;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::State;
-use rsky_lexicon::com::atproto::repo::{ApplyWritesInput, ApplyWritesInputRefWrite};
+use rsky_lexicon::com::atproto::repo::{
+    ApplyWritesInput, ApplyWritesInputRefWrite, ApplyWritesOutput, ApplyWritesOutputRefResult,
+    CommitMeta, RefWriteResult,
+};
 use std::str::FromStr;
 
 async fn inner_apply_writes(
     body: Json<ApplyWritesInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
-) -> Result<()> {
+    blobstore: &State<BlobStoreCreator>,
+) -> Result<ApplyWritesOutput> {
     let tx: ApplyWritesInput = body.into_inner();
     let ApplyWritesInput {
         repo,
@@ -39,6 +43,14 @@ async fn inner_apply_writes(
         swap_commit,
         ..
     } = tx;
+    for write in &tx.writes {
+        let collection = match write {
+            ApplyWritesInputRefWrite::Create(write) => &write.collection,
+            ApplyWritesInputRefWrite::Update(write) => &write.collection,
+            ApplyWritesInputRefWrite::Delete(write) => &write.collection,
+        };
+        auth.assert_collection(collection)?;
+    }
     let account = AccountManager::get_account(
         &repo,
         Some(AvailabilityFlags {
@@ -61,11 +73,17 @@ async fn inner_apply_writes(
             bail!("Too many writes. Max: 200")
         }
 
-        let writes: Vec<PreparedWrite> = stream::iter(tx.writes)
+        let prepared: Vec<(PreparedWrite, Option<ValidationStatus>)> = stream::iter(tx.writes)
             .then(|write| async move {
-                Ok::<PreparedWrite, anyhow::Error>(match write {
-                    ApplyWritesInputRefWrite::Create(write) => PreparedWrite::Create(
-                        prepare_create(PrepareCreateOpts {
+                Ok::<(PreparedWrite, Option<ValidationStatus>), anyhow::Error>(match write {
+                    ApplyWritesInputRefWrite::Create(mut write) => {
+                        let validation_status = LEXICONS.validate_record(
+                            &write.collection,
+                            write.rkey.as_deref(),
+                            &mut write.value,
+                            validate,
+                        )?;
+                        let write = prepare_create(PrepareCreateOpts {
                             did: did.clone(),
                             collection: write.collection,
                             rkey: write.rkey,
@@ -73,10 +91,17 @@ async fn inner_apply_writes(
                             record: serde_json::from_value(write.value)?,
                             validate,
                         })
-                        .await?,
-                    ),
-                    ApplyWritesInputRefWrite::Update(write) => PreparedWrite::Update(
-                        prepare_update(PrepareUpdateOpts {
+                        .await?;
+                        (PreparedWrite::Create(write), validation_status)
+                    }
+                    ApplyWritesInputRefWrite::Update(mut write) => {
+                        let validation_status = LEXICONS.validate_record(
+                            &write.collection,
+                            Some(&write.rkey),
+                            &mut write.value,
+                            validate,
+                        )?;
+                        let write = prepare_update(PrepareUpdateOpts {
                             did: did.clone(),
                             collection: write.collection,
                             rkey: write.rkey,
@@ -84,22 +109,26 @@ async fn inner_apply_writes(
                             record: serde_json::from_value(write.value)?,
                             validate,
                         })
-                        .await?,
-                    ),
-                    ApplyWritesInputRefWrite::Delete(write) => {
+                        .await?;
+                        (PreparedWrite::Update(write), validation_status)
+                    }
+                    ApplyWritesInputRefWrite::Delete(write) => (
                         PreparedWrite::Delete(prepare_delete(PrepareDeleteOpts {
                             did: did.clone(),
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
-                        }))
-                    }
+                        })),
+                        None,
+                    ),
                 })
             })
             .collect::<Vec<_>>()
             .await
             .into_iter()
-            .collect::<Result<Vec<PreparedWrite>, _>>()?;
+            .collect::<Result<Vec<(PreparedWrite, Option<ValidationStatus>)>, _>>()?;
+        let (writes, validation_statuses): (Vec<PreparedWrite>, Vec<Option<ValidationStatus>>) =
+            prepared.into_iter().unzip();
 
         let swap_commit_cid = match swap_commit {
             Some(swap_commit) => Some(Cid::from_str(&swap_commit)?),
@@ -107,7 +136,7 @@ async fn inner_apply_writes(
         };
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+            ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
         let commit = actor_store
             .process_writes(writes.clone(), swap_commit_cid)
@@ -116,8 +145,32 @@ async fn inner_apply_writes(
         let mut lock = sequencer.sequencer.write().await;
         lock.sequence_commit(did.clone(), commit.clone(), writes)
             .await?;
-        AccountManager::update_repo_root(did.to_string(), commit.cid, commit.rev)?;
-        Ok(())
+        AccountManager::update_repo_root(did.to_string(), commit.cid, commit.rev.clone())?;
+
+        let results = writes
+            .iter()
+            .zip(validation_statuses)
+            .map(|(write, validation_status)| match write {
+                PreparedWrite::Create(write) => ApplyWritesOutputRefResult::Create(RefWriteResult {
+                    uri: write.uri.clone(),
+                    cid: write.cid.to_string(),
+                    validation_status: validation_status.map(|status| status.to_string()),
+                }),
+                PreparedWrite::Update(write) => ApplyWritesOutputRefResult::Update(RefWriteResult {
+                    uri: write.uri.clone(),
+                    cid: write.cid.to_string(),
+                    validation_status: validation_status.map(|status| status.to_string()),
+                }),
+                PreparedWrite::Delete(_) => ApplyWritesOutputRefResult::Delete {},
+            })
+            .collect();
+        Ok(ApplyWritesOutput {
+            commit: CommitMeta {
+                cid: commit.cid.to_string(),
+                rev: commit.rev,
+            },
+            results,
+        })
     } else {
         bail!("Could not find repo: `{repo}`")
     }
@@ -125,16 +178,31 @@ async fn inner_apply_writes(
 
 #[rocket::post("/xrpc/com.atproto.repo.applyWrites", format = "json", data = "<body>")]
 pub async fn apply_writes(
+    _rate_limit: RateLimit,
     body: Json<ApplyWritesInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
-) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    blobstore: &State<BlobStoreCreator>,
+) -> Result<Json<ApplyWritesOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     println!("@LOG: debug apply_writes {body:#?}");
-    match inner_apply_writes(body, auth, sequencer, s3_config).await {
-        Ok(()) => Ok(()),
+    match inner_apply_writes(body, auth, sequencer, blobstore).await {
+        Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
+            if let Some(error) = error.downcast_ref::<ValidationError>() {
+                let bad_request = ErrorMessageResponse {
+                    code: Some(ErrorCode::BadRequest),
+                    message: Some(error.to_string()),
+                };
+                return Err(status::Custom(Status::BadRequest, Json(bad_request)));
+            }
+            if error.to_string().starts_with("Forbidden") {
+                let forbidden = ErrorMessageResponse {
+                    code: Some(ErrorCode::Forbidden),
+                    message: Some(error.to_string()),
+                };
+                return Err(status::Custom(Status::Forbidden, Json(forbidden)));
+            }
             let internal_error = ErrorMessageResponse {
                 code: Some(ErrorCode::InternalServerError),
                 message: Some(error.to_string()),
@@ -5,17 +5,18 @@
  */
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::auth_verifier::AccessRepoWrite;
 use crate::SharedSequencer;
 use crate::repository::ActorStore;
+use crate::lexicon::{ValidationError, LEXICONS};
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
+use crate::rate_limiter::RateLimit;
 use rsky_pds::repo::types::{PreparedDelete, PreparedWrite};
 use rsky_pds::repo::{
     prepare_create, prepare_delete, PrepareCreateOpts, PrepareDeleteOpts,
 };
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use libipld::Cid;
 use rocket::http::Status;
 use rocket::response::status;
@@ -26,18 +27,19 @@ use std::str::FromStr;
 
 async fn inner_create_record(
     body: Json<CreateRecordInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<CreateRecordOutput> {
     let CreateRecordInput {
         repo,
         collection,
-        record,
+        mut record,
         rkey,
         validate,
         swap_commit,
     } = body.into_inner();
+    auth.assert_collection(&collection)?;
     let account = AccountManager::get_account(
         &repo,
         Some(AvailabilityFlags {
@@ -58,6 +60,8 @@ async fn inner_create_record(
             Some(swap_commit) => Some(Cid::from_str(&swap_commit)?),
             None => None,
         };
+        let validation_status =
+            LEXICONS.validate_record(&collection, rkey.as_deref(), &mut record, validate)?;
         let write = prepare_create(PrepareCreateOpts {
             did: did.clone(),
             collection: collection.clone(),
@@ -69,7 +73,7 @@ async fn inner_create_record(
         .await?;
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+            ActorStore::new(did.clone(), blobstore.create(did.clone()));
         let backlink_conflicts: Vec<String> = match validate {
             Some(true) => {
                 actor_store
@@ -116,6 +120,7 @@ async fn inner_create_record(
         Ok(CreateRecordOutput {
             uri: write.uri,
             cid: write.cid.to_string(),
+            validation_status: validation_status.map(|status| status.to_string()),
         })
     } else {
         bail!("Could not find repo: `{repo}`")
@@ -128,16 +133,31 @@ async fn inner_create_record(
     data = "<body>"
 )]
 pub async fn create_record(
+    _rate_limit: RateLimit,
     body: Json<CreateRecordInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<CreateRecordOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     println!("@LOG: debug create_record {body:#?}");
-    match inner_create_record(body, auth, sequencer, s3_config).await {
+    match inner_create_record(body, auth, sequencer, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
+            if let Some(error) = error.downcast_ref::<ValidationError>() {
+                let bad_request = ErrorMessageResponse {
+                    code: Some(ErrorCode::BadRequest),
+                    message: Some(error.to_string()),
+                };
+                return Err(status::Custom(Status::BadRequest, Json(bad_request)));
+            }
+            if error.to_string().starts_with("Forbidden") {
+                let forbidden = ErrorMessageResponse {
+                    code: Some(ErrorCode::Forbidden),
+                    message: Some(error.to_string()),
+                };
+                return Err(status::Custom(Status::Forbidden, Json(forbidden)));
+            }
             let internal_error = ErrorMessageResponse {
                 code: Some(ErrorCode::InternalServerError),
                 message: Some(error.to_string()),
@@ -5,15 +5,15 @@
  */
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::auth_verifier::AccessRepoWrite;
 use crate::repository::ActorStore;
 use crate::SharedSequencer;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
+use crate::rate_limiter::RateLimit;
 use rsky_pds::repo::types::PreparedWrite;
 use rsky_pds::repo::{prepare_delete, PrepareDeleteOpts};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use libipld::Cid;
 use rocket::http::Status;
 use rocket::response::status;
@@ -24,9 +24,9 @@ use std::str::FromStr;
 
 async fn inner_delete_record(
     body: Json<DeleteRecordInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<()> {
     let DeleteRecordInput {
         repo,
@@ -35,6 +35,7 @@ async fn inner_delete_record(
         swap_record,
         swap_commit,
     } = body.into_inner();
+    auth.assert_collection(&collection)?;
     let account = AccountManager::get_account(
         &repo,
         Some(AvailabilityFlags {
@@ -68,7 +69,7 @@ async fn inner_delete_record(
                 swap_cid: swap_record_cid,
             });
             let mut actor_store =
-                ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+                ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
             let record = actor_store
                 .record
@@ -103,15 +104,23 @@ async fn inner_delete_record(
     data = "<body>"
 )]
 pub async fn delete_record(
+    _rate_limit: RateLimit,
     body: Json<DeleteRecordInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_delete_record(body, auth, sequencer, s3_config).await {
+    match inner_delete_record(body, auth, sequencer, blobstore).await {
         Ok(()) => Ok(()),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
+            if error.to_string().starts_with("Forbidden") {
+                let forbidden = ErrorMessageResponse {
+                    code: Some(ErrorCode::Forbidden),
+                    message: Some(error.to_string()),
+                };
+                return Err(status::Custom(Status::Forbidden, Json(forbidden)));
+            }
             let internal_error = ErrorMessageResponse {
                 code: Some(ErrorCode::InternalServerError),
                 message: Some(error.to_string()),
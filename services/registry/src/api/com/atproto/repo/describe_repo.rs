@@ -7,10 +7,9 @@ use crate::account_manager::AccountManager;
 use crate::repository::ActorStore;
 use crate::INVALID_HANDLE;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
 use rsky_pds::{common, SharedIdResolver};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -21,7 +20,7 @@ use rsky_lexicon::com::atproto::repo::DescribeRepoOutput;
 async fn inner_describe_repo(
     repo: String,
     id_resolver: &State<SharedIdResolver>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<DescribeRepoOutput> {
     let account = AccountManager::get_account(&repo, None).await?;
     match account {
@@ -38,7 +37,7 @@ async fn inner_describe_repo(
 
             let mut actor_store = ActorStore::new(
                 account.did.clone(),
-                S3BlobStore::new(account.did.clone(), s3_config),
+                blobstore.create(account.did.clone()),
             );
             let collections = actor_store.record.list_collections().await?;
 
@@ -57,9 +56,9 @@ async fn inner_describe_repo(
 pub async fn describe_repo(
     repo: String,
     id_resolver: &State<SharedIdResolver>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<DescribeRepoOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_describe_repo(repo, id_resolver, s3_config).await {
+    match inner_describe_repo(repo, id_resolver, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("{error:?}");
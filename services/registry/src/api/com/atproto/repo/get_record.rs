@@ -8,10 +8,9 @@ use crate::config::BSKY_APP_VIEW_CONFIG;
 use crate::pipethrough::{pipethrough, OverrideOpts, ProxyRequest};
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
 use rsky_pds::repo::make_aturi;
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -23,7 +22,7 @@ async fn inner_get_record(
     collection: String,
     rkey: String,
     cid: Option<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     req: ProxyRequest<'_>,
 ) -> Result<GetRecordOutput> {
     let did = AccountManager::get_did_for_actor(&repo, None).await?;
@@ -33,7 +32,7 @@ async fn inner_get_record(
         let uri = make_aturi(did.clone(), Some(collection), Some(rkey));
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+            ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
         match actor_store.record.get_record(&uri, cid, None).await {
             Ok(Some(record)) if record.takedown_ref.is_none() => Ok(GetRecordOutput {
@@ -75,10 +74,10 @@ pub async fn get_record(
     collection: String,
     rkey: String,
     cid: Option<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     req: ProxyRequest<'_>,
 ) -> Result<Json<GetRecordOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_record(repo, collection, rkey, cid, s3_config, req).await {
+    match inner_get_record(repo, collection, rkey, cid, blobstore, req).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
@@ -1,4 +1,173 @@
-#[rocket::post("/xrpc/com.atproto.repo.importRepo")]
-pub async fn import_repo() {
-    unimplemented!()
-}
\ No newline at end of file
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::AccountManager;
+use crate::api::com::atproto::repo::{assert_repo_availability, resolve_atproto_key};
+use crate::auth_verifier::AccessFull;
+use crate::config::CORE_CONFIG;
+use crate::repository::car::read_car_with_root;
+use crate::repository::storage::RepoReader;
+use crate::repository::{ActorStore, Repo};
+use crate::sequencer;
+use crate::{SharedIdResolver, SharedSequencer};
+use anyhow::{bail, Result};
+use crate::repository::blobstore::BlobStoreCreator;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use rsky_pds::repo::types::{PreparedDelete, PreparedWrite};
+use rsky_pds::repo::util;
+use rsky_pds::repo::{prepare_create, prepare_delete, PrepareCreateOpts, PrepareDeleteOpts};
+use std::collections::HashSet;
+
+async fn inner_import_repo(
+    body: Data<'_>,
+    auth: AccessFull,
+    blobstore: &State<BlobStoreCreator>,
+    id_resolver: &State<SharedIdResolver>,
+    sequencer: &State<SharedSequencer>,
+) -> Result<()> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    assert_repo_availability(&did, true).await?;
+
+    let bytes = body
+        .open(CORE_CONFIG.import_repo_limit().bytes())
+        .into_bytes()
+        .await?;
+    if !bytes.is_complete() {
+        bail!("InvalidRequest: Repo is too large to import");
+    }
+    let car = read_car_with_root(&bytes.into_inner())?;
+
+    // Load the imported repo purely from the CAR's blocks
+    let mut storage = RepoReader::new(None, did.clone(), None);
+    storage.cache.add_map(car.blocks.clone())?;
+    let mut repo = Repo::load(&mut storage, Some(car.root)).await?;
+    let commit = repo.commit();
+    if commit.did != did {
+        bail!("InvalidRequest: Imported repo belongs to `{0}`, not `{did}`", commit.did);
+    }
+    let did_key = resolve_atproto_key(id_resolver, &did).await?;
+    if !util::verify_commit_sig(commit.clone(), &did_key)? {
+        bail!("InvalidRequest: Invalid signature on imported commit");
+    }
+
+    // Walking the tree also ensures every MST node and record is present in the CAR
+    let mut writes: Vec<PreparedWrite> = Vec::new();
+    let mut imported_uris: HashSet<String> = HashSet::new();
+    for record in repo.walk_records(None) {
+        let mut write = prepare_create(PrepareCreateOpts {
+            did: did.clone(),
+            collection: record.collection,
+            rkey: Some(record.rkey),
+            swap_cid: None,
+            record: record.record,
+            validate: Some(false),
+        })
+        .await?;
+        // keep the cid that was committed to rather than our re-encoding of it
+        write.cid = record.cid;
+        imported_uris.insert(write.uri.clone());
+        writes.push(PreparedWrite::Create(write));
+    }
+
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
+    let current_root = actor_store.storage.get_root().await;
+
+    // Records we had indexed that are no longer part of the repo
+    let existing_uris = actor_store.record.list_all_uris().await?;
+    let stale_deletes = existing_uris
+        .into_iter()
+        .filter(|uri| !imported_uris.contains(uri))
+        .map(|uri| {
+            let uri_without_prefix = uri.replace("at://", "");
+            let parts = uri_without_prefix.split("/").collect::<Vec<&str>>();
+            if let (Some(uri_collection), Some(uri_rkey)) = (parts.get(1), parts.get(2)) {
+                Ok(PreparedWrite::Delete(prepare_delete(PrepareDeleteOpts {
+                    did: did.clone(),
+                    collection: uri_collection.to_string(),
+                    rkey: uri_rkey.to_string(),
+                    swap_cid: None,
+                })))
+            } else {
+                bail!("Issue parsing uri `{uri}`")
+            }
+        })
+        .collect::<Result<Vec<PreparedWrite>>>()?;
+
+    actor_store
+        .storage
+        .put_many(car.blocks.clone(), commit.rev.clone())
+        .await?;
+    actor_store
+        .storage
+        .update_root(car.root, commit.rev.clone(), Some(current_root.is_none()))
+        .await?;
+
+    for delete in &stale_deletes {
+        if let PreparedWrite::Delete(PreparedDelete { uri, .. }) = delete {
+            actor_store.blob.disassociate_record(uri).await?;
+        }
+    }
+    actor_store.index_writes(stale_deletes, &commit.rev).await?;
+    actor_store.index_writes(writes.clone(), &commit.rev).await?;
+
+    // Blobs are not part of the CAR, so only record the references; they will show up in
+    // listMissingBlobs until they are uploaded
+    for write in writes {
+        if let PreparedWrite::Create(write) = write {
+            for blob in write.blobs {
+                actor_store.blob.associate_blob(blob, write.uri.clone()).await?;
+            }
+        }
+    }
+    AccountManager::update_repo_root(did.clone(), car.root, commit.rev.clone())?;
+    // Commits sequenced before the import describe history the imported repo replaces. A
+    // deactivated account (the usual case when migrating in) gets its sync event on activation.
+    let mut excluding_seqs = None;
+    let status = AccountManager::get_account_status(&did).await?;
+    if matches!(status, AccountStatus::Active) {
+        let mut lock = sequencer.sequencer.write().await;
+        let sync_seq = lock
+            .sequence_sync_evt(did.clone(), car.root, commit.rev, &car.blocks)
+            .await?;
+        excluding_seqs = Some(vec![sync_seq]);
+    }
+    sequencer::invalidate_commits_for_user(&did, excluding_seqs).await?;
+    Ok(())
+}
+
+/// Import a repo in the form of a CAR file. Requires Content-Length HTTP header to be set.
+#[rocket::post("/xrpc/com.atproto.repo.importRepo", data = "<body>")]
+pub async fn import_repo(
+    body: Data<'_>,
+    auth: AccessFull,
+    blobstore: &State<BlobStoreCreator>,
+    id_resolver: &State<SharedIdResolver>,
+    sequencer: &State<SharedSequencer>,
+) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_import_repo(body, auth, blobstore, id_resolver, sequencer).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
@@ -7,9 +7,8 @@ use crate::auth_verifier::AccessFull;
 use crate::repository::blob::ListMissingBlobsOpts;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -21,12 +20,12 @@ pub async fn list_missing_blobs(
     limit: Option<u16>,
     cursor: Option<String>,
     auth: AccessFull,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<ListMissingBlobsOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     let did = auth.access.credentials.unwrap().did.unwrap();
     let limit: u16 = limit.unwrap_or(500);
 
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
     match actor_store
         .blob
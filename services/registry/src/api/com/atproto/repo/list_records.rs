@@ -6,9 +6,8 @@
 use crate::account_manager::AccountManager;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -30,7 +29,7 @@ async fn inner_list_records(
     rkeyEnd: Option<String>,
     // Flag to reverse the order of the returned records.
     reverse: bool,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<ListRecordsOutput> {
     if limit > 100 {
         bail!("Error: limit can not be greater than 100")
@@ -38,7 +37,7 @@ async fn inner_list_records(
     let did = AccountManager::get_did_for_actor(&repo, None).await?;
     if let Some(did) = did {
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+            ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
         let records: Vec<Record> = actor_store
             .record
@@ -99,13 +98,13 @@ pub async fn list_records(
     rkeyEnd: Option<String>,
     // Flag to reverse the order of the returned records.
     reverse: Option<bool>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<ListRecordsOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     let limit = limit.unwrap_or(50);
     let reverse = reverse.unwrap_or(false);
 
     match inner_list_records(
-        repo, collection, limit, cursor, rkeyStart, rkeyEnd, reverse, s3_config,
+        repo, collection, limit, cursor, rkeyStart, rkeyEnd, reverse, blobstore,
     )
     .await
     {
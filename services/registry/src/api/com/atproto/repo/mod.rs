@@ -5,7 +5,10 @@
  */
 use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
 use crate::account_manager::AccountManager;
+use crate::SharedIdResolver;
 use anyhow::{bail, Result};
+use rsky_identity::did::atproto_data::get_did_key_from_multibase;
+use rsky_pds::common::get_verification_material;
 
 pub async fn assert_repo_availability(
     did: &String,
@@ -36,6 +39,23 @@ pub async fn assert_repo_availability(
     }
 }
 
+/// Resolves the `did:key` a repo's commits are signed with from its DID document, bypassing the
+/// cache so a recently rotated key is picked up.
+pub async fn resolve_atproto_key(id_resolver: &SharedIdResolver, did: &String) -> Result<String> {
+    let mut lock = id_resolver.id_resolver.write().await;
+    let did_doc = match lock.did.ensure_resolve(did, Some(true)).await {
+        Err(err) => bail!("could not resolve did document for `{did}`: `{err}`"),
+        Ok(res) => res,
+    };
+    match get_verification_material(&did_doc, &"atproto".to_string()) {
+        None => bail!("missing or bad atproto key in did document"),
+        Some(parsed_key) => match get_did_key_from_multibase(parsed_key)? {
+            None => bail!("missing or bad atproto key in did document"),
+            Some(did_key) => Ok(did_key),
+        },
+    }
+}
+
 pub fn routes() -> Vec<rocket::Route> {
     routes![
         apply_writes::apply_writes,
@@ -5,17 +5,18 @@
  */
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::auth_verifier::AccessRepoWrite;
 use crate::SharedSequencer;
-use crate::repository::ActorStore;
+use crate::repository::{ActorStore, CommitDataWithOps};
+use crate::lexicon::{ValidationError, LEXICONS};
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
-use rsky_pds::repo::types::{CommitData, PreparedWrite};
+use crate::rate_limiter::RateLimit;
+use rsky_pds::repo::types::PreparedWrite;
 use rsky_pds::repo::{
     make_aturi, prepare_create, prepare_update, PrepareCreateOpts, PrepareUpdateOpts,
 };
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use libipld::Cid;
 use rocket::http::Status;
 use rocket::response::status;
@@ -26,19 +27,20 @@ use std::str::FromStr;
 
 async fn inner_put_record(
     body: Json<PutRecordInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<PutRecordOutput> {
     let PutRecordInput {
         repo,
         collection,
         rkey,
         validate,
-        record,
+        mut record,
         swap_record,
         swap_commit,
     } = body.into_inner();
+    auth.assert_collection(&collection)?;
     let account = AccountManager::get_account(
         &repo,
         Some(AvailabilityFlags {
@@ -55,6 +57,8 @@ async fn inner_put_record(
         if did != auth.access.credentials.unwrap().did.unwrap() {
             bail!("AuthRequiredError")
         }
+        let validation_status =
+            LEXICONS.validate_record(&collection, Some(&rkey), &mut record, validate)?;
         // @TODO: Use ATUri
         let uri = make_aturi(did.clone(), Some(collection.clone()), Some(rkey.clone()));
         let swap_commit_cid = match swap_commit {
@@ -65,9 +69,9 @@ async fn inner_put_record(
             Some(swap_record) => Some(Cid::from_str(&swap_record)?),
             None => None,
         };
-        let (commit, write): (Option<CommitData>, PreparedWrite) = {
+        let (commit, write): (Option<CommitDataWithOps>, PreparedWrite) = {
             let mut actor_store =
-                ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+                ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
             let current = actor_store
                 .record
@@ -120,6 +124,7 @@ async fn inner_put_record(
         Ok(PutRecordOutput {
             uri: write.uri().to_string(),
             cid: write.cid().unwrap().to_string(),
+            validation_status: validation_status.map(|status| status.to_string()),
         })
     } else {
         bail!("Could not find repo: `{repo}`")
@@ -128,16 +133,31 @@ async fn inner_put_record(
 
 #[rocket::post("/xrpc/com.atproto.repo.putRecord", format = "json", data = "<body>")]
 pub async fn put_record(
+    _rate_limit: RateLimit,
     body: Json<PutRecordInput>,
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<PutRecordOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     println!("@LOG: debug put_record {body:#?}");
-    match inner_put_record(body, auth, sequencer, s3_config).await {
+    match inner_put_record(body, auth, sequencer, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
+            if let Some(error) = error.downcast_ref::<ValidationError>() {
+                let bad_request = ErrorMessageResponse {
+                    code: Some(ErrorCode::BadRequest),
+                    message: Some(error.to_string()),
+                };
+                return Err(status::Custom(Status::BadRequest, Json(bad_request)));
+            }
+            if error.to_string().starts_with("Forbidden") {
+                let forbidden = ErrorMessageResponse {
+                    code: Some(ErrorCode::Forbidden),
+                    message: Some(error.to_string()),
+                };
+                return Err(status::Custom(Status::Forbidden, Json(forbidden)));
+            }
             let internal_error = ErrorMessageResponse {
                 code: Some(ErrorCode::InternalServerError),
                 message: Some(error.to_string()),
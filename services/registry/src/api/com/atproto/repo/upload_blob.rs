@@ -3,14 +3,14 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
-use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::auth_verifier::AccessRepoWrite;
 use crate::repository::ActorStore;
 use rsky_pds::common::ContentType;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
+use crate::rate_limiter::RateLimit;
 use rsky_pds::repo::types::{BlobConstraint, PreparedBlobRef};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::data::Data;
 use rocket::http::Status;
 use rocket::response::status;
@@ -19,16 +19,16 @@ use rocket::State;
 use rsky_lexicon::com::atproto::repo::{Blob, BlobOutput};
 
 async fn inner_upload_blob(
-    auth: AccessStandardIncludeChecks,
+    auth: AccessRepoWrite,
     blob: Data<'_>,
     content_type: ContentType,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<BlobOutput> {
     let requester = auth.access.credentials.unwrap().did.unwrap();
 
     let actor_store = ActorStore::new(
         requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config),
+        blobstore.create(requester.clone()),
     );
 
     let metadata = actor_store
@@ -71,23 +71,26 @@ async fn inner_upload_blob(
 
 #[rocket::post("/xrpc/com.atproto.repo.uploadBlob", data = "<blob>")]
 pub async fn upload_blob(
-    auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
+    auth: AccessRepoWrite,
     blob: Data<'_>,
     content_type: ContentType,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<BlobOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_upload_blob(auth, blob, content_type, s3_config).await {
+    match inner_upload_blob(auth, blob, content_type, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("{error:?}");
+            let (status, code) = if error.to_string().starts_with("BlobTooLarge") {
+                (Status::PayloadTooLarge, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
             let internal_error = ErrorMessageResponse {
-                code: Some(ErrorCode::InternalServerError),
+                code: Some(code),
                 message: Some(error.to_string()),
             };
-            return Err(status::Custom(
-                Status::InternalServerError,
-                Json(internal_error),
-            ));
+            return Err(status::Custom(status, Json(internal_error)));
         }
     }
 }
\ No newline at end of file
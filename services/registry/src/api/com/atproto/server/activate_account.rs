@@ -8,14 +8,12 @@ use crate::account_manager::AccountManager;
 use crate::api::com::atproto::server::assert_valid_did_documents_for_service;
 use crate::auth_verifier::AccessFull;
 use crate::repository::ActorStore;
+use crate::sequencer;
 use crate::SharedSequencer;
 use crate::INVALID_HANDLE;
-use rsky_pds::repo::cid_set::CidSet;
-use rsky_pds::repo::types::CommitData;
-use crate::repository::aws::s3::S3BlobStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -24,10 +22,13 @@ use rocket::State;
 async fn inner_activate_account(
     auth: AccessFull,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<()> {
     let requester = auth.access.credentials.unwrap().did.unwrap();
-    assert_valid_did_documents_for_service(requester.clone()).await?;
+    // The DID document has to point at us before we start serving the repo
+    if let Err(error) = assert_valid_did_documents_for_service(requester.clone()).await {
+        bail!("InvalidRequest: {error}");
+    }
 
     let account = AccountManager::get_account(
         &requester,
@@ -43,29 +44,26 @@ async fn inner_activate_account(
 
         let mut actor_store = ActorStore::new(
             requester.clone(),
-            S3BlobStore::new(requester.clone(), s3_config),
+            blobstore.create(requester.clone()),
         );
         let root = actor_store.storage.get_root_detailed().await?;
         let blocks = actor_store.storage.get_blocks(vec![root.cid]).await?;
-        let commit_data = CommitData {
-            cid: root.cid,
-            rev: root.rev,
-            since: None,
-            prev: None,
-            new_blocks: blocks.blocks,
-            removed_cids: CidSet::new(None),
-        };
 
         // @NOTE: we're over-emitting for now for backwards compatibility, can reduce this in the future
         let status = AccountManager::get_account_status(&requester).await?;
+        let handle = account.handle.unwrap_or(INVALID_HANDLE.to_string());
         let mut lock = sequencer.sequencer.write().await;
+        lock.sequence_identity_evt(requester.clone(), Some(handle.clone()))
+            .await?;
         lock.sequence_account_evt(requester.clone(), status).await?;
-        lock.sequence_handle_update(
-            requester.clone(),
-            account.handle.unwrap_or(INVALID_HANDLE.to_string()),
-        )
-        .await?;
-        lock.sequence_commit(requester, commit_data, vec![]).await?;
+        lock.sequence_handle_update(requester.clone(), handle)
+            .await?;
+        // Consumers can't know what changed while the account was inactive, so they're told to
+        // resync from the current commit instead
+        let sync_seq = lock
+            .sequence_sync_evt(requester.clone(), root.cid, root.rev, &blocks.blocks)
+            .await?;
+        sequencer::invalidate_commits_for_user(&requester, Some(vec![sync_seq])).await?;
         Ok(())
     } else {
         bail!("User not found")
@@ -76,10 +74,17 @@ async fn inner_activate_account(
 pub async fn activate_account(
     auth: AccessFull,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_activate_account(auth, sequencer, s3_config).await {
+    match inner_activate_account(auth, sequencer, blobstore).await {
         Ok(_) => Ok(()),
+        Err(error) if error.to_string().starts_with("InvalidRequest") => {
+            let bad_request = ErrorMessageResponse {
+                code: Some(ErrorCode::BadRequest),
+                message: Some(error.to_string()),
+            };
+            return Err(status::Custom(Status::BadRequest, Json(bad_request)));
+        }
         Err(error) => {
             eprintln!("Internal Error: {error}");
             let internal_error = ErrorMessageResponse {
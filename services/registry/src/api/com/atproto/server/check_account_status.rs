@@ -8,9 +8,8 @@ use crate::api::com::atproto::server::is_valid_did_doc_for_service;
 use crate::auth_verifier::AccessFull;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use crate::repository::ActorStore;
-use crate::repository::aws::s3::S3BlobStore;
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use futures::try_join;
 use rocket::http::Status;
 use rocket::response::status;
@@ -20,13 +19,13 @@ use rsky_lexicon::com::atproto::server::CheckAccountStatusOutput;
 
 async fn inner_check_account_status(
     auth: AccessFull,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<CheckAccountStatusOutput> {
     let requester = auth.access.credentials.unwrap().did.unwrap();
 
     let mut actor_store = ActorStore::new(
         requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config),
+        blobstore.create(requester.clone()),
     );
     let (repo_root, repo_blocks, indexed_records, imported_blobs, expected_blobs) = try_join!(
         actor_store.storage.get_root_detailed(),
@@ -57,9 +56,9 @@ async fn inner_check_account_status(
 #[rocket::get("/xrpc/com.atproto.server.checkAccountStatus")]
 pub async fn check_account_status(
     auth: AccessFull,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<CheckAccountStatusOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_check_account_status(auth, s3_config).await {
+    match inner_check_account_status(auth, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("Internal Error: {error}");
@@ -3,65 +3,78 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
-use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::helpers::account::{AccountStatus, AvailabilityFlags};
+use crate::account_manager::helpers::session::SessionClient;
 use crate::account_manager::{AccountManager, CreateAccountOpts};
 use crate::api::com::atproto::server::safe_resolve_did_doc;
 use crate::auth_verifier::UserDidAuthOptional;
-use crate::config::SECRET_CONFIG;
+use crate::config::CORE_CONFIG;
 use crate::handle::explicit_slurs::has_explicit_slur;
 use crate::handle::normalize_handle;
 use crate::handle::reserved::is_handle_reserved;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use crate::SharedIdResolver;
 use crate::SharedSequencer;
+use crate::rate_limiter::RateLimit;
+use crate::signer::{LocalSigner, Signer};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use email_address::*;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::server::{CreateAccountInput, CreateAccountOutput};
-use secp256k1::{Keypair, Secp256k1, SecretKey};
+use secp256k1::SecretKey;
+use std::sync::Arc;
 
-#[allow(unused_assignments)]
 async fn inner_server_create_account(
     mut body: CreateAccountInput,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     id_resolver: &State<SharedIdResolver>,
+    client: SessionClient,
 ) -> Result<CreateAccountOutput, anyhow::Error> {
     let CreateAccountInput {
         email,
         handle,
-        mut did, // @TODO: Allow people to bring their own DID
+        did,
         password,
+        invite_code,
         ..
     } = body.clone();
-    let deactivated = false;
     if let Some(input_recovery_key) = &body.recovery_key {
         body.recovery_key = Some(input_recovery_key.to_owned());
     }
 
-    let secp = Secp256k1::new();
-    let private_key = SECRET_CONFIG.repo_signing_key.clone();
-    let secret_key = SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-    let signing_key = Keypair::from_secret_key(&secp, &secret_key);
-    match super::create_did_and_plc_op(&handle, &body, signing_key).await {
-        Ok(did_resp) => {
-            did = Some(did_resp);
+    // An account that brings its own DID is migrating in from another PDS, so it starts out
+    // deactivated and is signed for with the key it reserved through reserveSigningKey.
+    let deactivated = did.is_some();
+    let (did, signer) = match did {
+        Some(did) => {
+            AccountManager::reserve_signing_key(Some(did.clone())).await?;
+            let signer = AccountManager::get_signer(&did).await?;
+            (did, signer)
         }
-        Err(error) => {
-            eprintln!("{:?}", error);
-            bail!("Failed to create DID")
+        None => {
+            let secret_key = SecretKey::new(&mut rand::thread_rng());
+            let signer: Arc<dyn Signer> = Arc::new(LocalSigner::new(secret_key));
+            match super::create_did_and_plc_op(&handle, &body, &*signer).await {
+                Ok(did) => {
+                    AccountManager::store_signing_key(&did, &secret_key).await?;
+                    (did, signer)
+                }
+                Err(error) => {
+                    eprintln!("{:?}", error);
+                    bail!("Failed to create DID")
+                }
+            }
         }
-    }
-    let did = did.unwrap();
+    };
 
-    let mut actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
-    let commit = match actor_store.create_repo(signing_key, Vec::new()).await {
+    let mut actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
+    let commit = match actor_store.create_repo(&*signer, Vec::new()).await {
         Ok(commit) => commit,
         Err(error) => {
             eprintln!("{:?}", error);
@@ -78,7 +91,9 @@ async fn inner_server_create_account(
         password,
         repo_cid: commit.cid,
         repo_rev: commit.rev.clone(),
+        invite_code,
         deactivated: Some(deactivated),
+        client,
     })
     .await?;
 
@@ -110,11 +125,13 @@ async fn inner_server_create_account(
     data = "<body>"
 )]
 pub async fn create_account(
+    _rate_limit: RateLimit,
     body: Json<CreateAccountInput>,
     auth: UserDidAuthOptional,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     id_resolver: &State<SharedIdResolver>,
+    client: SessionClient,
 ) -> Result<Json<CreateAccountOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     let requester = match auth.access {
         Some(access) if access.credentials.is_some() => access.credentials.unwrap().iss,
@@ -132,7 +149,7 @@ pub async fn create_account(
         }
     };
 
-    match inner_server_create_account(input, sequencer, s3_config, id_resolver).await {
+    match inner_server_create_account(input, sequencer, blobstore, id_resolver, client).await {
         Ok(response) => Ok(Json(response)),
         Err(error) => {
             eprintln!("Internal Error: {error}");
@@ -150,18 +167,18 @@ pub async fn create_account(
 
 pub async fn validate_inputs_for_local_pds(
     input: CreateAccountInput,
-    _requester: Option<String>,
+    requester: Option<String>,
 ) -> Result<CreateAccountInput> {
     let CreateAccountInput {
         email,
         handle,
         did,
+        invite_code,
         verification_code,
         verification_phone,
         password,
         recovery_key,
         plc_op,
-        ..
     } = input;
 
     if plc_op.is_some() {
@@ -170,6 +187,17 @@ pub async fn validate_inputs_for_local_pds(
     if email.is_none() {
         bail!("Email is required");
     };
+    let invite_code = if CORE_CONFIG.invite_required() {
+        match invite_code {
+            None => bail!("No invite code provided"),
+            Some(invite_code) => {
+                AccountManager::ensure_invite_is_available(&invite_code).await?;
+                Some(invite_code)
+            }
+        }
+    } else {
+        None
+    };
     match email {
         None => bail!("Email is required"),
         Some(email) => {
@@ -181,8 +209,22 @@ pub async fn validate_inputs_for_local_pds(
             if password.is_none() {
                 bail!("Password is required");
             };
-            if did.is_some() {
-                bail!("Not yet allowing people to bring their own DID");
+            if let Some(did) = &did {
+                // Migrating in requires a service auth token from the account's current PDS
+                if requester.as_ref() != Some(did) {
+                    bail!("Missing auth to create account with did: {did}");
+                }
+                let did_accnt = AccountManager::get_account(
+                    did,
+                    Some(AvailabilityFlags {
+                        include_deactivated: Some(true),
+                        include_taken_down: Some(true),
+                    }),
+                )
+                .await?;
+                if did_accnt.is_some() {
+                    bail!("Account already exists for did: {did}");
+                }
             };
             if !super::validate_handle(&handle) {
                 bail!("Invalid handle");
@@ -204,7 +246,7 @@ pub async fn validate_inputs_for_local_pds(
                 email: Some(email),
                 handle,
                 did,
-                invite_code: None,
+                invite_code,
                 verification_code,
                 verification_phone,
                 password,
@@ -20,11 +20,20 @@ pub async fn create_app_password(
     body: Json<CreateAppPasswordInput>,
     auth: AccessFull,
 ) -> Result<Json<CreateAppPasswordOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    let CreateAppPasswordInput { name } = body.into_inner();
-    match AccountManager::create_app_password(auth.access.credentials.unwrap().did.unwrap(), name)
-        .await
+    match AccountManager::create_app_password(
+        auth.access.credentials.unwrap().did.unwrap(),
+        body.into_inner(),
+    )
+    .await
     {
         Ok(app_password) => Ok(Json(app_password)),
+        Err(error) if error.to_string().starts_with("InvalidRequest") => {
+            let bad_request = ErrorMessageResponse {
+                code: Some(ErrorCode::BadRequest),
+                message: Some(error.to_string()),
+            };
+            return Err(status::Custom(Status::BadRequest, Json(bad_request)));
+        }
         Err(error) => {
             eprintln!("Internal Error: {error}");
             let internal_error = ErrorMessageResponse {
@@ -0,0 +1,67 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::invite::ADMIN_CREATOR;
+use crate::account_manager::AccountManager;
+use crate::api::com::atproto::server::gen_invite_code;
+use crate::auth_verifier::AdminToken;
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::server::{
+    AccountCodes, CreateInviteCodeInput, CreateInviteCodeOutput,
+};
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+
+async fn inner_create_invite_code(body: CreateInviteCodeInput) -> Result<CreateInviteCodeOutput> {
+    let CreateInviteCodeInput {
+        use_count,
+        for_account,
+    } = body;
+    if use_count < 1 {
+        bail!("InvalidRequest: useCount must be at least 1");
+    }
+    let code = gen_invite_code();
+    AccountManager::create_invite_codes(
+        vec![AccountCodes {
+            account: for_account.unwrap_or(ADMIN_CREATOR.to_string()),
+            codes: vec![code.clone()],
+        }],
+        use_count,
+    )
+    .await?;
+    Ok(CreateInviteCodeOutput { code })
+}
+
+/// Create an invite code.
+#[rocket::post(
+    "/xrpc/com.atproto.server.createInviteCode",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn create_invite_code(
+    body: Json<CreateInviteCodeInput>,
+    _auth: AdminToken,
+) -> Result<Json<CreateInviteCodeOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_create_invite_code(body.into_inner()).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
@@ -0,0 +1,72 @@
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::invite::ADMIN_CREATOR;
+use crate::account_manager::AccountManager;
+use crate::api::com::atproto::server::gen_invite_codes;
+use crate::auth_verifier::AdminToken;
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::server::{
+    AccountCodes, CreateInviteCodesInput, CreateInviteCodesOutput,
+};
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+
+async fn inner_create_invite_codes(
+    body: CreateInviteCodesInput,
+) -> Result<CreateInviteCodesOutput> {
+    let CreateInviteCodesInput {
+        code_count,
+        use_count,
+        for_accounts,
+    } = body;
+    if code_count < 1 || use_count < 1 {
+        bail!("InvalidRequest: codeCount and useCount must be at least 1");
+    }
+    let for_accounts = for_accounts.unwrap_or(vec![ADMIN_CREATOR.to_string()]);
+    let account_codes = for_accounts
+        .into_iter()
+        .map(|account| AccountCodes {
+            account,
+            codes: gen_invite_codes(code_count as usize),
+        })
+        .collect::<Vec<AccountCodes>>();
+    AccountManager::create_invite_codes(account_codes.clone(), use_count).await?;
+    Ok(CreateInviteCodesOutput {
+        codes: account_codes,
+    })
+}
+
+/// Create invite codes.
+#[rocket::post(
+    "/xrpc/com.atproto.server.createInviteCodes",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn create_invite_codes(
+    body: Json<CreateInviteCodesInput>,
+    _auth: AdminToken,
+) -> Result<Json<CreateInviteCodesOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_create_invite_codes(body.into_inner()).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
@@ -3,22 +3,82 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
-use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
+use crate::account_manager::helpers::session::SessionClient;
 use crate::account_manager::AccountManager;
+use crate::database::models::{AppPassword, EmailTokenPurpose};
+use crate::mailer;
+use crate::mailer::IdentifierAndTokenParams;
 use crate::INVALID_HANDLE;
-use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use anyhow::bail;
+use crate::rate_limiter::RateLimit;
+use anyhow::{bail, Result};
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rsky_lexicon::com::atproto::server::{CreateSessionInput, CreateSessionOutput};
+use serde_json::{json, Value};
+
+/// Checks the second factor of a sign in with the account password. Without a token, a code is
+/// emailed to accounts that sign in with email codes, and the client is told to ask for one.
+/// An authenticator app code or one of the account's recovery codes is accepted too.
+pub async fn assert_auth_factor(user: &ActorAccount, token: Option<String>) -> Result<()> {
+    let email_auth_factor = match (&user.email, &user.email_confirmed_at) {
+        (Some(_), Some(_)) => AccountManager::get_email_auth_factor(&user.did).await?,
+        _ => false,
+    };
+    let totp = AccountManager::has_totp(&user.did).await?;
+    if !email_auth_factor && !totp {
+        return Ok(());
+    }
+
+    let token = match token {
+        Some(token) if !token.trim().is_empty() => token,
+        _ if email_auth_factor => {
+            let email = user.email.clone().unwrap();
+            let token =
+                AccountManager::create_email_token(&user.did, EmailTokenPurpose::TwoFactorCode)
+                    .await?;
+            mailer::send_sign_in_code(
+                email.clone(),
+                IdentifierAndTokenParams {
+                    identifier: user.handle.clone().unwrap_or(email),
+                    token,
+                },
+            )
+            .await?;
+            bail!("AuthFactorTokenRequired: A sign in code has been sent to your email address")
+        }
+        _ => bail!("AuthFactorTokenRequired: Enter the code from your authenticator app"),
+    };
+    if totp
+        && (AccountManager::verify_totp(&user.did, &token).await?
+            || AccountManager::use_recovery_code(&user.did, &token).await?)
+    {
+        return Ok(());
+    }
+    if email_auth_factor
+        && AccountManager::assert_valid_email_token(
+            &user.did,
+            EmailTokenPurpose::TwoFactorCode,
+            &token,
+        )
+        .await
+        .is_ok()
+    {
+        AccountManager::delete_email_token(&user.did, EmailTokenPurpose::TwoFactorCode).await?;
+        return Ok(());
+    }
+    bail!("InvalidToken: Sign in code is invalid or expired")
+}
 
 async fn inner_create_session(
     body: Json<CreateSessionInput>,
+    client: SessionClient,
 ) -> Result<CreateSessionOutput, anyhow::Error> {
     let CreateSessionInput {
         password,
         identifier,
+        auth_factor_token,
     } = body.into_inner();
     let identifier = identifier.to_lowercase();
 
@@ -45,26 +105,32 @@ async fn inner_create_session(
         }
     };
     if let Ok(Some(user)) = user {
-        let mut app_password_name: Option<String> = None;
+        let mut app_password: Option<AppPassword> = None;
         let valid_account_pass =
             AccountManager::verify_account_password(&user.did, &password).await?;
         if !valid_account_pass {
-            app_password_name = AccountManager::verify_app_password(&user.did, &password).await?;
-            if app_password_name.is_none() {
+            app_password = AccountManager::verify_app_password(&user.did, &password).await?;
+            if app_password.is_none() {
                 bail!("Invalid identifier or password")
             }
         }
         if user.takedown_ref.is_some() {
             bail!("Account has been taken down")
         }
+        // App passwords are for clients that can't prompt for a code, and can't change the
+        // account's password or email
+        if app_password.is_none() {
+            assert_auth_factor(&user, auth_factor_token).await?;
+        }
         let (access_jwt, refresh_jwt) =
-            AccountManager::create_session(user.did.clone(), app_password_name).await?;
+            AccountManager::create_session(user.did.clone(), app_password, &client).await?;
         Ok(CreateSessionOutput {
             did: user.did,
             did_doc: None,
             handle: user.handle.unwrap_or(INVALID_HANDLE.to_string()),
             email: user.email,
             email_confirmed: Some(user.email_confirmed_at.is_some()),
+            email_auth_factor: Some(AccountManager::get_email_auth_factor(&user.did).await?),
             access_jwt,
             refresh_jwt,
         })
@@ -79,21 +145,29 @@ async fn inner_create_session(
     data = "<body>"
 )]
 pub async fn create_session(
+    _rate_limit: RateLimit,
     body: Json<CreateSessionInput>,
-) -> Result<Json<CreateSessionOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    // @TODO: Add rate limiting
-
-    match inner_create_session(body).await {
+    client: SessionClient,
+) -> Result<Json<CreateSessionOutput>, status::Custom<Json<Value>>> {
+    match inner_create_session(body, client).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("{error:?}");
-            let internal_error = ErrorMessageResponse {
-                code: Some(ErrorCode::InternalServerError),
-                message: Some(error.to_string()),
+            let message = error.to_string();
+            // Clients look for these by name to prompt for a sign in code
+            let (status, name) = if message.starts_with("AuthFactorTokenRequired") {
+                (Status::Unauthorized, "AuthFactorTokenRequired")
+            } else if message.starts_with("InvalidToken") {
+                (Status::Unauthorized, "InvalidToken")
+            } else {
+                (Status::InternalServerError, "InternalServerError")
             };
             return Err(status::Custom(
-                Status::InternalServerError,
-                Json(internal_error),
+                status,
+                Json(json!({
+                    "error": name,
+                    "message": message
+                })),
             ));
         }
     }
@@ -11,9 +11,8 @@ use crate::sequencer;
 use crate::SharedSequencer;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::repository::aws::s3::S3BlobStore;
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -23,7 +22,7 @@ use rsky_lexicon::com::atproto::server::DeleteAccountInput;
 async fn inner_delete_account(
     body: Json<DeleteAccountInput>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<()> {
     let DeleteAccountInput {
         did,
@@ -51,7 +50,7 @@ async fn inner_delete_account(
         .await?;
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+            ActorStore::new(did.clone(), blobstore.create(did.clone()));
         actor_store.destroy().await?;
         AccountManager::delete_account(&did).await?;
         let mut lock = sequencer.sequencer.write().await;
@@ -60,7 +59,7 @@ async fn inner_delete_account(
             .await?;
         let tombstone_seq = lock.sequence_tombstone(did.clone()).await?;
 
-        sequencer::delete_all_for_user(&did, Some(vec![account_seq, tombstone_seq])).await?;
+        sequencer::invalidate_all_for_user(&did, Some(vec![account_seq, tombstone_seq])).await?;
         Ok(())
     } else {
         bail!("account not found")
@@ -75,10 +74,10 @@ async fn inner_delete_account(
 pub async fn delete_account(
     body: Json<DeleteAccountInput>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     _auth: AdminToken
 ) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_delete_account(body, sequencer, s3_config).await {
+    match inner_delete_account(body, sequencer, blobstore).await {
         Ok(_) => Ok(()),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
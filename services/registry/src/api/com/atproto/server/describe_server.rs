@@ -23,7 +23,7 @@ pub async fn describe_server(
     Ok(Json(DescribeServerOutput {
         did: SERVICE_CONFIG.did.clone(),
         available_user_domains,
-        invite_code_required: None,
+        invite_code_required: Some(CORE_CONFIG.invite_required()),
         phone_verification_required: None,
         links: DescribeServerRefLinks {
             privacy_policy,
@@ -1,5 +1,168 @@
-// /xrpc/com.atproto.server.getAccountInviteCodes?<includeUsed>&<createAvailable>
-#[rocket::get("/xrpc/com.atproto.server.getAccountInviteCodes")]
-pub async fn get_account_invite_codes() {
-    unimplemented!();
-}
\ No newline at end of file
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::helpers::invite::ADMIN_CREATOR;
+use crate::account_manager::AccountManager;
+use crate::api::com::atproto::server::gen_invite_codes;
+use crate::auth_verifier::AccessFull;
+use crate::config::CORE_CONFIG;
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::server::{GetAccountInviteCodesOutput, InviteCode};
+use rsky_pds::common::time::from_str_to_millis;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use std::time::SystemTime;
+
+/// How many routine codes `codes`' owner has earned but not yet been given, and how many routine
+/// codes they will have in total once those are created.
+fn calculate_codes_to_create(
+    user_created_at: i64,
+    codes: &Vec<InviteCode>,
+    epoch: i64,
+    interval: i64,
+    now: i64,
+) -> Result<(usize, usize)> {
+    // explicitly gifted admin codes don't count towards routine codes
+    let routine_codes = codes
+        .iter()
+        .filter(|code| code.created_by != ADMIN_CREATOR)
+        .collect::<Vec<&InviteCode>>();
+    // codes are earned from whichever is later, the epoch or the account's creation
+    let earning_since = epoch.max(user_created_at);
+    let could_create = ((now - earning_since).max(0) / interval) as usize;
+    let mut created = 0;
+    for code in &routine_codes {
+        if from_str_to_millis(&code.created_at)? >= earning_since {
+            created += 1;
+        }
+    }
+    let to_create = could_create.saturating_sub(created);
+    Ok((to_create, routine_codes.len() + to_create))
+}
+
+async fn inner_get_account_invite_codes(
+    include_used: bool,
+    create_available: bool,
+    auth: AccessFull,
+) -> Result<GetAccountInviteCodesOutput> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let account = AccountManager::get_account(
+        &did,
+        Some(AvailabilityFlags {
+            include_deactivated: Some(true),
+            include_taken_down: None,
+        }),
+    )
+    .await?;
+    let account = match account {
+        None => bail!("Account not found"),
+        Some(account) => account,
+    };
+
+    let mut user_codes = AccountManager::get_account_invite_codes(&did).await?;
+    if let (true, true, Some(interval)) = (
+        create_available,
+        CORE_CONFIG.invite_required(),
+        CORE_CONFIG.invite_interval(),
+    ) {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis() as i64;
+        let (to_create, total) = calculate_codes_to_create(
+            from_str_to_millis(&account.created_at)?,
+            &user_codes,
+            CORE_CONFIG.invite_epoch(),
+            interval,
+            now,
+        )?;
+        if to_create > 0 {
+            let invites_disabled = AccountManager::get_account_invites_disabled(&did)
+                .await?
+                .map_or(false, |(invites_disabled, _)| invites_disabled);
+            let created = AccountManager::create_account_invite_codes(
+                &did,
+                gen_invite_codes(to_create),
+                total,
+                invites_disabled,
+            )
+            .await?;
+            user_codes.extend(created);
+        }
+    }
+
+    let codes = user_codes
+        .into_iter()
+        .filter(|code| !code.disabled)
+        .filter(|code| include_used || (code.uses.len() as i32) < code.available)
+        .collect();
+    Ok(GetAccountInviteCodesOutput { codes })
+}
+
+/// Get all invite codes for the current account.
+#[allow(non_snake_case)]
+#[rocket::get("/xrpc/com.atproto.server.getAccountInviteCodes?<includeUsed>&<createAvailable>")]
+pub async fn get_account_invite_codes(
+    includeUsed: Option<bool>,
+    // Controls whether any new 'earned' but not 'created' invites should be created.
+    createAvailable: Option<bool>,
+    auth: AccessFull,
+) -> Result<Json<GetAccountInviteCodesOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    let include_used = includeUsed.unwrap_or(true);
+    let create_available = createAvailable.unwrap_or(true);
+
+    match inner_get_account_invite_codes(include_used, create_available, auth).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let internal_error = ErrorMessageResponse {
+                code: Some(ErrorCode::InternalServerError),
+                message: Some(error.to_string()),
+            };
+            return Err(status::Custom(
+                Status::InternalServerError,
+                Json(internal_error),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60 * 1000;
+
+    fn code(created_by: &str, created_at: &str) -> InviteCode {
+        InviteCode {
+            code: "example-com-abcde-fghij".to_string(),
+            available: 1,
+            disabled: false,
+            for_account: "did:example:alice".to_string(),
+            created_by: created_by.to_string(),
+            created_at: created_at.to_string(),
+            uses: vec![],
+        }
+    }
+
+    #[test]
+    fn earns_codes_per_interval_not_counting_admin_codes() -> Result<()> {
+        let created_at = from_str_to_millis(&"2024-11-01T00:00:00.000Z".to_string())?;
+        let codes = vec![
+            code("did:example:alice", "2024-11-02T00:00:00.000Z"),
+            code(ADMIN_CREATOR, "2024-11-02T00:00:00.000Z"),
+        ];
+        let (to_create, total) =
+            calculate_codes_to_create(created_at, &codes, 0, 7 * DAY, created_at + 22 * DAY)?;
+        assert_eq!((to_create, total), (2, 3));
+
+        let (to_create, total) =
+            calculate_codes_to_create(created_at, &codes, 0, 7 * DAY, created_at + 6 * DAY)?;
+        assert_eq!((to_create, total), (0, 1));
+        Ok(())
+    }
+}
@@ -5,7 +5,7 @@
  */
 use crate::account_manager::helpers::auth::{create_service_jwt, ServiceJwtParams};
 use crate::auth_verifier::AccessFull;
-use crate::config::SECRET_CONFIG;
+use crate::account_manager::AccountManager;
 use crate::pipethrough::{PRIVILEGED_METHODS, PROTECTED_METHODS};
 use rsky_pds::common::time::{from_micros_to_utc, HOUR, MINUTE};
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
@@ -16,7 +16,6 @@ use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rsky_lexicon::com::atproto::server::GetServiceAuthOutput;
-use secp256k1::SecretKey;
 use std::time::SystemTime;
 
 pub async fn inner_get_service_auth(
@@ -27,9 +26,7 @@ pub async fn inner_get_service_auth(
 ) -> Result<String> {
     let credentials = auth.access.credentials.unwrap();
     let did = credentials.clone().did.unwrap();
-    // We just use the repo signing key
-    let private_key = SECRET_CONFIG.repo_signing_key.clone();
-    let keypair = SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
+    let signer = AccountManager::get_signer(&did).await?;
     let exp = match exp {
         None => None,
         Some(exp) => Some(exp * 1000),
@@ -60,7 +57,7 @@ pub async fn inner_get_service_auth(
         exp: None,
         lxm,
         jti: None,
-        keypair,
+        signer,
     })
     .await
 }
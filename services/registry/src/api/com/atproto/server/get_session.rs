@@ -17,6 +17,7 @@ pub async fn get_session(
     auth: AccessStandard,
 ) -> Result<Json<GetSessionOutput>, status::Custom<Json<ErrorMessageResponse>>> {
     let did = auth.access.credentials.unwrap().did.unwrap();
+    let email_auth_factor = AccountManager::get_email_auth_factor(&did).await.ok();
     match AccountManager::get_account(&did, None).await {
         Ok(Some(user)) => Ok(Json(GetSessionOutput {
             handle: user.handle.unwrap_or(INVALID_HANDLE.to_string()),
@@ -24,6 +25,7 @@ pub async fn get_session(
             email: user.email,
             did_doc: None,
             email_confirmed: Some(user.email_confirmed_at.is_some()),
+            email_auth_factor,
         })),
         _ => {
             let internal_error = ErrorMessageResponse {
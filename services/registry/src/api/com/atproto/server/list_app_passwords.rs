@@ -3,6 +3,7 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
+use crate::account_manager::helpers::password::AppPasswordScopes;
 use crate::account_manager::AccountManager;
 use crate::auth_verifier::AccessFull;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
@@ -20,9 +21,17 @@ pub async fn list_app_passwords(
         Ok(passwords) => {
             let passwords: Vec<AppPassword> = passwords
                 .into_iter()
-                .map(|password| AppPassword {
-                    name: password.0,
-                    created_at: password.1,
+                .map(|password| {
+                    let scopes = AppPasswordScopes::from_app_password(&password);
+                    AppPassword {
+                        name: password.name,
+                        created_at: password.created_at,
+                        privileged: Some(password.privileged == 1),
+                        read_only: Some(scopes.read_only),
+                        collections: scopes.collections,
+                        expires_at: password.expires_at,
+                        last_used_at: password.last_used_at,
+                    }
                 })
                 .collect();
             Ok(Json(ListAppPasswordsOutput { passwords }))
@@ -3,12 +3,14 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
-use rsky_pds::common::sign::atproto_sign;
 use crate::database::models::*;
 use serde::{Serialize, Deserialize};
-use crate::config::{CORE_CONFIG, IDENTITY_CONFIG, SECRET_CONFIG};
+use crate::config::{CORE_CONFIG, IDENTITY_CONFIG};
 use crate::{SharedIdResolver, APP_USER_AGENT};
 use crate::plc;
+use crate::plc::operations::signing_bytes;
+use crate::account_manager::AccountManager;
+use crate::signer::{LocalSigner, Signer, ROTATION_SIGNER};
 use anyhow::{bail, Result};
 use data_encoding::BASE32;
 use diesel::prelude::*;
@@ -20,9 +22,10 @@ use reqwest;
 use rocket::State;
 use rsky_identity::types::DidDocument;
 use rsky_lexicon::com::atproto::server::CreateAccountInput;
-use secp256k1::{Keypair, PublicKey, Secp256k1, SecretKey};
+use secp256k1::PublicKey;
 use serde_json::Value;
 use sha2::{Digest, Sha256};
+use std::sync::Arc;
 use unsigned_varint::encode::u16 as encode_varint;
 
 const DID_KEY_PREFIX: &str = "did:key:";
@@ -79,6 +82,15 @@ pub fn get_random_token() -> String {
     token[0..5].to_owned() + "-" + &token[5..10]
 }
 
+/// Formatted {hostname}-xxxxx-xxxxx, with the dots in the hostname replaced by dashes
+pub fn gen_invite_code() -> String {
+    CORE_CONFIG.hostname().replace(".", "-") + "-" + &get_random_token()
+}
+
+pub fn gen_invite_codes(count: usize) -> Vec<String> {
+    (0..count).map(|_| gen_invite_code()).collect()
+}
+
 pub async fn safe_resolve_did_doc(
     id_resolver: &State<SharedIdResolver>,
     did: &String,
@@ -118,11 +130,14 @@ pub fn lookup_user_by_handle(handle: &str, conn: &mut PgConnection) -> Result<Ac
     Ok(result)
 }
 
-pub fn sign(mut genesis: PlcGenesisOperation, private_key: &SecretKey) -> PlcGenesisOperation {
-    let genesis_sig = atproto_sign(&genesis, private_key).unwrap();
+pub async fn sign(
+    mut genesis: PlcGenesisOperation,
+    signer: &dyn Signer,
+) -> Result<PlcGenesisOperation> {
+    let genesis_sig = signer.sign(&signing_bytes(&genesis)?).await?;
     // Base 64 encode signature bytes
     genesis.sig = Some(base64_url::encode(&genesis_sig).replace("=", ""));
-    genesis
+    Ok(genesis)
 }
 
 /// https://github.com/gnunicorn/rust-multicodec/blob/master/src/lib.rs#L249-L260
@@ -148,39 +163,35 @@ pub fn encode_did_key(pubkey: &PublicKey) -> String {
     format!("{DID_KEY_PREFIX}{pk_multibase}")
 }
 
-pub fn get_keys_from_private_key_str(private_key: String) -> Result<(SecretKey, PublicKey)> {
-    let secp = Secp256k1::new();
-    let decoded_key = hex::decode(private_key.as_bytes()).map_err(|error| {
-        let context = format!("Issue decoding hex '{}'", private_key);
-        anyhow::Error::new(error).context(context)
-    })?;
-    let secret_key = SecretKey::from_slice(&decoded_key).map_err(|error| {
-        let context = format!("Issue creating secret key from input '{}'", private_key);
-        anyhow::Error::new(error).context(context)
-    })?;
-    let public_key = secret_key.public_key(&secp);
-    Ok((secret_key, public_key))
+/// The inverse of `encode_did_key`.
+pub fn decode_did_key(did_key: &str) -> Result<PublicKey> {
+    let pk_multibase = match did_key.strip_prefix(DID_KEY_PREFIX) {
+        None => bail!("Not a did:key `{did_key}`"),
+        Some(pk_multibase) => pk_multibase,
+    };
+    let (_, pk_wrapped) = multibase::decode(pk_multibase)?;
+    match pk_wrapped.strip_prefix(&[0xe7, 0x01]) {
+        None => bail!("Not a secp256k1 did:key `{did_key}`"),
+        Some(pk_compact) => Ok(PublicKey::from_slice(pk_compact)?),
+    }
 }
 
 pub async fn create_did_and_plc_op(
     handle: &str,
     input: &CreateAccountInput,
-    signing_key: Keypair,
+    signing_key: &dyn Signer,
 ) -> Result<String> {
-    let private_key: String;
-    if let Some(recovery_key) = &input.recovery_key {
-        private_key = recovery_key.clone();
-    } else {
-        private_key = SECRET_CONFIG.pds_rotation_key.clone();
-    }
-    let (secret_key, public_key) = get_keys_from_private_key_str(private_key)?;
+    let rotation_signer: Arc<dyn Signer> = match &input.recovery_key {
+        Some(recovery_key) => Arc::new(LocalSigner::from_hex(recovery_key)?),
+        None => ROTATION_SIGNER.clone(),
+    };
 
     println!("Generating and signing PLC directory genesis operation...");
     let mut create_op = PlcGenesisOperation {
         r#type: "plc_operation".to_owned(),
-        rotation_keys: vec![encode_did_key(&public_key)],
+        rotation_keys: vec![rotation_signer.did_key()],
         verification_methods: PlcGenesisVerificationMethods {
-            atproto: encode_did_key(&signing_key.public_key()),
+            atproto: signing_key.did_key(),
         },
         also_known_as: vec![format!("at://{handle}")],
         services: PlcGenesisServices {
@@ -195,7 +206,7 @@ pub async fn create_did_and_plc_op(
         prev: None,
         sig: None,
     };
-    create_op = sign(create_op, &secret_key);
+    create_op = sign(create_op, &*rotation_signer).await?;
     let json = serde_json::to_string(&create_op).unwrap();
     let hashmap_genesis: IndexMap<String, Value> = serde_json::from_str(&json).unwrap();
     let signed_genesis_bytes = serde_ipld_dagcbor::to_vec(&hashmap_genesis).unwrap();
@@ -244,11 +255,14 @@ pub async fn assert_valid_did_documents_for_service(did: String) -> Result<()> {
             Some(key) => Some(key.clone()),
             None => None,
         };
-        assert_valid_doc_contents(AssertionContents {
-            pds_endpoint,
-            signing_key,
-            rotation_keys: Some(resolved.rotation_keys),
-        })
+        assert_valid_doc_contents(
+            &did,
+            AssertionContents {
+                pds_endpoint,
+                signing_key,
+                rotation_keys: Some(resolved.rotation_keys),
+            },
+        )
         .await?;
     } else {
         bail!("Not yet supporting did:web")
@@ -256,15 +270,13 @@ pub async fn assert_valid_did_documents_for_service(did: String) -> Result<()> {
     Ok(())
 }
 
-pub async fn assert_valid_doc_contents(contents: AssertionContents) -> Result<()> {
+pub async fn assert_valid_doc_contents(did: &String, contents: AssertionContents) -> Result<()> {
     let AssertionContents {
         signing_key,
         pds_endpoint,
         rotation_keys,
     } = contents;
-    let private_key = SECRET_CONFIG.pds_rotation_key.clone();
-    let (_, plc_rotation_key) = get_keys_from_private_key_str(private_key)?;
-    let plc_rotation_key = encode_did_key(&plc_rotation_key);
+    let plc_rotation_key = ROTATION_SIGNER.did_key();
 
     if let Some(rotation_keys) = rotation_keys {
         if !rocket::form::validate::Contains::contains(&rotation_keys, plc_rotation_key) {
@@ -276,12 +288,8 @@ pub async fn assert_valid_doc_contents(contents: AssertionContents) -> Result<()
         bail!("DID document atproto_pds service endpoint does not match PDS public url")
     }
 
-    let repo_signing_key = SECRET_CONFIG.repo_signing_key.clone();
-    let repo_signing_keypair =
-        SecretKey::from_slice(&hex::decode(repo_signing_key.as_bytes()).unwrap()).unwrap();
-    let secp = Secp256k1::new();
-    let repo_public_key = repo_signing_keypair.public_key(&secp);
-    if signing_key.is_none() || signing_key.unwrap() != encode_did_key(&repo_public_key) {
+    let repo_signer = AccountManager::get_signer(did).await?;
+    if signing_key.is_none() || signing_key.unwrap() != repo_signer.did_key() {
         bail!("DID document verification method does not match expected signing key")
     }
     Ok(())
@@ -303,6 +311,8 @@ pub fn routes() -> Vec<rocket::Route> {
         reset_password::reset_password,
         update_email::update_email,
         reserve_signing_key::reserve_signing_key,
+        create_invite_code::create_invite_code,
+        create_invite_codes::create_invite_codes,
         get_account_invite_codes::get_account_invite_codes,
         request_account_delete::request_account_delete,
         request_email_confirmation::request_email_confirmation,
@@ -329,6 +339,8 @@ pub mod describe_server;
 pub mod reset_password;
 pub mod update_email;
 pub mod reserve_signing_key;
+pub mod create_invite_code;
+pub mod create_invite_codes;
 pub mod get_account_invite_codes;
 pub mod request_account_delete;
 pub mod request_email_confirmation;
@@ -4,6 +4,7 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::helpers::session::SessionClient;
 use crate::account_manager::AccountManager;
 use crate::auth_verifier::Refresh;
 use crate::INVALID_HANDLE;
@@ -15,7 +16,10 @@ use rocket::response::status;
 use rocket::serde::json::Json;
 use rsky_lexicon::com::atproto::server::RefreshSessionOutput;
 
-async fn inner_refresh_session(auth: Refresh) -> Result<RefreshSessionOutput> {
+async fn inner_refresh_session(
+    auth: Refresh,
+    client: SessionClient,
+) -> Result<RefreshSessionOutput> {
     let Credentials { did, token_id, .. } = auth.access.credentials.unwrap();
     let did = did.unwrap();
     let token_id = token_id.unwrap();
@@ -32,7 +36,7 @@ async fn inner_refresh_session(auth: Refresh) -> Result<RefreshSessionOutput> {
         if user.takedown_ref.is_some() {
             bail!("Account has been taken down")
         }
-        let rotated = AccountManager::rotate_refresh_token(&token_id).await?;
+        let rotated = AccountManager::rotate_refresh_token(&token_id, &client).await?;
         if let Some(rotated) = rotated {
             Ok(RefreshSessionOutput {
                 handle: user.handle.unwrap_or(INVALID_HANDLE.to_string()),
@@ -52,8 +56,9 @@ async fn inner_refresh_session(auth: Refresh) -> Result<RefreshSessionOutput> {
 #[rocket::post("/xrpc/com.atproto.server.refreshSession")]
 pub async fn refresh_session(
     auth: Refresh,
+    client: SessionClient,
 ) -> Result<Json<RefreshSessionOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_refresh_session(auth).await {
+    match inner_refresh_session(auth, client).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("Internal Error: {error}");
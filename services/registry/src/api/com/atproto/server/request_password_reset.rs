@@ -9,6 +9,7 @@ use crate::auth_verifier::AccessStandardIncludeChecks;
 use crate::mailer;
 use crate::mailer::IdentifierAndTokenParams;
 use crate::database::models::EmailTokenPurpose;
+use crate::rate_limiter::RateLimit;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::{bail, Result};
 use rocket::http::Status;
@@ -57,6 +58,7 @@ async fn inner_request_password_reset(body: Json<RequestPasswordResetInput>) ->
     data = "<body>"
 )]
 pub async fn request_password_reset(
+    _rate_limit: RateLimit,
     body: Json<RequestPasswordResetInput>,
     _auth: AccessStandardIncludeChecks,
 ) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
@@ -1,4 +1,52 @@
-#[rocket::post("/xrpc/com.atproto.server.reserveSigningKey")]
-pub async fn reserve_signing_key() {
-    unimplemented!();
-}
\ No newline at end of file
+/**
+ * Implementation from https://github.com/bluesky-social/atproto
+ * Modified to work with our own DB
+ * License: https://github.com/bluesky-social/atproto/blob/main/LICENSE.txt
+ */
+use crate::account_manager::AccountManager;
+use crate::rate_limiter::RateLimit;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use anyhow::Result;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::server::{ReserveSigningKeyInput, ReserveSigningKeyOutput};
+
+async fn inner_reserve_signing_key(body: ReserveSigningKeyInput) -> Result<ReserveSigningKeyOutput> {
+    let signing_key = AccountManager::reserve_signing_key(body.did).await?;
+    Ok(ReserveSigningKeyOutput { signing_key })
+}
+
+/// Reserve a repo signing key, for use with account creation. Necessary so that a DID PLC update
+/// operation can be constructed during an account migration.
+#[rocket::post(
+    "/xrpc/com.atproto.server.reserveSigningKey",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn reserve_signing_key(
+    body: Json<ReserveSigningKeyInput>,
+    _rate_limit: RateLimit,
+) -> Result<Json<ReserveSigningKeyOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_reserve_signing_key(body.into_inner()).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) if error.to_string().starts_with("InvalidRequest") => {
+            let bad_request = ErrorMessageResponse {
+                code: Some(ErrorCode::BadRequest),
+                message: Some(error.to_string()),
+            };
+            return Err(status::Custom(Status::BadRequest, Json(bad_request)));
+        }
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let internal_error = ErrorMessageResponse {
+                code: Some(ErrorCode::InternalServerError),
+                message: Some("Internal error".to_string()),
+            };
+            return Err(status::Custom(
+                Status::InternalServerError,
+                Json(internal_error),
+            ));
+        }
+    }
+}
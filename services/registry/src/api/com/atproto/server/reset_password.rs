@@ -4,6 +4,7 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::account_manager::{AccountManager, ResetPasswordOpts};
+use crate::rate_limiter::RateLimit;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use rocket::http::Status;
 use rocket::response::status;
@@ -16,6 +17,7 @@ use rsky_lexicon::com::atproto::server::ResetPasswordInput;
     data = "<body>"
 )]
 pub async fn reset_password(
+    _rate_limit: RateLimit,
     body: Json<ResetPasswordInput>,
 ) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
     let ResetPasswordInput { token, password } = body.into_inner();
@@ -16,7 +16,11 @@ use rsky_lexicon::com::atproto::server::UpdateEmailInput;
 
 async fn inner_update_email(body: Json<UpdateEmailInput>, auth: AccessFull) -> Result<()> {
     let did = auth.access.credentials.unwrap().did.unwrap();
-    let UpdateEmailInput { email, token } = body.into_inner();
+    let UpdateEmailInput {
+        email,
+        token,
+        email_auth_factor,
+    } = body.into_inner();
     if !mailchecker::is_valid(&email) {
         bail!("This email address is not supported, please use a different email.")
     }
@@ -43,15 +47,35 @@ async fn inner_update_email(body: Json<UpdateEmailInput>, auth: AccessFull) -> R
                 bail!("Confirmation token required")
             }
         }
-        match AccountManager::update_email(UpdateEmailOpts { did, email }).await {
-            Ok(_) => Ok(()),
-            Err(e) => match e.downcast_ref() {
-                Some(AccountHelperError::UserAlreadyExistsError) => {
-                    bail!("This email address is already in use, please use a different email.")
-                }
-                _ => Err(e),
-            },
+        let email_changed = account.email.as_ref() != Some(&email.to_lowercase());
+        if email_auth_factor == Some(true)
+            && (email_changed || account.email_confirmed_at.is_none())
+        {
+            bail!("Email must be confirmed before it can be used to sign in")
         }
+        if email_changed {
+            match AccountManager::update_email(UpdateEmailOpts {
+                did: did.clone(),
+                email,
+            })
+            .await
+            {
+                Ok(_) => (),
+                Err(e) => match e.downcast_ref() {
+                    Some(AccountHelperError::UserAlreadyExistsError) => {
+                        bail!("This email address is already in use, please use a different email.")
+                    }
+                    _ => return Err(e),
+                },
+            }
+        } else {
+            AccountManager::delete_email_token(&did, EmailTokenPurpose::UpdateEmail).await?;
+        }
+        // Sign in codes shouldn't go to an address that hasn't been confirmed yet
+        if let Some(enabled) = email_auth_factor.or(email_changed.then_some(false)) {
+            AccountManager::set_email_auth_factor(&did, enabled).await?;
+        }
+        Ok(())
     } else {
         bail!("Account not found")
     }
@@ -6,30 +6,99 @@
 use crate::api::com::atproto::repo::assert_repo_availability;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
-use aws_sdk_s3::operation::get_object::GetObjectError;
-use aws_sdk_s3::primitives::AggregatedBytes;
+use crate::repository::blobstore::{BlobStoreCreator, BlobStream};
 use libipld::Cid;
 use rocket::http::{Header, Status};
-use rocket::response::status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, status, Responder};
 use rocket::serde::json::Json;
-use rocket::{Responder, State};
+use rocket::{Request, Response, State};
+use rsky_pds::repo::error::BlobError;
 use std::str::FromStr;
 
-#[derive(Responder)]
-#[response(status = 200)]
-pub struct BlobResponder(Vec<u8>, Header<'static>, Header<'static>, Header<'static>);
+/// The raw `Range` header of a request, if it sent one.
+pub struct RangeHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RangeHeader(
+            req.headers().get_one("range").map(str::to_string),
+        ))
+    }
+}
+
+/// A blob, or the requested range of one, streamed back as it's read from the blobstore.
+pub struct BlobResponder {
+    status: Status,
+    headers: Vec<Header<'static>>,
+    body: Option<BlobStream>,
+}
+
+impl<'r> Responder<'r, 'static> for BlobResponder {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut res = Response::build();
+        res.status(self.status);
+        for header in self.headers {
+            res.header(header);
+        }
+        if let Some(body) = self.body {
+            res.streamed_body(body);
+        }
+        res.ok()
+    }
+}
+
+/// Resolves a `Range` header against a blob of `size` bytes into an inclusive `(start, end)`.
+///
+/// Only single `bytes=` ranges are supported. Anything else is ignored and the whole blob is
+/// served, as RFC 9110 allows. `Err` means the range can't be satisfied.
+fn parse_range(header: &str, size: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let last = size.saturating_sub(1);
+    let range = if start.is_empty() {
+        // bytes=-n is the last n bytes
+        match end.parse::<u64>() {
+            Ok(0) => return Err(()),
+            Ok(n) => (size.saturating_sub(n), last),
+            Err(_) => return Ok(None),
+        }
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return Ok(None);
+        };
+        match end.parse::<u64>() {
+            _ if end.is_empty() => (start, last),
+            Ok(end) if start <= end => (start, end.min(last)),
+            _ => return Ok(None),
+        }
+    };
+    if range.0 >= size {
+        return Err(());
+    }
+    Ok(Some(range))
+}
 
 async fn inner_get_blob(
     did: String,
     cid: String,
-    s3_config: &State<SdkConfig>,
+    range: RangeHeader,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
-) -> Result<(Vec<u8>, Option<String>)> {
+) -> Result<BlobResponder> {
     let is_user_or_admin = if let Some(access) = auth.access {
         auth_verifier::is_user_or_admin(access, &did)
     } else {
@@ -38,38 +107,77 @@ async fn inner_get_blob(
     let _ = assert_repo_availability(&did, is_user_or_admin).await?;
 
     let cid = Cid::from_str(&cid)?;
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
 
-    let found = actor_store.blob.get_blob(cid).await?;
-    let buf: AggregatedBytes = found.stream.collect().await?;
-    Ok((buf.to_vec(), found.mime_type))
+    let metadata = actor_store.blob.get_blob_metadata(cid).await?;
+    let size = metadata.size as u64;
+    let mut headers = vec![
+        Header::new("accept-ranges", "bytes"),
+        Header::new(
+            "content-type",
+            metadata
+                .mime_type
+                .unwrap_or("application/octet-stream".to_string()),
+        ),
+        Header::new("content-security-policy", "default-src 'none'; sandbox"),
+    ];
+    let range = match range.0 {
+        Some(header) => parse_range(&header, size),
+        None => Ok(None),
+    };
+    match range {
+        Ok(None) => {
+            let stream = actor_store.blob.blobstore.get_stream(cid).await?;
+            headers.push(Header::new("content-length", size.to_string()));
+            Ok(BlobResponder {
+                status: Status::Ok,
+                headers,
+                body: Some(stream),
+            })
+        }
+        Ok(Some((start, end))) => {
+            let stream = actor_store
+                .blob
+                .blobstore
+                .get_range(cid, start, end)
+                .await?;
+            headers.push(Header::new("content-length", (end - start + 1).to_string()));
+            headers.push(Header::new(
+                "content-range",
+                format!("bytes {start}-{end}/{size}"),
+            ));
+            Ok(BlobResponder {
+                status: Status::PartialContent,
+                headers,
+                body: Some(stream),
+            })
+        }
+        Err(()) => {
+            headers.push(Header::new("content-range", format!("bytes */{size}")));
+            Ok(BlobResponder {
+                status: Status::RangeNotSatisfiable,
+                headers,
+                body: None,
+            })
+        }
+    }
 }
 
-/// Get a blob associated with a given account. Returns the full blob as originally uploaded.
-/// Does not require auth; implemented by PDS.
+/// Get a blob associated with a given account. Returns the full blob as originally uploaded, or
+/// the part of it asked for with a `Range` header. Does not require auth; implemented by PDS.
 #[rocket::get("/xrpc/com.atproto.sync.getBlob?<did>&<cid>")]
 pub async fn get_blob(
     did: String,
     cid: String,
-    s3_config: &State<SdkConfig>,
+    range: RangeHeader,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
 ) -> Result<BlobResponder, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_blob(did, cid, s3_config, auth).await {
-        Ok(res) => {
-            let (bytes, mime_type) = res;
-            Ok(BlobResponder(
-                bytes.clone(),
-                Header::new("content-length", bytes.len().to_string()),
-                Header::new(
-                    "content-type",
-                    mime_type.unwrap_or("application/octet-stream".to_string()),
-                ),
-                Header::new("content-security-policy", "default-src 'none'; sandbox"),
-            ))
-        }
+    match inner_get_blob(did, cid, range, blobstore, auth).await {
+        Ok(res) => Ok(res),
         Err(error) => {
             return match error.downcast_ref() {
-                Some(GetObjectError::NoSuchKey(_)) => {
+                Some(BlobError::BlobNotFoundError) => {
                     eprintln!("Error: {}", error);
                     let internal_error = ErrorMessageResponse {
                         code: Some(ErrorCode::NotFound),
@@ -92,4 +200,23 @@ pub async fn get_blob(
             // @TODO: Need to update error handling to return 404 if we have it but it's in tmp
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_byte_ranges() {
+        assert_eq!(parse_range("bytes=0-4", 10), Ok(Some((0, 4))));
+        assert_eq!(parse_range("bytes=5-", 10), Ok(Some((5, 9))));
+        assert_eq!(parse_range("bytes=-3", 10), Ok(Some((7, 9))));
+        assert_eq!(parse_range("bytes=-30", 10), Ok(Some((0, 9))));
+        assert_eq!(parse_range("bytes=8-100", 10), Ok(Some((8, 9))));
+        assert_eq!(parse_range("bytes=10-", 10), Err(()));
+        assert_eq!(parse_range("bytes=-0", 10), Err(()));
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), Ok(None));
+        assert_eq!(parse_range("bytes=4-1", 10), Ok(None));
+        assert_eq!(parse_range("items=0-4", 10), Ok(None));
+    }
+}
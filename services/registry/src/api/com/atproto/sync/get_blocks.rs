@@ -4,31 +4,27 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::api::com::atproto::repo::assert_repo_availability;
+use crate::api::com::atproto::sync::CarResponder;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
-use crate::repository::aws::s3::S3BlobStore;
+use crate::repository::car::{blocks_to_car_stream, CarStream};
 use crate::repository::ActorStore;
-use rsky_pds::car::read_car_bytes;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use libipld::Cid;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
-use rocket::{Responder, State};
+use rocket::State;
 use std::str::FromStr;
 
-#[derive(Responder)]
-#[response(status = 200, content_type = "application/vnd.ipld.car")]
-pub struct BlockResponder(Vec<u8>);
-
 async fn inner_get_blocks(
     did: String,
     cids: Vec<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
-) -> Result<Vec<u8>> {
+) -> Result<CarStream> {
     let is_user_or_admin = if let Some(access) = auth.access {
         auth_verifier::is_user_or_admin(access, &did)
     } else {
@@ -41,7 +37,7 @@ async fn inner_get_blocks(
         .map(|c| Cid::from_str(&c).map_err(anyhow::Error::new))
         .collect::<Result<Vec<Cid>>>()?;
 
-    let mut actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+    let mut actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
     let got = actor_store.storage.get_blocks(cids).await?;
 
     if got.missing.len() > 0 {
@@ -53,8 +49,7 @@ async fn inner_get_blocks(
         bail!("Could not find cids: `{missing_str:?}`");
     }
 
-    let car = read_car_bytes(None, got.blocks).await?;
-    Ok(car)
+    blocks_to_car_stream(None, got.blocks)
 }
 
 /// Get data blocks from a given repo, by CID. For example, intermediate MST nodes, or records.
@@ -63,11 +58,11 @@ async fn inner_get_blocks(
 pub async fn get_blocks(
     did: String,
     cids: Vec<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
-) -> Result<BlockResponder, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_blocks(did, cids, s3_config, auth).await {
-        Ok(res) => Ok(BlockResponder(res)),
+) -> Result<CarResponder, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_get_blocks(did, cids, blobstore, auth).await {
+        Ok(res) => Ok(CarResponder(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
             let internal_error = ErrorMessageResponse {
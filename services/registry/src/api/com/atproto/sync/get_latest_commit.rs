@@ -6,11 +6,10 @@
 use crate::api::com::atproto::repo::assert_repo_availability;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -19,7 +18,7 @@ use rsky_lexicon::com::atproto::sync::GetLatestCommitOutput;
 
 async fn inner_get_latest_commit(
     did: String,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
 ) -> Result<GetLatestCommitOutput> {
     let is_user_or_admin = if let Some(access) = auth.access {
@@ -29,7 +28,7 @@ async fn inner_get_latest_commit(
     };
     let _ = assert_repo_availability(&did, is_user_or_admin).await?;
 
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
     match actor_store.storage.get_root_detailed().await {
         Ok(res) => Ok(GetLatestCommitOutput {
             cid: res.cid.to_string(),
@@ -42,10 +41,10 @@ async fn inner_get_latest_commit(
 #[rocket::get("/xrpc/com.atproto.sync.getLatestCommit?<did>")]
 pub async fn get_latest_commit(
     did: String,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
 ) -> Result<Json<GetLatestCommitOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_latest_commit(did, s3_config, auth).await {
+    match inner_get_latest_commit(did, blobstore, auth).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
@@ -4,40 +4,37 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::api::com::atproto::repo::assert_repo_availability;
+use crate::api::com::atproto::sync::CarResponder;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
-use crate::repository::aws::s3::S3BlobStore;
+use crate::repository::car::CarStream;
 use crate::repository::ActorStore;
 use crate::{auth_verifier, repository};
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use rsky_pds::repo::types::RecordPath;
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use libipld::Cid;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
-use rocket::{Responder, State};
+use rocket::State;
 use std::str::FromStr;
 
-#[derive(Responder)]
-#[response(status = 200, content_type = "application/vnd.ipld.car")]
-pub struct BlockResponder(Vec<u8>);
-
 async fn inner_get_record(
     did: String,
     collection: String,
     rkey: String,
     commit: Option<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
-) -> Result<Vec<u8>> {
+) -> Result<CarStream> {
     let is_user_or_admin = if let Some(access) = auth.access {
         auth_verifier::is_user_or_admin(access, &did)
     } else {
         false
     };
     let _ = assert_repo_availability(&did, is_user_or_admin).await?;
-    let mut actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+    let mut actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
     let commit: Option<Cid> = match commit {
         Some(commit) => Some(Cid::from_str(&commit)?),
         None => actor_store.storage.get_root().await,
@@ -64,11 +61,11 @@ pub async fn get_record(
     collection: String,
     rkey: String,
     commit: Option<String>, // DEPRECATED: referenced a repo commit by CID, and retrieved record as of that commit
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
-) -> Result<BlockResponder, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_record(did, collection, rkey, commit, s3_config, auth).await {
-        Ok(res) => Ok(BlockResponder(res)),
+) -> Result<CarResponder, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_get_record(did, collection, rkey, commit, blobstore, auth).await {
+        Ok(res) => Ok(CarResponder(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
             let internal_error = ErrorMessageResponse {
@@ -4,28 +4,25 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::api::com::atproto::repo::assert_repo_availability;
+use crate::api::com::atproto::sync::CarResponder;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
-use crate::repository::aws::s3::S3BlobStore;
+use crate::repository::car::CarStream;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::{bail, Result};
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
-use rocket::{Responder, State};
-
-#[derive(Responder)]
-#[response(status = 200, content_type = "application/vnd.ipld.car")]
-pub struct BlockResponder(Vec<u8>);
+use rocket::State;
 
 async fn get_car_stream(
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     did: String,
     since: Option<String>,
-) -> Result<Vec<u8>> {
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+) -> Result<CarStream> {
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
     match actor_store.storage.get_car_stream(since).await {
         Err(_) => bail!("Could not find repo for DID: {did}"),
         Ok(carstream) => Ok(carstream),
@@ -35,16 +32,16 @@ async fn get_car_stream(
 async fn inner_get_repo(
     did: String,
     since: Option<String>, // The revision ('rev') of the repo to create a diff from.
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
-) -> Result<Vec<u8>> {
+) -> Result<CarStream> {
     let is_user_or_admin = if let Some(access) = auth.access {
         auth_verifier::is_user_or_admin(access, &did)
     } else {
         false
     };
     let _ = assert_repo_availability(&did, is_user_or_admin).await?;
-    get_car_stream(s3_config, did, since).await
+    get_car_stream(blobstore, did, since).await
 }
 
 /// Download a repository export as CAR file. Optionally only a 'diff' since a previous revision.
@@ -53,11 +50,11 @@ async fn inner_get_repo(
 pub async fn get_repo(
     did: String,
     since: Option<String>, // The revision ('rev') of the repo to create a diff from.
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
-) -> Result<BlockResponder, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_repo(did, since, s3_config, auth).await {
-        Ok(res) => Ok(BlockResponder(res)),
+) -> Result<CarResponder, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_get_repo(did, since, blobstore, auth).await {
+        Ok(res) => Ok(CarResponder(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
             let internal_error = ErrorMessageResponse {
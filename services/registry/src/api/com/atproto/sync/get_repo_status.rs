@@ -7,24 +7,23 @@ use crate::account_manager::helpers::account::{
     format_account_status, AccountStatus, FormattedAccountStatus,
 };
 use crate::api::com::atproto::repo::assert_repo_availability;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::sync::{GetRepoStatusOutput, RepoStatus};
 
-async fn inner_get_repo(did: String, s3_config: &State<SdkConfig>) -> Result<GetRepoStatusOutput> {
+async fn inner_get_repo(did: String, blobstore: &State<BlobStoreCreator>) -> Result<GetRepoStatusOutput> {
     let account = assert_repo_availability(&did, true).await?;
     let FormattedAccountStatus { active, status } = format_account_status(Some(account));
 
     let mut rev: Option<String> = None;
     if active {
-        let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+        let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
         let root = actor_store.storage.get_root_detailed().await?;
         rev = Some(root.rev);
     }
@@ -51,9 +50,9 @@ async fn inner_get_repo(did: String, s3_config: &State<SdkConfig>) -> Result<Get
 #[rocket::get("/xrpc/com.atproto.sync.getRepoStatus?<did>")]
 pub async fn get_repo_status(
     did: String,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
 ) -> Result<Json<GetRepoStatusOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_get_repo(did, s3_config).await {
+    match inner_get_repo(did, blobstore).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
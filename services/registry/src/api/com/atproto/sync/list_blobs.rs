@@ -6,12 +6,11 @@
 use crate::api::com::atproto::repo::assert_repo_availability;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::blob::ListBlobsOpts;
 use crate::repository::ActorStore;
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -23,7 +22,7 @@ async fn inner_list_blobs(
     since: Option<String>, // Optional revision of the repo to list blobs since.
     limit: Option<u16>,
     cursor: Option<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
 ) -> Result<ListBlobsOutput> {
     let is_user_or_admin = if let Some(access) = auth.access {
@@ -33,7 +32,7 @@ async fn inner_list_blobs(
     };
     let _ = assert_repo_availability(&did, is_user_or_admin).await?;
 
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config));
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
     let blob_cids = actor_store
         .blob
         .list_blobs(ListBlobsOpts {
@@ -61,10 +60,10 @@ pub async fn list_blobs(
     since: Option<String>, // Optional revision of the repo to list blobs since.
     limit: Option<u16>,
     cursor: Option<String>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     auth: OptionalAccessOrAdminToken,
 ) -> Result<Json<ListBlobsOutput>, status::Custom<Json<ErrorMessageResponse>>> {
-    match inner_list_blobs(did, since, limit, cursor, s3_config, auth).await {
+    match inner_list_blobs(did, since, limit, cursor, blobstore, auth).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             eprintln!("@LOG: ERROR: {error}");
@@ -1,3 +1,11 @@
+use crate::repository::car::CarStream;
+use futures::StreamExt;
+use rocket::http::ContentType;
+use rocket::response::{self, Responder};
+use rocket::{Request, Response};
+use std::io::{self, Cursor};
+use tokio_util::io::StreamReader;
+
 pub mod get_blob;
 pub mod get_blocks;
 pub mod get_latest_commit;
@@ -8,6 +16,26 @@ pub mod list_blobs;
 pub mod list_repos;
 pub mod subscribe_repos;
 
+/// A CAR archive streamed to the client as it's produced. The status is sent before the
+/// archive is complete, so an error partway through aborts the response; the client sees a
+/// failed transfer rather than a truncated archive.
+pub struct CarResponder(pub CarStream);
+
+impl<'r> Responder<'r, 'static> for CarResponder {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let body = StreamReader::new(self.0.map(|chunk| {
+            chunk.map(Cursor::new).map_err(|error| {
+                eprintln!("@LOG: ERROR: couldn't finish writing CAR: {error}");
+                io::Error::other(error.to_string())
+            })
+        }));
+        Response::build()
+            .header(ContentType::new("application", "vnd.ipld.car"))
+            .streamed_body(body)
+            .ok()
+    }
+}
+
 pub fn routes() -> Vec<rocket::Route> {
     routes![
         get_blob::get_blob,
@@ -5,24 +5,26 @@
  */
 use rsky_pds::common::time::from_str_to_utc;
 use rsky_pds::common::RFC3339_VARIANT;
-use rsky_pds::crawlers::Crawlers;
 use rsky_pds::xrpc_server::stream::frames::{ErrorFrame, Frame, MessageFrame, MessageFrameOpts};
 use rsky_pds::xrpc_server::stream::types::ErrorFrameBody;
-use crate::config::{CORE_CONFIG, SUBSCRIPTION_CONFIG};
+use crate::config::SUBSCRIPTION_CONFIG;
 use crate::sequencer::events::{
-    AccountEvt, CommitEvt, HandleEvt, IdentityEvt, SeqEvt, TombstoneEvt, TypedAccountEvt,
-    TypedCommitEvt, TypedHandleEvt, TypedIdentityEvt, TypedTombstoneEvt,
+    AccountEvt, CommitEvt, HandleEvt, IdentityEvt, SeqEvt, SyncEvt, TombstoneEvt,
+    TypedAccountEvt, TypedCommitEvt, TypedHandleEvt, TypedIdentityEvt, TypedSyncEvt,
+    TypedTombstoneEvt,
 };
-use crate::sequencer::outbox::{Outbox, OutboxOpts};
+use crate::sequencer::outbox::Outbox;
 use crate::sequencer::Sequencer;
+use crate::SharedSequencer;
 use chrono::offset::Utc as UtcOffset;
 use chrono::{DateTime, Duration};
 use futures::{pin_mut, StreamExt};
 use rocket::tokio::select;
-use rocket::Shutdown;
+use rocket::{Shutdown, State};
 use rsky_lexicon::com::atproto::sync::{
     SubscribeReposAccount, SubscribeReposCommit, SubscribeReposCommitOperation,
-    SubscribeReposHandle, SubscribeReposIdentity, SubscribeReposTombstone,
+    SubscribeReposHandle, SubscribeReposIdentity, SubscribeReposInfo, SubscribeReposSync,
+    SubscribeReposTombstone,
 };
 use serde_json::json;
 use std::time::SystemTime;
@@ -36,6 +38,99 @@ fn get_backfill_limit(ms: u64) -> String {
     format!("{}", dt.format(RFC3339_VARIANT))
 }
 
+/// Sent before streaming from the oldest available event when some events after the client's
+/// cursor are no longer available.
+fn outdated_cursor_info() -> Vec<u8> {
+    let info = SubscribeReposInfo {
+        name: "OutdatedCursor".to_string(),
+        message: Some("Requested cursor exceeded limit. Possibly missing events".to_string()),
+    };
+    MessageFrame::new(info, Some(MessageFrameOpts { r#type: Some("#info".to_string()) }))
+        .to_bytes()
+        .expect("couldn't translate info to binary.")
+}
+
+/// Where a subscriber asking for events after `cursor` starts: the frames to send it first, and
+/// the cursor to backfill the outbox from. An `Err` is the frame to send before closing.
+async fn resolve_cursor(
+    sequencer: &Sequencer,
+    cursor: i64,
+    backfill_time: String,
+) -> Result<(Vec<Message>, Option<i64>), Message> {
+    let Ok(next) = sequencer.next_seq(cursor).await else {
+        return Err(Message::Text(
+            json!({
+                "$type": "#error",
+                "name": "NextError",
+                "message": "Failed to fetch next event."
+            })
+            .to_string(),
+        ));
+    };
+    let Ok(curr) = sequencer.curr().await else {
+        return Err(Message::Text(
+            json!({
+                "$type": "#error",
+                "name": "CurrError",
+                "message": "Failed to fetch current event."
+            })
+            .to_string(),
+        ));
+    };
+    let Ok(earliest) = sequencer.earliest().await else {
+        return Err(Message::Text(
+            json!({
+                "$type": "#error",
+                "name": "EarliestError",
+                "message": "Failed to fetch earliest event."
+            })
+            .to_string(),
+        ));
+    };
+    // Events right after the cursor have been removed by the retention policy
+    let trimmed = matches!(earliest, Some(earliest) if cursor + 1 < earliest);
+    if cursor > curr.unwrap_or(0) {
+        let error_frame = ErrorFrame::new(ErrorFrameBody {
+            error: "FutureCursor".to_string(),
+            message: Some("Cursor in the future.".to_string()),
+        });
+        let frame = Message::Binary(
+            error_frame
+                .to_bytes()
+                .expect("couldn't translate error to binary."),
+        );
+        return Ok((vec![frame], None));
+    }
+    match next {
+        Some(next) if next.sequenced_at < backfill_time => {
+            let info = Message::Binary(outdated_cursor_info());
+            match sequencer.earliest_after_time(backfill_time).await {
+                Ok(Some(start_evt)) if start_evt.seq.is_some() => {
+                    Ok((vec![info], Some(start_evt.seq.unwrap() - 1)))
+                }
+                Ok(None) => Ok((vec![info], None)),
+                _ => {
+                    let error_frame = ErrorFrame::new(ErrorFrameBody {
+                        error: "EarliestAfterTimeError".to_string(),
+                        message: Some(
+                            "Failed to fetch earliest event after backfill time.".to_string(),
+                        ),
+                    });
+                    Err(Message::Binary(
+                        error_frame
+                            .to_bytes()
+                            .expect("couldn't translate error to binary."),
+                    ))
+                }
+            }
+        }
+        _ => match trimmed {
+            true => Ok((vec![Message::Binary(outdated_cursor_info())], Some(cursor))),
+            false => Ok((vec![], Some(cursor))),
+        },
+    }
+}
+
 /// Repository event stream, aka Firehose endpoint. Outputs repo commits with diff data,
 /// and identity update events, for all repositories on the current server. See the atproto
 /// specifications for details around stream sequencing, repo versioning, CAR diff format, and more.
@@ -46,76 +141,28 @@ pub async fn subscribe_repos<'a>(
     cursor: Option<i64>,
     mut shutdown: Shutdown,
     ws: ws::WebSocket,
+    sequencer: &State<SharedSequencer>,
 ) -> ws::Stream!['a] {
+    let sequencer_lock = sequencer.sequencer.read().await.clone();
     ws::Stream! { ws =>
-        let sequencer_lock = Sequencer::new(
-            Crawlers::new(CORE_CONFIG.hostname().clone(), CORE_CONFIG.crawlers.clone()),
-            None,
-        );
-        let mut outbox = Outbox::new(
-            sequencer_lock.clone(),
-            Some(OutboxOpts {
-                max_buffer_size: SUBSCRIPTION_CONFIG.repo_backfill_limit_ms as usize,
-            })
-        );
+        let mut outbox = Outbox::new(sequencer_lock.clone());
 
         println!("@LOG DEBUG: request to com.atproto.sync.subscribeRepos; Cursor={cursor:?}");
         let backfill_time = get_backfill_limit(SUBSCRIPTION_CONFIG.repo_backfill_limit_ms);
 
         let mut outbox_cursor: Option<i64> = None;
         if let Some(cursor) = cursor {
-            let next = match sequencer_lock.next_seq(cursor).await {
-                Ok(next) => next,
-                Err(_) => {
-                    yield Message::Text(json!({
-                        "$type": "#error",
-                        "name": "NextError",
-                        "message": "Failed to fetch next event."
-                    }).to_string());
-                    return;
+            match resolve_cursor(&sequencer_lock, cursor, backfill_time).await {
+                Ok((frames, cursor)) => {
+                    for frame in frames {
+                        yield frame;
+                    }
+                    outbox_cursor = cursor;
                 }
-            };
-            let curr = match sequencer_lock.curr().await {
-                Ok(curr) => curr,
-                Err(_) => {
-                    yield Message::Text(json!({
-                        "$type": "#error",
-                        "name": "CurrError",
-                        "message": "Failed to fetch current event."
-                    }).to_string());
+                Err(frame) => {
+                    yield frame;
                     return;
                 }
-            };
-            match cursor > curr.unwrap_or(0) {
-                true => {
-                    let error_frame = ErrorFrame::new(ErrorFrameBody {
-                        error: "FutureCursor".to_string(),
-                        message: Some("Cursor in the future.".to_string()),
-                    });
-                    yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                },
-                false => match next {
-                    Some(next) if next.sequenced_at < backfill_time => {
-                        let error_frame = ErrorFrame::new(ErrorFrameBody {
-                            error: "OutdatedCursor".to_string(),
-                            message: Some("Requested cursor exceeded limit. Possibly missing events.".to_string()),
-                        });
-                        yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                        match sequencer_lock.earliest_after_time(backfill_time).await {
-                            Ok(Some(start_evt)) if start_evt.seq.is_some() => outbox_cursor = Some(start_evt.seq.unwrap() - 1),
-                            Ok(None) => outbox_cursor = None,
-                            _ => {
-                                let error_frame = ErrorFrame::new(ErrorFrameBody {
-                                    error: "EarliestAfterTimeError".to_string(),
-                                    message: Some("Failed to fetch earliest event after backfill time.".to_string()),
-                                });
-                                yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                                return;
-                            }
-                        }
-                    },
-                    _ => outbox_cursor = Some(cursor)
-                }
             }
         }
 
@@ -152,7 +199,7 @@ pub async fn subscribe_repos<'a>(
                     match evt {
                         SeqEvt::TypedCommitEvt(commit) => {
                             let TypedCommitEvt { r#type, seq, time, evt } = commit;
-                            let CommitEvt { rebase, too_big, repo, commit, prev, rev, since, blocks, ops, blobs } = evt;
+                            let CommitEvt { rebase, too_big, repo, commit, prev, prev_data, rev, since, blocks, ops, blobs } = evt;
                             let subscribe_commit_evt = SubscribeReposCommit {
                                 seq,
                                 time: from_str_to_utc(&time),
@@ -161,6 +208,7 @@ pub async fn subscribe_repos<'a>(
                                 repo,
                                 commit,
                                 prev,
+                                prev_data,
                                 rev,
                                 since,
                                 blocks,
@@ -170,6 +218,7 @@ pub async fn subscribe_repos<'a>(
                                         None => None,
                                         Some(cid) => Some(cid)
                                     },
+                                    prev: op.prev,
                                     action: op.action.to_string()
                                 }).collect::<Vec<SubscribeReposCommitOperation>>(),
                                 blobs: blobs.into_iter().map(|blob| blob.to_string()).collect::<Vec<String>>(),
@@ -188,6 +237,30 @@ pub async fn subscribe_repos<'a>(
                             };
                             yield Message::Binary(binary);
                         },
+                        SeqEvt::TypedSyncEvt(sync) => {
+                            let TypedSyncEvt { r#type, seq, time, evt } = sync;
+                            let SyncEvt { did, blocks, rev } = evt;
+                            let subscribe_sync_evt = SubscribeReposSync {
+                                seq,
+                                did,
+                                blocks,
+                                rev,
+                                time: from_str_to_utc(&time),
+                            };
+                            let message_frame = MessageFrame::new(subscribe_sync_evt, Some(MessageFrameOpts { r#type: Some(format!("#{0}",r#type)) }));
+                            let binary = match message_frame.to_bytes() {
+                                Ok(binary) => binary,
+                                Err(_) => {
+                                    let error_frame = ErrorFrame::new(ErrorFrameBody {
+                                        error: "SerializationError".to_string(),
+                                        message: Some("Failed to serialize event to message frame.".to_string()),
+                                    });
+                                    yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                                    return;
+                                }
+                            };
+                            yield Message::Binary(binary);
+                        },
                         SeqEvt::TypedHandleEvt(handle) => {
                             let TypedHandleEvt { r#type, seq, time, evt } = handle;
                             let HandleEvt { did, handle } = evt;
@@ -329,4 +402,85 @@ pub async fn subscribe_repos<'a>(
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::janitor::jobs::sweep_repo_seq_before;
+    use crate::sequencer::TEST_LOCK;
+    use rsky_pds::common;
+    use rsky_pds::crawlers::Crawlers;
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn outdated_cursors_start_from_the_earliest_retained_event() {
+        let _lock = TEST_LOCK.lock().await;
+        let mut sequencer = Sequencer::new(Crawlers::new("localhost".to_string(), vec![]), None);
+        let did = format!("did:example:{}", common::get_random_str());
+        let mut seqs = Vec::new();
+        for _ in 0..4 {
+            seqs.push(
+                sequencer
+                    .sequence_identity_evt(did.clone(), None)
+                    .await
+                    .unwrap(),
+            );
+            tokio::time::sleep(TokioDuration::from_millis(5)).await;
+        }
+        // The first two events, and everything before them, fall out of retention
+        let cutoff = sequencer
+            .request_seq_range(crate::sequencer::RequestSeqRangeOpts {
+                earliest_seq: Some(seqs[1]),
+                latest_seq: None,
+                earliest_time: None,
+                limit: Some(1),
+            })
+            .await
+            .unwrap()
+            .remove(0);
+        let SeqEvt::TypedIdentityEvt(cutoff) = cutoff else {
+            panic!("expected an identity event")
+        };
+        sweep_repo_seq_before(&cutoff.time).await.unwrap();
+        assert_eq!(sequencer.earliest().await.unwrap(), Some(seqs[2]));
+
+        let cursor = seqs[0] - 1;
+        let (frames, outbox_cursor) =
+            resolve_cursor(&sequencer, cursor, "2000-01-01T00:00:00.000Z".to_string())
+                .await
+                .unwrap();
+        assert_eq!(frames, vec![Message::Binary(outdated_cursor_info())]);
+        let Message::Binary(info) = &frames[0] else {
+            unreachable!()
+        };
+        for expected in [&b"#info"[..], b"OutdatedCursor"] {
+            assert!(info
+                .windows(expected.len())
+                .any(|window| window == expected));
+        }
+
+        let mut outbox = Outbox::new(sequencer.clone());
+        let events = outbox.events(outbox_cursor).await;
+        pin_mut!(events);
+        for seq in &seqs[2..] {
+            let evt = events.next().await.unwrap().unwrap();
+            assert_eq!(evt.seq(), *seq);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn current_cursors_get_no_info() {
+        let _lock = TEST_LOCK.lock().await;
+        let mut sequencer = Sequencer::new(Crawlers::new("localhost".to_string(), vec![]), None);
+        let did = format!("did:example:{}", common::get_random_str());
+        let seq = sequencer.sequence_identity_evt(did, None).await.unwrap();
+
+        let (frames, outbox_cursor) =
+            resolve_cursor(&sequencer, seq - 1, "2000-01-01T00:00:00.000Z".to_string())
+                .await
+                .unwrap();
+        assert!(frames.is_empty());
+        assert_eq!(outbox_cursor, Some(seq - 1));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod rotate_signing_key;
+pub mod verify_repo;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        rotate_signing_key::rotate_signing_key,
+        verify_repo::verify_repo,
+    ]
+}
@@ -0,0 +1,252 @@
+use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::AccountManager;
+use crate::api::com::atproto::repo::assert_repo_availability;
+use crate::auth_verifier::AdminToken;
+use crate::config::IDENTITY_CONFIG;
+use crate::plc;
+use crate::repository::storage::RepoReader;
+use crate::repository::Repo;
+use crate::signer::{LocalSigner, Signer, ROTATION_SIGNER};
+use crate::SharedSequencer;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_pds::common::tid::{Ticker, TID};
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use secp256k1::SecretKey;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct RotateSigningKeyInput {
+    pub did: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RotateSigningKeyOutput {
+    pub did: String,
+    /// The new key, as a did:key.
+    #[serde(rename = "signingKey")]
+    pub signing_key: String,
+    /// Rev of the head commit re-signed with the new key.
+    pub rev: String,
+}
+
+/// The steps of giving an account a new key, apart so the order they run in can be tested.
+#[async_trait]
+trait KeySwap {
+    /// A key staged by an earlier attempt that didn't finish.
+    async fn staged(&self, did: &String) -> Result<Option<SecretKey>>;
+    /// Saves the new key without using it yet.
+    async fn stage(&self, did: &String, secret_key: &SecretKey) -> Result<()>;
+    /// Lists the key in the account's DID document.
+    async fn publish(&self, did: &String, key_did: &String) -> Result<()>;
+    /// Signs with the key from now on.
+    async fn activate(&self, did: &String, key_did: &String) -> Result<()>;
+}
+
+struct PlcKeySwap {
+    plc_client: plc::Client,
+}
+
+#[async_trait]
+impl KeySwap for PlcKeySwap {
+    async fn staged(&self, did: &String) -> Result<Option<SecretKey>> {
+        AccountManager::get_staged_signing_key(did).await
+    }
+
+    async fn stage(&self, did: &String, secret_key: &SecretKey) -> Result<()> {
+        AccountManager::stage_signing_key(did, secret_key).await?;
+        Ok(())
+    }
+
+    async fn publish(&self, did: &String, key_did: &String) -> Result<()> {
+        self.plc_client
+            .update_atproto_key(did, &**ROTATION_SIGNER, key_did)
+            .await
+    }
+
+    async fn activate(&self, did: &String, key_did: &String) -> Result<()> {
+        AccountManager::activate_signing_key(did, key_did).await
+    }
+}
+
+/// The key is saved before it's published, and only used once the DID document lists it. A
+/// rotation that fails part way leaves the account signing with its old key, and trying again
+/// carries on with the key that was saved.
+async fn swap_signing_key(swap: &dyn KeySwap, did: &String) -> Result<SecretKey> {
+    let secret_key = match swap.staged(did).await? {
+        Some(secret_key) => secret_key,
+        None => {
+            let secret_key = SecretKey::new(&mut rand::thread_rng());
+            swap.stage(did, &secret_key).await?;
+            secret_key
+        }
+    };
+    let key_did = LocalSigner::new(secret_key).did_key();
+    swap.publish(did, &key_did).await?;
+    swap.activate(did, &key_did).await?;
+    Ok(secret_key)
+}
+
+async fn inner_rotate_signing_key(
+    body: Json<RotateSigningKeyInput>,
+    sequencer: &State<SharedSequencer>,
+) -> Result<RotateSigningKeyOutput> {
+    let RotateSigningKeyInput { did } = body.into_inner();
+    let account = assert_repo_availability(&did, true).await?;
+    if !did.starts_with("did:plc:") {
+        // A did:web document is hosted by its owner, so we can't publish a new key in it
+        bail!("InvalidRequest: Only did:plc signing keys can be rotated, not `{did}`");
+    }
+
+    let swap = PlcKeySwap {
+        plc_client: plc::Client::new(IDENTITY_CONFIG.plc_url.clone()),
+    };
+    let signer = LocalSigner::new(swap_signing_key(&swap, &did).await?);
+    let signing_key = signer.did_key();
+
+    // The head commit has to verify against the new key too
+    let mut storage = RepoReader::new(None, did.clone(), None);
+    let mut repo = Repo::load(&mut storage, None).await?;
+    let rev = Ticker::new()
+        .next(Some(TID::new(repo.commit().rev)?))
+        .to_string();
+    let repo = repo.resign_commit(rev.clone(), &signer).await?;
+    AccountManager::update_repo_root(did.clone(), repo.cid(), rev.clone())?;
+
+    let mut lock = sequencer.sequencer.write().await;
+    lock.sequence_identity_evt(did.clone(), account.handle)
+        .await?;
+    let status = AccountManager::get_account_status(&did).await?;
+    if matches!(status, AccountStatus::Active) {
+        let blocks = storage.get_blocks(vec![repo.cid()]).await?;
+        lock.sequence_sync_evt(did.clone(), repo.cid(), rev.clone(), &blocks.blocks)
+            .await?;
+    }
+    Ok(RotateSigningKeyOutput {
+        did,
+        signing_key,
+        rev,
+    })
+}
+
+/// Replace an account's repo signing key with a newly generated one, updating its DID document
+/// and re-signing the head of its repo.
+#[rocket::post(
+    "/xrpc/gg.campground.admin.rotateSigningKey",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn rotate_signing_key(
+    body: Json<RotateSigningKeyInput>,
+    sequencer: &State<SharedSequencer>,
+    _auth: AdminToken,
+) -> Result<Json<RotateSigningKeyOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_rotate_signing_key(body, sequencer).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else if error.to_string().starts_with("RepoNotFound") {
+                (Status::NotFound, ErrorCode::NotFound)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records the steps taken, failing at `fail_at`.
+    #[derive(Default)]
+    struct RecordingSwap {
+        steps: Mutex<Vec<&'static str>>,
+        staged: Mutex<Option<SecretKey>>,
+        fail_at: Option<&'static str>,
+    }
+
+    impl RecordingSwap {
+        fn step(&self, step: &'static str) -> Result<()> {
+            self.steps.lock().unwrap().push(step);
+            if self.fail_at == Some(step) {
+                bail!("{step} failed")
+            }
+            Ok(())
+        }
+
+        fn steps(&self) -> Vec<&'static str> {
+            self.steps.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl KeySwap for RecordingSwap {
+        async fn staged(&self, _did: &String) -> Result<Option<SecretKey>> {
+            Ok(*self.staged.lock().unwrap())
+        }
+
+        async fn stage(&self, _did: &String, secret_key: &SecretKey) -> Result<()> {
+            self.step("stage")?;
+            *self.staged.lock().unwrap() = Some(*secret_key);
+            Ok(())
+        }
+
+        async fn publish(&self, _did: &String, _key_did: &String) -> Result<()> {
+            self.step("publish")
+        }
+
+        async fn activate(&self, _did: &String, _key_did: &String) -> Result<()> {
+            self.step("activate")
+        }
+    }
+
+    #[tokio::test]
+    async fn saves_keys_before_publishing_them() {
+        let did = "did:plc:alice".to_string();
+        let swap = RecordingSwap::default();
+        swap_signing_key(&swap, &did).await.unwrap();
+        assert_eq!(swap.steps(), vec!["stage", "publish", "activate"]);
+
+        // Nothing is published if the key couldn't be saved
+        let swap = RecordingSwap {
+            fail_at: Some("stage"),
+            ..Default::default()
+        };
+        assert!(swap_signing_key(&swap, &did).await.is_err());
+        assert_eq!(swap.steps(), vec!["stage"]);
+    }
+
+    #[tokio::test]
+    async fn retries_with_the_saved_key() {
+        let did = "did:plc:alice".to_string();
+        let mut swap = RecordingSwap {
+            fail_at: Some("activate"),
+            ..Default::default()
+        };
+        assert!(swap_signing_key(&swap, &did).await.is_err());
+        let staged = swap.staged.lock().unwrap().unwrap();
+
+        swap.fail_at = None;
+        let secret_key = swap_signing_key(&swap, &did).await.unwrap();
+        assert_eq!(secret_key, staged);
+        assert_eq!(
+            swap.steps(),
+            vec!["stage", "publish", "activate", "publish", "activate"]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::api::com::atproto::repo::{assert_repo_availability, resolve_atproto_key};
+use crate::auth_verifier::AdminToken;
+use crate::repository::blobstore::BlobStoreCreator;
+use crate::repository::integrity::{verify_repo as verify, IntegrityReport};
+use crate::repository::ActorStore;
+use crate::SharedIdResolver;
+use anyhow::Result;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyRepoInput {
+    pub did: String,
+    /// Rebuild the `record` and `backlink` indexes from the MST.
+    pub repair: Option<bool>,
+}
+
+async fn inner_verify_repo(
+    body: Json<VerifyRepoInput>,
+    blobstore: &State<BlobStoreCreator>,
+    id_resolver: &State<SharedIdResolver>,
+) -> Result<IntegrityReport> {
+    let VerifyRepoInput { did, repair } = body.into_inner();
+    assert_repo_availability(&did, true).await?;
+
+    // Still check the blocks and indexes when the DID document can't be resolved
+    let did_key = match resolve_atproto_key(id_resolver, &did).await {
+        Ok(did_key) => Some(did_key),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: couldn't resolve signing key for `{did}`: {error}");
+            None
+        }
+    };
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
+    verify(&actor_store, did_key.as_ref(), repair.unwrap_or(false)).await
+}
+
+/// Walk a repo and report where its stored blocks, record indexes and commit signature
+/// disagree with each other, optionally rebuilding the record indexes from the MST.
+#[rocket::post(
+    "/xrpc/gg.campground.admin.verifyRepo",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn verify_repo(
+    body: Json<VerifyRepoInput>,
+    blobstore: &State<BlobStoreCreator>,
+    id_resolver: &State<SharedIdResolver>,
+    _auth: AdminToken,
+) -> Result<Json<IntegrityReport>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_verify_repo(body, blobstore, id_resolver).await {
+        Ok(report) => Ok(Json(report)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("RepoNotFound") {
+                (Status::NotFound, ErrorCode::NotFound)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
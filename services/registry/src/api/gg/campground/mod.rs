@@ -0,0 +1,9 @@
+pub mod admin;
+pub mod server;
+
+pub fn routes() -> Vec<rocket::Route> {
+    let mut routes = Vec::new();
+    routes.append(&mut admin::routes());
+    routes.append(&mut server::routes());
+    routes
+}
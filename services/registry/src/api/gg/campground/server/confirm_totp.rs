@@ -0,0 +1,64 @@
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use anyhow::Result;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmTotpInput {
+    /// Current code from the authenticator app.
+    pub token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfirmTotpOutput {
+    /// Single use codes that stand in for the app. They can't be shown again.
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+async fn inner_confirm_totp(
+    body: Json<ConfirmTotpInput>,
+    auth: AccessFull,
+) -> Result<ConfirmTotpOutput> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let ConfirmTotpInput { token } = body.into_inner();
+    let recovery_codes = AccountManager::confirm_totp(&did, &token).await?;
+    Ok(ConfirmTotpOutput { recovery_codes })
+}
+
+/// Finish enrolling an authenticator app, after which sign in with the account password needs
+/// a code from it.
+#[rocket::post(
+    "/xrpc/gg.campground.server.confirmTotp",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn confirm_totp(
+    body: Json<ConfirmTotpInput>,
+    auth: AccessFull,
+) -> Result<Json<ConfirmTotpOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_confirm_totp(body, auth).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest")
+                || error.to_string().starts_with("InvalidToken")
+            {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
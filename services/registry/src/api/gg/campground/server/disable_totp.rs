@@ -0,0 +1,60 @@
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTotpInput {
+    /// Current code from the authenticator app, or a recovery code.
+    pub token: String,
+}
+
+async fn inner_disable_totp(body: Json<DisableTotpInput>, auth: AccessFull) -> Result<()> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let DisableTotpInput { token } = body.into_inner();
+    if !AccountManager::has_totp(&did).await? {
+        bail!("InvalidRequest: No authenticator app is enabled for this account");
+    }
+    if !AccountManager::verify_totp(&did, &token).await?
+        && !AccountManager::use_recovery_code(&did, &token).await?
+    {
+        bail!("InvalidToken: Code is invalid or expired");
+    }
+    AccountManager::delete_totp(&did).await
+}
+
+/// Stop asking for authenticator app codes at sign in, and discard the account's recovery codes.
+#[rocket::post(
+    "/xrpc/gg.campground.server.disableTotp",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn disable_totp(
+    body: Json<DisableTotpInput>,
+    auth: AccessFull,
+) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_disable_totp(body, auth).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest")
+                || error.to_string().starts_with("InvalidToken")
+            {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use crate::config::CORE_CONFIG;
+use anyhow::Result;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Serialize)]
+pub struct EnrollTotpOutput {
+    /// Base32 shared secret, for apps where it's typed in.
+    pub secret: String,
+    /// `otpauth://` URI with the secret, for showing as a QR code.
+    pub uri: String,
+}
+
+async fn inner_enroll_totp(auth: AccessFull) -> Result<EnrollTotpOutput> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let secret = AccountManager::create_totp(&did).await?;
+
+    let issuer = CORE_CONFIG.hostname();
+    let account = AccountManager::get_account(&did, None)
+        .await?
+        .and_then(|account| account.handle)
+        .unwrap_or(did);
+    let mut uri = Url::parse("otpauth://totp/")?;
+    uri.set_path(&format!("{issuer}:{account}"));
+    uri.query_pairs_mut()
+        .append_pair("secret", &secret)
+        .append_pair("issuer", &issuer);
+    Ok(EnrollTotpOutput {
+        secret,
+        uri: uri.to_string(),
+    })
+}
+
+/// Start enrolling an authenticator app for sign in. It isn't asked for until a code from it
+/// is confirmed with gg.campground.server.confirmTotp.
+#[rocket::post("/xrpc/gg.campground.server.enrollTotp")]
+pub async fn enroll_totp(
+    auth: AccessFull,
+) -> Result<Json<EnrollTotpOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_enroll_totp(auth).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("InvalidRequest") {
+                (Status::BadRequest, ErrorCode::BadRequest)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::{AccessFull, CurrentSession};
+use anyhow::Result;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct SessionView {
+    pub id: String,
+    /// Set when the session was signed in with an app password.
+    #[serde(rename = "appPasswordName", skip_serializing_if = "Option::is_none")]
+    pub app_password_name: Option<String>,
+    /// Set when this is an OAuth grant, to the client it was given to.
+    #[serde(rename = "clientId", skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(rename = "userAgent", skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "ipAddress", skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    /// When the session was created or last refreshed. OAuth grants only record the former.
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: String,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListSessionsOutput {
+    pub sessions: Vec<SessionView>,
+}
+
+async fn inner_list_sessions(
+    auth: AccessFull,
+    current: CurrentSession,
+) -> Result<ListSessionsOutput> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let (sessions, grants) = AccountManager::list_sessions(&did).await?;
+    let mut sessions: Vec<SessionView> = sessions
+        .into_iter()
+        .map(|session| SessionView {
+            current: current.id.as_ref() == Some(&session.id),
+            id: session.id,
+            app_password_name: session.app_password_name,
+            client_id: None,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_used_at: session.last_used_at,
+        })
+        .chain(grants.into_iter().map(|grant| SessionView {
+            current: false,
+            id: grant.id,
+            app_password_name: None,
+            client_id: Some(grant.client_id),
+            user_agent: None,
+            ip_address: None,
+            last_used_at: grant.created_at.clone(),
+            created_at: grant.created_at,
+        }))
+        .collect();
+    sessions.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at));
+    Ok(ListSessionsOutput { sessions })
+}
+
+/// List the account's signed in sessions and OAuth grants, most recently used first.
+#[rocket::get("/xrpc/gg.campground.server.listSessions")]
+pub async fn list_sessions(
+    auth: AccessFull,
+    current: CurrentSession,
+) -> Result<Json<ListSessionsOutput>, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_list_sessions(auth, current).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            Err(status::Custom(
+                Status::InternalServerError,
+                Json(ErrorMessageResponse {
+                    code: Some(ErrorCode::InternalServerError),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
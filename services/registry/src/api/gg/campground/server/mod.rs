@@ -0,0 +1,17 @@
+pub mod confirm_totp;
+pub mod disable_totp;
+pub mod enroll_totp;
+pub mod list_sessions;
+pub mod revoke_all_sessions;
+pub mod revoke_session;
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        confirm_totp::confirm_totp,
+        disable_totp::disable_totp,
+        enroll_totp::enroll_totp,
+        list_sessions::list_sessions,
+        revoke_all_sessions::revoke_all_sessions,
+        revoke_session::revoke_session,
+    ]
+}
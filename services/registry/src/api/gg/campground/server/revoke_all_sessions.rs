@@ -0,0 +1,28 @@
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+
+/// Sign every session of the account out, including the one making the request, and revoke its
+/// OAuth grants.
+#[rocket::post("/xrpc/gg.campground.server.revokeAllSessions")]
+pub async fn revoke_all_sessions(
+    auth: AccessFull,
+) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match AccountManager::revoke_all_sessions(&did).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            Err(status::Custom(
+                Status::InternalServerError,
+                Json(ErrorMessageResponse {
+                    code: Some(ErrorCode::InternalServerError),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
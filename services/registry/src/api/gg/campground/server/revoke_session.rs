@@ -0,0 +1,54 @@
+use crate::account_manager::AccountManager;
+use crate::auth_verifier::AccessFull;
+use anyhow::{bail, Result};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeSessionInput {
+    /// Id of the session, as returned by listSessions.
+    pub id: String,
+}
+
+async fn inner_revoke_session(body: Json<RevokeSessionInput>, auth: AccessFull) -> Result<()> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let RevokeSessionInput { id } = body.into_inner();
+    if !AccountManager::revoke_session(&did, &id).await? {
+        bail!("SessionNotFound: No session `{id}` for this account");
+    }
+    Ok(())
+}
+
+/// Sign a session out, or revoke an OAuth grant. Its refresh token stops working straight away,
+/// and so do access tokens issued to it.
+#[rocket::post(
+    "/xrpc/gg.campground.server.revokeSession",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn revoke_session(
+    body: Json<RevokeSessionInput>,
+    auth: AccessFull,
+) -> Result<(), status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_revoke_session(body, auth).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: {error}");
+            let (status, code) = if error.to_string().starts_with("SessionNotFound") {
+                (Status::NotFound, ErrorCode::NotFound)
+            } else {
+                (Status::InternalServerError, ErrorCode::InternalServerError)
+            };
+            Err(status::Custom(
+                status,
+                Json(ErrorMessageResponse {
+                    code: Some(code),
+                    message: Some(error.to_string()),
+                }),
+            ))
+        }
+    }
+}
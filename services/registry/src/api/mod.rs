@@ -4,7 +4,7 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
-use crate::auth_verifier::AccessStandard;
+use crate::auth_verifier::{assert_privileged_method, AccessStandard};
 use crate::pipethrough::{pipethrough_procedure, ProxyRequest};
 use anyhow::Result;
 use rocket::http::{Header, Status};
@@ -42,6 +42,13 @@ pub async fn bsky_api_forwarder(
     auth: AccessStandard,
     req: ProxyRequest<'_>,
 ) -> Result<ProxyResponder, status::Custom<Json<ErrorMessageResponse>>> {
+    if let Err(error) = assert_privileged_method(&auth.access, &nsid.0) {
+        let forbidden = ErrorMessageResponse {
+            code: Some(ErrorCode::Forbidden),
+            message: Some(error.to_string()),
+        };
+        return Err(status::Custom(Status::Forbidden, Json(forbidden)));
+    }
     let requester: Option<String> = match auth.access.credentials {
         None => None,
         Some(credentials) => credentials.did,
@@ -78,9 +85,11 @@ pub fn routes() -> Vec<rocket::Route> {
     routes.append(&mut app::routes());
     routes.append(&mut chat::routes());
     routes.append(&mut com::routes());
+    routes.append(&mut gg::routes());
     routes
 }
 
 pub mod app;
 pub mod chat;
-pub mod com;
\ No newline at end of file
+pub mod com;
+pub mod gg;
\ No newline at end of file
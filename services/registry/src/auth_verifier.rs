@@ -7,7 +7,7 @@
 use core::str;
 
 use anyhow::{bail, Result};
-use rocket::http::Status;
+use rocket::http::{Method, Status};
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::State;
 use jwt_simple::claims::Audiences;
@@ -30,12 +30,18 @@ use base64::{engine::general_purpose::STANDARD as base64pad, Engine as _};
 use rsky_identity::did::atproto_data::get_did_key_from_multibase;
 use rsky_identity::types::DidDocument;
 use rsky_pds::SharedIdResolver;
-use secp256k1::{Keypair, Secp256k1, SecretKey};
+use secp256k1::PublicKey;
 
 use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
 use crate::account_manager::helpers::auth::CustomClaimObj;
+use crate::account_manager::helpers::password::AppPasswordScopes;
+use crate::account_manager::helpers::session::SessionClient;
 use crate::account_manager::AccountManager;
-use crate::config::{CORE_CONFIG, ENTRYWAY_CONFIG, MOD_SERVICE_CONFIG, SECRET_CONFIG, SERVICE_CONFIG};
+use crate::common::client_ip;
+use crate::config::{CORE_CONFIG, ENTRYWAY_CONFIG, MOD_SERVICE_CONFIG, SERVICE_CONFIG};
+use crate::oauth::access_token::{validate_dpop_token, DPOP};
+use crate::pipethrough::PRIVILEGED_METHODS;
+use crate::signer::{Signer, PDS_SIGNER};
 
 const INFINITY: u64 = u64::MAX;
 
@@ -100,6 +106,11 @@ pub async fn access_check<'r>(
     scopes: Vec<AuthScope>,
     opts: Option<ValidateAccessTokenOpts>,
 ) -> Outcome<AccessOutput, AuthError> {
+    // Every guard for the requester's own account goes through here, so this covers any handler
+    // that changes something
+    if let Err(error) = assert_method_allowed(&app_password_scopes(req), req.method()) {
+        return Outcome::Error((Status::Forbidden, AuthError::BadJwt(error.to_string())));
+    }
     match validate_access_token(req, scopes, opts).await {
         Ok(access) => Outcome::Success(access),
         Err(error) => match error.downcast_ref() {
@@ -275,6 +286,99 @@ impl<'r> FromRequest<'r> for RevokeRefreshToken {
     }
 }
 
+/// Where the request came from, for recording against the session it signs in or refreshes.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SessionClient {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(SessionClient {
+            user_agent: req.headers().get_one("User-Agent").map(str::to_string),
+            ip_address: client_ip(req).map(|ip| ip.to_string()),
+        })
+    }
+}
+
+/// The session the request's bearer token was issued for. `id` is `None` for tokens issued
+/// before sessions were recorded, and for requests without a valid session token.
+pub struct CurrentSession {
+    pub id: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CurrentSession {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(CurrentSession {
+            id: session_claims(req).and_then(|claims| claims.sid),
+        })
+    }
+}
+
+/// For writes to the requester's repo: `AccessStandardIncludeChecks` with the limits of the app
+/// password behind it. Limits on collections need the request body, so handlers check those with
+/// `assert_collection`.
+pub struct AccessRepoWrite {
+    pub access: AccessOutput,
+    pub scopes: AppPasswordScopes,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AccessRepoWrite {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match AccessStandardIncludeChecks::from_request(req).await {
+            Outcome::Success(output) => Outcome::Success(AccessRepoWrite {
+                access: output.access,
+                scopes: app_password_scopes(req),
+            }),
+            Outcome::Error(error) => Outcome::Error(error),
+            Outcome::Forward(_) => panic!("Outcome::Forward returned"),
+        }
+    }
+}
+
+impl AccessRepoWrite {
+    pub fn assert_collection(&self, collection: &str) -> Result<()> {
+        if !self.scopes.allows_collection(collection) {
+            bail!("Forbidden: App password can't write to `{collection}`")
+        }
+        Ok(())
+    }
+}
+
+/// Read only app passwords can only make queries, whether they're handled here or proxied.
+pub fn assert_method_allowed(scopes: &AppPasswordScopes, method: Method) -> Result<()> {
+    if scopes.read_only && !matches!(method, Method::Get | Method::Head) {
+        bail!("Forbidden: App password is read only")
+    }
+    Ok(())
+}
+
+/// Chat and the other privileged methods need a session signed in with the account password, a
+/// privileged app password, or OAuth with chat access.
+pub fn assert_privileged_method(access: &AccessOutput, nsid: &str) -> Result<()> {
+    if (nsid.starts_with("chat.bsky.") || PRIVILEGED_METHODS.contains(nsid))
+        && !is_privileged(access)
+    {
+        bail!("Forbidden: `{nsid}` needs a privileged app password")
+    }
+    Ok(())
+}
+
+/// Whether the token can use privileged methods such as chat.
+pub fn is_privileged(access: &AccessOutput) -> bool {
+    matches!(
+        access
+            .credentials
+            .as_ref()
+            .and_then(|credentials| credentials.scope.as_ref()),
+        Some(AuthScope::Access) | Some(AuthScope::AppPassPrivileged)
+    )
+}
+
 pub struct UserDidAuth {
     pub access: AccessOutput,
 }
@@ -557,12 +661,8 @@ pub async fn validate_bearer_token<'r>(
 ) -> Result<ValidatedBearer> {
     let token = bearer_token_from_req(request)?;
     if let Some(token) = token {
-        let secp = Secp256k1::new();
-        let private_key = SECRET_CONFIG.pds_private_key.clone();
-        let secret_key =
-            SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-        let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
-        let payload = verify_jwt(token.clone(), jwt_key, verify_options).await?;
+        let jwt_key = PDS_SIGNER.public_key();
+        let payload = verify_jwt(token.clone(), &jwt_key, verify_options).await?;
         let JwtPayload {
             sub, aud, scope, ..
         } = payload.clone();
@@ -597,23 +697,27 @@ pub async fn validate_bearer_token<'r>(
     }
 }
 
-// @TODO: Implement DPop/OAuth
+/// Accepts either a session access token (`Bearer`) or an OAuth access token (`DPoP`).
 pub async fn validate_access_token<'r>(
     request: &'r Request<'_>,
     scopes: Vec<AuthScope>,
     opts: Option<ValidateAccessTokenOpts>,
 ) -> Result<AccessOutput> {
-    let mut options = VerificationOptions::default();
-    options.allowed_audiences = Some(HashSet::from_strings(&[
-        SERVICE_CONFIG.did.clone()
-    ]));
     let ValidatedBearer {
         did,
         scope,
         token,
         audience,
         ..
-    } = validate_bearer_token(request, scopes, Some(options)).await?;
+    } = if is_dpop_token(request) {
+        validate_dpop_token(request, scopes).await?
+    } else {
+        let mut options = VerificationOptions::default();
+        options.allowed_audiences = Some(HashSet::from_strings(&[
+            SERVICE_CONFIG.did.clone()
+        ]));
+        validate_bearer_token(request, scopes, Some(options)).await?
+    };
     let ValidateAccessTokenOpts {
         check_takedown,
         check_deactivated,
@@ -737,6 +841,13 @@ pub fn is_bearer_token(request: &Request) -> bool {
     }
 }
 
+pub fn is_dpop_token(request: &Request) -> bool {
+    match request.headers().get_one("Authorization") {
+        None => false,
+        Some(auth_header) => auth_header.starts_with(DPOP),
+    }
+}
+
 pub fn is_basic_token(request: &Request) -> bool {
     match request.headers().get_one("Authorization") {
         None => false,
@@ -744,6 +855,24 @@ pub fn is_basic_token(request: &Request) -> bool {
     }
 }
 
+/// Claims of the request's bearer token, if it's one of our session tokens and still valid.
+fn session_claims(request: &Request) -> Option<CustomClaimObj> {
+    let token = bearer_token_from_req(request).ok()??;
+    let public_key = ES256kPublicKey::from_bytes(&PDS_SIGNER.public_key().serialize()).ok()?;
+    let claims = public_key
+        .verify_token::<CustomClaimObj>(&token, None)
+        .ok()?;
+    Some(claims.custom)
+}
+
+/// Limits of the app password behind the request's bearer token. Sessions signed in with the
+/// account password and OAuth aren't limited by these.
+pub fn app_password_scopes(request: &Request) -> AppPasswordScopes {
+    session_claims(request)
+        .and_then(|claims| claims.app_password)
+        .unwrap_or_default()
+}
+
 pub fn bearer_token_from_req(request: &Request) -> Result<Option<String>> {
     match request.headers().get_one("authorization") {
         Some(header) if !header.starts_with("Bearer ") => Ok(None),
@@ -757,12 +886,17 @@ pub fn bearer_token_from_req(request: &Request) -> Result<Option<String>> {
 
 pub async fn verify_jwt(
     jwt: String,
-    jwt_key: Keypair,
+    jwt_key: &PublicKey,
     verify_options: Option<VerificationOptions>,
 ) -> Result<JwtPayload> {
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let public_key = key.public_key();
+    let public_key = ES256kPublicKey::from_bytes(&jwt_key.serialize())?;
     let claims = public_key.verify_token::<CustomClaimObj>(&jwt, verify_options)?;
+    // Revoking a session stops its tokens before they expire
+    if let (Some(sid), Some(sub)) = (&claims.custom.sid, &claims.subject) {
+        if !AccountManager::session_exists(sid, sub).await? {
+            bail!("Session has been revoked")
+        }
+    }
 
     Ok(JwtPayload {
         scope: AuthScope::from_str(&claims.custom.scope)?,
@@ -797,4 +931,134 @@ pub fn parse_basic_auth(token: &str) -> Option<BasicAuth> {
         }),
         _ => None,
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_manager::helpers::auth::{create_access_token, CreateTokensOpts};
+    use crate::api::app::bsky::actor::put_preferences::put_preferences;
+    use crate::api::app::bsky::notification::register_push::register_push;
+    use crate::api::com::atproto::identity::update_handle::update_handle;
+    use crate::repository::blobstore::BlobStoreCreator;
+    use crate::sequencer::Sequencer;
+    use crate::SharedSequencer;
+    use rocket::http::{ContentType, Header};
+    use rocket::local::asynchronous::Client;
+    use rsky_pds::crawlers::Crawlers;
+    use tokio::sync::RwLock;
+
+    fn app_password(scope: AuthScope) -> AccessOutput {
+        AccessOutput {
+            credentials: Some(Credentials {
+                r#type: "access".to_string(),
+                did: Some("did:example:alice".to_string()),
+                scope: Some(scope),
+                audience: None,
+                token_id: None,
+                aud: None,
+                iss: None,
+                is_privileged: None,
+            }),
+            artifacts: None,
+        }
+    }
+
+    async fn bearer(scopes: AppPasswordScopes) -> Header<'static> {
+        let token = create_access_token(CreateTokensOpts {
+            did: "did:example:alice".to_string(),
+            signer: PDS_SIGNER.clone(),
+            service_did: SERVICE_CONFIG.did.clone(),
+            scope: Some(AuthScope::AppPass),
+            jti: None,
+            expires_in: None,
+            session_id: None,
+            app_password_scopes: Some(scopes),
+            valid_until: None,
+        })
+        .await
+        .unwrap();
+        Header::new("Authorization", format!("Bearer {token}"))
+    }
+
+    #[rocket::get("/query")]
+    fn query(_auth: AccessStandard) {}
+
+    #[rocket::async_test]
+    async fn read_only_app_passwords_cant_change_anything() {
+        let rocket = rocket::build()
+            .mount(
+                "/",
+                rocket::routes![put_preferences, register_push, update_handle, query],
+            )
+            .manage(BlobStoreCreator::Disk(std::env::temp_dir()))
+            .manage(crate::id_resolver())
+            .manage(SharedSequencer {
+                sequencer: RwLock::new(Sequencer::new(
+                    Crawlers::new("localhost".to_string(), vec![]),
+                    None,
+                )),
+            });
+        let client = Client::untracked(rocket).await.unwrap();
+        let read_only = bearer(AppPasswordScopes {
+            read_only: true,
+            collections: None,
+        })
+        .await;
+
+        for (path, body) in [
+            (
+                "/xrpc/app.bsky.actor.putPreferences",
+                r#"{"preferences":[]}"#,
+            ),
+            (
+                "/xrpc/app.bsky.notification.registerPush",
+                r#"{"serviceDid":"did:web:push.example","token":"t","platform":"web","appId":"a"}"#,
+            ),
+            (
+                "/xrpc/com.atproto.identity.updateHandle",
+                r#"{"handle":"alice.test"}"#,
+            ),
+        ] {
+            let response = client
+                .post(path)
+                .header(ContentType::JSON)
+                .header(read_only.clone())
+                .body(body)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Forbidden, "{path}");
+        }
+
+        let response = client.get("/query").header(read_only).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn limits_app_passwords() {
+        let read_only = AppPasswordScopes {
+            read_only: true,
+            collections: None,
+        };
+        assert!(assert_method_allowed(&read_only, Method::Get).is_ok());
+        assert!(assert_method_allowed(&read_only, Method::Post).is_err());
+        assert!(assert_method_allowed(&AppPasswordScopes::default(), Method::Post).is_ok());
+
+        let write = AccessRepoWrite {
+            access: app_password(AuthScope::AppPass),
+            scopes: AppPasswordScopes {
+                read_only: false,
+                collections: Some(vec!["gg.campground.*".to_string()]),
+            },
+        };
+        assert!(write
+            .assert_collection("gg.campground.actor.profile")
+            .is_ok());
+        assert!(write.assert_collection("app.bsky.feed.post").is_err());
+
+        let standard = app_password(AuthScope::AppPass);
+        assert!(assert_privileged_method(&standard, "chat.bsky.convo.sendMessage").is_err());
+        assert!(assert_privileged_method(&standard, "app.bsky.feed.getTimeline").is_ok());
+        let privileged = app_password(AuthScope::AppPassPrivileged);
+        assert!(assert_privileged_method(&privileged, "chat.bsky.convo.sendMessage").is_ok());
+    }
+}
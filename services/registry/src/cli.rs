@@ -0,0 +1,50 @@
+//! Maintenance commands run with `campground-registry admin <command> [args]` instead of starting
+//! the server. They use the same configuration and database as the server.
+use crate::api::com::atproto::repo::{assert_repo_availability, resolve_atproto_key};
+use crate::config::BLOBSTORE_CONFIG;
+use crate::repository::blobstore::BlobStoreCreator;
+use crate::repository::integrity::verify_repo;
+use crate::repository::ActorStore;
+use anyhow::{bail, Result};
+
+/// The first argument that runs a maintenance command. Anything else starts the server, so
+/// arguments passed by a supervisor or container runtime can't be mistaken for a command.
+pub const SUBCOMMAND: &str = "admin";
+
+const USAGE: &str = "Usage: campground-registry admin verify-repo <did> [--repair]";
+
+/// Runs the command named by `args`, which follow [`SUBCOMMAND`], and returns the process exit
+/// code.
+pub async fn run(args: Vec<String>) -> Result<i32> {
+    match args.first().map(String::as_str) {
+        Some("verify-repo") => verify_repo_command(&args[1..]).await,
+        _ => bail!("{USAGE}"),
+    }
+}
+
+/// Prints the integrity report for a repo as JSON, exiting with 1 if it found discrepancies.
+async fn verify_repo_command(args: &[String]) -> Result<i32> {
+    let did = match args.iter().find(|arg| !arg.starts_with("--")) {
+        None => bail!("{USAGE}"),
+        Some(did) => did.clone(),
+    };
+    let repair = args.iter().any(|arg| arg == "--repair");
+    assert_repo_availability(&did, true).await?;
+
+    let id_resolver = crate::id_resolver();
+    let did_key = match resolve_atproto_key(&id_resolver, &did).await {
+        Ok(did_key) => Some(did_key),
+        Err(error) => {
+            eprintln!("@LOG: ERROR: couldn't resolve signing key for `{did}`: {error}");
+            None
+        }
+    };
+    let blobstore = BlobStoreCreator::from_config(&BLOBSTORE_CONFIG).await?;
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
+    let report = verify_repo(&actor_store, did_key.as_ref(), repair).await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    match report.is_consistent() {
+        true => Ok(0),
+        false => Ok(1),
+    }
+}
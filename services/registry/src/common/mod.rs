@@ -1,5 +1,8 @@
+use crate::config::CORE_CONFIG;
+use rocket::Request;
 use rsky_pds::common::{validate_url, get_did};
 use rsky_identity::types::DidDocument;
+use std::net::IpAddr;
 
 pub use rsky_pds::common::GetServiceEndpointOpts;
 
@@ -27,4 +30,38 @@ pub fn get_service_endpoint(doc: DidDocument, opts: GetServiceEndpointOpts) -> O
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// The address a request came from: the peer it was received from, or what the proxy it came
+/// through says when `core.trusted_proxy_header` is set. Proxies append to `X-Forwarded-For`, so
+/// the last address in the header is the one the trusted proxy saw.
+pub fn client_ip(req: &Request<'_>) -> Option<IpAddr> {
+    let forwarded = CORE_CONFIG
+        .trusted_proxy_header
+        .as_ref()
+        .and_then(|header| req.headers().get_one(header))
+        .and_then(parse_forwarded_ip);
+    forwarded.or(req.remote().map(|remote| remote.ip()))
+}
+
+fn parse_forwarded_ip(header: &str) -> Option<IpAddr> {
+    header.rsplit(',').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_last_forwarded_address() {
+        assert_eq!(
+            parse_forwarded_ip("203.0.113.7"),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            parse_forwarded_ip("10.0.0.1, 2001:db8::1"),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(parse_forwarded_ip("unknown"), None);
+    }
+}
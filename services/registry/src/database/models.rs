@@ -33,6 +33,15 @@ pub struct Account {
     #[diesel(column_name = emailConfirmedAt)]
     #[serde(rename = "emailConfirmedAt")]
     pub email_confirmed_at: Option<String>,
+    #[diesel(column_name = invitesDisabled)]
+    #[serde(rename = "invitesDisabled")]
+    pub invites_disabled: i16,
+    #[diesel(column_name = inviteNote)]
+    #[serde(rename = "inviteNote")]
+    pub invite_note: Option<String>,
+    #[diesel(column_name = emailAuthFactor)]
+    #[serde(rename = "emailAuthFactor")]
+    pub email_auth_factor: i16,
 }
 
 #[derive(
@@ -93,6 +102,17 @@ pub struct AppPassword {
     #[diesel(column_name = createdAt)]
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    pub privileged: i16,
+    #[diesel(column_name = readOnly)]
+    #[serde(rename = "readOnly")]
+    pub read_only: i16,
+    pub collections: Option<String>,
+    #[diesel(column_name = expiresAt)]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<String>,
+    #[diesel(column_name = lastUsedAt)]
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<String>,
 }
 
 #[derive(
@@ -143,6 +163,10 @@ pub struct Blob {
     #[diesel(column_name = takedownRef)]
     #[serde(rename = "takedownRef")]
     pub takedown_ref: Option<String>,
+    /// Milliseconds, for videos.
+    pub duration: Option<i32>,
+    pub container: Option<String>,
+    pub codec: Option<String>,
 }
 
 #[derive(
@@ -170,6 +194,7 @@ pub enum EmailTokenPurpose {
     ResetPassword,
     DeleteAccount,
     PlcOperation,
+    TwoFactorCode,
 }
 
 impl EmailTokenPurpose {
@@ -180,6 +205,7 @@ impl EmailTokenPurpose {
             EmailTokenPurpose::ResetPassword => "reset_password",
             EmailTokenPurpose::DeleteAccount => "delete_account",
             EmailTokenPurpose::PlcOperation => "plc_operation",
+            EmailTokenPurpose::TwoFactorCode => "2fa_code",
         }
     }
 
@@ -190,6 +216,7 @@ impl EmailTokenPurpose {
             "reset_password" => Ok(EmailTokenPurpose::ResetPassword),
             "delete_account" => Ok(EmailTokenPurpose::DeleteAccount),
             "plc_operation" => Ok(EmailTokenPurpose::PlcOperation),
+            "2fa_code" => Ok(EmailTokenPurpose::TwoFactorCode),
             _ => bail!("Unable to parse as EmailTokenPurpose: `{s:?}`"),
         }
     }
@@ -232,6 +259,142 @@ pub struct EmailToken {
     pub requested_at: String,
 }
 
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(code))]
+#[diesel(table_name = crate::schema::registry::invite_code)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InviteCode {
+    pub code: String,
+    #[diesel(column_name = availableUses)]
+    #[serde(rename = "availableUses")]
+    pub available_uses: i32,
+    pub disabled: i16,
+    #[diesel(column_name = forAccount)]
+    #[serde(rename = "forAccount")]
+    pub for_account: String,
+    #[diesel(column_name = createdBy)]
+    #[serde(rename = "createdBy")]
+    pub created_by: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(code, used_by))]
+#[diesel(table_name = crate::schema::registry::invite_code_use)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InviteCodeUse {
+    pub code: String,
+    #[diesel(column_name = usedBy)]
+    #[serde(rename = "usedBy")]
+    pub used_by: String,
+    #[diesel(column_name = usedAt)]
+    #[serde(rename = "usedAt")]
+    pub used_at: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::registry::oauth_request)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OAuthRequest {
+    pub id: String,
+    #[diesel(column_name = clientId)]
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[diesel(column_name = clientAuth)]
+    #[serde(rename = "clientAuth")]
+    pub client_auth: String,
+    pub parameters: String,
+    #[diesel(column_name = dpopJkt)]
+    #[serde(rename = "dpopJkt")]
+    pub dpop_jkt: String,
+    pub did: Option<String>,
+    pub code: Option<String>,
+    #[diesel(column_name = expiresAt)]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = csrfToken)]
+    #[serde(rename = "csrfToken")]
+    pub csrf_token: Option<String>,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::registry::oauth_token)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OAuthToken {
+    pub id: String,
+    pub did: String,
+    #[diesel(column_name = clientId)]
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[diesel(column_name = clientAuth)]
+    #[serde(rename = "clientAuth")]
+    pub client_auth: String,
+    pub scope: String,
+    #[diesel(column_name = dpopJkt)]
+    #[serde(rename = "dpopJkt")]
+    pub dpop_jkt: String,
+    #[diesel(column_name = refreshToken)]
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = expiresAt)]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+}
+
 #[derive(
     Queryable,
     Identifiable,
@@ -290,6 +453,30 @@ pub struct RecordBlob {
     pub did: String,
 }
 
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(did, code))]
+#[diesel(table_name = crate::schema::registry::recovery_code)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RecoveryCode {
+    pub did: String,
+    /// Hex-encoded SHA-256 hash of the code.
+    pub code: String,
+    #[diesel(column_name = usedAt)]
+    #[serde(rename = "usedAt")]
+    pub used_at: Option<String>,
+}
+
 #[derive(
     Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Default, Serialize, Deserialize,
 )]
@@ -307,6 +494,9 @@ pub struct RefreshToken {
     #[diesel(column_name = appPasswordName)]
     #[serde(rename = "appPasswordName")]
     pub app_password_name: Option<String>,
+    #[diesel(column_name = sessionId)]
+    #[serde(rename = "sessionId")]
+    pub session_id: Option<String>,
 }
 
 #[derive(
@@ -392,4 +582,107 @@ impl RepoSeq {
             seq: None,         // default values used on insert
         }
     }
-}
\ No newline at end of file
+}
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(table_name = crate::schema::registry::session)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Session {
+    pub id: String,
+    pub did: String,
+    #[diesel(column_name = appPasswordName)]
+    #[serde(rename = "appPasswordName")]
+    pub app_password_name: Option<String>,
+    #[diesel(column_name = userAgent)]
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[diesel(column_name = ipAddress)]
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = lastUsedAt)]
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(did))]
+#[diesel(table_name = crate::schema::registry::signing_key)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SigningKey {
+    pub did: String,
+    #[diesel(column_name = keyDid)]
+    #[serde(rename = "keyDid")]
+    pub key_did: String,
+    /// Hex-encoded AES-256-GCM ciphertext, or the plain hex key for rows with no `key_nonce`.
+    #[diesel(column_name = privateKey)]
+    #[serde(rename = "privateKey")]
+    pub private_key: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = keyNonce)]
+    #[serde(rename = "keyNonce")]
+    pub key_nonce: Option<String>,
+    /// The account a rotation staged this key for, until it switches over to it.
+    #[diesel(column_name = pendingFor)]
+    #[serde(rename = "pendingFor")]
+    pub pending_for: Option<String>,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(did))]
+#[diesel(table_name = crate::schema::registry::totp)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Totp {
+    pub did: String,
+    /// Hex-encoded AES-256-GCM ciphertext of the shared secret.
+    pub secret: String,
+    #[diesel(column_name = secretNonce)]
+    #[serde(rename = "secretNonce")]
+    pub secret_nonce: String,
+    /// Time step of the last code accepted, so a code can't be used twice.
+    #[diesel(column_name = lastUsedStep)]
+    #[serde(rename = "lastUsedStep")]
+    pub last_used_step: Option<i64>,
+    #[diesel(column_name = confirmedAt)]
+    #[serde(rename = "confirmedAt")]
+    pub confirmed_at: Option<String>,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
@@ -0,0 +1,246 @@
+//! Resized, re-encoded variants of image blobs, served at
+//! `/img/<preset>/plain/<did>/<cid>@<format>` so the registry can stand in for an appview CDN.
+//! Variants are made on first request and cached in the account's blobstore next to the
+//! original.
+use crate::api::com::atproto::repo::assert_repo_availability;
+use crate::config::IMAGE_SERVER_CONFIG;
+use crate::rate_limiter::RateLimit;
+use crate::repository::blobstore::BlobStoreCreator;
+use crate::repository::ActorStore;
+use anyhow::{anyhow, bail, Result};
+use libipld::Cid;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, status, Responder};
+use rocket::serde::json::Json;
+use rocket::{Request, Response, State};
+use rsky_pds::models::{ErrorCode, ErrorMessageResponse};
+use rsky_pds::repo::error::BlobError;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use tokio::sync::Semaphore;
+
+pub mod processor;
+
+/// Resizing is CPU bound, so only this many images are processed at once and the rest wait.
+static PROCESSING: LazyLock<Semaphore> =
+    LazyLock::new(|| Semaphore::new(std::thread::available_parallelism().map_or(2, |n| n.get())));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    /// Crop to the preset's aspect ratio, then scale down to its size.
+    Cover,
+    /// Scale down to fit within the preset's size.
+    Inside,
+}
+
+/// The variants the Bluesky app asks a CDN for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Avatar,
+    AvatarThumbnail,
+    Banner,
+    FeedThumbnail,
+    FeedFullsize,
+}
+
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "avatar" => Some(Preset::Avatar),
+            "avatar_thumbnail" => Some(Preset::AvatarThumbnail),
+            "banner" => Some(Preset::Banner),
+            "feed_thumbnail" => Some(Preset::FeedThumbnail),
+            "feed_fullsize" => Some(Preset::FeedFullsize),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Preset::Avatar => "avatar",
+            Preset::AvatarThumbnail => "avatar_thumbnail",
+            Preset::Banner => "banner",
+            Preset::FeedThumbnail => "feed_thumbnail",
+            Preset::FeedFullsize => "feed_fullsize",
+        }
+    }
+
+    pub const fn size(self) -> (u32, u32) {
+        match self {
+            Preset::Avatar => (1000, 1000),
+            Preset::AvatarThumbnail => (128, 128),
+            Preset::Banner => (3000, 1000),
+            Preset::FeedThumbnail => (1000, 1000),
+            Preset::FeedFullsize => (2000, 2000),
+        }
+    }
+
+    pub const fn fit(self) -> Fit {
+        match self {
+            Preset::Avatar | Preset::AvatarThumbnail | Preset::Banner => Fit::Cover,
+            Preset::FeedThumbnail | Preset::FeedFullsize => Fit::Inside,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Jpeg,
+    Webp,
+}
+
+impl Format {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "jpeg" | "jpg" => Some(Format::Jpeg),
+            "webp" => Some(Format::Webp),
+            _ => None,
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpeg",
+            Format::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(self) -> ContentType {
+        match self {
+            Format::Jpeg => ContentType::JPEG,
+            Format::Webp => ContentType::WEBP,
+        }
+    }
+}
+
+/// A `cdn_url_pattern` pointing at this server's image endpoint.
+pub fn url_pattern(hostname: &str) -> String {
+    format!("https://{hostname}/img/{{}}/plain/{{}}/{{}}@jpeg")
+}
+
+pub struct DerivedImage {
+    format: Format,
+    bytes: Vec<u8>,
+}
+
+impl<'r> Responder<'r, 'static> for DerivedImage {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(self.format.content_type())
+            // Variants of a CID never change, but a takedown has to be able to stop them being
+            // served, so they aren't marked immutable.
+            .header(Header::new("cache-control", "public, max-age=86400"))
+            .header(Header::new(
+                "content-security-policy",
+                "default-src 'none'; sandbox",
+            ))
+            .sized_body(self.bytes.len(), Cursor::new(self.bytes))
+            .ok()
+    }
+}
+
+async fn inner_get_image(
+    preset: &str,
+    did: String,
+    file: &str,
+    blobstore: &State<BlobStoreCreator>,
+) -> Result<DerivedImage> {
+    let Some(preset) = Preset::from_name(preset) else {
+        bail!("InvalidRequest: Unknown image preset `{preset}`")
+    };
+    let (cid, format) = match file.split_once('@') {
+        None => (file, Format::Jpeg),
+        Some((cid, extension)) => match Format::from_extension(extension) {
+            Some(format) => (cid, format),
+            None => bail!("InvalidRequest: Unsupported image format `{extension}`"),
+        },
+    };
+    let Ok(cid) = Cid::from_str(cid) else {
+        bail!("InvalidRequest: Invalid CID `{cid}`")
+    };
+    assert_repo_availability(&did, false).await?;
+
+    let actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
+    let metadata = actor_store.blob.get_blob_metadata(cid).await?;
+    if !metadata
+        .mime_type
+        .is_some_and(|mime_type| mime_type.starts_with("image/"))
+    {
+        bail!("InvalidRequest: Blob is not an image")
+    }
+
+    let blobstore = actor_store.blob.blobstore;
+    // The quality is part of the name so changing it doesn't keep serving the old variants.
+    let quality = IMAGE_SERVER_CONFIG.quality();
+    let variant = format!("{}_q{quality}@{}", preset.name(), format.extension());
+    match blobstore.get_derived(cid, variant.clone()).await {
+        Ok(bytes) => return Ok(DerivedImage { format, bytes }),
+        Err(error) if matches!(error.downcast_ref(), Some(BlobError::BlobNotFoundError)) => (),
+        Err(error) => return Err(error),
+    }
+
+    let original = blobstore.get_bytes(cid).await?;
+    let permit = PROCESSING.acquire().await?;
+    let bytes =
+        tokio::task::spawn_blocking(move || processor::derive(&original, preset, format, quality))
+            .await?
+            .map_err(|error| {
+                anyhow!("InvalidRequest: Blob could not be read as an image: {error}")
+            })?;
+    drop(permit);
+    // A failed write only means the next request processes the image again.
+    if let Err(error) = blobstore.put_derived(cid, variant, bytes.clone()).await {
+        eprintln!("@LOG: ERROR: couldn't cache image variant of {cid} for {did}: {error}");
+    }
+    Ok(DerivedImage { format, bytes })
+}
+
+/// Get an image blob resized for `preset` and re-encoded as JPEG or WebP, with its metadata
+/// stripped. Does not require auth, so it's rate limited per IP; usable as an appview
+/// `cdn_url_pattern`.
+#[rocket::get("/img/<preset>/plain/<did>/<file>")]
+pub async fn get_image(
+    preset: &str,
+    did: String,
+    file: &str,
+    blobstore: &State<BlobStoreCreator>,
+    _rate_limit: RateLimit,
+) -> Result<DerivedImage, status::Custom<Json<ErrorMessageResponse>>> {
+    match inner_get_image(preset, did, file, blobstore).await {
+        Ok(res) => Ok(res),
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            let not_found = matches!(error.downcast_ref(), Some(BlobError::BlobNotFoundError))
+                || error.to_string().starts_with("RepoNotFound")
+                || error.to_string().starts_with("RepoTakendown")
+                || error.to_string().starts_with("RepoDeactivated");
+            if not_found {
+                let not_found_error = ErrorMessageResponse {
+                    code: Some(ErrorCode::NotFound),
+                    message: Some("cannot find image".to_owned()),
+                };
+                Err(status::Custom(Status::NotFound, Json(not_found_error)))
+            } else if error.to_string().starts_with("InvalidRequest") {
+                let bad_request = ErrorMessageResponse {
+                    code: Some(ErrorCode::BadRequest),
+                    message: Some(error.to_string()),
+                };
+                Err(status::Custom(Status::BadRequest, Json(bad_request)))
+            } else {
+                let internal_error = ErrorMessageResponse {
+                    code: Some(ErrorCode::InternalServerError),
+                    message: Some("Internal error".to_string()),
+                };
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(internal_error),
+                ))
+            }
+        }
+    }
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![get_image]
+}
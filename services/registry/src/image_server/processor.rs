@@ -0,0 +1,139 @@
+use crate::image_server::{Fit, Format, Preset};
+use anyhow::{anyhow, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageReader, Limits, Rgb, RgbImage};
+use std::io::Cursor;
+
+/// Decoding stops with an error past this many bytes of pixel data, so a small, highly
+/// compressed upload can't make us allocate gigabytes.
+const MAX_ALLOC: u64 = 256 * 1024 * 1024;
+
+/// Decodes `bytes`, applies its EXIF orientation, resizes it for `preset` and re-encodes it as
+/// `format` at `quality` (1 to 100). The output is written from the pixels alone, so EXIF and
+/// other metadata (location, camera serials) don't carry over.
+pub fn derive(bytes: &[u8], preset: Preset, format: Format, quality: u8) -> Result<Vec<u8>> {
+    let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_ALLOC);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+
+    let img = resize(img, preset);
+    let quality = quality.clamp(1, 100);
+    match format {
+        Format::Jpeg => {
+            let mut out = Vec::new();
+            JpegEncoder::new_with_quality(&mut out, quality).encode_image(&flatten(img))?;
+            Ok(out)
+        }
+        // image's own WebP encoder is lossless only, which makes photos several times larger
+        // than the JPEG, so libwebp does the lossy encoding.
+        Format::Webp => {
+            let (width, height) = (img.width(), img.height());
+            let webp = match img.color().has_alpha() {
+                true => webp::Encoder::from_rgba(img.to_rgba8().as_raw(), width, height)
+                    .encode_simple(false, quality as f32),
+                false => webp::Encoder::from_rgb(img.to_rgb8().as_raw(), width, height)
+                    .encode_simple(false, quality as f32),
+            };
+            let webp = webp.map_err(|error| anyhow!("couldn't encode WebP: {error:?}"))?;
+            Ok(webp.to_vec())
+        }
+    }
+}
+
+/// Images are only ever scaled down. `Cover` crops to the preset's aspect ratio first, keeping
+/// the centre; `Inside` keeps the aspect ratio and fits within the preset's bounds.
+fn resize(img: DynamicImage, preset: Preset) -> DynamicImage {
+    let (width, height) = preset.size();
+    match preset.fit() {
+        Fit::Inside if img.width() <= width && img.height() <= height => img,
+        Fit::Inside => img.resize(width, height, FilterType::Lanczos3),
+        Fit::Cover => {
+            let (img_width, img_height) = (img.width() as u64, img.height() as u64);
+            let (crop_width, crop_height) =
+                match img_width * height as u64 > img_height * width as u64 {
+                    true => (img_height * width as u64 / height as u64, img_height),
+                    false => (img_width, img_width * height as u64 / width as u64),
+                };
+            let (crop_width, crop_height) = (crop_width.max(1) as u32, crop_height.max(1) as u32);
+            let cropped = img.crop_imm(
+                (img.width() - crop_width) / 2,
+                (img.height() - crop_height) / 2,
+                crop_width,
+                crop_height,
+            );
+            match crop_width > width {
+                true => cropped.resize_exact(width, height, FilterType::Lanczos3),
+                false => cropped,
+            }
+        }
+    }
+}
+
+/// JPEG has no alpha channel, so transparent areas are composited onto white rather than left as
+/// whatever colour the transparent pixels happen to hold.
+fn flatten(img: DynamicImage) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        RgbaImage::new(width, height)
+            .write_to(&mut out, ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn derives_variants() {
+        let avatar = derive(&png(400, 300), Preset::AvatarThumbnail, Format::Jpeg, 85).unwrap();
+        let avatar = image::load_from_memory(&avatar).unwrap();
+        assert_eq!((avatar.width(), avatar.height()), (128, 128));
+
+        let thumb = derive(&png(1200, 600), Preset::FeedThumbnail, Format::Webp, 85).unwrap();
+        assert_eq!(image::guess_format(&thumb).unwrap(), ImageFormat::WebP);
+        let thumb = image::load_from_memory(&thumb).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (1000, 500));
+
+        // Smaller images are cropped to shape but not enlarged.
+        let avatar = derive(&png(300, 200), Preset::Avatar, Format::Jpeg, 85).unwrap();
+        let avatar = image::load_from_memory(&avatar).unwrap();
+        assert_eq!((avatar.width(), avatar.height()), (200, 200));
+    }
+
+    #[test]
+    fn encodes_webp_lossily() {
+        let photo = RgbImage::from_fn(800, 600, |x, y| {
+            Rgb([(x % 251) as u8, (y % 241) as u8, ((x * y) % 239) as u8])
+        });
+        let mut png = Cursor::new(Vec::new());
+        photo.write_to(&mut png, ImageFormat::Png).unwrap();
+        let png = png.into_inner();
+
+        let low = derive(&png, Preset::FeedFullsize, Format::Webp, 20).unwrap();
+        let high = derive(&png, Preset::FeedFullsize, Format::Webp, 90).unwrap();
+        assert!(low.len() < high.len());
+        let low = image::load_from_memory(&low).unwrap();
+        assert_eq!((low.width(), low.height()), (800, 600));
+        // Out of range qualities are clamped rather than rejected
+        assert!(derive(&png, Preset::AvatarThumbnail, Format::Webp, 0).is_ok());
+    }
+}
@@ -0,0 +1,317 @@
+use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::helpers::{auth, email_token, session};
+use crate::account_manager::AccountManager;
+use crate::config::JANITOR_CONFIG;
+use crate::database::establish_connection;
+use crate::repository::blobstore::BlobStoreCreator;
+use crate::repository::ActorStore;
+use crate::sequencer::{self, Sequencer};
+use anyhow::Result;
+use chrono::{DateTime, Duration, ParseError, Utc};
+use diesel::dsl::{exists, max, not};
+use diesel::*;
+use rsky_pds::common;
+use rsky_pds::common::RFC3339_VARIANT;
+
+/// Rows handled per query, so a large backlog doesn't get loaded all at once.
+const BATCH_SIZE: i64 = 500;
+
+/// Timestamp `millis` ago, formatted like `common::now()` so the two compare as strings.
+fn ago(millis: u64) -> String {
+    (Utc::now() - Duration::milliseconds(millis as i64))
+        .format(RFC3339_VARIANT)
+        .to_string()
+}
+
+/// Removes uploads that no record picked up within `temp_blob_max_age`.
+pub async fn sweep_temp_blobs(blobstore: &BlobStoreCreator) -> Result<u64> {
+    let cutoff = ago(JANITOR_CONFIG.temp_blob_max_age());
+    sweep_temp_blobs_before(blobstore, &cutoff, BATCH_SIZE).await
+}
+
+async fn sweep_temp_blobs_before(
+    blobstore: &BlobStoreCreator,
+    cutoff: &String,
+    batch_size: i64,
+) -> Result<u64> {
+    use crate::schema::registry::blob::dsl as BlobSchema;
+    use crate::schema::registry::record_blob::dsl as RecordBlobSchema;
+    let conn = &mut establish_connection()?;
+
+    let mut removed = 0;
+    loop {
+        let batch: Vec<(String, String, Option<String>)> = BlobSchema::blob
+            .filter(BlobSchema::tempKey.is_not_null())
+            .filter(BlobSchema::createdAt.le(cutoff))
+            .filter(not(exists(
+                RecordBlobSchema::record_blob
+                    .filter(RecordBlobSchema::blobCid.eq(BlobSchema::cid))
+                    .filter(RecordBlobSchema::did.eq(BlobSchema::did)),
+            )))
+            .select((BlobSchema::did, BlobSchema::cid, BlobSchema::tempKey))
+            .limit(batch_size)
+            .load(conn)?;
+        if batch.is_empty() {
+            return Ok(removed);
+        }
+        for (did, cid, temp_key) in batch {
+            let Some(temp_key) = temp_key else { continue };
+            // Only remove the upload if the row still points at it. If a record made it permanent
+            // in the meantime the row no longer matches and the blob is left alone.
+            let deleted = delete(BlobSchema::blob)
+                .filter(BlobSchema::did.eq(&did))
+                .filter(BlobSchema::cid.eq(&cid))
+                .filter(BlobSchema::tempKey.eq(&temp_key))
+                .execute(conn)?;
+            // A failed delete leaves the upload behind, but the row is gone either way so the
+            // rest of the sweep carries on.
+            if deleted > 0 {
+                if let Err(error) = blobstore.create(did.clone()).delete_temp(temp_key).await {
+                    eprintln!(
+                        "@LOG: ERROR: janitor failed to remove upload {cid} of {did}: {error}"
+                    );
+                }
+                removed += 1;
+            }
+        }
+    }
+}
+
+/// Deletes deactivated accounts whose `deleteAfter` has passed, the same way
+/// `com.atproto.admin.deleteAccount` does.
+pub async fn sweep_account_deletions(
+    blobstore: &BlobStoreCreator,
+    sequencer: &mut Sequencer,
+) -> Result<u64> {
+    use crate::schema::registry::actor::dsl as ActorSchema;
+    let conn = &mut establish_connection()?;
+
+    // `deleteAfter` comes from the client, so it's parsed rather than compared as a string.
+    let scheduled: Vec<(String, Option<String>)> = ActorSchema::actor
+        .filter(ActorSchema::deleteAfter.is_not_null())
+        .select((ActorSchema::did, ActorSchema::deleteAfter))
+        .load(conn)?;
+    let now = Utc::now();
+    let mut removed = 0;
+    for (did, delete_after) in scheduled {
+        let Some(delete_after) = delete_after else {
+            continue;
+        };
+        match is_due(&delete_after, now) {
+            Ok(true) => (),
+            Ok(false) => continue,
+            Err(error) => {
+                eprintln!("@LOG: janitor skipping {did} with invalid deleteAfter: {error}");
+                continue;
+            }
+        }
+
+        let mut actor_store = ActorStore::new(did.clone(), blobstore.create(did.clone()));
+        actor_store.destroy().await?;
+        AccountManager::delete_account(&did).await?;
+        let tombstone_seq = sequencer.sequence_tombstone(did.clone()).await?;
+        let account_seq = sequencer
+            .sequence_account_evt(did.clone(), AccountStatus::Deleted)
+            .await?;
+        sequencer::invalidate_all_for_user(&did, Some(vec![account_seq, tombstone_seq])).await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+fn is_due(delete_after: &str, now: DateTime<Utc>) -> Result<bool, ParseError> {
+    Ok(DateTime::parse_from_rfc3339(delete_after)? <= now)
+}
+
+/// Removes expired refresh tokens. Sessions only clear out their own account's tokens, so tokens
+/// of accounts that never sign in again would otherwise stay forever. Sessions left without any
+/// tokens go with them.
+pub async fn sweep_refresh_tokens() -> Result<u64> {
+    let tokens = auth::delete_all_expired_refresh_tokens(common::now()).await?;
+    let sessions = session::delete_orphaned_sessions().await?;
+    Ok((tokens + sessions) as u64)
+}
+
+pub async fn sweep_email_tokens() -> Result<u64> {
+    let cutoff = ago(JANITOR_CONFIG.email_token_max_age());
+    Ok(email_token::delete_email_tokens_requested_before(cutoff).await? as u64)
+}
+
+pub async fn sweep_reserved_keys() -> Result<u64> {
+    let cutoff = ago(JANITOR_CONFIG.reserved_key_max_age());
+    Ok(AccountManager::delete_unclaimed_signing_keys(&cutoff).await? as u64)
+}
+
+/// Removes invalidated firehose events and those older than `repo_seq_max_age`. The newest event
+/// is always kept, so the current seq is still known after a quiet spell.
+pub async fn sweep_repo_seq() -> Result<u64> {
+    let cutoff = ago(JANITOR_CONFIG.repo_seq_max_age());
+    sweep_repo_seq_before(&cutoff).await
+}
+
+pub(crate) async fn sweep_repo_seq_before(cutoff: &String) -> Result<u64> {
+    use crate::schema::registry::repo_seq::dsl as RepoSeqSchema;
+    let conn = &mut establish_connection()?;
+
+    let latest: Option<i64> = RepoSeqSchema::repo_seq
+        .select(max(RepoSeqSchema::seq))
+        .first(conn)?;
+    let Some(latest) = latest else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    loop {
+        let batch: Vec<i64> = RepoSeqSchema::repo_seq
+            .filter(RepoSeqSchema::seq.lt(latest))
+            .filter(
+                RepoSeqSchema::invalidated
+                    .ne(0)
+                    .or(RepoSeqSchema::sequencedAt.lt(cutoff)),
+            )
+            .select(RepoSeqSchema::seq)
+            .limit(BATCH_SIZE)
+            .load(conn)?;
+        if batch.is_empty() {
+            return Ok(removed);
+        }
+        removed += delete(RepoSeqSchema::repo_seq)
+            .filter(RepoSeqSchema::seq.eq_any(batch))
+            .execute(conn)? as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_pds::common::get_random_str;
+
+    #[test]
+    fn parses_delete_after() {
+        let now = DateTime::parse_from_rfc3339("2024-11-10T12:00:00.000Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(is_due("2024-11-10T11:59:59.999Z", now), Ok(true));
+        assert_eq!(is_due("2024-11-10T12:00:00Z", now), Ok(true));
+        assert_eq!(is_due("2024-11-10T12:00:01.000Z", now), Ok(false));
+        // Offsets are taken into account rather than compared as text
+        assert_eq!(is_due("2024-11-10T13:30:00+02:00", now), Ok(true));
+        assert!(is_due("next tuesday", now).is_err());
+        assert!(is_due("", now).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn sweeps_old_unreferenced_uploads() {
+        use crate::schema::registry::blob::dsl as BlobSchema;
+        use crate::schema::registry::record_blob::dsl as RecordBlobSchema;
+        let conn = &mut establish_connection().unwrap();
+        let location = std::env::temp_dir().join(format!("registry-janitor-{}", get_random_str()));
+        let blobstore = BlobStoreCreator::Disk(location);
+        let did = format!("did:example:{}", get_random_str());
+        let store = blobstore.create(did.clone());
+
+        let old = "2000-01-01T00:00:00.000Z";
+        let cutoff = "2001-01-01T00:00:00.000Z".to_string();
+        let mut uploads = Vec::new();
+        for (cid, created_at, temp, referenced) in [
+            ("old1", old, true, false),
+            ("old2", old, true, false),
+            ("old3", old, true, false),
+            ("referenced", old, true, true),
+            ("permanent", old, false, false),
+            ("new", "2002-01-01T00:00:00.000Z", true, false),
+        ] {
+            let temp_key = match temp {
+                true => Some(store.put_temp(&mut b"upload".as_slice()).await.unwrap()),
+                false => None,
+            };
+            insert_into(BlobSchema::blob)
+                .values((
+                    BlobSchema::cid.eq(cid),
+                    BlobSchema::did.eq(&did),
+                    BlobSchema::mimeType.eq("text/plain"),
+                    BlobSchema::size.eq(6),
+                    BlobSchema::tempKey.eq(&temp_key),
+                    BlobSchema::createdAt.eq(created_at),
+                ))
+                .execute(conn)
+                .unwrap();
+            if referenced {
+                insert_into(RecordBlobSchema::record_blob)
+                    .values((
+                        RecordBlobSchema::blobCid.eq(cid),
+                        RecordBlobSchema::recordUri.eq(format!("at://{did}/gg.example/1")),
+                        RecordBlobSchema::did.eq(&did),
+                    ))
+                    .execute(conn)
+                    .unwrap();
+            }
+            uploads.push((cid, temp_key));
+        }
+
+        // Smaller batches than there are uploads to remove, so it has to go round again
+        let removed = sweep_temp_blobs_before(&blobstore, &cutoff, 2)
+            .await
+            .unwrap();
+        assert!(removed >= 3);
+
+        let remaining: Vec<String> = BlobSchema::blob
+            .filter(BlobSchema::did.eq(&did))
+            .select(BlobSchema::cid)
+            .order(BlobSchema::cid)
+            .load(conn)
+            .unwrap();
+        assert_eq!(remaining, vec!["new", "permanent", "referenced"]);
+        for (cid, temp_key) in uploads {
+            if let Some(temp_key) = temp_key {
+                let kept = remaining.iter().any(|remaining| remaining == cid);
+                assert_eq!(store.has_temp(temp_key).await.unwrap(), kept, "{cid}");
+            }
+        }
+
+        delete(RecordBlobSchema::record_blob.filter(RecordBlobSchema::did.eq(&did)))
+            .execute(conn)
+            .unwrap();
+        delete(BlobSchema::blob.filter(BlobSchema::did.eq(&did)))
+            .execute(conn)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn sweeps_invalidated_and_old_events() {
+        use crate::schema::registry::repo_seq::dsl as RepoSeqSchema;
+        use crate::sequencer::events::format_seq_identity_evt;
+        use rsky_pds::crawlers::Crawlers;
+        let _lock = sequencer::TEST_LOCK.lock().await;
+        let mut sequencer = Sequencer::new(Crawlers::new("localhost".to_string(), vec![]), None);
+        let did = format!("did:example:{}", get_random_str());
+
+        let mut old = format_seq_identity_evt(did.clone(), None).await.unwrap();
+        old.sequenced_at = "2001-01-01T00:00:00.000Z".to_string();
+        let old = sequencer.sequence_evt(old).await.unwrap();
+        // Invalidated below, as when an account is deleted
+        sequencer
+            .sequence_identity_evt(did.clone(), None)
+            .await
+            .unwrap();
+        let kept = sequencer
+            .sequence_identity_evt(did.clone(), None)
+            .await
+            .unwrap();
+        sequencer::invalidate_all_for_user(&did, Some(vec![old, kept]))
+            .await
+            .unwrap();
+
+        let removed = sweep_repo_seq_before(&"2002-01-01T00:00:00.000Z".to_string())
+            .await
+            .unwrap();
+        assert!(removed >= 2);
+        let remaining: Vec<i64> = RepoSeqSchema::repo_seq
+            .filter(RepoSeqSchema::did.eq(&did))
+            .select(RepoSeqSchema::seq)
+            .load(&mut establish_connection().unwrap())
+            .unwrap();
+        assert_eq!(remaining, vec![kept]);
+    }
+}
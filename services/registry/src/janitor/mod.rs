@@ -0,0 +1,182 @@
+//! Scheduled cleanup of state nothing else removes: uploads that were never referenced, accounts
+//! past their `deleteAfter`, expired tokens, firehose events that were invalidated or are past
+//! the retention window, and signing keys reserved for DIDs that never created an account. Each
+//! job runs on its own interval from `JANITOR_CONFIG` and holds a Postgres advisory lock while it
+//! sweeps, so when several registry nodes share a database only one of them works on a job at a
+//! time.
+use crate::config::JANITOR_CONFIG;
+use crate::database::{establish_connection, DbConnection};
+use crate::repository::blobstore::BlobStoreCreator;
+use crate::sequencer::Sequencer;
+use anyhow::Result;
+use diesel::dsl::sql;
+use diesel::sql_types::{Bool, Int4};
+use diesel::*;
+use metrics::{counter, histogram};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+
+pub mod jobs;
+
+/// First key of the two-key advisory locks, so the janitor's locks can't collide with any others.
+const LOCK_NAMESPACE: i32 = 0x6a616e69; // "jani"
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    TempBlobs,
+    AccountDeletions,
+    RefreshTokens,
+    EmailTokens,
+    RepoSeq,
+    ReservedKeys,
+}
+
+impl Job {
+    const ALL: [Job; 6] = [
+        Job::TempBlobs,
+        Job::AccountDeletions,
+        Job::RefreshTokens,
+        Job::EmailTokens,
+        Job::RepoSeq,
+        Job::ReservedKeys,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Job::TempBlobs => "temp_blobs",
+            Job::AccountDeletions => "account_deletions",
+            Job::RefreshTokens => "refresh_tokens",
+            Job::EmailTokens => "email_tokens",
+            Job::RepoSeq => "repo_seq",
+            Job::ReservedKeys => "reserved_keys",
+        }
+    }
+
+    fn interval(self) -> Option<Duration> {
+        let millis = match self {
+            Job::TempBlobs => JANITOR_CONFIG.temp_blob_interval(),
+            Job::AccountDeletions => JANITOR_CONFIG.account_deletion_interval(),
+            Job::RefreshTokens => JANITOR_CONFIG.refresh_token_interval(),
+            Job::EmailTokens => JANITOR_CONFIG.email_token_interval(),
+            Job::RepoSeq => JANITOR_CONFIG.repo_seq_interval(),
+            Job::ReservedKeys => JANITOR_CONFIG.reserved_key_interval(),
+        };
+        match millis {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Janitor {
+    blobstore: BlobStoreCreator,
+    sequencer: Sequencer,
+}
+
+impl Janitor {
+    pub fn new(blobstore: BlobStoreCreator, sequencer: Sequencer) -> Self {
+        Janitor {
+            blobstore,
+            sequencer,
+        }
+    }
+
+    /// Spawns a task for each job that has an interval.
+    pub fn start(self) {
+        for job in Job::ALL {
+            if let Some(interval) = job.interval() {
+                let janitor = self.clone();
+                tokio::spawn(async move { janitor.run(job, interval).await });
+            }
+        }
+    }
+
+    async fn run(mut self, job: Job, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            self.sweep(job).await;
+        }
+    }
+
+    /// Runs one sweep of `job` unless another node holds its lock, recording how it went.
+    pub async fn sweep(&mut self, job: Job) {
+        let name = job.name();
+        // The lock belongs to this connection's session, so it's held for the whole sweep and
+        // released before the connection goes back to the pool.
+        let mut conn = match establish_connection() {
+            Ok(conn) => conn,
+            Err(error) => {
+                eprintln!("@LOG: ERROR: janitor couldn't connect for {name}: {error}");
+                counter!("janitor_sweeps_total", "job" => name, "outcome" => "error").increment(1);
+                return;
+            }
+        };
+        match try_lock(&mut conn, job) {
+            Ok(true) => (),
+            Ok(false) => {
+                counter!("janitor_sweeps_total", "job" => name, "outcome" => "skipped")
+                    .increment(1);
+                return;
+            }
+            Err(error) => {
+                eprintln!("@LOG: ERROR: janitor couldn't lock {name}: {error}");
+                counter!("janitor_sweeps_total", "job" => name, "outcome" => "error").increment(1);
+                return;
+            }
+        }
+
+        let started = Instant::now();
+        let res = match job {
+            Job::TempBlobs => jobs::sweep_temp_blobs(&self.blobstore).await,
+            Job::AccountDeletions => {
+                jobs::sweep_account_deletions(&self.blobstore, &mut self.sequencer).await
+            }
+            Job::RefreshTokens => jobs::sweep_refresh_tokens().await,
+            Job::EmailTokens => jobs::sweep_email_tokens().await,
+            Job::RepoSeq => jobs::sweep_repo_seq().await,
+            Job::ReservedKeys => jobs::sweep_reserved_keys().await,
+        };
+        histogram!("janitor_sweep_duration_seconds", "job" => name)
+            .record(started.elapsed().as_secs_f64());
+        if let Err(error) = unlock(&mut conn, job) {
+            eprintln!("@LOG: ERROR: janitor couldn't unlock {name}: {error}");
+        }
+
+        match res {
+            Ok(removed) => {
+                counter!("janitor_sweeps_total", "job" => name, "outcome" => "success")
+                    .increment(1);
+                counter!("janitor_removed_total", "job" => name).increment(removed);
+            }
+            Err(error) => {
+                eprintln!("@LOG: ERROR: janitor sweep of {name} failed: {error}");
+                counter!("janitor_sweeps_total", "job" => name, "outcome" => "error").increment(1);
+            }
+        }
+    }
+}
+
+fn try_lock(conn: &mut DbConnection, job: Job) -> Result<bool> {
+    Ok(select(
+        sql::<Bool>("pg_try_advisory_lock(")
+            .bind::<Int4, _>(LOCK_NAMESPACE)
+            .sql(", ")
+            .bind::<Int4, _>(job as i32)
+            .sql(")"),
+    )
+    .get_result(conn)?)
+}
+
+fn unlock(conn: &mut DbConnection, job: Job) -> Result<bool> {
+    Ok(select(
+        sql::<Bool>("pg_advisory_unlock(")
+            .bind::<Int4, _>(LOCK_NAMESPACE)
+            .sql(", ")
+            .bind::<Int4, _>(job as i32)
+            .sql(")"),
+    )
+    .get_result(conn)?)
+}
@@ -0,0 +1,132 @@
+//! Syntax checks for the string formats lexicons can declare.
+//! These only check syntax; whether a DID resolves or a handle is allowed on this registry is
+//! decided elsewhere.
+use chrono::DateTime;
+use lazy_static::lazy_static;
+use libipld::Cid;
+use regex::Regex;
+use std::str::FromStr;
+
+lazy_static! {
+    static ref DID_REGEX: Regex = Regex::new(r"^did:[a-z]+:[a-zA-Z0-9._:%-]*[a-zA-Z0-9._-]$").unwrap();
+    static ref HANDLE_REGEX: Regex = Regex::new(
+        r"^([a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?\.)+[a-zA-Z]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?$"
+    )
+    .unwrap();
+    static ref NSID_REGEX: Regex = Regex::new(
+        r"^[a-zA-Z]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(\.[a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)+(\.[a-zA-Z]([a-zA-Z0-9]{0,62})?)$"
+    )
+    .unwrap();
+    static ref DATETIME_REGEX: Regex = Regex::new(
+        r"^[0-9]{4}-[01][0-9]-[0-3][0-9]T[0-2][0-9]:[0-6][0-9]:[0-6][0-9](\.[0-9]{1,20})?(Z|([+-][0-2][0-9]:[0-5][0-9]))$"
+    )
+    .unwrap();
+    static ref RECORD_KEY_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9_~.:-]{1,512}$").unwrap();
+    static ref TID_REGEX: Regex =
+        Regex::new(r"^[234567abcdefghij][234567abcdefghijklmnopqrstuvwxyz]{12}$").unwrap();
+    static ref LANGUAGE_REGEX: Regex = Regex::new(r"^(i|[a-z]{2,3})(-[a-zA-Z0-9]+)*$").unwrap();
+    static ref URI_REGEX: Regex = Regex::new(r"^[a-z][a-z0-9+.-]*:[^\s]+$").unwrap();
+}
+
+pub fn is_valid_did(did: &str) -> bool {
+    did.len() <= 2048 && DID_REGEX.is_match(did)
+}
+
+pub fn is_valid_handle(handle: &str) -> bool {
+    handle.len() <= 253 && HANDLE_REGEX.is_match(handle)
+}
+
+pub fn is_valid_at_identifier(identifier: &str) -> bool {
+    match identifier.starts_with("did:") {
+        true => is_valid_did(identifier),
+        false => is_valid_handle(identifier),
+    }
+}
+
+pub fn is_valid_nsid(nsid: &str) -> bool {
+    nsid.len() <= 317 && NSID_REGEX.is_match(nsid)
+}
+
+pub fn is_valid_record_key(rkey: &str) -> bool {
+    rkey != "." && rkey != ".." && RECORD_KEY_REGEX.is_match(rkey)
+}
+
+/// `at://<authority>[/<collection>[/<rkey>]]`, without query or fragment.
+pub fn is_valid_at_uri(uri: &str) -> bool {
+    if uri.len() > 8192 {
+        return false;
+    }
+    let path = match uri.strip_prefix("at://") {
+        Some(path) => path,
+        None => return false,
+    };
+    let mut parts = path.split('/');
+    let authority_is_valid = parts.next().is_some_and(is_valid_at_identifier);
+    let collection_is_valid = parts.next().map_or(true, is_valid_nsid);
+    let rkey_is_valid = parts.next().map_or(true, is_valid_record_key);
+    authority_is_valid && collection_is_valid && rkey_is_valid && parts.next().is_none()
+}
+
+pub fn is_valid_datetime(datetime: &str) -> bool {
+    DATETIME_REGEX.is_match(datetime)
+        && !datetime.ends_with("-00:00")
+        && DateTime::parse_from_rfc3339(datetime).is_ok()
+}
+
+pub fn is_valid_cid(cid: &str) -> bool {
+    Cid::from_str(cid).is_ok()
+}
+
+pub fn is_valid_tid(tid: &str) -> bool {
+    TID_REGEX.is_match(tid)
+}
+
+pub fn is_valid_language(language: &str) -> bool {
+    LANGUAGE_REGEX.is_match(language)
+}
+
+pub fn is_valid_uri(uri: &str) -> bool {
+    uri.len() <= 8192 && URI_REGEX.is_match(uri)
+}
+
+/// Checks `value` against a lexicon string format. Formats we don't know about are let through,
+/// so a lexicon using a newer format doesn't make every record of that type unwritable.
+pub fn is_valid_format(format: &str, value: &str) -> bool {
+    match format {
+        "did" => is_valid_did(value),
+        "handle" => is_valid_handle(value),
+        "at-identifier" => is_valid_at_identifier(value),
+        "nsid" => is_valid_nsid(value),
+        "record-key" => is_valid_record_key(value),
+        "at-uri" => is_valid_at_uri(value),
+        "datetime" => is_valid_datetime(value),
+        "cid" => is_valid_cid(value),
+        "tid" => is_valid_tid(value),
+        "language" => is_valid_language(value),
+        "uri" => is_valid_uri(value),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_formats() {
+        assert!(is_valid_did("did:plc:ewvi7nxzyoun6zhxrhs64oiz"));
+        assert!(!is_valid_did("did:PLC:ewvi7nxzyoun6zhxrhs64oiz"));
+        assert!(is_valid_handle("alice.campground.gg"));
+        assert!(!is_valid_handle("alice"));
+        assert!(is_valid_at_uri(
+            "at://did:plc:ewvi7nxzyoun6zhxrhs64oiz/app.bsky.feed.post/3jzfcijpj2z2a"
+        ));
+        assert!(is_valid_at_uri("at://alice.campground.gg"));
+        assert!(!is_valid_at_uri("https://campground.gg"));
+        assert!(is_valid_datetime("1985-04-12T23:20:50.123Z"));
+        assert!(is_valid_datetime("1985-04-12T23:20:50+02:00"));
+        assert!(!is_valid_datetime("1985-04-12 23:20:50Z"));
+        assert!(!is_valid_datetime("1985-04-12T23:20:50"));
+        assert!(is_valid_format("some-future-format", "anything"));
+    }
+}
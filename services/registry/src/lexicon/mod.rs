@@ -0,0 +1,276 @@
+//! Lexicon schema validation for records written to repos.
+//!
+//! Every lexicon document under `lexicons/` at the root of the repository is embedded into the
+//! binary at build time, which covers the `gg.campground.*` lexicons along with the `app.bsky` and
+//! `chat.bsky` records we accept on behalf of Bluesky clients.
+use crate::lexicon::validation::Validator;
+use anyhow::{bail, Result};
+use include_dir::{include_dir, Dir};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+use thiserror::Error;
+
+pub mod formats;
+mod validation;
+
+static LEXICON_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/../../lexicons");
+
+pub static LEXICONS: LazyLock<Lexicons> =
+    LazyLock::new(|| Lexicons::load(&LEXICON_DIR).expect("Failed to load lexicons"));
+
+/// A record that doesn't match its lexicon, or that has none when one was required.
+#[derive(Error, Debug)]
+#[error("Invalid record: {0}")]
+pub struct ValidationError(pub String);
+
+/// Reported back to clients as `validationStatus` on writes.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationStatus {
+    Valid,
+    /// No lexicon is known for the record's collection, so it was written unchecked.
+    Unknown,
+}
+
+impl fmt::Display for ValidationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationStatus::Valid => write!(f, "valid"),
+            ValidationStatus::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Lexicon definitions keyed by their full id, e.g. `app.bsky.feed.post#replyRef`.
+#[derive(Debug, Default)]
+pub struct Lexicons {
+    defs: HashMap<String, Value>,
+}
+
+impl Lexicons {
+    pub fn load(dir: &Dir<'_>) -> Result<Self> {
+        let mut lexicons = Lexicons::default();
+        lexicons.add_dir(dir)?;
+        Ok(lexicons)
+    }
+
+    fn add_dir(&mut self, dir: &Dir<'_>) -> Result<()> {
+        for file in dir.files() {
+            if file.path().extension().is_some_and(|ext| ext == "json") {
+                let document: Value = serde_json::from_slice(file.contents())?;
+                self.add_document(document)?;
+            }
+        }
+        for dir in dir.dirs() {
+            self.add_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn add_document(&mut self, document: Value) -> Result<()> {
+        let id = match document.get("id").and_then(Value::as_str) {
+            Some(id) => id.to_string(),
+            None => bail!("Lexicon document is missing its id"),
+        };
+        let defs = match document.get("defs").and_then(Value::as_object) {
+            Some(defs) => defs,
+            None => bail!("Lexicon `{id}` has no defs"),
+        };
+        for (name, def) in defs {
+            self.defs.insert(format!("{id}#{name}"), def.clone());
+        }
+        Ok(())
+    }
+
+    /// Looks up a definition by id, where a bare NSID refers to its `main` definition.
+    pub fn get_def(&self, id: &str) -> Option<&Value> {
+        match id.contains('#') {
+            true => self.defs.get(id),
+            false => self.defs.get(&format!("{id}#main")),
+        }
+    }
+
+    /// Checks a record about to be written to `collection` against its lexicon, filling in its
+    /// `$type` with the collection if it has none.
+    ///
+    /// Follows the `validate` flag of the write endpoints: `Some(false)` skips validation
+    /// entirely, `Some(true)` requires the lexicon to be known, and `None` validates when the
+    /// lexicon is known and otherwise lets the record through as `Unknown`.
+    pub fn validate_record(
+        &self,
+        collection: &str,
+        rkey: Option<&str>,
+        record: &mut Value,
+        validate: Option<bool>,
+    ) -> Result<Option<ValidationStatus>, ValidationError> {
+        if let Some(record) = record.as_object_mut() {
+            if !record.contains_key("$type") {
+                record.insert("$type".to_string(), Value::from(collection));
+            }
+        }
+        if validate == Some(false) {
+            return Ok(None);
+        }
+        match record.get("$type").and_then(Value::as_str) {
+            Some(r#type) if r#type == collection => (),
+            _ => return Err(ValidationError(format!("Record/$type must be {collection}"))),
+        }
+        let def = match self.get_def(collection) {
+            Some(def) if def.get("type").and_then(Value::as_str) == Some("record") => def,
+            Some(_) => return Err(ValidationError(format!("{collection} is not a record type"))),
+            None if validate == Some(true) => {
+                return Err(ValidationError(format!("Lexicon not found: {collection}")))
+            }
+            None => return Ok(Some(ValidationStatus::Unknown)),
+        };
+        if let Some(rkey) = rkey {
+            let key_is_valid = match def.get("key").and_then(Value::as_str) {
+                Some("tid") => formats::is_valid_tid(rkey),
+                Some(key) if key.starts_with("literal:") => Some(rkey) == key.strip_prefix("literal:"),
+                _ => formats::is_valid_record_key(rkey),
+            };
+            if !key_is_valid {
+                return Err(ValidationError(format!("Invalid record key for {collection}: {rkey}")));
+            }
+        }
+        Validator { lexicons: self }.validate(collection, "Record", def, record)?;
+        Ok(Some(ValidationStatus::Valid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DID: &str = "did:plc:ewvi7nxzyoun6zhxrhs64oiz";
+
+    fn post(text: &str) -> Value {
+        json!({
+            "$type": "app.bsky.feed.post",
+            "text": text,
+            "createdAt": "2024-11-06T12:00:00.000Z",
+        })
+    }
+
+    #[test]
+    fn validates_bundled_records() {
+        let status = LEXICONS.validate_record("app.bsky.feed.post", None, &mut post("hello"), None);
+        assert_eq!(status.unwrap(), Some(ValidationStatus::Valid));
+
+        let too_long = "🏕️".repeat(301);
+        assert!(LEXICONS
+            .validate_record("app.bsky.feed.post", None, &mut post(&too_long), None)
+            .is_err());
+
+        let mut missing_created_at = post("hello");
+        missing_created_at.as_object_mut().unwrap().remove("createdAt");
+        assert!(LEXICONS
+            .validate_record("app.bsky.feed.post", None, &mut missing_created_at, None)
+            .is_err());
+
+        let mut follow = json!({
+            "$type": "app.bsky.graph.follow",
+            "subject": "not a did",
+            "createdAt": "2024-11-06T12:00:00.000Z",
+        });
+        assert!(LEXICONS
+            .validate_record("app.bsky.graph.follow", None, &mut follow, None)
+            .is_err());
+    }
+
+    #[test]
+    fn validates_unions() {
+        let mut with_embed = post("look");
+        with_embed["embed"] = json!({
+            "$type": "app.bsky.embed.record",
+            "record": {
+                "uri": format!("at://{DID}/app.bsky.feed.post/3jzfcijpj2z2a"),
+                "cid": "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm",
+            },
+        });
+        assert!(LEXICONS
+            .validate_record("app.bsky.feed.post", None, &mut with_embed, None)
+            .is_ok());
+
+        with_embed["embed"]["record"]["uri"] = json!("https://campground.gg");
+        assert!(LEXICONS
+            .validate_record("app.bsky.feed.post", None, &mut with_embed, None)
+            .is_err());
+
+        // Open unions let unknown types through
+        with_embed["embed"] = json!({ "$type": "gg.campground.embed.unknown" });
+        assert!(LEXICONS
+            .validate_record("app.bsky.feed.post", None, &mut with_embed, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn fills_in_missing_types() {
+        let mut untyped = post("hello");
+        untyped.as_object_mut().unwrap().remove("$type");
+        assert_eq!(
+            LEXICONS
+                .validate_record("app.bsky.feed.post", None, &mut untyped, None)
+                .unwrap(),
+            Some(ValidationStatus::Valid)
+        );
+        assert_eq!(untyped, post("hello"));
+
+        let mut mistyped = post("hello");
+        assert!(LEXICONS
+            .validate_record("app.bsky.feed.like", None, &mut mistyped, None)
+            .is_err());
+        assert_eq!(mistyped["$type"], "app.bsky.feed.post");
+    }
+
+    #[test]
+    fn honours_the_validate_flag() {
+        let mut record = json!({ "$type": "gg.campground.unknown.thing" });
+        let collection = "gg.campground.unknown.thing";
+        assert_eq!(
+            LEXICONS.validate_record(collection, None, &mut record, None).unwrap(),
+            Some(ValidationStatus::Unknown)
+        );
+        assert!(LEXICONS
+            .validate_record(collection, None, &mut record, Some(true))
+            .is_err());
+        assert_eq!(
+            LEXICONS
+                .validate_record("app.bsky.feed.post", None, &mut post(&"a".repeat(5000)), Some(false))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn validates_campground_records() {
+        let mut status = json!({
+            "$type": "gg.campground.actor.status",
+            "statusType": "donotdisturb",
+            "activities": [{
+                "$type": "gg.campground.actor.defs#activityCustom",
+                "emoji": "🏕️",
+                "state": "Pitching a tent",
+            }],
+        });
+        assert_eq!(
+            LEXICONS
+                .validate_record("gg.campground.actor.status", Some("self"), &mut status, None)
+                .unwrap(),
+            Some(ValidationStatus::Valid)
+        );
+        assert!(LEXICONS
+            .validate_record("gg.campground.actor.status", Some("3jzfcijpj2z2a"), &mut status, None)
+            .is_err());
+
+        let mut away = status.clone();
+        away["statusType"] = json!("away");
+        assert!(LEXICONS
+            .validate_record("gg.campground.actor.status", Some("self"), &mut away, None)
+            .is_err());
+    }
+}
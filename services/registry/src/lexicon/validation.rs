@@ -0,0 +1,305 @@
+//! Walks a value alongside the lexicon definition it's meant to match.
+use crate::lexicon::formats::is_valid_format;
+use crate::lexicon::{Lexicons, ValidationError};
+use serde_json::{Map, Value};
+use unicode_segmentation::UnicodeSegmentation;
+
+type ValidationResult = Result<(), ValidationError>;
+
+fn invalid(path: &str, message: &str) -> ValidationResult {
+    Err(ValidationError(format!("{path} {message}")))
+}
+
+fn get_u64(def: &Value, key: &str) -> Option<u64> {
+    def.get(key).and_then(Value::as_u64)
+}
+
+/// Expands a ref relative to the lexicon document it appears in, e.g. `#image` in
+/// `app.bsky.embed.images` to `app.bsky.embed.images#image`, and `app.bsky.embed.images`
+/// to `app.bsky.embed.images#main`.
+pub fn expand_ref(lexicon_id: &str, r#ref: &str) -> String {
+    match r#ref.split_once('#') {
+        Some(("", name)) => format!("{lexicon_id}#{name}"),
+        Some(_) => r#ref.to_string(),
+        None => format!("{}#main", r#ref),
+    }
+}
+
+pub struct Validator<'a> {
+    pub lexicons: &'a Lexicons,
+}
+
+impl<'a> Validator<'a> {
+    /// `lexicon_id` is the document `def` came from, for resolving local refs.
+    pub fn validate(&self, lexicon_id: &str, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        match def.get("type").and_then(Value::as_str) {
+            Some("null") => match value {
+                Value::Null => Ok(()),
+                _ => invalid(path, "must be null"),
+            },
+            Some("boolean") => self.validate_boolean(path, def, value),
+            Some("integer") => self.validate_integer(path, def, value),
+            Some("string") => self.validate_string(path, def, value),
+            Some("bytes") => self.validate_bytes(path, def, value),
+            Some("cid-link") => match value.get("$link").and_then(Value::as_str) {
+                Some(link) if is_valid_format("cid", link) => Ok(()),
+                _ => invalid(path, "must be a CID link"),
+            },
+            Some("blob") => self.validate_blob(path, def, value),
+            Some("array") => self.validate_array(lexicon_id, path, def, value),
+            Some("object") => self.validate_object(lexicon_id, path, def, value),
+            Some("ref") => {
+                let r#ref = def.get("ref").and_then(Value::as_str).unwrap_or_default();
+                self.validate_ref(&expand_ref(lexicon_id, r#ref), path, value)
+            }
+            Some("union") => self.validate_union(lexicon_id, path, def, value),
+            Some("unknown") => match value {
+                Value::Object(_) => Ok(()),
+                _ => invalid(path, "must be an object"),
+            },
+            Some("record") => match def.get("record") {
+                Some(record) => self.validate_object(lexicon_id, path, record, value),
+                None => invalid(path, "has a record definition without a schema"),
+            },
+            Some(r#type) => invalid(path, &format!("has unsupported lexicon type `{}`", r#type)),
+            None => invalid(path, "has a lexicon definition without a type"),
+        }
+    }
+
+    fn validate_boolean(&self, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let value = match value.as_bool() {
+            Some(value) => value,
+            None => return invalid(path, "must be a boolean"),
+        };
+        match def.get("const").and_then(Value::as_bool) {
+            Some(expected) if expected != value => invalid(path, &format!("must be {expected}")),
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_integer(&self, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let value = match value.as_i64() {
+            Some(value) => value,
+            None => return invalid(path, "must be an integer"),
+        };
+        if let Some(expected) = def.get("const").and_then(Value::as_i64) {
+            if expected != value {
+                return invalid(path, &format!("must be {expected}"));
+            }
+        }
+        if let Some(allowed) = def.get("enum").and_then(Value::as_array) {
+            if !allowed.iter().any(|allowed| allowed.as_i64() == Some(value)) {
+                return invalid(path, "must be one of the allowed values");
+            }
+        }
+        if let Some(minimum) = def.get("minimum").and_then(Value::as_i64) {
+            if value < minimum {
+                return invalid(path, &format!("can not be less than {minimum}"));
+            }
+        }
+        if let Some(maximum) = def.get("maximum").and_then(Value::as_i64) {
+            if value > maximum {
+                return invalid(path, &format!("can not be greater than {maximum}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_string(&self, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let value = match value.as_str() {
+            Some(value) => value,
+            None => return invalid(path, "must be a string"),
+        };
+        if let Some(expected) = def.get("const").and_then(Value::as_str) {
+            if expected != value {
+                return invalid(path, &format!("must be {expected}"));
+            }
+        }
+        if let Some(allowed) = def.get("enum").and_then(Value::as_array) {
+            if !allowed.iter().any(|allowed| allowed.as_str() == Some(value)) {
+                return invalid(path, "must be one of the allowed values");
+            }
+        }
+        // Lengths are in UTF-8 bytes, as that's what ends up stored
+        if let Some(min_length) = get_u64(def, "minLength") {
+            if (value.len() as u64) < min_length {
+                return invalid(path, &format!("must not be shorter than {min_length} bytes"));
+            }
+        }
+        if let Some(max_length) = get_u64(def, "maxLength") {
+            if value.len() as u64 > max_length {
+                return invalid(path, &format!("must not be longer than {max_length} bytes"));
+            }
+        }
+        if def.get("minGraphemes").is_some() || def.get("maxGraphemes").is_some() {
+            let graphemes = value.graphemes(true).count() as u64;
+            if let Some(min_graphemes) = get_u64(def, "minGraphemes") {
+                if graphemes < min_graphemes {
+                    return invalid(path, &format!("must not be shorter than {min_graphemes} graphemes"));
+                }
+            }
+            if let Some(max_graphemes) = get_u64(def, "maxGraphemes") {
+                if graphemes > max_graphemes {
+                    return invalid(path, &format!("must not be longer than {max_graphemes} graphemes"));
+                }
+            }
+        }
+        match def.get("format").and_then(Value::as_str) {
+            Some(format) if !is_valid_format(format, value) => {
+                invalid(path, &format!("must be a valid {format}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_bytes(&self, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let bytes = match value.get("$bytes").and_then(Value::as_str) {
+            Some(bytes) => bytes,
+            None => return invalid(path, "must be bytes"),
+        };
+        // Unpadded base64, so four characters make three bytes
+        let len = (bytes.trim_end_matches('=').len() * 3 / 4) as u64;
+        if let Some(min_length) = get_u64(def, "minLength") {
+            if len < min_length {
+                return invalid(path, &format!("must not be smaller than {min_length} bytes"));
+            }
+        }
+        if let Some(max_length) = get_u64(def, "maxLength") {
+            if len > max_length {
+                return invalid(path, &format!("must not be larger than {max_length} bytes"));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_blob(&self, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let (mime_type, size) = match (
+            value.get("$type").and_then(Value::as_str),
+            value.get("ref").and_then(|r#ref| r#ref.get("$link")),
+            value.get("mimeType").and_then(Value::as_str),
+        ) {
+            (Some("blob"), Some(Value::String(_)), Some(mime_type)) => {
+                match value.get("size").and_then(Value::as_u64) {
+                    Some(size) => (mime_type, Some(size)),
+                    None => return invalid(path, "must have a size"),
+                }
+            }
+            // Legacy blobs are `{cid, mimeType}` and carry no size
+            (None, None, Some(mime_type)) if value.get("cid").is_some_and(Value::is_string) => {
+                (mime_type, None)
+            }
+            _ => return invalid(path, "must be a blob"),
+        };
+        if let Some(accept) = def.get("accept").and_then(Value::as_array) {
+            let accepted = accept.iter().filter_map(Value::as_str).any(|accept| {
+                accept == "*/*"
+                    || accept == mime_type
+                    || accept
+                        .strip_suffix("/*")
+                        .is_some_and(|prefix| mime_type.starts_with(&format!("{prefix}/")))
+            });
+            if !accepted {
+                return invalid(path, &format!("has a mime type that isn't accepted: {mime_type}"));
+            }
+        }
+        match (get_u64(def, "maxSize"), size) {
+            (Some(max_size), Some(size)) if size > max_size => {
+                invalid(path, &format!("must not be larger than {max_size} bytes"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_array(&self, lexicon_id: &str, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let items = match value.as_array() {
+            Some(items) => items,
+            None => return invalid(path, "must be an array"),
+        };
+        if let Some(min_length) = get_u64(def, "minLength") {
+            if (items.len() as u64) < min_length {
+                return invalid(path, &format!("must not have fewer than {min_length} elements"));
+            }
+        }
+        if let Some(max_length) = get_u64(def, "maxLength") {
+            if items.len() as u64 > max_length {
+                return invalid(path, &format!("must not have more than {max_length} elements"));
+            }
+        }
+        match def.get("items") {
+            Some(item_def) => items.iter().enumerate().try_for_each(|(i, item)| {
+                self.validate(lexicon_id, &format!("{path}/{i}"), item_def, item)
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_object(&self, lexicon_id: &str, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let object: &Map<String, Value> = match value.as_object() {
+            Some(object) => object,
+            None => return invalid(path, "must be an object"),
+        };
+        let nullable: Vec<&str> = def
+            .get("nullable")
+            .and_then(Value::as_array)
+            .map(|nullable| nullable.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        if let Some(required) = def.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str) {
+                match object.get(key) {
+                    None => return invalid(path, &format!("must have the property \"{key}\"")),
+                    Some(Value::Null) if !nullable.contains(&key) => {
+                        return invalid(path, &format!("must have the property \"{key}\""))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        // Properties the lexicon doesn't know about are allowed, so records can be extended
+        if let Some(properties) = def.get("properties").and_then(Value::as_object) {
+            for (key, property_def) in properties {
+                match object.get(key) {
+                    None => (),
+                    Some(Value::Null) if nullable.contains(&key.as_str()) => (),
+                    Some(property) => {
+                        self.validate(lexicon_id, &format!("{path}/{key}"), property_def, property)?
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_ref(&self, r#ref: &str, path: &str, value: &Value) -> ValidationResult {
+        let def = match self.lexicons.get_def(r#ref) {
+            Some(def) => def,
+            None => return invalid(path, &format!("references an unknown lexicon `{}`", r#ref)),
+        };
+        let lexicon_id = r#ref.split_once('#').map_or(r#ref, |(id, _)| id);
+        self.validate(lexicon_id, path, def, value)
+    }
+
+    fn validate_union(&self, lexicon_id: &str, path: &str, def: &Value, value: &Value) -> ValidationResult {
+        let r#type = match value.get("$type").and_then(Value::as_str) {
+            Some(r#type) => expand_ref(lexicon_id, r#type),
+            None => return invalid(path, "must be an object which includes the \"$type\" property"),
+        };
+        let refs: Vec<String> = def
+            .get("refs")
+            .and_then(Value::as_array)
+            .map(|refs| {
+                refs.iter()
+                    .filter_map(Value::as_str)
+                    .map(|r#ref| expand_ref(lexicon_id, r#ref))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if refs.contains(&r#type) {
+            self.validate_ref(&r#type, path, value)
+        } else if def.get("closed").and_then(Value::as_bool) == Some(true) {
+            invalid(path, &format!("$type must be one of {}", refs.join(", ")))
+        } else {
+            // Open unions accept types they don't know about, which can't be checked any further
+            Ok(())
+        }
+    }
+}
@@ -41,6 +41,20 @@ struct UpdateEmailTemplate<'a> {
     token: &'a str,
 }
 
+#[derive(Template)]
+#[template(path = "plc_operation.html")]
+struct PlcOperationTemplate<'a> {
+    identifier: &'a str,
+    token: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "sign_in_code.html")]
+struct SignInCodeTemplate<'a> {
+    identifier: &'a str,
+    token: &'a str,
+}
+
 pub struct MailOpts {
     pub to: String,
     pub subject: String,
@@ -163,14 +177,26 @@ pub async fn send_update_email(to: String, params: IdentifierAndTokenParams) ->
     .await
 }
 
-// pub async fn send_plc_operation(to: String, params: IdentifierAndTokenParams) -> Result<()> {
-//     let template = PLCUpdateTemplate {
-//         identifier: &params.identifier,
-//         token: &params.token,
-//     };
-//     send_template(MailOpts {
-//         to,
-//         subject: "PLC Update Operation Requested".to_string(),
-//     }, &template)
-//     .await
-// }
\ No newline at end of file
+pub async fn send_plc_operation(to: String, params: IdentifierAndTokenParams) -> Result<()> {
+    let template = PlcOperationTemplate {
+        identifier: &params.identifier,
+        token: &params.token,
+    };
+    send_template(MailOpts {
+        to,
+        subject: "PLC Update Operation Requested".to_string(),
+    }, &template)
+    .await
+}
+
+pub async fn send_sign_in_code(to: String, params: IdentifierAndTokenParams) -> Result<()> {
+    let template = SignInCodeTemplate {
+        identifier: &params.identifier,
+        token: &params.token,
+    };
+    send_template(MailOpts {
+        to,
+        subject: "Sign In Code".to_string(),
+    }, &template)
+    .await
+}
\ No newline at end of file
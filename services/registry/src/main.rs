@@ -42,7 +42,10 @@
 use std::env;
 use account_manager::AccountManager;
 use api::bsky_api_forwarder;
-use config::{BSKY_APP_VIEW_CONFIG, S3_CONFIG};
+use auth_verifier::AdminToken;
+use config::{BLOBSTORE_CONFIG, BSKY_APP_VIEW_CONFIG, IMAGE_SERVER_CONFIG, JANITOR_CONFIG};
+use janitor::Janitor;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use rocket::shield::{NoSniff, Shield};
 use rsky_identity::types::{DidCache, IdentityResolverOpts};
 use rsky_identity::IdResolver;
@@ -53,7 +56,7 @@ use rocket::http::Status;
 use rocket::response::status;
 use rocket::http::Header;
 use rocket::serde::json::Json;
-use rocket::{Request, Response};
+use rocket::{Request, Response, State};
 use diesel::prelude::*;
 use diesel::sql_types::Int4;
 use reqwest as _;
@@ -62,11 +65,11 @@ use rsky_pds::crawlers::Crawlers;
 use rsky_pds::SharedIdResolver;
 use crate::read_after_write::viewer::{LocalViewerCreator, LocalViewer, LocalViewerCreatorParams};
 use crate::sequencer::Sequencer;
+use crate::signer::{Signer, PDS_SIGNER, REPO_SIGNER, ROTATION_SIGNER};
+use crate::repository::blobstore::BlobStoreCreator;
 use atrium_api::client::AtpServiceClient;
 use atrium_xrpc_client::reqwest::{ReqwestClient, ReqwestClientBuilder};
 use crate::config::{IDENTITY_CONFIG, CORE_CONFIG};
-use event_emitter_rs::EventEmitter;
-use lazy_static::lazy_static;
 
 #[macro_use] extern crate rocket;
 
@@ -84,9 +87,16 @@ mod common;
 mod config;
 mod schema;
 mod handle;
+mod lexicon;
 mod xrpc;
 mod plc;
 mod api;
+mod oauth;
+mod rate_limiter;
+mod janitor;
+mod image_server;
+mod cli;
+mod signer;
 
 pub const INVALID_HANDLE: &'static str = "handle.invalid";
 pub static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
@@ -106,13 +116,6 @@ pub struct SharedATPAgent {
     pub app_view_agent: Option<RwLock<AtpServiceClient<ReqwestClient>>>,
 }
 
-
-// Use lazy_static! because the size of EventEmitter is not known at compile time
-lazy_static! {
-    // Export the emitter with `pub` keyword
-    pub static ref EVENT_EMITTER: RwLock<EventEmitter> = RwLock::new(EventEmitter::new());
-}
-
 struct CORS;
 
 #[get("/robots.txt")]
@@ -165,6 +168,12 @@ async fn health() -> Result<
     }
 }
 
+/// Prometheus metrics for this node.
+#[get("/metrics")]
+async fn prometheus_metrics(handle: &State<PrometheusHandle>, _auth: AdminToken) -> String {
+    handle.render()
+}
+
 #[catch(default)]
 async fn default_catcher(status: Status, _request: &Request<'_>) -> Json<rsky_pds::models::ErrorMessageResponse> {
     let internal_error = rsky_pds::models::ErrorMessageResponse {
@@ -217,10 +226,34 @@ impl Fairing for CORS {
         ));
         response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
         response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        response.set_header(Header::new(
+            "Access-Control-Expose-Headers",
+            "DPoP-Nonce, WWW-Authenticate",
+        ));
+    }
+}
+
+pub fn id_resolver() -> SharedIdResolver {
+    SharedIdResolver {
+        id_resolver: RwLock::new(IdResolver::new(IdentityResolverOpts {
+            timeout: None,
+            plc_url: Some(IDENTITY_CONFIG.plc_url.clone()),
+            did_cache: Some(DidCache::new(None, None)),
+            backup_nameservers: IDENTITY_CONFIG.handle_backup_name_servers.clone()
+        })),
     }
 }
 
 pub async fn init() -> Result<rocket::Rocket<rocket::Build>> {
+    // Load the server keys now, so a bad key file or signer config fails at startup
+    println!("Token signing key: {}", PDS_SIGNER.did_key());
+    println!("Rotation key: {}", ROTATION_SIGNER.did_key());
+    println!("Repo signing key: {}", REPO_SIGNER.did_key());
+    let encrypted = AccountManager::encrypt_plaintext_signing_keys().await?;
+    if encrypted > 0 {
+        println!("Encrypted {encrypted} signing keys stored before the keystore");
+    }
+
     let sequencer = SharedSequencer {
         sequencer: RwLock::new(Sequencer::new(
             Crawlers::new(CORE_CONFIG.hostname(), CORE_CONFIG.crawlers.clone()),
@@ -230,16 +263,15 @@ pub async fn init() -> Result<rocket::Rocket<rocket::Build>> {
     let mut background_sequencer = sequencer.sequencer.write().await.clone();
     tokio::spawn(async move { background_sequencer.start().await });
 
-    let aws_sdk_config = S3_CONFIG.to_sdk_config().await;
+    let blobstore = BlobStoreCreator::from_config(&BLOBSTORE_CONFIG).await?;
+    let metrics_handle = PrometheusBuilder::new().install_recorder()?;
 
-    let id_resolver = SharedIdResolver {
-        id_resolver: RwLock::new(IdResolver::new(IdentityResolverOpts {
-            timeout: None,
-            plc_url: Some(IDENTITY_CONFIG.plc_url.clone()),
-            did_cache: Some(DidCache::new(None, None)),
-            backup_nameservers: IDENTITY_CONFIG.handle_backup_name_servers.clone()
-        })),
-    };
+    if JANITOR_CONFIG.enabled() {
+        let janitor_sequencer = sequencer.sequencer.write().await.clone();
+        Janitor::new(blobstore.clone(), janitor_sequencer).start();
+    }
+
+    let id_resolver = id_resolver();
 
     let app_view_agent = match &*BSKY_APP_VIEW_CONFIG {
         None => SharedATPAgent {
@@ -273,37 +305,58 @@ pub async fn init() -> Result<rocket::Rocket<rocket::Build>> {
                 Some(ref bsky_app_view) => Some(bsky_app_view.did.clone()),
             },
             appview_cdn_url_pattern: match &*BSKY_APP_VIEW_CONFIG {
-                None => None,
                 Some(ref bsky_app_view) => bsky_app_view.cdn_url_pattern.clone(),
+                None => match IMAGE_SERVER_CONFIG.enabled() {
+                    true => Some(image_server::url_pattern(&CORE_CONFIG.hostname())),
+                    false => None,
+                },
+            },
+            appview_video_url_pattern: match &*BSKY_APP_VIEW_CONFIG {
+                None => None,
+                Some(ref bsky_app_view) => bsky_app_view.video_url_pattern.clone(),
             },
         })),
     };
 
     let shield = Shield::default().enable(NoSniff::Enable);
 
-    let rocket = rocket::build()
+    let mut rocket = rocket::build()
         .mount("/", routes![
             robots,
             health,
+            prometheus_metrics,
             bsky_api_forwarder,
             all_options
         ])
         .mount("/", api::routes())
         .mount("/.well-known", well_known::routes())
-        .register("/", catchers![default_catcher])
+        .mount("/oauth", oauth::routes())
+        .register("/", catchers![default_catcher, rate_limiter::rate_limit_exceeded])
         .attach(shield)
         .attach(CORS)
+        .attach(rate_limiter::RateLimitHeaders)
         .manage(sequencer)
+        .manage(rate_limiter::SharedRateLimiter::default())
         .manage(local_viewer)
-        .manage(aws_sdk_config)
+        .manage(blobstore)
+        .manage(metrics_handle)
         .manage(id_resolver)
         .manage(app_view_agent);
+    if IMAGE_SERVER_CONFIG.enabled() {
+        rocket = rocket.mount("/", image_server::routes());
+    }
 
     Ok(rocket)
 }
 
 #[rocket::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some(cli::SUBCOMMAND) {
+        let code = cli::run(args[1..].to_vec()).await?;
+        std::process::exit(code);
+    }
+
     let rocket = init().await?;
 
     rocket.launch().await?;
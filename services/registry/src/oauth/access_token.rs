@@ -0,0 +1,124 @@
+use crate::account_manager::helpers::auth::sign_jwt;
+use crate::config::SERVICE_CONFIG;
+use crate::database::models::OAuthToken;
+use crate::oauth::dpop::DpopRequest;
+use crate::oauth::{
+    issuer, store, ACCESS_TOKEN_EXPIRES_IN, SCOPE_ATPROTO, SCOPE_TRANSITION_CHAT,
+    SCOPE_TRANSITION_GENERIC,
+};
+use crate::signer::{Signer, PDS_SIGNER};
+use anyhow::{bail, Result};
+use jwt_simple::prelude::*;
+use rocket::Request;
+use rsky_pds::auth_verifier::{AuthScope, JwtPayload, ValidatedBearer};
+
+pub const DPOP: &str = "DPoP ";
+
+/// Confirmation claim (RFC 9449 section 6) binding the token to the client's DPoP key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthClaims {
+    pub scope: String,
+    pub client_id: String,
+    pub cnf: Confirmation,
+}
+
+/// The session scope an OAuth scope string grants. OAuth sessions are never given full access,
+/// so account management stays behind password sessions.
+pub fn auth_scope_for(scope: &str) -> Result<AuthScope> {
+    let scopes = scope.split(' ').collect::<Vec<&str>>();
+    if !scopes.contains(&SCOPE_ATPROTO) || !scopes.contains(&SCOPE_TRANSITION_GENERIC) {
+        bail!("Bad token scope")
+    }
+    if scopes.contains(&SCOPE_TRANSITION_CHAT) {
+        Ok(AuthScope::AppPassPrivileged)
+    } else {
+        Ok(AuthScope::AppPass)
+    }
+}
+
+/// Access tokens carry the token row's id as their `jti`, so deleting the row revokes them.
+pub async fn create_access_token(token: &OAuthToken) -> Result<String> {
+    let claims = Claims::with_custom_claims(
+        OAuthClaims {
+            scope: token.scope.clone(),
+            client_id: token.client_id.clone(),
+            cnf: Confirmation {
+                jkt: token.dpop_jkt.clone(),
+            },
+        },
+        Duration::from_secs(ACCESS_TOKEN_EXPIRES_IN),
+    )
+    .with_audience(SERVICE_CONFIG.did.clone())
+    .with_subject(token.did.clone())
+    .with_issuer(issuer())
+    .with_jwt_id(token.id.clone());
+    sign_jwt(&**PDS_SIGNER, &claims).await
+}
+
+pub fn decode_access_token(
+    jwt: &str,
+    verify_options: Option<VerificationOptions>,
+) -> Result<JWTClaims<OAuthClaims>> {
+    let public_key = ES256kPublicKey::from_bytes(&PDS_SIGNER.public_key().serialize())?;
+    Ok(public_key.verify_token::<OAuthClaims>(jwt, verify_options)?)
+}
+
+pub fn dpop_token_from_req(request: &Request) -> Option<String> {
+    match request.headers().get_one("authorization") {
+        Some(header) if header.starts_with(DPOP) => Some(header[DPOP.len()..].to_string()),
+        _ => None,
+    }
+}
+
+/// Resource server side of OAuth: checks a `DPoP` authorization header and its proof.
+pub async fn validate_dpop_token<'r>(
+    request: &'r Request<'_>,
+    scopes: Vec<AuthScope>,
+) -> Result<ValidatedBearer> {
+    let token = match dpop_token_from_req(request) {
+        None => bail!("AuthMissing"),
+        Some(token) => token,
+    };
+    let mut options = VerificationOptions::default();
+    options.allowed_audiences = Some(HashSet::from_strings(&[SERVICE_CONFIG.did.clone()]));
+    options.allowed_issuers = Some(HashSet::from_strings(&[issuer()]));
+    let claims = decode_access_token(&token, Some(options))?;
+
+    let jkt = DpopRequest::from_req(request)
+        .verify(Some(token.as_str()), false)
+        .await?;
+    if jkt != claims.custom.cnf.jkt {
+        bail!("DPoP key does not match token")
+    }
+    let (sub, jti) = match (&claims.subject, &claims.jwt_id) {
+        (Some(sub), Some(jti)) => (sub.clone(), jti.clone()),
+        _ => bail!("Malformed token"),
+    };
+    match store::get_token(&jti).await? {
+        Some(found) if found.did == sub => (),
+        _ => bail!("Token has been revoked"),
+    }
+    let scope = auth_scope_for(&claims.custom.scope)?;
+    if scopes.len() > 0 && !scopes.contains(&scope) {
+        bail!("Bad token scope")
+    }
+    Ok(ValidatedBearer {
+        did: sub,
+        scope: scope.clone(),
+        audience: Some(SERVICE_CONFIG.did.clone()),
+        token,
+        payload: JwtPayload {
+            scope,
+            sub: claims.subject,
+            aud: claims.audiences,
+            exp: claims.expires_at,
+            iat: claims.issued_at,
+            jti: claims.jwt_id,
+        },
+    })
+}
@@ -0,0 +1,246 @@
+use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
+use crate::account_manager::AccountManager;
+use crate::api::com::atproto::server::create_session::assert_auth_factor;
+use crate::config::CORE_CONFIG;
+use crate::database::models::OAuthRequest;
+use crate::oauth::client::get_client_metadata;
+use crate::oauth::par::{AuthorizationParameters, REQUEST_URI_PREFIX};
+use crate::oauth::{issuer, oauth_error_response, random_id, store, OAuthError};
+use crate::rate_limiter::RateLimit;
+use anyhow::{bail, Result};
+use askama::Template;
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::response::content::RawHtml;
+use rocket::response::{status, Redirect};
+use rocket::serde::json::Json;
+use rocket::Either;
+use url::{form_urlencoded, Url};
+
+/// Holds the sign-in form's one-time token too, so the form only works in the browser it was
+/// shown in.
+const CSRF_COOKIE: &str = "oauth_csrf";
+
+#[derive(Template)]
+#[template(path = "oauth_authorize.html")]
+struct AuthorizeTemplate<'a> {
+    client_name: &'a str,
+    hostname: &'a str,
+    scope: &'a str,
+    request_uri: &'a str,
+    csrf_token: &'a str,
+    identifier: &'a str,
+    error: Option<&'a str>,
+    auth_factor: bool,
+}
+
+#[derive(Debug, FromForm)]
+pub struct AuthorizeForm {
+    pub request_uri: String,
+    pub csrf_token: String,
+    pub identifier: String,
+    pub password: String,
+    pub auth_factor_token: Option<String>,
+    pub decision: String,
+}
+
+/// Looks up a pending request that hasn't been consented to yet.
+async fn get_pending_request(request_uri: &String) -> Result<(OAuthRequest, AuthorizationParameters)> {
+    let id = match request_uri.strip_prefix(REQUEST_URI_PREFIX) {
+        None => bail!("invalid_request: Invalid request_uri"),
+        Some(id) => id.to_string(),
+    };
+    let request = match store::get_request(&id).await? {
+        Some(request) if request.code.is_none() => request,
+        _ => bail!("invalid_request: Unknown or expired request_uri"),
+    };
+    let parameters = serde_json::from_str(&request.parameters)?;
+    Ok((request, parameters))
+}
+
+/// Shows the sign-in form with a new one-time token, which only the next submission can use.
+async fn render(
+    cookies: &CookieJar<'_>,
+    request: &OAuthRequest,
+    parameters: &AuthorizationParameters,
+    identifier: &str,
+    error: Option<&str>,
+    auth_factor: bool,
+) -> Result<RawHtml<String>> {
+    let metadata = get_client_metadata(&request.client_id).await?;
+    let csrf_token = random_id("csrf");
+    store::set_request_csrf_token(&request.id, &csrf_token).await?;
+    cookies.add(
+        Cookie::build((CSRF_COOKIE, csrf_token.clone()))
+            .path("/oauth")
+            .http_only(true)
+            .secure(issuer().starts_with("https://"))
+            .same_site(SameSite::Lax),
+    );
+    let template = AuthorizeTemplate {
+        client_name: &metadata.display_name(),
+        hostname: &CORE_CONFIG.hostname(),
+        scope: &parameters.scope,
+        request_uri: &format!("{REQUEST_URI_PREFIX}{}", request.id),
+        csrf_token: &csrf_token,
+        identifier,
+        error,
+        auth_factor,
+    };
+    Ok(RawHtml(template.render()?))
+}
+
+/// Builds the redirect back to the client, in the query or fragment as it asked.
+fn redirect_to_client(
+    parameters: &AuthorizationParameters,
+    mut params: Vec<(&str, String)>,
+) -> Result<Redirect> {
+    if let Some(state) = &parameters.state {
+        params.push(("state", state.clone()));
+    }
+    params.push(("iss", issuer()));
+    let mut url = Url::parse(&parameters.redirect_uri)?;
+    if parameters.response_mode == "fragment" {
+        let fragment = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        url.set_fragment(Some(&fragment));
+    } else {
+        url.query_pairs_mut().extend_pairs(params);
+    }
+    Ok(Redirect::to(url.to_string()))
+}
+
+async fn login(identifier: &String, password: &String) -> Result<Option<ActorAccount>> {
+    let identifier = identifier.to_lowercase();
+    let availability_flags = Some(AvailabilityFlags {
+        include_deactivated: Some(true),
+        include_taken_down: Some(true),
+    });
+    let user = match identifier.contains("@") {
+        true => AccountManager::get_account_by_email(&identifier, availability_flags).await?,
+        false => AccountManager::get_account(&identifier, availability_flags).await?,
+    };
+    match user {
+        // App passwords can't be used to grant OAuth sessions
+        Some(user) if AccountManager::verify_account_password(&user.did, password).await? => {
+            Ok(Some(user))
+        }
+        _ => Ok(None),
+    }
+}
+
+async fn inner_authorize(
+    cookies: &CookieJar<'_>,
+    client_id: String,
+    request_uri: String,
+) -> Result<RawHtml<String>> {
+    let (request, parameters) = get_pending_request(&request_uri).await?;
+    if request.client_id != client_id {
+        bail!("invalid_request: client_id does not match request")
+    }
+    let login_hint = parameters.login_hint.clone().unwrap_or_default();
+    render(cookies, &request, &parameters, &login_hint, None, false).await
+}
+
+async fn inner_authorize_decision(
+    cookies: &CookieJar<'_>,
+    body: AuthorizeForm,
+) -> Result<Either<Redirect, RawHtml<String>>> {
+    let (request, parameters) = get_pending_request(&body.request_uri).await?;
+    let cookie = cookies.get(CSRF_COOKIE).map(|cookie| cookie.value());
+    if cookie != Some(body.csrf_token.as_str())
+        || !store::consume_request_csrf_token(&request.id, &body.csrf_token).await?
+    {
+        bail!("invalid_request: Sign in form has expired, try signing in again")
+    }
+    if body.decision != "accept" {
+        store::delete_request(&request.id).await?;
+        let redirect = redirect_to_client(
+            &parameters,
+            vec![
+                ("error", "access_denied".to_string()),
+                ("error_description", "Access denied".to_string()),
+            ],
+        )?;
+        return Ok(Either::Left(redirect));
+    }
+
+    let user = match login(&body.identifier, &body.password).await? {
+        Some(user) => user,
+        None => {
+            let page = render(
+                cookies,
+                &request,
+                &parameters,
+                &body.identifier,
+                Some("Invalid identifier or password"),
+                body.auth_factor_token.is_some(),
+            )
+            .await?;
+            return Ok(Either::Right(page));
+        }
+    };
+    if user.takedown_ref.is_some() {
+        let page = render(
+            cookies,
+            &request,
+            &parameters,
+            &body.identifier,
+            Some("Account has been taken down"),
+            false,
+        )
+        .await?;
+        return Ok(Either::Right(page));
+    }
+    // Same second factor as createSession, asked for on the page instead of by the client
+    if let Err(error) = assert_auth_factor(&user, body.auth_factor_token).await {
+        let message = error.to_string();
+        let message = match message.split_once(": ") {
+            Some(("AuthFactorTokenRequired" | "InvalidToken", message)) => message,
+            _ => return Err(error),
+        };
+        let page = render(
+            cookies,
+            &request,
+            &parameters,
+            &body.identifier,
+            Some(message),
+            true,
+        )
+        .await?;
+        return Ok(Either::Right(page));
+    }
+
+    let code = random_id("cod");
+    store::set_request_code(&request.id, &user.did, &code).await?;
+    let redirect = redirect_to_client(&parameters, vec![("code", code)])?;
+    Ok(Either::Left(redirect))
+}
+
+/// Sign-in and consent page for a pushed authorization request.
+#[rocket::get("/authorize?<client_id>&<request_uri>")]
+pub async fn authorize(
+    cookies: &CookieJar<'_>,
+    client_id: String,
+    request_uri: String,
+) -> Result<RawHtml<String>, status::Custom<Json<OAuthError>>> {
+    match inner_authorize(cookies, client_id, request_uri).await {
+        Ok(res) => Ok(res),
+        Err(error) => Err(oauth_error_response(error)),
+    }
+}
+
+/// Submission of the sign-in and consent page. Redirects back to the client with a code, or
+/// re-renders the page if the credentials were wrong.
+#[rocket::post("/authorize", data = "<body>")]
+pub async fn authorize_decision(
+    cookies: &CookieJar<'_>,
+    body: Form<AuthorizeForm>,
+    _rate_limit: RateLimit,
+) -> Result<Either<Redirect, RawHtml<String>>, status::Custom<Json<OAuthError>>> {
+    match inner_authorize_decision(cookies, body.into_inner()).await {
+        Ok(res) => Ok(res),
+        Err(error) => Err(oauth_error_response(error)),
+    }
+}
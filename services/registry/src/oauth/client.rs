@@ -0,0 +1,343 @@
+//! OAuth clients aren't registered ahead of time: the `client_id` is the https URL of a JSON
+//! client metadata document, fetched whenever the client talks to the authorization server.
+use crate::oauth::dpop::{now_secs, Jwk, Jws};
+use crate::oauth::{issuer, store, SCOPE_ATPROTO};
+use crate::APP_USER_AGENT;
+use anyhow::{bail, Result};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use url::{Host, Url};
+
+pub const AUTH_METHOD_NONE: &str = "none";
+pub const AUTH_METHOD_PRIVATE_KEY_JWT: &str = "private_key_jwt";
+pub const CLIENT_ASSERTION_TYPE_JWT_BEARER: &str =
+    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// Metadata documents and JWKS are small; anything bigger isn't one.
+const MAX_DOCUMENT_SIZE: usize = 64 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fetches the documents clients point us at. Anyone can make us fetch a URL by starting an
+/// authorization, so only https is followed, never redirects, and never to hosts on a private,
+/// loopback or link-local address.
+static FETCHER: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .timeout(FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .https_only(true)
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .expect("Failed to build OAuth client fetcher")
+});
+
+/// Resolves hostnames, refusing any that have a non-public address among their records.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .collect::<Vec<SocketAddr>>();
+            if addrs.is_empty() || addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+                return Err(
+                    format!("{} doesn't resolve to a public address", name.as_str()).into(),
+                );
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local, fc00::/7, and link-local, fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Hostnames are checked as they're resolved, but IP addresses in the URL never are.
+fn assert_fetchable(url: &str) -> Result<Url> {
+    let url = Url::parse(url)?;
+    if url.scheme() != "https" {
+        bail!("{url} isn't an https URL")
+    }
+    let ip = match url.host() {
+        None => bail!("{url} has no host"),
+        Some(Host::Domain(_)) => return Ok(url),
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+    };
+    if !is_public_ip(ip) {
+        bail!("{url} isn't a public address")
+    }
+    Ok(url)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientMetadata {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub client_uri: Option<String>,
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub grant_types: Vec<String>,
+    #[serde(default)]
+    pub response_types: Vec<String>,
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub dpop_bound_access_tokens: bool,
+    pub token_endpoint_auth_method: Option<String>,
+    pub token_endpoint_auth_signing_alg: Option<String>,
+    pub jwks: Option<Jwks>,
+    pub jwks_uri: Option<String>,
+}
+
+impl ClientMetadata {
+    pub fn auth_method(&self) -> &str {
+        self.token_endpoint_auth_method
+            .as_deref()
+            .unwrap_or(AUTH_METHOD_NONE)
+    }
+
+    pub fn is_confidential(&self) -> bool {
+        self.auth_method() == AUTH_METHOD_PRIVATE_KEY_JWT
+    }
+
+    pub fn display_name(&self) -> String {
+        self.client_name.clone().unwrap_or(self.client_id.clone())
+    }
+
+    /// Every scope requested must be one the client declared for itself, and `atproto` is
+    /// always required.
+    pub fn assert_scope_allowed(&self, scope: &str) -> Result<()> {
+        let declared = self.scope.as_deref().unwrap_or_default();
+        let declared = declared.split(' ').collect::<Vec<&str>>();
+        let requested = scope.split(' ').collect::<Vec<&str>>();
+        if !requested.contains(&SCOPE_ATPROTO) {
+            bail!("invalid_scope: The atproto scope is required")
+        }
+        if let Some(scope) = requested.iter().find(|scope| !declared.contains(scope)) {
+            bail!("invalid_scope: Scope {scope} was not declared by the client")
+        }
+        Ok(())
+    }
+
+    async fn get_jwks(&self) -> Result<Jwks> {
+        match (&self.jwks, &self.jwks_uri) {
+            (Some(jwks), _) => Ok(jwks.clone()),
+            (None, Some(jwks_uri)) => match fetch_json(jwks_uri).await {
+                Ok(jwks) => Ok(jwks),
+                Err(error) => bail!("invalid_client: Unable to fetch client jwks: {error}"),
+            },
+            (None, None) => bail!("invalid_client: Client has no jwks"),
+        }
+    }
+}
+
+/// The client authentication parameters that may accompany PAR and token requests.
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientAssertionClaims {
+    iss: String,
+    sub: String,
+    aud: Value,
+    exp: i64,
+    jti: String,
+}
+
+async fn fetch_json<T: DeserializeOwned>(url: &String) -> Result<T> {
+    let url = assert_fetchable(url)?;
+    let mut res = FETCHER.get(url.clone()).send().await?.error_for_status()?;
+    if res
+        .content_length()
+        .is_some_and(|length| length > MAX_DOCUMENT_SIZE as u64)
+    {
+        bail!("{url} is too large")
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_DOCUMENT_SIZE {
+            bail!("{url} is too large")
+        }
+    }
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Fetches and validates the metadata document for `client_id`.
+pub async fn get_client_metadata(client_id: &String) -> Result<ClientMetadata> {
+    let url = match Url::parse(client_id) {
+        Ok(url) if url.scheme() == "https" => url,
+        _ => bail!("invalid_client: client_id must be an https URL"),
+    };
+    let metadata: ClientMetadata = match fetch_json(&url.to_string()).await {
+        Ok(metadata) => metadata,
+        Err(error) => bail!("invalid_client: Unable to fetch client metadata: {error}"),
+    };
+    if &metadata.client_id != client_id {
+        bail!("invalid_client: client_id does not match client metadata")
+    }
+    if !metadata.dpop_bound_access_tokens {
+        bail!("invalid_client: Client must use dpop_bound_access_tokens")
+    }
+    if !metadata
+        .grant_types
+        .contains(&"authorization_code".to_string())
+    {
+        bail!("invalid_client: Client must support the authorization_code grant")
+    }
+    if !metadata.response_types.contains(&"code".to_string()) {
+        bail!("invalid_client: Client must support the code response type")
+    }
+    if metadata.redirect_uris.is_empty() {
+        bail!("invalid_client: Client has no redirect_uris")
+    }
+    if let Some(jwks_uri) = &metadata.jwks_uri {
+        if let Err(error) = assert_fetchable(jwks_uri) {
+            bail!("invalid_client: Invalid jwks_uri: {error}")
+        }
+    }
+    match metadata.auth_method() {
+        AUTH_METHOD_NONE | AUTH_METHOD_PRIVATE_KEY_JWT => (),
+        method => bail!("invalid_client: Unsupported token_endpoint_auth_method {method}"),
+    }
+    Ok(metadata)
+}
+
+/// Authenticates the client by the method its metadata declares and returns that method.
+pub async fn authenticate_client(
+    metadata: &ClientMetadata,
+    credentials: &ClientCredentials,
+) -> Result<String> {
+    if !metadata.is_confidential() {
+        if credentials.client_assertion.is_some() {
+            bail!("invalid_client: Public clients must not send a client assertion")
+        }
+        return Ok(AUTH_METHOD_NONE.to_string());
+    }
+
+    let assertion = match (&credentials.client_assertion_type, &credentials.client_assertion) {
+        (Some(assertion_type), Some(assertion))
+            if assertion_type == CLIENT_ASSERTION_TYPE_JWT_BEARER =>
+        {
+            assertion
+        }
+        _ => bail!("invalid_client: Client assertion required"),
+    };
+    let jws = match Jws::parse(assertion) {
+        Ok(jws) => jws,
+        Err(error) => bail!("invalid_client: {error}"),
+    };
+    if let Some(alg) = &metadata.token_endpoint_auth_signing_alg {
+        if alg != &jws.header.alg {
+            bail!("invalid_client: Unexpected client assertion algorithm")
+        }
+    }
+    let jwks = metadata.get_jwks().await?;
+    let key = match jwks
+        .keys
+        .iter()
+        .find(|key| key.kid.is_some() && key.kid == jws.header.kid)
+    {
+        None => bail!("invalid_client: Client assertion key not found"),
+        Some(key) => key,
+    };
+    if let Err(error) = jws.verify(key) {
+        bail!("invalid_client: Invalid client assertion signature: {error}")
+    }
+    let claims: ClientAssertionClaims = match serde_json::from_slice(&jws.payload) {
+        Ok(claims) => claims,
+        Err(error) => bail!("invalid_client: {error}"),
+    };
+    if claims.iss != metadata.client_id || claims.sub != metadata.client_id {
+        bail!("invalid_client: Client assertion issuer mismatch")
+    }
+    let issuer = Value::String(issuer());
+    let audience_ok = match &claims.aud {
+        Value::Array(audiences) => audiences.contains(&issuer),
+        aud => aud == &issuer,
+    };
+    if !audience_ok {
+        bail!("invalid_client: Client assertion audience mismatch")
+    }
+    if claims.exp <= now_secs() as i64 {
+        bail!("invalid_client: Client assertion expired")
+    }
+    if !store::register_jti(&claims.jti, claims.exp).await? {
+        bail!("invalid_client: Client assertion replayed")
+    }
+    Ok(AUTH_METHOD_PRIVATE_KEY_JWT.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn only_fetches_public_https_urls() {
+        assert!(assert_fetchable("https://app.example.com/client-metadata.json").is_ok());
+        assert!(assert_fetchable("https://93.184.215.14/jwks.json").is_ok());
+        assert!(assert_fetchable("http://app.example.com/client-metadata.json").is_err());
+        for url in [
+            "https://127.0.0.1/",
+            "https://10.1.2.3/",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.64.0.1/",
+            "https://[::1]/",
+            "https://[fd00::1]/",
+            "https://[fe80::1]/",
+            "https://[::ffff:192.168.0.1]/",
+        ] {
+            assert!(assert_fetchable(url).is_err(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_hosts_resolving_to_loopback() {
+        let resolved = PublicResolver
+            .resolve(Name::from_str("localhost").unwrap())
+            .await;
+        assert!(resolved.is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! DPoP (RFC 9449) proof verification. Proofs are compact JWS carrying their own public key, which
+//! jwt-simple can't express, so the header and signature are checked by hand here.
+use crate::config::{CORE_CONFIG, SECRET_CONFIG};
+use crate::oauth::store;
+use anyhow::{bail, Result};
+use p256::ecdsa::signature::Verifier;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use secp256k1::{ecdsa, Message, PublicKey, Secp256k1};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::time::SystemTime;
+
+/// Seconds each server nonce is handed out for. The previous window's nonce is still accepted.
+const NONCE_WINDOW: u64 = 3 * 60;
+/// How far a proof's `iat` may be in the past.
+const MAX_PROOF_AGE: i64 = 5 * 60;
+/// How far a proof's `iat` may be in the future, to allow for clock skew.
+const MAX_CLOCK_SKEW: i64 = 60;
+
+/// Public EC key in JWK form.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub y: String,
+    pub kid: Option<String>,
+    pub alg: Option<String>,
+}
+
+impl Jwk {
+    /// RFC 7638 thumbprint: the required members in lexicographic order, without whitespace.
+    pub fn thumbprint(&self) -> String {
+        let canonical = format!(
+            r#"{{"crv":"{}","kty":"{}","x":"{}","y":"{}"}}"#,
+            self.crv, self.kty, self.x, self.y
+        );
+        base64_url::encode(&Sha256::digest(canonical.as_bytes())).replace("=", "")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JwsHeader {
+    pub typ: Option<String>,
+    pub alg: String,
+    pub kid: Option<String>,
+    pub jwk: Option<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DpopPayload {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: i64,
+    nonce: Option<String>,
+    ath: Option<String>,
+}
+
+/// A compact JWS split into its parts, with the header decoded.
+#[derive(Debug)]
+pub struct Jws {
+    pub header: JwsHeader,
+    pub payload: Vec<u8>,
+    signing_input: String,
+    signature: Vec<u8>,
+}
+
+impl Jws {
+    pub fn parse(token: &str) -> Result<Self> {
+        let parts = token.split('.').collect::<Vec<&str>>();
+        let (header, payload, signature) = match parts[..] {
+            [header, payload, signature] => (header, payload, signature),
+            _ => bail!("Malformed JWT"),
+        };
+        Ok(Jws {
+            header: serde_json::from_slice(&base64_url::decode(header)?)?,
+            payload: base64_url::decode(payload)?,
+            signing_input: format!("{header}.{payload}"),
+            signature: base64_url::decode(signature)?,
+        })
+    }
+
+    /// Checks the signature against `key`, using the algorithm named in the header.
+    pub fn verify(&self, key: &Jwk) -> Result<()> {
+        if key.kty != "EC" {
+            bail!("Unsupported key type {}", key.kty)
+        }
+        let mut sec1 = vec![0x04];
+        sec1.extend(base64_url::decode(&key.x)?);
+        sec1.extend(base64_url::decode(&key.y)?);
+        match (self.header.alg.as_str(), key.crv.as_str()) {
+            ("ES256", "P-256") => {
+                let public_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&sec1)?;
+                let signature = p256::ecdsa::Signature::from_slice(&self.signature)?;
+                public_key.verify(self.signing_input.as_bytes(), &signature)?;
+            }
+            ("ES256K", "secp256k1") => {
+                let public_key = PublicKey::from_slice(&sec1)?;
+                let mut signature = ecdsa::Signature::from_compact(&self.signature)?;
+                signature.normalize_s();
+                let hash = Sha256::digest(self.signing_input.as_bytes());
+                let message = Message::from_digest_slice(hash.as_ref())?;
+                Secp256k1::verification_only().verify_ecdsa(&message, &signature, &public_key)?;
+            }
+            (alg, crv) => bail!("Unsupported algorithm {alg} for curve {crv}"),
+        }
+        Ok(())
+    }
+}
+
+/// The parts of a request a DPoP proof has to be bound to.
+#[derive(Debug, Clone)]
+pub struct DpopRequest {
+    pub proof: Option<String>,
+    pub htm: String,
+    pub htu: String,
+}
+
+impl DpopRequest {
+    pub fn from_req(req: &Request<'_>) -> Self {
+        DpopRequest {
+            proof: req.headers().get_one("DPoP").map(|proof| proof.to_string()),
+            htm: req.method().as_str().to_string(),
+            htu: format!("{}{}", CORE_CONFIG.public_url(), req.uri().path()),
+        }
+    }
+
+    /// Verifies the proof and returns the thumbprint of the key it was signed with. The
+    /// authorization server insists on a fresh server nonce; resource requests must also prove
+    /// possession of `access_token` via the `ath` claim.
+    pub async fn verify(&self, access_token: Option<&str>, require_nonce: bool) -> Result<String> {
+        let proof = match &self.proof {
+            None => bail!("invalid_dpop_proof: DPoP proof required"),
+            Some(proof) => proof,
+        };
+        let jws = match Jws::parse(proof) {
+            Ok(jws) => jws,
+            Err(error) => bail!("invalid_dpop_proof: {error}"),
+        };
+        if jws.header.typ.as_deref() != Some("dpop+jwt") {
+            bail!("invalid_dpop_proof: Invalid DPoP proof type")
+        }
+        let jwk = match &jws.header.jwk {
+            None => bail!("invalid_dpop_proof: DPoP proof is missing its key"),
+            Some(jwk) => jwk,
+        };
+        if let Err(error) = jws.verify(jwk) {
+            bail!("invalid_dpop_proof: Invalid DPoP proof signature: {error}")
+        }
+        let payload: DpopPayload = match serde_json::from_slice(&jws.payload) {
+            Ok(payload) => payload,
+            Err(error) => bail!("invalid_dpop_proof: {error}"),
+        };
+
+        if payload.htm != self.htm {
+            bail!("invalid_dpop_proof: DPoP htm mismatch")
+        }
+        // htu is compared without query and fragment
+        let htu = payload.htu.split(['?', '#']).next().unwrap_or_default();
+        if htu != self.htu {
+            bail!("invalid_dpop_proof: DPoP htu mismatch")
+        }
+        let now = now_secs() as i64;
+        if payload.iat > now + MAX_CLOCK_SKEW || payload.iat < now - MAX_PROOF_AGE {
+            bail!("invalid_dpop_proof: DPoP proof is not fresh")
+        }
+        match &payload.nonce {
+            None if require_nonce => bail!("use_dpop_nonce: Authorization server requires nonce in DPoP proof"),
+            Some(nonce) if !is_valid_nonce(nonce) => bail!("use_dpop_nonce: DPoP nonce mismatch"),
+            _ => (),
+        }
+        if let Some(access_token) = access_token {
+            let ath = base64_url::encode(&Sha256::digest(access_token.as_bytes())).replace("=", "");
+            if payload.ath.as_deref() != Some(ath.as_str()) {
+                bail!("invalid_dpop_proof: DPoP ath mismatch")
+            }
+        }
+        if !store::register_jti(&payload.jti, payload.iat + MAX_PROOF_AGE).await? {
+            bail!("invalid_dpop_proof: DPoP proof replayed")
+        }
+        Ok(jwk.thumbprint())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DpopRequest {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(DpopRequest::from_req(req))
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("timestamp in secs since UNIX epoch")
+        .as_secs()
+}
+
+/// Nonces are derived from a server secret and the current window rather than stored. The
+/// keystore key is used since the JWT signing key may not be held in process.
+fn nonce_for_window(window: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"dpop-nonce");
+    hasher.update(SECRET_CONFIG.keystore_key.as_bytes());
+    hasher.update(window.to_be_bytes());
+    base64_url::encode(&hasher.finalize()).replace("=", "")
+}
+
+pub fn current_nonce() -> String {
+    nonce_for_window(now_secs() / NONCE_WINDOW)
+}
+
+pub fn is_valid_nonce(nonce: &str) -> bool {
+    let window = now_secs() / NONCE_WINDOW;
+    nonce == nonce_for_window(window) || nonce == nonce_for_window(window.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_rfc7638_thumbprint() {
+        // RFC 7638 only gives an RSA example, so this is the P-256 key from RFC 7515 appendix A.3
+        let jwk = Jwk {
+            kty: "EC".to_string(),
+            crv: "P-256".to_string(),
+            x: "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU".to_string(),
+            y: "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0".to_string(),
+            kid: None,
+            alg: None,
+        };
+        assert_eq!(jwk.thumbprint(), "oKIywvGUpTVTyxMQ3bwIIeQUudfr_CkLMjCE19ECD-U");
+    }
+}
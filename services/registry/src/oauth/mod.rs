@@ -0,0 +1,161 @@
+//! atproto OAuth authorization server. Clients are identified by the URL of their client metadata
+//! document, must push their authorization requests (PAR), use PKCE and bind every token to a
+//! DPoP key. Access tokens are accepted wherever the `AccessStandard` family of guards is used.
+use crate::config::CORE_CONFIG;
+use anyhow::Error;
+use rocket::http::{Header, Status};
+use rocket::response::status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub mod access_token;
+pub mod authorize;
+pub mod client;
+pub mod dpop;
+pub mod par;
+pub mod revoke;
+pub mod store;
+pub mod token;
+
+/// Seconds a pushed authorization request may wait for the user to consent.
+pub const PAR_EXPIRES_IN: i64 = 5 * 60;
+/// Seconds an authorization code may wait to be exchanged.
+pub const CODE_EXPIRES_IN: i64 = 60;
+/// Seconds an access token is valid for.
+pub const ACCESS_TOKEN_EXPIRES_IN: u64 = 60 * 60;
+/// Seconds a refresh token for a public client is valid for.
+pub const PUBLIC_CLIENT_REFRESH_EXPIRES_IN: i64 = 14 * 24 * 60 * 60;
+/// Seconds a refresh token for a confidential client is valid for.
+pub const CONFIDENTIAL_CLIENT_REFRESH_EXPIRES_IN: i64 = 180 * 24 * 60 * 60;
+
+pub const SCOPE_ATPROTO: &str = "atproto";
+pub const SCOPE_TRANSITION_GENERIC: &str = "transition:generic";
+pub const SCOPE_TRANSITION_CHAT: &str = "transition:chat.bsky";
+pub const SCOPES_SUPPORTED: [&str; 3] = [SCOPE_ATPROTO, SCOPE_TRANSITION_GENERIC, SCOPE_TRANSITION_CHAT];
+
+pub const DPOP_SIGNING_ALGS: [&str; 2] = ["ES256", "ES256K"];
+
+/// Error body defined by RFC 6749 section 5.2.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthError {
+    pub error: String,
+    pub error_description: String,
+}
+
+const ERROR_CODES: [&str; 9] = [
+    "invalid_request",
+    "invalid_client",
+    "invalid_grant",
+    "unauthorized_client",
+    "unsupported_grant_type",
+    "invalid_scope",
+    "access_denied",
+    "invalid_dpop_proof",
+    "use_dpop_nonce",
+];
+
+impl OAuthError {
+    /// Errors raised with an OAuth error code prefix, e.g. `bail!("invalid_grant: ...")`, keep
+    /// that code. Anything else is reported as a `server_error`.
+    pub fn from_error(error: &Error) -> Self {
+        let message = error.to_string();
+        match message.split_once(": ") {
+            Some((code, description)) if ERROR_CODES.contains(&code) => OAuthError {
+                error: code.to_string(),
+                error_description: description.to_string(),
+            },
+            _ => OAuthError {
+                error: "server_error".to_string(),
+                error_description: "Internal error".to_string(),
+            },
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self.error.as_str() {
+            "server_error" => Status::InternalServerError,
+            "invalid_client" => Status::Unauthorized,
+            _ => Status::BadRequest,
+        }
+    }
+}
+
+pub fn oauth_error_response(error: Error) -> status::Custom<Json<OAuthError>> {
+    eprintln!("@LOG: ERROR: {error}");
+    let error = OAuthError::from_error(&error);
+    status::Custom(error.status(), Json(error))
+}
+
+/// Every response from the authorization server hands out a fresh DPoP nonce.
+#[derive(Responder)]
+pub struct WithDpopNonce<R> {
+    pub inner: R,
+    pub nonce: Header<'static>,
+}
+
+impl<R> WithDpopNonce<R> {
+    pub fn new(inner: R) -> Self {
+        WithDpopNonce {
+            inner,
+            nonce: Header::new("DPoP-Nonce", dpop::current_nonce()),
+        }
+    }
+}
+
+/// Opaque identifier for request URIs, codes and refresh tokens, e.g. `cod-<64 hex chars>`.
+pub fn random_id(prefix: &str) -> String {
+    format!("{prefix}-{}", hex::encode(rand::random::<[u8; 32]>()))
+}
+
+pub fn issuer() -> String {
+    CORE_CONFIG.public_url()
+}
+
+/// Served at /.well-known/oauth-protected-resource
+pub fn protected_resource_metadata() -> Value {
+    json!({
+        "resource": issuer(),
+        "authorization_servers": [issuer()],
+        "scopes_supported": SCOPES_SUPPORTED,
+        "bearer_methods_supported": ["header"],
+        "resource_documentation": "https://atproto.com",
+    })
+}
+
+/// Served at /.well-known/oauth-authorization-server
+pub fn authorization_server_metadata() -> Value {
+    let issuer = issuer();
+    json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/oauth/authorize"),
+        "token_endpoint": format!("{issuer}/oauth/token"),
+        "pushed_authorization_request_endpoint": format!("{issuer}/oauth/par"),
+        "revocation_endpoint": format!("{issuer}/oauth/revoke"),
+        "require_pushed_authorization_requests": true,
+        "scopes_supported": SCOPES_SUPPORTED,
+        "subject_types_supported": ["public"],
+        "response_types_supported": ["code"],
+        "response_modes_supported": ["query", "fragment"],
+        "grant_types_supported": ["authorization_code", "refresh_token"],
+        "code_challenge_methods_supported": ["S256"],
+        "token_endpoint_auth_methods_supported": ["none", "private_key_jwt"],
+        "token_endpoint_auth_signing_alg_values_supported": DPOP_SIGNING_ALGS,
+        "dpop_signing_alg_values_supported": DPOP_SIGNING_ALGS,
+        "authorization_response_iss_parameter_supported": true,
+        "request_parameter_supported": false,
+        "request_uri_parameter_supported": true,
+        "require_request_uri_registration": true,
+        "client_id_metadata_document_supported": true,
+    })
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        par::par,
+        authorize::authorize,
+        authorize::authorize_decision,
+        token::token,
+        revoke::revoke
+    ]
+}
@@ -0,0 +1,116 @@
+use crate::database::models::OAuthRequest;
+use crate::oauth::client::{authenticate_client, get_client_metadata, ClientCredentials};
+use crate::oauth::dpop::DpopRequest;
+use crate::oauth::store;
+use crate::oauth::{oauth_error_response, random_id, OAuthError, WithDpopNonce, PAR_EXPIRES_IN};
+use anyhow::{bail, Result};
+use rocket::form::Form;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::common;
+use serde::{Deserialize, Serialize};
+
+pub const REQUEST_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+
+#[derive(Debug, FromForm)]
+pub struct ParForm {
+    pub client_id: String,
+    pub response_type: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub code_challenge_method: String,
+    pub response_mode: Option<String>,
+    pub login_hint: Option<String>,
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+}
+
+/// The parts of the authorization request needed once the user has consented.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationParameters {
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub response_mode: String,
+    pub login_hint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParOutput {
+    pub request_uri: String,
+    pub expires_in: i64,
+}
+
+async fn inner_par(body: ParForm, dpop: DpopRequest) -> Result<ParOutput> {
+    let dpop_jkt = dpop.verify(None, true).await?;
+    let metadata = get_client_metadata(&body.client_id).await?;
+    let client_auth = authenticate_client(
+        &metadata,
+        &ClientCredentials {
+            client_assertion_type: body.client_assertion_type,
+            client_assertion: body.client_assertion,
+        },
+    )
+    .await?;
+
+    if body.response_type != "code" {
+        bail!("invalid_request: Unsupported response_type")
+    }
+    if !metadata.redirect_uris.contains(&body.redirect_uri) {
+        bail!("invalid_request: redirect_uri was not registered by the client")
+    }
+    metadata.assert_scope_allowed(&body.scope)?;
+    if body.code_challenge_method != "S256" {
+        bail!("invalid_request: code_challenge_method must be S256")
+    }
+    if body.code_challenge.is_empty() {
+        bail!("invalid_request: code_challenge is required")
+    }
+    let response_mode = body.response_mode.unwrap_or("query".to_string());
+    if response_mode != "query" && response_mode != "fragment" {
+        bail!("invalid_request: Unsupported response_mode")
+    }
+
+    let parameters = AuthorizationParameters {
+        redirect_uri: body.redirect_uri,
+        scope: body.scope,
+        state: body.state,
+        code_challenge: body.code_challenge,
+        response_mode,
+        login_hint: body.login_hint,
+    };
+    let id = random_id("req");
+    store::create_request(OAuthRequest {
+        id: id.clone(),
+        client_id: body.client_id,
+        client_auth,
+        parameters: serde_json::to_string(&parameters)?,
+        dpop_jkt,
+        did: None,
+        code: None,
+        expires_at: store::expires_in(PAR_EXPIRES_IN),
+        created_at: common::now(),
+        csrf_token: None,
+    })
+    .await?;
+    Ok(ParOutput {
+        request_uri: format!("{REQUEST_URI_PREFIX}{id}"),
+        expires_in: PAR_EXPIRES_IN,
+    })
+}
+
+/// Pushed authorization request (RFC 9126). The only way to start an authorization.
+#[rocket::post("/par", data = "<body>")]
+pub async fn par(
+    body: Form<ParForm>,
+    dpop: DpopRequest,
+) -> WithDpopNonce<Result<status::Custom<Json<ParOutput>>, status::Custom<Json<OAuthError>>>> {
+    WithDpopNonce::new(match inner_par(body.into_inner(), dpop).await {
+        Ok(res) => Ok(status::Custom(Status::Created, Json(res))),
+        Err(error) => Err(oauth_error_response(error)),
+    })
+}
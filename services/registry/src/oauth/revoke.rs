@@ -0,0 +1,31 @@
+use crate::oauth::access_token::decode_access_token;
+use crate::oauth::store;
+use anyhow::Result;
+use rocket::form::Form;
+
+#[derive(Debug, FromForm)]
+pub struct RevokeForm {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+}
+
+async fn inner_revoke(body: RevokeForm) -> Result<()> {
+    if store::delete_token_by_refresh_token(&body.token).await? {
+        return Ok(());
+    }
+    // Revoking an access token ends the whole session it belongs to
+    if let Ok(claims) = decode_access_token(&body.token, None) {
+        if let Some(jti) = claims.jwt_id {
+            store::delete_token(&jti).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Token revocation (RFC 7009). Unknown tokens are not an error.
+#[rocket::post("/revoke", data = "<body>")]
+pub async fn revoke(body: Form<RevokeForm>) {
+    if let Err(error) = inner_revoke(body.into_inner()).await {
+        eprintln!("@LOG: ERROR: {error}");
+    }
+}
@@ -0,0 +1,255 @@
+use crate::database::establish_connection;
+use crate::database::models::{OAuthRequest, OAuthToken};
+use anyhow::Result;
+use chrono::offset::Utc as UtcOffset;
+use chrono::{DateTime, Duration};
+use diesel::*;
+use rsky_pds::common;
+use rsky_pds::common::RFC3339_VARIANT;
+
+/// Timestamp `seconds` from now, formatted like `common::now()` so the two compare as strings.
+pub fn expires_in(seconds: i64) -> String {
+    (UtcOffset::now() + Duration::seconds(seconds))
+        .format(RFC3339_VARIANT)
+        .to_string()
+}
+
+fn timestamp_at(secs: i64) -> String {
+    DateTime::<UtcOffset>::from_timestamp(secs, 0)
+        .unwrap_or_default()
+        .format(RFC3339_VARIANT)
+        .to_string()
+}
+
+pub fn is_expired(expires_at: &String) -> bool {
+    expires_at <= &common::now()
+}
+
+// Authorization requests
+// ---------
+
+pub async fn create_request(request: OAuthRequest) -> Result<()> {
+    use crate::schema::registry::oauth_request::dsl as OAuthRequestSchema;
+    let conn = &mut establish_connection()?;
+
+    // Opportunistically clear out requests nobody finished
+    delete(OAuthRequestSchema::oauth_request)
+        .filter(OAuthRequestSchema::expiresAt.le(common::now()))
+        .execute(conn)?;
+    insert_into(OAuthRequestSchema::oauth_request)
+        .values(request)
+        .execute(conn)?;
+    Ok(())
+}
+
+pub async fn get_request(id: &String) -> Result<Option<OAuthRequest>> {
+    use crate::schema::registry::oauth_request::dsl as OAuthRequestSchema;
+    let conn = &mut establish_connection()?;
+
+    let found = OAuthRequestSchema::oauth_request
+        .filter(OAuthRequestSchema::id.eq(id))
+        .filter(OAuthRequestSchema::expiresAt.gt(common::now()))
+        .select(OAuthRequest::as_select())
+        .first(conn)
+        .optional()?;
+    Ok(found)
+}
+
+/// Records the user's consent. The request then lives only as long as its code.
+pub async fn set_request_code(id: &String, did: &String, code: &String) -> Result<()> {
+    use crate::schema::registry::oauth_request::dsl as OAuthRequestSchema;
+    let conn = &mut establish_connection()?;
+
+    update(OAuthRequestSchema::oauth_request)
+        .filter(OAuthRequestSchema::id.eq(id))
+        .set((
+            OAuthRequestSchema::did.eq(did),
+            OAuthRequestSchema::code.eq(code),
+            OAuthRequestSchema::expiresAt.eq(expires_in(super::CODE_EXPIRES_IN)),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Replaces the one-time token of the sign-in form for a pending request.
+pub async fn set_request_csrf_token(id: &String, csrf_token: &String) -> Result<()> {
+    use crate::schema::registry::oauth_request::dsl as OAuthRequestSchema;
+    let conn = &mut establish_connection()?;
+
+    update(OAuthRequestSchema::oauth_request)
+        .filter(OAuthRequestSchema::id.eq(id))
+        .set(OAuthRequestSchema::csrfToken.eq(csrf_token))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Uses up the sign-in form token of a pending request. Returns whether it was the current one,
+/// which only one submission of the form can see.
+pub async fn consume_request_csrf_token(id: &String, csrf_token: &String) -> Result<bool> {
+    use crate::schema::registry::oauth_request::dsl as OAuthRequestSchema;
+    let conn = &mut establish_connection()?;
+
+    let updated = update(OAuthRequestSchema::oauth_request)
+        .filter(OAuthRequestSchema::id.eq(id))
+        .filter(OAuthRequestSchema::csrfToken.eq(csrf_token))
+        .filter(OAuthRequestSchema::code.is_null())
+        .set(OAuthRequestSchema::csrfToken.eq(None::<String>))
+        .execute(conn)?;
+    Ok(updated == 1)
+}
+
+pub async fn delete_request(id: &String) -> Result<()> {
+    use crate::schema::registry::oauth_request::dsl as OAuthRequestSchema;
+    let conn = &mut establish_connection()?;
+
+    delete(OAuthRequestSchema::oauth_request)
+        .filter(OAuthRequestSchema::id.eq(id))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Codes are single use: the request is deleted whether or not the exchange goes on to succeed.
+pub async fn consume_code(code: &String) -> Result<Option<OAuthRequest>> {
+    use crate::schema::registry::oauth_request::dsl as OAuthRequestSchema;
+    let conn = &mut establish_connection()?;
+
+    let found = delete(OAuthRequestSchema::oauth_request)
+        .filter(OAuthRequestSchema::code.eq(code))
+        .returning(OAuthRequest::as_returning())
+        .get_result(conn)
+        .optional()?;
+    Ok(found.filter(|request| !is_expired(&request.expires_at)))
+}
+
+// Tokens
+// ---------
+
+pub async fn create_token(token: OAuthToken) -> Result<()> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    insert_into(OAuthTokenSchema::oauth_token)
+        .values(token)
+        .execute(conn)?;
+    Ok(())
+}
+
+pub async fn get_token(id: &String) -> Result<Option<OAuthToken>> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    let found = OAuthTokenSchema::oauth_token
+        .filter(OAuthTokenSchema::id.eq(id))
+        .filter(OAuthTokenSchema::expiresAt.gt(common::now()))
+        .select(OAuthToken::as_select())
+        .first(conn)
+        .optional()?;
+    Ok(found)
+}
+
+pub async fn get_token_by_refresh_token(refresh_token: &String) -> Result<Option<OAuthToken>> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    let found = OAuthTokenSchema::oauth_token
+        .filter(OAuthTokenSchema::refreshToken.eq(refresh_token))
+        .filter(OAuthTokenSchema::expiresAt.gt(common::now()))
+        .select(OAuthToken::as_select())
+        .first(conn)
+        .optional()?;
+    Ok(found)
+}
+
+/// Swaps in a new refresh token, failing if `refresh_token` was already used by a concurrent
+/// request.
+pub async fn rotate_refresh_token(
+    id: &String,
+    refresh_token: &String,
+    next_refresh_token: &String,
+) -> Result<bool> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    let updated = update(OAuthTokenSchema::oauth_token)
+        .filter(OAuthTokenSchema::id.eq(id))
+        .filter(OAuthTokenSchema::refreshToken.eq(refresh_token))
+        .set(OAuthTokenSchema::refreshToken.eq(next_refresh_token))
+        .execute(conn)?;
+    Ok(updated > 0)
+}
+
+pub async fn delete_token(id: &String) -> Result<()> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    delete(OAuthTokenSchema::oauth_token)
+        .filter(OAuthTokenSchema::id.eq(id))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub async fn delete_token_by_refresh_token(refresh_token: &String) -> Result<bool> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    let deleted = delete(OAuthTokenSchema::oauth_token)
+        .filter(OAuthTokenSchema::refreshToken.eq(refresh_token))
+        .execute(conn)?;
+    Ok(deleted > 0)
+}
+
+/// Grants `did` has given clients that can still be refreshed, newest first.
+pub async fn list_tokens_by_did(did: &String) -> Result<Vec<OAuthToken>> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(OAuthTokenSchema::oauth_token
+        .filter(OAuthTokenSchema::did.eq(did))
+        .filter(OAuthTokenSchema::expiresAt.gt(common::now()))
+        .order(OAuthTokenSchema::createdAt.desc())
+        .select(OAuthToken::as_select())
+        .load(conn)?)
+}
+
+/// Deletes the grant `id` if it belongs to `did`. Returns whether there was one.
+pub async fn delete_token_by_did(did: &String, id: &String) -> Result<bool> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    let deleted = delete(OAuthTokenSchema::oauth_token)
+        .filter(OAuthTokenSchema::did.eq(did))
+        .filter(OAuthTokenSchema::id.eq(id))
+        .execute(conn)?;
+    Ok(deleted > 0)
+}
+
+/// Deletes every grant `did` has given. Returns how many there were.
+pub async fn delete_tokens_by_did(did: &String) -> Result<usize> {
+    use crate::schema::registry::oauth_token::dsl as OAuthTokenSchema;
+    let conn = &mut establish_connection()?;
+
+    Ok(delete(OAuthTokenSchema::oauth_token)
+        .filter(OAuthTokenSchema::did.eq(did))
+        .execute(conn)?)
+}
+
+// Replay protection
+// ---------
+
+/// Remembers `jti` until `expires_at` (unix seconds). Returns false if it has been seen before.
+pub async fn register_jti(jti: &String, expires_at: i64) -> Result<bool> {
+    use crate::schema::registry::oauth_used_jti::dsl as OAuthUsedJtiSchema;
+    let conn = &mut establish_connection()?;
+
+    delete(OAuthUsedJtiSchema::oauth_used_jti)
+        .filter(OAuthUsedJtiSchema::expiresAt.le(common::now()))
+        .execute(conn)?;
+    let inserted = insert_into(OAuthUsedJtiSchema::oauth_used_jti)
+        .values((
+            OAuthUsedJtiSchema::jti.eq(jti),
+            OAuthUsedJtiSchema::expiresAt.eq(timestamp_at(expires_at)),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
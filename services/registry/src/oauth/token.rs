@@ -0,0 +1,194 @@
+use crate::database::models::OAuthToken;
+use crate::oauth::access_token::create_access_token;
+use crate::oauth::client::{authenticate_client, get_client_metadata, ClientCredentials, ClientMetadata};
+use crate::oauth::dpop::DpopRequest;
+use crate::oauth::par::AuthorizationParameters;
+use crate::oauth::{
+    oauth_error_response, random_id, store, OAuthError, WithDpopNonce, ACCESS_TOKEN_EXPIRES_IN,
+    CONFIDENTIAL_CLIENT_REFRESH_EXPIRES_IN, PUBLIC_CLIENT_REFRESH_EXPIRES_IN,
+};
+use anyhow::{bail, Result};
+use rocket::form::Form;
+use rocket::http::Header;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rsky_pds::common;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, FromForm)]
+pub struct TokenForm {
+    pub grant_type: String,
+    pub client_id: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenOutput {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub refresh_token: String,
+    pub scope: String,
+    pub sub: String,
+}
+
+/// Token responses must not be cached (RFC 6749 section 5.1).
+#[derive(Responder)]
+pub struct TokenResponse {
+    pub inner: Json<TokenOutput>,
+    pub cache_control: Header<'static>,
+}
+
+/// PKCE S256 (RFC 7636 section 4.6).
+fn verify_code_challenge(code_verifier: &str, code_challenge: &str) -> bool {
+    let computed = base64_url::encode(&Sha256::digest(code_verifier.as_bytes())).replace("=", "");
+    computed == code_challenge
+}
+
+async fn token_output(token: &OAuthToken) -> Result<TokenOutput> {
+    Ok(TokenOutput {
+        access_token: create_access_token(token).await?,
+        token_type: "DPoP".to_string(),
+        expires_in: ACCESS_TOKEN_EXPIRES_IN,
+        refresh_token: token.refresh_token.clone(),
+        scope: token.scope.clone(),
+        sub: token.did.clone(),
+    })
+}
+
+/// Clients must keep authenticating the way they did when the grant was made.
+async fn authenticate(body: &TokenForm, client_auth: &String) -> Result<ClientMetadata> {
+    let metadata = get_client_metadata(&body.client_id).await?;
+    let method = authenticate_client(
+        &metadata,
+        &ClientCredentials {
+            client_assertion_type: body.client_assertion_type.clone(),
+            client_assertion: body.client_assertion.clone(),
+        },
+    )
+    .await?;
+    if &method != client_auth {
+        bail!("invalid_grant: Client authentication method changed")
+    }
+    Ok(metadata)
+}
+
+async fn authorization_code_grant(body: TokenForm, dpop_jkt: String) -> Result<TokenOutput> {
+    let (code, redirect_uri, code_verifier) =
+        match (&body.code, &body.redirect_uri, &body.code_verifier) {
+            (Some(code), Some(redirect_uri), Some(code_verifier)) => {
+                (code, redirect_uri, code_verifier)
+            }
+            _ => bail!("invalid_request: code, redirect_uri and code_verifier are required"),
+        };
+    let request = match store::consume_code(code).await? {
+        Some(request) => request,
+        None => bail!("invalid_grant: Invalid or expired code"),
+    };
+    let parameters: AuthorizationParameters = serde_json::from_str(&request.parameters)?;
+    if request.client_id != body.client_id {
+        bail!("invalid_grant: Code was issued to another client")
+    }
+    if &parameters.redirect_uri != redirect_uri {
+        bail!("invalid_grant: redirect_uri mismatch")
+    }
+    if !verify_code_challenge(code_verifier, &parameters.code_challenge) {
+        bail!("invalid_grant: Invalid code_verifier")
+    }
+    if request.dpop_jkt != dpop_jkt {
+        bail!("invalid_dpop_proof: DPoP key does not match the authorization request")
+    }
+    let did = match request.did {
+        Some(did) => did,
+        None => bail!("invalid_grant: Request was not authorized"),
+    };
+    let metadata = authenticate(&body, &request.client_auth).await?;
+
+    let refresh_expires_in = if metadata.is_confidential() {
+        CONFIDENTIAL_CLIENT_REFRESH_EXPIRES_IN
+    } else {
+        PUBLIC_CLIENT_REFRESH_EXPIRES_IN
+    };
+    let token = OAuthToken {
+        id: random_id("tok"),
+        did,
+        client_id: request.client_id,
+        client_auth: request.client_auth,
+        scope: parameters.scope,
+        dpop_jkt,
+        refresh_token: random_id("ref"),
+        created_at: common::now(),
+        expires_at: store::expires_in(refresh_expires_in),
+    };
+    store::create_token(token.clone()).await?;
+    token_output(&token).await
+}
+
+/// Refresh tokens rotate on every use but the session keeps its original expiry.
+async fn refresh_token_grant(body: TokenForm, dpop_jkt: String) -> Result<TokenOutput> {
+    let refresh_token = match &body.refresh_token {
+        Some(refresh_token) => refresh_token,
+        None => bail!("invalid_request: refresh_token is required"),
+    };
+    let mut token = match store::get_token_by_refresh_token(refresh_token).await? {
+        Some(token) => token,
+        None => bail!("invalid_grant: Invalid or expired refresh token"),
+    };
+    if token.client_id != body.client_id {
+        bail!("invalid_grant: Refresh token was issued to another client")
+    }
+    if token.dpop_jkt != dpop_jkt {
+        bail!("invalid_dpop_proof: DPoP key does not match the refresh token")
+    }
+    authenticate(&body, &token.client_auth).await?;
+
+    let next_refresh_token = random_id("ref");
+    if !store::rotate_refresh_token(&token.id, refresh_token, &next_refresh_token).await? {
+        bail!("invalid_grant: Refresh token was already used")
+    }
+    token.refresh_token = next_refresh_token;
+    token_output(&token).await
+}
+
+async fn inner_token(body: TokenForm, dpop: DpopRequest) -> Result<TokenOutput> {
+    let dpop_jkt = dpop.verify(None, true).await?;
+    match body.grant_type.as_str() {
+        "authorization_code" => authorization_code_grant(body, dpop_jkt).await,
+        "refresh_token" => refresh_token_grant(body, dpop_jkt).await,
+        _ => bail!("unsupported_grant_type: Unsupported grant_type"),
+    }
+}
+
+#[rocket::post("/token", data = "<body>")]
+pub async fn token(
+    body: Form<TokenForm>,
+    dpop: DpopRequest,
+) -> WithDpopNonce<Result<TokenResponse, status::Custom<Json<OAuthError>>>> {
+    WithDpopNonce::new(match inner_token(body.into_inner(), dpop).await {
+        Ok(res) => Ok(TokenResponse {
+            inner: Json(res),
+            cache_control: Header::new("Cache-Control", "no-store"),
+        }),
+        Err(error) => Err(oauth_error_response(error)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_s256_code_challenge() {
+        // RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+        assert!(verify_code_challenge(verifier, challenge));
+        assert!(!verify_code_challenge("not-the-verifier", challenge));
+    }
+}
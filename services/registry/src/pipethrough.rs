@@ -6,7 +6,9 @@
 use rsky_pds::repo::types::Ids;
 use rsky_pds::auth_verifier::AccessOutput;
 use crate::common::{get_service_endpoint, GetServiceEndpointOpts};
-use crate::auth_verifier::AccessStandard;
+use crate::auth_verifier::{
+    app_password_scopes, assert_method_allowed, assert_privileged_method, AccessStandard,
+};
 use crate::xrpc::types::{HandlerPipeThrough, InvalidRequestError, XRPCError};
 use crate::config::{ServiceConfig, BSKY_APP_VIEW_CONFIG, CORE_CONFIG, MOD_SERVICE_CONFIG, REPORT_SERVICE_CONFIG};
 use crate::{context, SharedIdResolver, APP_USER_AGENT};
@@ -53,8 +55,15 @@ impl<'r> FromRequest<'r> for HandlerPipeThrough {
     type Error = anyhow::Error;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Err(error) = assert_method_allowed(&app_password_scopes(req), req.method()) {
+            return Outcome::Error((Status::Forbidden, error));
+        }
         match AccessStandard::from_request(req).await {
             Outcome::Success(output) => {
+                let nsid = req.uri().path().to_string().replace("/xrpc/", "");
+                if let Err(error) = assert_privileged_method(&output.access, &nsid) {
+                    return Outcome::Error((Status::Forbidden, error));
+                }
                 let AccessOutput { credentials, .. } = output.access;
                 let requester: Option<String> = match credentials {
                     None => None,
@@ -119,6 +128,9 @@ impl<'r> FromRequest<'r> for ProxyRequest<'r> {
     type Error = anyhow::Error;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Err(error) = assert_method_allowed(&app_password_scopes(req), req.method()) {
+            return Outcome::Error((Status::Forbidden, error));
+        }
         let headers = req.headers().clone().into_iter().fold(
             BTreeMap::new(),
             |mut acc: BTreeMap<String, String>, cur| {
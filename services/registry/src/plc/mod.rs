@@ -5,11 +5,11 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use rsky_pds::common::encode_uri_component;
-use crate::plc::operations::update_handle_op;
+use crate::plc::operations::{update_atproto_key_op, update_handle_op};
 use crate::plc::types::{CompatibleOp, OpOrTombstone};
+use crate::signer::Signer;
 use crate::APP_USER_AGENT;
 use anyhow::{bail, Result};
-use secp256k1::SecretKey;
 use serde::de::DeserializeOwned;
 use types::{CompatibleOpOrTombstone, DocumentData};
 
@@ -45,7 +45,7 @@ impl Client {
         Ok(res.json().await?)
     }
 
-    async fn send_operation(&self, did: &String, op: &OpOrTombstone) -> Result<()> {
+    pub async fn send_operation(&self, did: &String, op: &OpOrTombstone) -> Result<()> {
         let client = reqwest::Client::builder()
             .user_agent(APP_USER_AGENT)
             .build()?;
@@ -101,7 +101,7 @@ impl Client {
     pub async fn update_handle(
         &self,
         did: &String,
-        signer: &SecretKey,
+        signer: &dyn Signer,
         handle: &String,
     ) -> Result<()> {
         let last_op: CompatibleOp = match self.ensure_last_op(did).await? {
@@ -115,6 +115,24 @@ impl Client {
         self.send_operation(&did, &OpOrTombstone::Operation(op))
             .await
     }
+
+    pub async fn update_atproto_key(
+        &self,
+        did: &String,
+        signer: &dyn Signer,
+        atproto_key: &String,
+    ) -> Result<()> {
+        let last_op: CompatibleOp = match self.ensure_last_op(did).await? {
+            CompatibleOpOrTombstone::CreateOpV1(last_op) => CompatibleOp::CreateOpV1(last_op),
+            CompatibleOpOrTombstone::Operation(last_op) => CompatibleOp::Operation(last_op),
+            CompatibleOpOrTombstone::Tombstone(_) => {
+                panic!("ensure_last_op() didn't prevent tombstone")
+            }
+        };
+        let op = update_atproto_key_op(last_op, signer, atproto_key.clone()).await?;
+        self.send_operation(&did, &OpOrTombstone::Operation(op))
+            .await
+    }
 }
 
 pub mod operations;
@@ -4,11 +4,13 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::plc::types::{CompatibleOp, CompatibleOpOrTombstone, Operation, Service, Tombstone};
+use crate::signer::Signer;
 use rsky_pds::common::ipld::cid_for_cbor;
-use rsky_pds::common::sign::atproto_sign;
 use anyhow::Result;
+use indexmap::IndexMap;
 use libipld::Cid;
-use secp256k1::SecretKey;
+use serde::Serialize;
+use serde_json::Value;
 use std::collections::BTreeMap;
 
 #[derive(Debug, Clone)]
@@ -21,7 +23,7 @@ pub struct CreateAtprotoUpdateOpOpts {
 
 pub async fn update_atproto_key_op(
     last_op: CompatibleOp,
-    signer: &SecretKey,
+    signer: &dyn Signer,
     signing_key: String,
 ) -> Result<Operation> {
     create_atproto_update_op(
@@ -39,7 +41,7 @@ pub async fn update_atproto_key_op(
 
 pub async fn update_handle_op(
     last_op: CompatibleOp,
-    signer: &SecretKey,
+    signer: &dyn Signer,
     handle: String,
 ) -> Result<Operation> {
     create_atproto_update_op(
@@ -57,7 +59,7 @@ pub async fn update_handle_op(
 
 pub async fn update_pds_op(
     last_op: CompatibleOp,
-    signer: &SecretKey,
+    signer: &dyn Signer,
     pds: String,
 ) -> Result<Operation> {
     create_atproto_update_op(
@@ -75,7 +77,7 @@ pub async fn update_pds_op(
 
 pub async fn update_rotation_keys_op(
     last_op: CompatibleOp,
-    signer: &SecretKey,
+    signer: &dyn Signer,
     rotation_keys: Vec<String>,
 ) -> Result<Operation> {
     create_atproto_update_op(
@@ -93,7 +95,7 @@ pub async fn update_rotation_keys_op(
 
 pub async fn create_atproto_update_op(
     last_op: CompatibleOp,
-    signer: &SecretKey,
+    signer: &dyn Signer,
     opts: CreateAtprotoUpdateOpOpts,
 ) -> Result<Operation> {
     create_update_op(last_op, signer, |normalized: Operation| -> Operation {
@@ -144,7 +146,7 @@ pub async fn create_atproto_update_op(
 
 pub async fn create_update_op<G>(
     last_op: CompatibleOp,
-    signer: &SecretKey,
+    signer: &dyn Signer,
     func: G,
 ) -> Result<Operation>
 where
@@ -165,14 +167,14 @@ where
     }
 }
 
-pub async fn tombstone_op(prev: Cid, key: &SecretKey) -> Result<Tombstone> {
+pub async fn tombstone_op(prev: Cid, signer: &dyn Signer) -> Result<Tombstone> {
     match add_signature(
         CompatibleOpOrTombstone::Tombstone(Tombstone {
             r#type: "plc_tombstone".to_string(),
             prev: prev.to_string(),
             sig: None,
         }),
-        key,
+        signer,
     )
     .await?
     {
@@ -181,8 +183,8 @@ pub async fn tombstone_op(prev: Cid, key: &SecretKey) -> Result<Tombstone> {
     }
 }
 
-pub async fn sign_operation(op: Operation, key: &SecretKey) -> Result<Operation> {
-    match add_signature(CompatibleOpOrTombstone::Operation(op), key).await? {
+pub async fn sign_operation(op: Operation, signer: &dyn Signer) -> Result<Operation> {
+    match add_signature(CompatibleOpOrTombstone::Operation(op), signer).await? {
         CompatibleOpOrTombstone::Operation(op) => Ok(op),
         _ => panic!("Enum type changed"),
     }
@@ -190,13 +192,21 @@ pub async fn sign_operation(op: Operation, key: &SecretKey) -> Result<Operation>
 
 pub async fn add_signature(
     mut obj: CompatibleOpOrTombstone,
-    key: &SecretKey,
+    signer: &dyn Signer,
 ) -> Result<CompatibleOpOrTombstone> {
-    let sig = atproto_sign(&obj, key)?.to_vec();
+    let sig = signer.sign(&signing_bytes(&obj)?).await?;
     obj.set_sig(base64_url::encode(&sig).replace("=", ""));
     Ok(obj)
 }
 
+/// The bytes an op's signature covers: its dag-cbor encoding, with fields in the order they're
+/// serialized in. Goes through JSON so nested maps keep that order too.
+pub fn signing_bytes<T: Serialize>(op: &T) -> Result<Vec<u8>> {
+    let json = serde_json::to_string(op)?;
+    let unsigned: IndexMap<String, Value> = serde_json::from_str(&json)?;
+    Ok(serde_ipld_dagcbor::to_vec(&unsigned)?)
+}
+
 pub fn normalize_op(op: CompatibleOp) -> Operation {
     match op {
         CompatibleOp::Operation(op) => op,
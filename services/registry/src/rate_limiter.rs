@@ -0,0 +1,332 @@
+//! In-memory token buckets, so a single node can throttle abusive clients without Redis.
+//! Endpoints opt in by taking the `RateLimit` guard; the limits themselves are looked up by NSID
+//! in `RATE_LIMIT_CONFIG`, or by path for endpoints outside of XRPC.
+use crate::auth_verifier::{is_bearer_token, is_dpop_token, validate_bearer_token};
+use crate::common::client_ip;
+use crate::config::{RateLimitKey, RateLimitRule, RATE_LIMIT_CONFIG, SERVICE_CONFIG};
+use crate::oauth::access_token::{decode_access_token, dpop_token_from_req};
+use jwt_simple::prelude::*;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::json::Json;
+use rocket::{Request, Response, State};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+/// How many requests to handle between sweeps of idle buckets.
+const SWEEP_INTERVAL: u64 = 10_000;
+/// Most buckets to keep, so a flood of new clients can't grow the map without bound.
+const MAX_BUCKETS: usize = 100_000;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    refill_per_ms: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    /// Full buckets are indistinguishable from new ones, so they can be dropped.
+    fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated_at).as_millis() as f64;
+        self.tokens + elapsed * self.refill_per_ms >= self.capacity
+    }
+}
+
+/// What the most constrained rule had left after a request, for the `RateLimit-*` headers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    pub window: u64,
+    pub exceeded: bool,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    buckets: HashMap<String, Bucket>,
+    max_buckets: usize,
+    requests: u64,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter {
+            buckets: HashMap::new(),
+            max_buckets: MAX_BUCKETS,
+            requests: 0,
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Takes a point from the bucket for `key` under `rule`, refilling it for the time elapsed
+    /// since it was last touched.
+    fn consume(&mut self, key: String, rule: &RateLimitRule, now: Instant) -> RateLimitStatus {
+        let capacity = rule.points as f64;
+        let refill_per_ms = capacity / rule.duration.max(1) as f64;
+        if self.buckets.len() >= self.max_buckets && !self.buckets.contains_key(&key) {
+            self.sweep(now);
+        }
+        let bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            capacity,
+            refill_per_ms,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_millis() as f64;
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_ms).min(capacity);
+        bucket.updated_at = now;
+        let exceeded = bucket.tokens < 1.0;
+        if !exceeded {
+            bucket.tokens -= 1.0;
+        }
+        let ms_until_full = (capacity - bucket.tokens) / refill_per_ms;
+        RateLimitStatus {
+            limit: rule.points,
+            remaining: bucket.tokens.floor() as u32,
+            reset: (ms_until_full / 1000.0).ceil() as u64,
+            window: rule.duration / 1000,
+            exceeded,
+        }
+    }
+
+    /// Applies every rule, reporting the exceeded one or else the one with the fewest points left.
+    pub fn check(
+        &mut self,
+        nsid: &str,
+        rules: &Vec<RateLimitRule>,
+        ip: &str,
+        did: Option<&str>,
+    ) -> Option<RateLimitStatus> {
+        let now = Instant::now();
+        self.requests += 1;
+        if self.requests % SWEEP_INTERVAL == 0 {
+            self.sweep(now);
+        }
+        rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let subject = match (rule.key, did) {
+                    (RateLimitKey::Did, Some(did)) => did,
+                    _ => ip,
+                };
+                self.consume(format!("{nsid}#{i}:{subject}"), rule, now)
+            })
+            .collect::<Vec<RateLimitStatus>>()
+            .into_iter()
+            .min_by_key(|status| (!status.exceeded, status.remaining))
+    }
+
+    /// Drops buckets that have refilled completely. If most of the cap is still taken after that,
+    /// the least recently used buckets go too, leaving room so the next sweep isn't straight away.
+    fn sweep(&mut self, now: Instant) {
+        self.buckets.retain(|_, bucket| !bucket.is_full(now));
+        let keep = (self.max_buckets * 9 / 10).max(1);
+        if self.buckets.len() > keep {
+            let mut updated_at = self
+                .buckets
+                .values()
+                .map(|bucket| bucket.updated_at)
+                .collect::<Vec<Instant>>();
+            updated_at.sort_unstable_by(|a, b| b.cmp(a));
+            let cutoff = updated_at[keep - 1];
+            self.buckets.retain(|_, bucket| bucket.updated_at >= cutoff);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SharedRateLimiter {
+    pub rate_limiter: Mutex<RateLimiter>,
+}
+
+/// The account making the request, if it presented a valid access token. Only signatures are
+/// checked here; the endpoint's own auth guard does the rest.
+async fn requester_did(req: &Request<'_>) -> Option<String> {
+    if is_dpop_token(req) {
+        let token = dpop_token_from_req(req)?;
+        decode_access_token(&token, None).ok()?.subject
+    } else if is_bearer_token(req) {
+        let mut options = VerificationOptions::default();
+        options.allowed_audiences = Some(HashSet::from_strings(&[SERVICE_CONFIG.did.clone()]));
+        validate_bearer_token(req, vec![], Some(options))
+            .await
+            .ok()
+            .map(|bearer| bearer.did)
+    } else {
+        None
+    }
+}
+
+/// What an endpoint's rules are configured under: the NSID for XRPC, `/img` for every image
+/// variant, and otherwise the path.
+fn limit_key(path: &str) -> &str {
+    match path.strip_prefix("/xrpc/") {
+        Some(nsid) => nsid,
+        None if path.starts_with("/img/") => "/img",
+        None => path,
+    }
+}
+
+/// Request guard that throttles the endpoint it's attached to.
+#[derive(Debug)]
+pub struct RateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimit {
+    type Error = RateLimitStatus;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if !RATE_LIMIT_CONFIG.enabled() {
+            return Outcome::Success(RateLimit);
+        }
+        let nsid = limit_key(req.uri().path().as_str()).to_string();
+        let rules = RATE_LIMIT_CONFIG.limits(&nsid);
+        if rules.is_empty() {
+            return Outcome::Success(RateLimit);
+        }
+        let ip = match client_ip(req) {
+            Some(ip) => ip.to_string(),
+            None => "unknown".to_string(),
+        };
+        let did = match rules.iter().any(|rule| rule.key == RateLimitKey::Did) {
+            true => requester_did(req).await,
+            false => None,
+        };
+        let rate_limiter = req.guard::<&State<SharedRateLimiter>>().await.unwrap();
+        let status = rate_limiter
+            .rate_limiter
+            .lock()
+            .unwrap()
+            .check(&nsid, &rules, &ip, did.as_deref());
+        req.local_cache(|| status.clone());
+        match status {
+            Some(status) if status.exceeded => Outcome::Error((Status::TooManyRequests, status)),
+            _ => Outcome::Success(RateLimit),
+        }
+    }
+}
+
+/// Adds the `RateLimit-*` headers to responses from rate limited endpoints.
+#[derive(Debug)]
+pub struct RateLimitHeaders;
+
+#[rocket::async_trait]
+impl Fairing for RateLimitHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Add RateLimit headers to responses",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let status: &Option<RateLimitStatus> = request.local_cache(|| None);
+        if let Some(status) = status {
+            let reset_at = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|now| now.as_secs() + status.reset)
+                .unwrap_or_default();
+            response.set_header(Header::new("RateLimit-Limit", status.limit.to_string()));
+            response.set_header(Header::new("RateLimit-Remaining", status.remaining.to_string()));
+            response.set_header(Header::new("RateLimit-Reset", reset_at.to_string()));
+            response.set_header(Header::new(
+                "RateLimit-Policy",
+                format!("{};w={}", status.limit, status.window),
+            ));
+            if status.exceeded {
+                response.set_header(Header::new("Retry-After", status.reset.to_string()));
+            }
+        }
+    }
+}
+
+#[catch(429)]
+pub async fn rate_limit_exceeded() -> Json<Value> {
+    Json(json!({
+        "error": "RateLimitExceeded",
+        "message": "Rate Limit Exceeded"
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn refills_over_the_window() {
+        let rule = RateLimitRule {
+            key: RateLimitKey::Ip,
+            points: 2,
+            duration: 1000,
+        };
+        let mut rate_limiter = RateLimiter::default();
+        let start = Instant::now();
+        assert!(!rate_limiter.consume("k".to_string(), &rule, start).exceeded);
+        assert!(!rate_limiter.consume("k".to_string(), &rule, start).exceeded);
+        let status = rate_limiter.consume("k".to_string(), &rule, start);
+        assert!(status.exceeded);
+        assert_eq!(status.remaining, 0);
+        assert_eq!(status.reset, 1);
+        // Other keys have their own bucket
+        assert!(!rate_limiter.consume("other".to_string(), &rule, start).exceeded);
+        // Half the window refills one point
+        let later = start + Duration::from_millis(500);
+        assert!(!rate_limiter.consume("k".to_string(), &rule, later).exceeded);
+        assert!(rate_limiter.consume("k".to_string(), &rule, later).exceeded);
+    }
+
+    #[test]
+    fn keys_limits_by_nsid_or_path() {
+        assert_eq!(
+            limit_key("/xrpc/com.atproto.server.createSession"),
+            "com.atproto.server.createSession"
+        );
+        assert_eq!(limit_key("/oauth/authorize"), "/oauth/authorize");
+        assert_eq!(
+            limit_key("/img/avatar/plain/did:plc:abc/bafkrei@jpeg"),
+            "/img"
+        );
+    }
+
+    #[test]
+    fn caps_the_number_of_buckets() {
+        let rule = RateLimitRule {
+            key: RateLimitKey::Ip,
+            points: 2,
+            duration: 1000,
+        };
+        let mut rate_limiter = RateLimiter {
+            max_buckets: 10,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        for i in 0..10 {
+            let now = start + Duration::from_millis(i);
+            rate_limiter.consume(format!("client{i}"), &rule, now);
+        }
+        assert_eq!(rate_limiter.buckets.len(), 10);
+
+        // A new client makes room by dropping the least recently used buckets
+        let now = start + Duration::from_millis(10);
+        rate_limiter.consume("new".to_string(), &rule, now);
+        assert!(rate_limiter.buckets.len() <= 10);
+        assert!(rate_limiter.buckets.contains_key("new"));
+        assert!(rate_limiter.buckets.contains_key("client9"));
+        assert!(!rate_limiter.buckets.contains_key("client0"));
+
+        // Buckets that have refilled go first
+        let later = now + Duration::from_secs(1);
+        rate_limiter.sweep(later);
+        assert!(rate_limiter.buckets.is_empty());
+    }
+}
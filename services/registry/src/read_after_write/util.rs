@@ -7,12 +7,11 @@ use rsky_pds::common::time::from_str_to_utc;
 use crate::pipethrough::parse_res;
 use crate::read_after_write::types::LocalRecords;
 use crate::read_after_write::viewer::{get_records_since_rev, LocalViewer};
-use crate::repository::aws::s3::S3BlobStore;
 use crate::repository::ActorStore;
 use crate::xrpc::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use anyhow::Result;
-use aws_config::SdkConfig;
+use crate::repository::blobstore::BlobStoreCreator;
 use chrono::offset::Utc as UtcOffset;
 use chrono::DateTime;
 use rocket::http::Status;
@@ -116,7 +115,7 @@ pub async fn handle_read_after_write<T: DeserializeOwned + serde::Serialize>(
     requester: String,
     res: HandlerPipeThrough,
     munge: MungeFn<T>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<T>> {
     match read_after_write_internal(
@@ -124,7 +123,7 @@ pub async fn handle_read_after_write<T: DeserializeOwned + serde::Serialize>(
         requester.clone(),
         res.clone(),
         munge,
-        s3_config,
+        blobstore,
         state_local_viewer,
     )
     .await
@@ -146,7 +145,7 @@ pub async fn read_after_write_internal<T: DeserializeOwned + serde::Serialize>(
     requester: String,
     res: HandlerPipeThrough,
     munge: MungeFn<T>,
-    s3_config: &State<SdkConfig>,
+    blobstore: &State<BlobStoreCreator>,
     state_local_viewer: &State<SharedLocalViewer>,
 ) -> Result<ReadAfterWriteResponse<T>> {
     let headers = &res.headers.clone().unwrap_or_else(|| BTreeMap::new());
@@ -156,7 +155,7 @@ pub async fn read_after_write_internal<T: DeserializeOwned + serde::Serialize>(
         Some(rev) => {
             let actor_store = ActorStore::new(
                 requester.clone(),
-                S3BlobStore::new(requester.clone(), s3_config),
+                blobstore.create(requester.clone()),
             );
             let local = get_records_since_rev(&actor_store, rev).await?;
             if local.count <= 0 {
@@ -3,16 +3,15 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
+use crate::account_manager::helpers::auth::{create_service_auth_headers, ServiceJwtParams};
 use crate::account_manager::AccountManager;
-use crate::config::SECRET_CONFIG;
 use crate::database::establish_connection;
 use crate::database::models;
 use crate::read_after_write::types::{LocalRecords, RecordDescript};
 use crate::read_after_write::util;
+use crate::repository::blob::GetBlobMetadataOutput;
 use crate::repository::ActorStore;
 use crate::{APP_USER_AGENT, INVALID_HANDLE};
-use rsky_pds::account_manager::helpers::auth::ServiceJwtParams;
-use rsky_pds::xrpc_server::auth::create_service_auth_headers;
 use rsky_pds::common::beginning_of_time;
 use rsky_pds::repo::types::Ids;
 use anyhow::{bail, Result};
@@ -39,18 +38,18 @@ use rsky_lexicon::app::bsky::actor::{Profile, ProfileView, ProfileViewBasic, Pro
 use rsky_lexicon::app::bsky::embed::external::{
     ExternalObject, View as ExternalView, ViewExternal,
 };
-use rsky_lexicon::app::bsky::embed::images::{View as ImagesView, ViewImage};
+use rsky_lexicon::app::bsky::embed::images::{AspectRatio, View as ImagesView, ViewImage};
 use rsky_lexicon::app::bsky::embed::record::{
     Record, View as RecordView, ViewNotFound as RecordViewNotFound, ViewRecord,
 };
 use rsky_lexicon::app::bsky::embed::record_with_media::{
     RecordWithMedia, View as RecordWithMediaView,
 };
+use rsky_lexicon::app::bsky::embed::video::{Video, View as VideoView};
 use rsky_lexicon::app::bsky::embed::{record, EmbedViews, Embeds, MediaUnion, MediaViewUnion};
 use rsky_lexicon::app::bsky::feed::{FeedViewPost, GeneratorView, Post, PostView};
 use rsky_lexicon::app::bsky::graph::ListView;
 use rsky_syntax::aturi::AtUri;
-use secp256k1::SecretKey;
 use std::str::FromStr;
 
 pub type Agent = AtpServiceClient<ReqwestClient>;
@@ -64,6 +63,7 @@ pub struct LocalViewerCreatorParams {
     pub appview_agent: Option<String>,
     pub appview_did: Option<String>,
     pub appview_cdn_url_pattern: Option<String>,
+    pub appview_video_url_pattern: Option<String>,
 }
 
 #[allow(missing_debug_implementations)]
@@ -75,6 +75,7 @@ pub struct LocalViewer {
     appview_agent_str: Option<String>,
     pub appview_did: Option<String>,
     pub appview_cdn_url_pattern: Option<String>,
+    pub appview_video_url_pattern: Option<String>,
 }
 
 impl LocalViewer {
@@ -85,6 +86,7 @@ impl LocalViewer {
         appview_agent_str: Option<String>,
         appview_did: Option<String>,
         appview_cdn_url_pattern: Option<String>,
+        appview_video_url_pattern: Option<String>,
     ) -> Self {
         LocalViewer {
             did: actor_store.did.clone(),
@@ -94,6 +96,7 @@ impl LocalViewer {
             appview_agent_str,
             appview_did,
             appview_cdn_url_pattern,
+            appview_video_url_pattern,
         }
     }
 
@@ -123,6 +126,7 @@ impl LocalViewer {
                 },
                 params.appview_did.clone(),
                 params.appview_cdn_url_pattern.clone(),
+                params.appview_video_url_pattern.clone(),
             )
         });
     }
@@ -142,20 +146,35 @@ impl LocalViewer {
         }
     }
 
+    pub fn get_video_url(&self, file: &str, cid: String) -> String {
+        match &self.appview_video_url_pattern {
+            None => format!(
+                "https://{}/xrpc/{}?did={}&cid={}",
+                self.pds_hostname,
+                Ids::ComAtprotoSyncGetBlob.as_str(),
+                self.did,
+                cid
+            ),
+            Some(appview_video_url_pattern) => {
+                let did =
+                    url::form_urlencoded::byte_serialize(self.did.as_bytes()).collect::<String>();
+                util::nodejs_format(&*appview_video_url_pattern, &[&did, &cid, &file])
+            }
+        }
+    }
+
     pub async fn service_auth_headers(&self, did: &String, lxm: &String) -> Result<HeaderMap> {
         match &self.appview_did {
             None => bail!("Could not find bsky appview did"),
             Some(appview_did) => {
-                let private_key = SECRET_CONFIG.repo_signing_key.clone();
-                let keypair =
-                    SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
+                let signer = AccountManager::get_signer(did).await?;
                 create_service_auth_headers(ServiceJwtParams {
                     iss: did.clone(),
                     aud: appview_did.clone(),
                     exp: None,
                     lxm: Some(lxm.clone()),
                     jti: None,
-                    keypair,
+                    signer,
                 })
                 .await
             }
@@ -305,24 +324,27 @@ impl LocalViewer {
         match embed {
             None => Ok(None),
             Some(embed) => match embed {
-                Embeds::Images(embed) => Ok(Some(
-                    self.format_simple_embed(MediaUnion::Images(embed)).await,
-                )),
-                Embeds::External(embed) => Ok(Some(
-                    self.format_simple_embed(MediaUnion::External(embed)).await,
-                )),
+                Embeds::Images(embed) => {
+                    Ok(self.format_simple_embed(MediaUnion::Images(embed)).await)
+                }
+                Embeds::External(embed) => {
+                    Ok(self.format_simple_embed(MediaUnion::External(embed)).await)
+                }
                 Embeds::Record(embed) => Ok(Some(EmbedViews::RecordView(
                     self.format_record_embed(embed).await?,
                 ))),
                 Embeds::RecordWithMedia(embed) => Ok(Some(EmbedViews::RecordWithMediaView(
                     self.format_record_with_media_embed(embed).await?,
                 ))),
-                _ => Ok(None), // @TODO: Handle video
+                Embeds::Video(embed) => {
+                    Ok(self.format_simple_embed(MediaUnion::Video(embed)).await)
+                }
             },
         }
     }
 
-    pub async fn format_simple_embed(&self, embed: MediaUnion) -> EmbedViews {
+    /// `None` for a video whose blob has neither a ref nor a legacy CID to serve it by.
+    pub async fn format_simple_embed(&self, embed: MediaUnion) -> Option<EmbedViews> {
         match embed {
             MediaUnion::Images(embed) => {
                 let images = embed
@@ -341,7 +363,7 @@ impl LocalViewer {
                         aspect_ratio: img.aspect_ratio,
                     })
                     .collect::<Vec<ViewImage>>();
-                EmbedViews::ImagesView(ImagesView { images })
+                Some(EmbedViews::ImagesView(ImagesView { images }))
             }
             MediaUnion::External(embed) => {
                 let ExternalObject {
@@ -350,7 +372,7 @@ impl LocalViewer {
                     description,
                     thumb,
                 } = embed.external;
-                EmbedViews::ExternalView(ExternalView {
+                Some(EmbedViews::ExternalView(ExternalView {
                     external: ViewExternal {
                         uri,
                         title,
@@ -363,9 +385,48 @@ impl LocalViewer {
                             )),
                         },
                     },
-                })
+                }))
+            }
+            MediaUnion::Video(embed) => {
+                let Video {
+                    video,
+                    alt,
+                    aspect_ratio,
+                    ..
+                } = embed;
+                // Blobs written in the legacy format only have the CID as a string
+                let cid = match (video.r#ref, video.cid) {
+                    (Some(cid), _) => cid,
+                    (None, Some(cid)) => lexicon_cid::Cid::from_str(&cid).ok()?,
+                    (None, None) => return None,
+                };
+                // The appview normally takes the aspect ratio from its own processing, so posts
+                // that don't set one fall back to what was probed on upload.
+                let aspect_ratio = match aspect_ratio {
+                    Some(aspect_ratio) => Some(aspect_ratio),
+                    None => match self.actor_store.blob.get_blob_metadata(cid).await {
+                        Ok(GetBlobMetadataOutput {
+                            width: Some(width),
+                            height: Some(height),
+                            ..
+                        }) => Some(AspectRatio {
+                            width: width as usize,
+                            height: height as usize,
+                        }),
+                        _ => None,
+                    },
+                };
+                Some(EmbedViews::VideoView(VideoView {
+                    cid: cid.to_string(),
+                    playlist: self.get_video_url("playlist.m3u8", cid.to_string()),
+                    thumbnail: match self.appview_video_url_pattern {
+                        None => None,
+                        Some(_) => Some(self.get_video_url("thumbnail.jpg", cid.to_string())),
+                    },
+                    alt,
+                    aspect_ratio,
+                }))
             }
-            _ => panic!("Can't handle video"), // @TODO: Handle video
         }
     }
 
@@ -474,8 +535,10 @@ impl LocalViewer {
         embed: RecordWithMedia,
     ) -> Result<RecordWithMediaView> {
         let media = match self.format_simple_embed(embed.media).await {
-            EmbedViews::ImagesView(media) => MediaViewUnion::ImagesView(media),
-            EmbedViews::ExternalView(media) => MediaViewUnion::ExternalView(media),
+            Some(EmbedViews::ImagesView(media)) => MediaViewUnion::ImagesView(media),
+            Some(EmbedViews::ExternalView(media)) => MediaViewUnion::ExternalView(media),
+            Some(EmbedViews::VideoView(media)) => MediaViewUnion::VideoView(media),
+            None => bail!("Video embed has no blob CID."),
             _ => bail!("Unexpected enum for media."),
         };
         let record = self.format_record_embed(embed.record).await?;
@@ -4,17 +4,27 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 
-use std::str::FromStr;
 // based on https://github.com/bluesky-social/atproto/blob/main/packages/aws/src/s3.ts
 use rsky_pds::common::get_random_str;
+use rsky_pds::repo::error::BlobError;
 use crate::config::S3_CONFIG;
-use anyhow::Result;
+use crate::repository::blobstore::{BlobStore, BlobStream, UploadStream};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
 use aws_config::SdkConfig;
 use aws_sdk_s3 as s3;
 use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::types::{Delete, ObjectCannedAcl, ObjectIdentifier};
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, Delete, ObjectCannedAcl, ObjectIdentifier,
+};
 use lexicon_cid::Cid;
+use tokio::io::AsyncReadExt;
+
+/// Uploads are buffered this much at a time. Anything larger goes up as a multipart upload,
+/// whose parts (other than the last) must be at least 5 MiB.
+const PART_SIZE: usize = 8 * 1024 * 1024;
 
 struct MoveObject {
     from: String,
@@ -59,111 +69,141 @@ impl S3BlobStore {
         format!("quarantine/{0}/{1}", self.bucket, cid.to_string())
     }
 
-    pub async fn put_temp(&self, bytes: Vec<u8>) -> Result<String> {
-        let key = self.gen_key();
-        let body = ByteStream::from(bytes);
-        self.client
-            .put_object()
-            .body(body)
-            .bucket(&S3_CONFIG.bucket)
-            .key(self.get_tmp_path(&key))
-            .acl(ObjectCannedAcl::PublicRead)
-            .send()
-            .await?;
-        Ok(key)
-    }
-
-    pub async fn make_permanent(&self, key: String, cid: Cid) -> Result<()> {
-        let already_has = self.has_stored(cid).await?;
-        if !already_has {
-            Ok(self
-                .move_object(MoveObject {
-                    from: self.get_tmp_path(&key),
-                    to: self.get_stored_path(cid),
-                })
-                .await?)
-        } else {
-            // already saved, so we no-op & just delete the temp
-            Ok(self.delete_key(self.get_tmp_path(&key)).await?)
-        }
+    fn get_derived_prefix(&self, cid: Cid) -> String {
+        format!("derived/{0}/{1}/", self.bucket, cid.to_string())
     }
 
-    pub async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
-        let body = ByteStream::from(bytes);
-        self.client
-            .put_object()
-            .body(body)
+    async fn delete_derived(&self, cid: Cid) -> Result<()> {
+        // A blob only has a handful of variants, well under a single page of results.
+        let res = self
+            .client
+            .list_objects_v2()
             .bucket(&S3_CONFIG.bucket)
-            .key(self.get_stored_path(cid))
-            .acl(ObjectCannedAcl::PublicRead)
+            .prefix(self.get_derived_prefix(cid))
             .send()
             .await?;
-        Ok(())
-    }
-
-    pub async fn quarantine(&self, cid: Cid) -> Result<()> {
-        Ok(self
-            .move_object(MoveObject {
-                from: self.get_stored_path(cid),
-                to: self.get_quarantined_path(cid),
-            })
-            .await?)
-    }
-
-    pub async fn unquarantine(&self, cid: Cid) -> Result<()> {
-        Ok(self
-            .move_object(MoveObject {
-                from: self.get_quarantined_path(cid),
-                to: self.get_stored_path(cid),
-            })
-            .await?)
+        let keys: Vec<String> = res
+            .contents()
+            .iter()
+            .filter_map(|object| object.key().map(str::to_string))
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.delete_many_keys(keys).await
     }
 
-    async fn get_object(&self, cid: Cid) -> Result<ByteStream> {
+    async fn get_object(&self, key: String, range: Option<String>) -> Result<ByteStream> {
         let res = self
             .client
             .get_object()
             .bucket(&S3_CONFIG.bucket)
-            .key(self.get_stored_path(cid))
+            .key(key)
+            .set_range(range)
             .send()
             .await;
         match res {
             Ok(res) => Ok(res.body),
-            Err(SdkError::ServiceError(s)) => Err(anyhow::Error::new(s.into_err())),
+            Err(SdkError::ServiceError(s)) => match s.into_err() {
+                GetObjectError::NoSuchKey(_) => Err(BlobError::BlobNotFoundError.into()),
+                e => Err(anyhow::Error::new(e)),
+            },
             Err(e) => Err(anyhow::Error::new(e.into_service_error())),
         }
     }
 
-    pub async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>> {
-        let res = self.get_object(cid).await?;
-        let bytes = res.collect().await.map(|data| data.into_bytes())?;
-        Ok(bytes.to_vec())
-    }
-
-    pub async fn get_stream(&self, cid: Cid) -> Result<ByteStream> {
-        Ok(self.get_object(cid).await?)
-    }
-
-    pub async fn delete(&self, cid: String) -> Result<()> {
-        Ok(self
-            .delete_key(self.get_stored_path(Cid::from_str(&cid)?))
-            .await?)
-    }
-
-    pub async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
-        let keys: Vec<String> = cids
-            .into_iter()
-            .map(|cid| self.get_stored_path(cid))
-            .collect();
-        Ok(self.delete_many_keys(keys).await?)
+    /// Fills `buf` from `stream` up to `PART_SIZE`, returning false once the stream is done.
+    async fn read_part(stream: UploadStream<'_>, buf: &mut Vec<u8>) -> Result<bool> {
+        buf.clear();
+        while buf.len() < PART_SIZE {
+            let read = (&mut *stream)
+                .take((PART_SIZE - buf.len()) as u64)
+                .read_to_end(buf)
+                .await?;
+            if read == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
-    pub async fn has_stored(&self, cid: Cid) -> Result<bool> {
-        Ok(self.has_key(self.get_stored_path(cid)).await)
+    async fn put_multipart(
+        &self,
+        key: &str,
+        first: Vec<u8>,
+        stream: UploadStream<'_>,
+    ) -> Result<()> {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&S3_CONFIG.bucket)
+            .key(key)
+            .acl(ObjectCannedAcl::PublicRead)
+            .send()
+            .await?;
+        let Some(upload_id) = upload.upload_id else {
+            bail!("S3 did not return an upload id");
+        };
+        let res = self.upload_parts(key, &upload_id, first, stream).await;
+        if res.is_err() {
+            let _ = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&S3_CONFIG.bucket)
+                .key(key)
+                .upload_id(&upload_id)
+                .send()
+                .await;
+        }
+        res
     }
 
-    pub async fn has_temp(&self, key: String) -> Result<bool> {
-        Ok(self.has_key(self.get_tmp_path(&key)).await)
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        mut part: Vec<u8>,
+        stream: UploadStream<'_>,
+    ) -> Result<()> {
+        let mut completed = Vec::new();
+        let mut more = true;
+        while !part.is_empty() {
+            let part_number = completed.len() as i32 + 1;
+            let mut next = Vec::with_capacity(PART_SIZE);
+            if more {
+                more = Self::read_part(stream, &mut next).await?;
+            }
+            let res = self
+                .client
+                .upload_part()
+                .bucket(&S3_CONFIG.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(part))
+                .send()
+                .await?;
+            completed.push(
+                CompletedPart::builder()
+                    .set_e_tag(res.e_tag)
+                    .part_number(part_number)
+                    .build(),
+            );
+            part = next;
+        }
+        self.client
+            .complete_multipart_upload()
+            .bucket(&S3_CONFIG.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(completed))
+                    .build(),
+            )
+            .send()
+            .await?;
+        Ok(())
     }
 
     async fn has_key(&self, key: String) -> bool {
@@ -226,4 +266,152 @@ impl S3BlobStore {
             .await?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put_temp(&self, stream: UploadStream<'_>) -> Result<String> {
+        let key = self.gen_key();
+        let path = self.get_tmp_path(&key);
+        let mut first = Vec::with_capacity(PART_SIZE);
+        if Self::read_part(stream, &mut first).await? {
+            // A full first part means there may be more to come. An upload of exactly
+            // PART_SIZE bytes ends up as a single part multipart upload, which is fine.
+            self.put_multipart(&path, first, stream).await?;
+        } else {
+            self.client
+                .put_object()
+                .body(ByteStream::from(first))
+                .bucket(&S3_CONFIG.bucket)
+                .key(path)
+                .acl(ObjectCannedAcl::PublicRead)
+                .send()
+                .await?;
+        }
+        Ok(key)
+    }
+
+    async fn make_permanent(&self, key: String, cid: Cid) -> Result<()> {
+        let already_has = self.has_stored(cid).await?;
+        if !already_has {
+            Ok(self
+                .move_object(MoveObject {
+                    from: self.get_tmp_path(&key),
+                    to: self.get_stored_path(cid),
+                })
+                .await?)
+        } else {
+            // already saved, so we no-op & just delete the temp
+            Ok(self.delete_key(self.get_tmp_path(&key)).await?)
+        }
+    }
+
+    async fn delete_temp(&self, key: String) -> Result<()> {
+        self.delete_key(self.get_tmp_path(&key)).await
+    }
+
+    async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+        let body = ByteStream::from(bytes);
+        self.client
+            .put_object()
+            .body(body)
+            .bucket(&S3_CONFIG.bucket)
+            .key(self.get_stored_path(cid))
+            .acl(ObjectCannedAcl::PublicRead)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn quarantine(&self, cid: Cid) -> Result<()> {
+        self.move_object(MoveObject {
+            from: self.get_stored_path(cid),
+            to: self.get_quarantined_path(cid),
+        })
+        .await?;
+        self.delete_derived(cid).await
+    }
+
+    async fn unquarantine(&self, cid: Cid) -> Result<()> {
+        Ok(self
+            .move_object(MoveObject {
+                from: self.get_quarantined_path(cid),
+                to: self.get_stored_path(cid),
+            })
+            .await?)
+    }
+
+    async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>> {
+        let res = self.get_object(self.get_stored_path(cid), None).await?;
+        let bytes = res.collect().await.map(|data| data.into_bytes())?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn get_stream(&self, cid: Cid) -> Result<BlobStream> {
+        Ok(Box::pin(
+            self.get_object(self.get_stored_path(cid), None)
+                .await?
+                .into_async_read(),
+        ))
+    }
+
+    async fn get_temp_stream(&self, key: String) -> Result<BlobStream> {
+        Ok(Box::pin(
+            self.get_object(self.get_tmp_path(&key), None)
+                .await?
+                .into_async_read(),
+        ))
+    }
+
+    async fn get_range(&self, cid: Cid, start: u64, end: u64) -> Result<BlobStream> {
+        let range = format!("bytes={start}-{end}");
+        Ok(Box::pin(
+            self.get_object(self.get_stored_path(cid), Some(range))
+                .await?
+                .into_async_read(),
+        ))
+    }
+
+    async fn put_derived(&self, cid: Cid, variant: String, bytes: Vec<u8>) -> Result<()> {
+        self.client
+            .put_object()
+            .body(ByteStream::from(bytes))
+            .bucket(&S3_CONFIG.bucket)
+            .key(format!("{0}{1}", self.get_derived_prefix(cid), variant))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn get_derived(&self, cid: Cid, variant: String) -> Result<Vec<u8>> {
+        let key = format!("{0}{1}", self.get_derived_prefix(cid), variant);
+        let res = self.get_object(key, None).await?;
+        let bytes = res.collect().await.map(|data| data.into_bytes())?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn delete(&self, cid: Cid) -> Result<()> {
+        self.delete_key(self.get_stored_path(cid)).await?;
+        self.delete_derived(cid).await
+    }
+
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
+        let keys: Vec<String> = cids
+            .iter()
+            .map(|cid| self.get_stored_path(*cid))
+            .collect();
+        self.delete_many_keys(keys).await?;
+        for cid in cids {
+            self.delete_derived(cid).await?;
+        }
+        Ok(())
+    }
+
+    async fn has_stored(&self, cid: Cid) -> Result<bool> {
+        Ok(self.has_key(self.get_stored_path(cid)).await)
+    }
+
+    async fn has_temp(&self, key: String) -> Result<bool> {
+        Ok(self.has_key(self.get_tmp_path(&key)).await)
+    }
+}
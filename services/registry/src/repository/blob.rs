@@ -4,29 +4,37 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
+use crate::config::CORE_CONFIG;
 use crate::database::establish_connection;
 use crate::database::models;
 use rsky_pds::common::ipld::sha256_raw_to_cid;
 use rsky_pds::common::now;
-use crate::repository::aws::s3::S3BlobStore;
+use crate::repository::blobstore::{BlobStore, BlobStream};
+use crate::repository::video::{self, VideoInfo};
 use rsky_pds::repo::blob_refs::BlobRef;
 use rsky_pds::repo::error::BlobError;
 use rsky_pds::repo::types::{PreparedBlobRef, PreparedWrite};
 use rsky_pds::{common, image};
 use anyhow::{bail, Result};
-use aws_sdk_s3::operation::get_object::GetObjectError;
-use aws_sdk_s3::primitives::ByteStream;
 use diesel::dsl::{count_distinct, exists, not};
 use diesel::sql_types::{Integer, Nullable, Text};
 use diesel::*;
 use futures::stream::{self, StreamExt};
-use futures::try_join;
 use lexicon_cid::Cid;
 use rocket::data::{Data, ToByteUnit};
 use rocket::form::validate::Contains;
 use rsky_lexicon::com::atproto::admin::StatusAttr;
 use rsky_lexicon::com::atproto::repo::ListMissingBlobsRefRecordBlob;
 use sha2::{Digest, Sha256};
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// How much of an upload is kept in memory for mime type and image sniffing.
+const SNIFF_LEN: usize = 256 * 1024;
 
 #[derive(Debug, Clone)]
 pub struct BlobMetadata {
@@ -36,11 +44,62 @@ pub struct BlobMetadata {
     pub mime_type: String,
     pub width: Option<i32>,
     pub height: Option<i32>,
+    /// Milliseconds, for videos.
+    pub duration: Option<i32>,
+    pub container: Option<String>,
+    pub codec: Option<String>,
+}
+
+/// Hashes and counts an upload as it streams into the blobstore, keeping the first `SNIFF_LEN`
+/// bytes for sniffing. Reads fail once more than `limit` bytes have come through.
+struct UploadReader<'r> {
+    inner: Pin<Box<dyn AsyncRead + Send + 'r>>,
+    limit: usize,
+    size: usize,
+    hasher: Sha256,
+    head: Vec<u8>,
+}
+
+impl<'r> UploadReader<'r> {
+    fn new(inner: Pin<Box<dyn AsyncRead + Send + 'r>>, limit: usize) -> Self {
+        UploadReader {
+            inner,
+            limit,
+            size: 0,
+            hasher: Sha256::new(),
+            head: Vec::new(),
+        }
+    }
+}
+
+impl AsyncRead for UploadReader<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(this.inner.as_mut().poll_read(cx, buf))?;
+        this.size += buf.filled().len() - before;
+        if this.size > this.limit {
+            buf.set_filled(before);
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "upload is larger than the blob upload limit",
+            )));
+        }
+        let read = &buf.filled()[before..];
+        this.hasher.update(read);
+        let keep = SNIFF_LEN.saturating_sub(this.head.len()).min(read.len());
+        this.head.extend_from_slice(&read[..keep]);
+        Poll::Ready(Ok(()))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct BlobReader {
-    pub blobstore: S3BlobStore,
+    pub blobstore: Arc<dyn BlobStore>,
     pub did: String,
 }
 
@@ -57,26 +116,24 @@ pub struct ListBlobsOpts {
     pub limit: u16,
 }
 
-#[derive(Debug)]
 pub struct GetBlobOutput {
     pub size: i32,
     pub mime_type: Option<String>,
-    pub stream: ByteStream,
+    pub stream: BlobStream,
 }
 
 #[derive(Debug, Clone)]
 pub struct GetBlobMetadataOutput {
     pub size: i32,
     pub mime_type: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
 }
 
 // Basically handles getting blob records from db
 impl BlobReader {
-    pub fn new(blobstore: S3BlobStore) -> Self {
-        BlobReader {
-            did: blobstore.bucket.clone(),
-            blobstore,
-        }
+    pub fn new(did: String, blobstore: Arc<dyn BlobStore>) -> Self {
+        BlobReader { did, blobstore }
     }
 
     pub async fn get_blob_metadata(&self, cid: Cid) -> Result<GetBlobMetadataOutput> {
@@ -92,27 +149,19 @@ impl BlobReader {
             .optional()?;
 
         match found {
-            None => bail!("Blob not found"),
+            None => Err(BlobError::BlobNotFoundError.into()),
             Some(found) => Ok(GetBlobMetadataOutput {
                 size: found.size,
                 mime_type: Some(found.mime_type),
+                width: found.width,
+                height: found.height,
             }),
         }
     }
 
     pub async fn get_blob(&self, cid: Cid) -> Result<GetBlobOutput> {
         let metadata = self.get_blob_metadata(cid).await?;
-        let blob_stream = match self.blobstore.get_stream(cid).await {
-            Ok(res) => res,
-            Err(e) => {
-                return match e.downcast_ref() {
-                    Some(GetObjectError::NoSuchKey(key)) => {
-                        Err(anyhow::Error::new(GetObjectError::NoSuchKey(key.clone())))
-                    }
-                    _ => bail!(e.to_string()),
-                }
-            }
-        };
+        let blob_stream = self.blobstore.get_stream(cid).await?;
         Ok(GetBlobOutput {
             size: metadata.size,
             mime_type: metadata.mime_type,
@@ -141,38 +190,71 @@ impl BlobReader {
         user_suggested_mime: String,
         blob: Data<'_>,
     ) -> Result<BlobMetadata> {
-        let blob_stream = blob.open(100.mebibytes());
-        let bytes = blob_stream.into_bytes().await?;
-        let size = bytes.n.written;
-        let bytes = bytes.into_inner();
-        let (temp_key, sha256, img_info, sniffed_mime) = try_join!(
-            self.blobstore.put_temp(bytes.clone()),
-            sha256_stream(bytes.clone()),
-            image::maybe_get_info(bytes.clone()),
-            image::mime_type_from_bytes(bytes.clone())
-        )?;
-
-        let cid = sha256_raw_to_cid(sha256);
+        let limit = CORE_CONFIG.blob_upload_limit();
+        // Open one byte past the limit so an upload that's too large can be told apart from one
+        // that fills it exactly.
+        let mut upload = UploadReader::new(Box::pin(blob.open((limit + 1).bytes())), limit);
+        let temp_key = match self.blobstore.put_temp(&mut upload).await {
+            Ok(temp_key) => temp_key,
+            Err(_) if upload.size > limit => {
+                bail!("BlobTooLarge: This file is too large. The maximum size is {limit} bytes")
+            }
+            Err(e) => return Err(e),
+        };
+        let UploadReader {
+            size, hasher, head, ..
+        } = upload;
+
+        let cid = sha256_raw_to_cid(hasher.finalize().to_vec());
+        // Only the head of the upload is sniffed, which can be too short for image headers with
+        // large metadata segments. That isn't worth failing the upload over.
+        let img_info = image::maybe_get_info(head.clone()).await.unwrap_or(None);
+        let sniffed_mime = image::mime_type_from_bytes(head).await?;
         let mime_type = sniffed_mime.unwrap_or(user_suggested_mime);
+        let video_info = if mime_type.starts_with("video/") {
+            self.probe_video(&temp_key, &mime_type).await
+        } else {
+            None
+        };
+        let (width, height) = match (img_info, &video_info) {
+            (Some(info), _) => (Some(info.width as i32), Some(info.height as i32)),
+            (None, Some(info)) => (info.width, info.height),
+            (None, None) => (None, None),
+        };
 
         Ok(BlobMetadata {
             temp_key,
             size: size as i64,
             cid,
             mime_type,
-            width: if let Some(ref info) = img_info {
-                Some(info.width.clone() as i32)
-            } else {
-                None
-            },
-            height: if let Some(info) = img_info {
-                Some(info.height as i32)
-            } else {
-                None
-            },
+            width,
+            height,
+            duration: video_info.as_ref().and_then(|info| info.duration),
+            container: video_info.as_ref().map(|info| info.container.clone()),
+            codec: video_info.and_then(|info| info.codec),
         })
     }
 
+    /// Reads the uploaded video back from the blobstore to probe it. The metadata is only
+    /// informational, so a file that can't be probed is still accepted.
+    async fn probe_video(&self, temp_key: &str, mime_type: &str) -> Option<VideoInfo> {
+        let res = async {
+            let mut stream = self.blobstore.get_temp_stream(temp_key.to_string()).await?;
+            video::probe(mime_type, &mut stream).await
+        }
+        .await;
+        match res {
+            Ok(info) => info,
+            Err(error) => {
+                eprintln!(
+                    "@LOG: couldn't probe {mime_type} upload for {}: {error}",
+                    self.did
+                );
+                None
+            }
+        }
+    }
+
     pub async fn track_untethered_blob(&self, metadata: BlobMetadata) -> Result<BlobRef> {
         use crate::schema::registry::blob::dsl as BlobSchema;
         let conn = &mut establish_connection()?;
@@ -183,6 +265,9 @@ impl BlobReader {
             mime_type,
             width,
             height,
+            duration,
+            container,
+            codec,
         } = metadata;
         let created_at = now();
 
@@ -199,9 +284,9 @@ impl BlobReader {
             }
         }
 
-        let upsert = sql_query("INSERT INTO registry.blob (cid, did, \"mimeType\", size, \"tempKey\", width, height, \"createdAt\", \"takedownRef\") \
+        let upsert = sql_query("INSERT INTO registry.blob (cid, did, \"mimeType\", size, \"tempKey\", width, height, duration, container, codec, \"createdAt\", \"takedownRef\") \
         VALUES \
-            ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
         ON CONFLICT (cid, did) DO UPDATE \
         SET \"tempKey\" = EXCLUDED.\"tempKey\" \
             WHERE registry.blob.\"tempKey\" is not null;");
@@ -213,6 +298,9 @@ impl BlobReader {
             .bind::<Nullable<Text>, _>(Some(temp_key.clone()))
             .bind::<Nullable<Integer>, _>(width)
             .bind::<Nullable<Integer>, _>(height)
+            .bind::<Nullable<Integer>, _>(duration)
+            .bind::<Nullable<Text>, _>(container)
+            .bind::<Nullable<Text>, _>(codec)
             .bind::<Text, _>(created_at)
             .bind::<Nullable<Text>, _>(None as Option<String>)
             .execute(conn)?;
@@ -317,7 +405,9 @@ impl BlobReader {
 
         // Original code queues a background job to delete by CID from S3 compatible blobstore
         let _ = stream::iter(cids_to_delete)
-            .then(|cid| async { Ok::<(), anyhow::Error>(self.blobstore.delete(cid).await?) })
+            .then(|cid| async move {
+                Ok::<(), anyhow::Error>(self.blobstore.delete(Cid::from_str(&cid)?).await?)
+            })
             .collect::<Vec<_>>()
             .await
             .into_iter()
@@ -370,6 +460,17 @@ impl BlobReader {
         Ok(())
     }
 
+    pub async fn disassociate_record(&self, record_uri: &String) -> Result<()> {
+        use crate::schema::registry::record_blob::dsl as RecordBlobSchema;
+        let conn = &mut establish_connection()?;
+
+        delete(RecordBlobSchema::record_blob)
+            .filter(RecordBlobSchema::recordUri.eq(record_uri))
+            .filter(RecordBlobSchema::did.eq(&self.did))
+            .execute(conn)?;
+        Ok(())
+    }
+
     pub async fn blob_count(&self) -> Result<i64> {
         use crate::schema::registry::blob::dsl as BlobSchema;
         let conn = &mut establish_connection()?;
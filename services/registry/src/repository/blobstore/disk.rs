@@ -0,0 +1,267 @@
+use crate::repository::blobstore::{BlobStore, BlobStream, UploadStream};
+use anyhow::Result;
+use async_trait::async_trait;
+use lexicon_cid::Cid;
+use rsky_pds::common::get_random_str;
+use rsky_pds::repo::error::BlobError;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Stores blobs on the local filesystem using the same layout as the S3 store:
+/// `tmp/<did>/<key>`, `blocks/<did>/<cid>`, `quarantine/<did>/<cid>` and
+/// `derived/<did>/<cid>/<variant>` under `location`.
+#[derive(Debug, Clone)]
+pub struct DiskBlobStore {
+    did: String,
+    location: PathBuf,
+}
+
+impl DiskBlobStore {
+    pub fn new(did: String, location: PathBuf) -> Self {
+        DiskBlobStore { did, location }
+    }
+
+    /// DIDs contain `:`, which isn't allowed in Windows paths, so everything outside a small
+    /// safe set is percent-encoded.
+    fn did_dir(&self) -> String {
+        self.did
+            .bytes()
+            .map(|b| match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            })
+            .collect()
+    }
+
+    fn get_tmp_path(&self, key: &str) -> PathBuf {
+        self.location.join("tmp").join(self.did_dir()).join(key)
+    }
+
+    fn get_stored_path(&self, cid: Cid) -> PathBuf {
+        self.location
+            .join("blocks")
+            .join(self.did_dir())
+            .join(cid.to_string())
+    }
+
+    fn get_quarantined_path(&self, cid: Cid) -> PathBuf {
+        self.location
+            .join("quarantine")
+            .join(self.did_dir())
+            .join(cid.to_string())
+    }
+
+    fn get_derived_dir(&self, cid: Cid) -> PathBuf {
+        self.location
+            .join("derived")
+            .join(self.did_dir())
+            .join(cid.to_string())
+    }
+
+    async fn remove_derived(&self, cid: Cid) -> Result<()> {
+        match fs::remove_dir_all(self.get_derived_dir(cid)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn write(path: &Path, stream: UploadStream<'_>) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut file = fs::File::create(path).await?;
+        let written = async {
+            tokio::io::copy(stream, &mut file).await?;
+            file.flush().await
+        }
+        .await;
+        if let Err(e) = written {
+            drop(file);
+            Self::remove(path).await?;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    async fn open(path: &Path) -> Result<fs::File> {
+        match fs::File::open(path).await {
+            Ok(file) => Ok(file),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(BlobError::BlobNotFoundError.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn move_file(from: &Path, to: &Path) -> Result<()> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        match fs::rename(from, to).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(BlobError::BlobNotFoundError.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn remove(path: &Path) -> Result<()> {
+        match fs::remove_file(path).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for DiskBlobStore {
+    async fn put_temp(&self, stream: UploadStream<'_>) -> Result<String> {
+        let key = get_random_str();
+        Self::write(&self.get_tmp_path(&key), stream).await?;
+        Ok(key)
+    }
+
+    async fn make_permanent(&self, key: String, cid: Cid) -> Result<()> {
+        if self.has_stored(cid).await? {
+            // already saved, so we no-op & just delete the temp
+            Self::remove(&self.get_tmp_path(&key)).await
+        } else {
+            Self::move_file(&self.get_tmp_path(&key), &self.get_stored_path(cid)).await
+        }
+    }
+
+    async fn delete_temp(&self, key: String) -> Result<()> {
+        Self::remove(&self.get_tmp_path(&key)).await
+    }
+
+    async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+        // Write under a temp key first so a partial write never shows up as a stored blob.
+        let key = self.put_temp(&mut bytes.as_slice()).await?;
+        Self::move_file(&self.get_tmp_path(&key), &self.get_stored_path(cid)).await
+    }
+
+    async fn quarantine(&self, cid: Cid) -> Result<()> {
+        Self::move_file(&self.get_stored_path(cid), &self.get_quarantined_path(cid)).await?;
+        self.remove_derived(cid).await
+    }
+
+    async fn unquarantine(&self, cid: Cid) -> Result<()> {
+        Self::move_file(&self.get_quarantined_path(cid), &self.get_stored_path(cid)).await
+    }
+
+    async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>> {
+        match fs::read(self.get_stored_path(cid)).await {
+            Ok(bytes) => Ok(bytes),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(BlobError::BlobNotFoundError.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_stream(&self, cid: Cid) -> Result<BlobStream> {
+        Ok(Box::pin(Self::open(&self.get_stored_path(cid)).await?))
+    }
+
+    async fn get_temp_stream(&self, key: String) -> Result<BlobStream> {
+        Ok(Box::pin(Self::open(&self.get_tmp_path(&key)).await?))
+    }
+
+    async fn get_range(&self, cid: Cid, start: u64, end: u64) -> Result<BlobStream> {
+        let mut file = Self::open(&self.get_stored_path(cid)).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok(Box::pin(file.take(end - start + 1)))
+    }
+
+    async fn put_derived(&self, cid: Cid, variant: String, bytes: Vec<u8>) -> Result<()> {
+        // Written to temp storage and moved into place so readers never see a partial variant.
+        let key = self.put_temp(&mut bytes.as_slice()).await?;
+        Self::move_file(
+            &self.get_tmp_path(&key),
+            &self.get_derived_dir(cid).join(variant),
+        )
+        .await
+    }
+
+    async fn get_derived(&self, cid: Cid, variant: String) -> Result<Vec<u8>> {
+        match fs::read(self.get_derived_dir(cid).join(variant)).await {
+            Ok(bytes) => Ok(bytes),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(BlobError::BlobNotFoundError.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn has_temp(&self, key: String) -> Result<bool> {
+        Ok(fs::try_exists(self.get_tmp_path(&key)).await?)
+    }
+
+    async fn has_stored(&self, cid: Cid) -> Result<bool> {
+        Ok(fs::try_exists(self.get_stored_path(cid)).await?)
+    }
+
+    async fn delete(&self, cid: Cid) -> Result<()> {
+        Self::remove(&self.get_stored_path(cid)).await?;
+        self.remove_derived(cid).await
+    }
+
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
+        for cid in cids {
+            self.delete(cid).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_pds::common::ipld::sha256_raw_to_cid;
+    use sha2::{Digest, Sha256};
+
+    #[tokio::test]
+    async fn moves_blobs_between_temp_stored_and_quarantine() {
+        let location = std::env::temp_dir().join(format!("registry-blobs-{}", get_random_str()));
+        let store = DiskBlobStore::new("did:plc:example".to_string(), location.clone());
+        let bytes = b"hello blob".to_vec();
+        let cid = sha256_raw_to_cid(Sha256::digest(&bytes).to_vec());
+
+        let key = store.put_temp(&mut bytes.as_slice()).await.unwrap();
+        assert!(store.has_temp(key.clone()).await.unwrap());
+        let abandoned = store.put_temp(&mut bytes.as_slice()).await.unwrap();
+        store.delete_temp(abandoned.clone()).await.unwrap();
+        assert!(!store.has_temp(abandoned).await.unwrap());
+        store.make_permanent(key.clone(), cid).await.unwrap();
+        assert!(!store.has_temp(key).await.unwrap());
+        assert_eq!(store.get_bytes(cid).await.unwrap(), bytes);
+        let mut range = Vec::new();
+        let mut stream = store.get_range(cid, 6, 9).await.unwrap();
+        stream.read_to_end(&mut range).await.unwrap();
+        assert_eq!(range, b"blob");
+        store
+            .put_derived(cid, "avatar@jpeg".to_string(), b"small".to_vec())
+            .await
+            .unwrap();
+        let variant = store.get_derived(cid, "avatar@jpeg".to_string()).await;
+        assert_eq!(variant.unwrap(), b"small");
+        assert!(location
+            .join("blocks/did%3Aplc%3Aexample")
+            .join(cid.to_string())
+            .exists());
+
+        store.quarantine(cid).await.unwrap();
+        assert!(!store.has_stored(cid).await.unwrap());
+        assert!(store
+            .get_derived(cid, "avatar@jpeg".to_string())
+            .await
+            .is_err());
+        let err = store.get_bytes(cid).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(BlobError::BlobNotFoundError)
+        ));
+        store.unquarantine(cid).await.unwrap();
+
+        store.delete_many(vec![cid]).await.unwrap();
+        assert!(!store.has_stored(cid).await.unwrap());
+        fs::remove_dir_all(location).await.unwrap();
+    }
+}
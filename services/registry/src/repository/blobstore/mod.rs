@@ -0,0 +1,80 @@
+use crate::config::{BlobStoreConfig, S3_CONFIG};
+use crate::repository::aws::s3::S3BlobStore;
+use anyhow::Result;
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use disk::DiskBlobStore;
+use lexicon_cid::Cid;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::AsyncRead;
+
+pub mod disk;
+
+pub type BlobStream = Pin<Box<dyn AsyncRead + Send>>;
+
+/// A borrowed upload body. Borrowed rather than boxed so request bodies that aren't `'static`
+/// can be streamed straight into storage.
+pub type UploadStream<'a> = &'a mut (dyn AsyncRead + Send + Unpin);
+
+/// Storage for a single account's blobs.
+///
+/// Uploads are kept under a random temp key until a record references them, at which point
+/// they're made permanent under their CID. Taken down blobs are moved to quarantine rather than
+/// deleted so the takedown can be reversed. Reads of missing blobs fail with
+/// `BlobError::BlobNotFoundError`.
+///
+/// Derived variants of a stored blob, such as resized images, are kept under the blob's CID and
+/// a variant name. Quarantining or deleting the blob removes them too.
+#[async_trait]
+pub trait BlobStore: Debug + Send + Sync {
+    /// Streams an upload into temp storage and returns its temp key. If reading `stream` fails
+    /// nothing is left behind under the key.
+    async fn put_temp(&self, stream: UploadStream<'_>) -> Result<String>;
+    async fn make_permanent(&self, key: String, cid: Cid) -> Result<()>;
+    /// Removes an upload that was never made permanent. Missing keys aren't an error.
+    async fn delete_temp(&self, key: String) -> Result<()>;
+    async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()>;
+    async fn quarantine(&self, cid: Cid) -> Result<()>;
+    async fn unquarantine(&self, cid: Cid) -> Result<()>;
+    async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>>;
+    async fn get_stream(&self, cid: Cid) -> Result<BlobStream>;
+    async fn get_temp_stream(&self, key: String) -> Result<BlobStream>;
+    /// Streams the inclusive byte range `start..=end` of a stored blob.
+    async fn get_range(&self, cid: Cid, start: u64, end: u64) -> Result<BlobStream>;
+    async fn put_derived(&self, cid: Cid, variant: String, bytes: Vec<u8>) -> Result<()>;
+    async fn get_derived(&self, cid: Cid, variant: String) -> Result<Vec<u8>>;
+    async fn has_temp(&self, key: String) -> Result<bool>;
+    async fn has_stored(&self, cid: Cid) -> Result<bool>;
+    async fn delete(&self, cid: Cid) -> Result<()>;
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<()>;
+}
+
+/// Opens the configured backend for an account. Managed by Rocket so endpoints can build an
+/// `ActorStore` without knowing which backend is in use.
+#[derive(Debug, Clone)]
+pub enum BlobStoreCreator {
+    S3(SdkConfig),
+    Disk(PathBuf),
+}
+
+impl BlobStoreCreator {
+    pub async fn from_config(config: &BlobStoreConfig) -> Result<Self> {
+        match config {
+            BlobStoreConfig::S3 => Ok(Self::S3(S3_CONFIG.to_sdk_config().await)),
+            BlobStoreConfig::Disk { location } => {
+                tokio::fs::create_dir_all(location).await?;
+                Ok(Self::Disk(PathBuf::from(location)))
+            }
+        }
+    }
+
+    pub fn create(&self, did: String) -> Arc<dyn BlobStore> {
+        match self {
+            Self::S3(cfg) => Arc::new(S3BlobStore::new(did, cfg)),
+            Self::Disk(location) => Arc::new(DiskBlobStore::new(did, location.clone())),
+        }
+    }
+}
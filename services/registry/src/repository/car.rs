@@ -0,0 +1,175 @@
+use anyhow::{bail, Result};
+use futures::stream::Stream;
+use lexicon_cid::Cid;
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::Ipld;
+use rsky_pds::repo::block_map::BlockMap;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::pin::Pin;
+
+/// A CAR archive produced a chunk at a time, so it can be sent while the rest is still being read.
+pub type CarStream = Pin<Box<dyn Stream<Item = Result<Vec<u8>>> + Send>>;
+
+#[derive(Debug)]
+pub struct CarWithRoot {
+    pub root: Cid,
+    pub blocks: BlockMap,
+}
+
+/// Encodes a CARv1 archive incrementally. The header is written on creation and each block as
+/// it's added; `take` hands back everything written since the last call, so only one chunk is
+/// ever held in memory.
+#[derive(Debug)]
+pub struct CarWriter {
+    buf: Vec<u8>,
+}
+
+impl CarWriter {
+    pub fn new(root: Option<&Cid>) -> Result<Self> {
+        let mut header = BTreeMap::new();
+        header.insert(
+            "roots".to_string(),
+            Ipld::List(root.into_iter().map(|root| Ipld::Link(*root)).collect()),
+        );
+        header.insert("version".to_string(), Ipld::Integer(1));
+        let header = DagCborCodec.encode(&Ipld::Map(header))?;
+
+        let mut writer = CarWriter { buf: Vec::new() };
+        writer.write_varint(header.len());
+        writer.buf.extend_from_slice(&header);
+        Ok(writer)
+    }
+
+    pub fn write_block(&mut self, cid: &Cid, bytes: &[u8]) {
+        let cid = cid.to_bytes();
+        self.write_varint(cid.len() + bytes.len());
+        self.buf.extend_from_slice(&cid);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+
+    fn write_varint(&mut self, n: usize) {
+        let mut buf = unsigned_varint::encode::u64_buffer();
+        self.buf
+            .extend_from_slice(unsigned_varint::encode::u64(n as u64, &mut buf));
+    }
+}
+
+/// Streams an archive of blocks that are already loaded, such as those for `getBlocks`.
+pub fn blocks_to_car_stream(root: Option<Cid>, blocks: BlockMap) -> Result<CarStream> {
+    let mut car = CarWriter::new(root.as_ref())?;
+    for block in blocks.entries()? {
+        car.write_block(&block.cid, &block.bytes);
+    }
+    Ok(Box::pin(futures::stream::once(
+        async move { Ok(car.take()) },
+    )))
+}
+
+/// Parse a CARv1 archive into its roots and blocks.
+pub fn read_car(bytes: &[u8]) -> Result<(Vec<Cid>, BlockMap)> {
+    let (header_len, rest) = unsigned_varint::decode::u64(bytes)?;
+    let header_len = header_len as usize;
+    if rest.len() < header_len {
+        bail!("Invalid CAR: truncated header");
+    }
+    let header: Ipld = DagCborCodec.decode(&rest[..header_len])?;
+    let roots = match header {
+        Ipld::Map(ref header) => {
+            match header.get("version") {
+                Some(Ipld::Integer(1)) => (),
+                _ => bail!("Invalid CAR: unsupported version"),
+            }
+            match header.get("roots") {
+                Some(Ipld::List(roots)) => roots
+                    .iter()
+                    .map(|root| match root {
+                        Ipld::Link(cid) => Ok(*cid),
+                        _ => bail!("Invalid CAR: root is not a cid"),
+                    })
+                    .collect::<Result<Vec<Cid>>>()?,
+                _ => bail!("Invalid CAR: missing roots"),
+            }
+        }
+        _ => bail!("Invalid CAR: header is not a map"),
+    };
+
+    let mut blocks = BlockMap::new();
+    let mut remaining = &rest[header_len..];
+    while !remaining.is_empty() {
+        let (section_len, rest) = unsigned_varint::decode::u64(remaining)?;
+        let section_len = section_len as usize;
+        if rest.len() < section_len {
+            bail!("Invalid CAR: truncated block");
+        }
+        let section = &rest[..section_len];
+        let mut reader = Cursor::new(section);
+        let cid = Cid::read_bytes(&mut reader)?;
+        let content = section[reader.position() as usize..].to_vec();
+        blocks.set(cid, content);
+        remaining = &rest[section_len..];
+    }
+    Ok((roots, blocks))
+}
+
+/// Parse a CARv1 archive that is expected to have exactly one root (i.e. a repo export).
+pub fn read_car_with_root(bytes: &[u8]) -> Result<CarWithRoot> {
+    let (roots, blocks) = read_car(bytes)?;
+    if roots.len() != 1 {
+        bail!("Expected one root, got {}", roots.len());
+    }
+    Ok(CarWithRoot {
+        root: roots[0],
+        blocks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_pds::car::read_car_bytes;
+
+    #[actix_rt::test]
+    async fn reads_car_written_by_read_car_bytes() -> Result<()> {
+        let mut blocks = BlockMap::new();
+        let first = blocks.add(String::from("first"))?;
+        let second = blocks.add(String::from("second"))?;
+        let car = read_car_bytes(Some(&first), blocks).await?;
+
+        let parsed = read_car_with_root(&car)?;
+        assert_eq!(parsed.root, first);
+        assert!(parsed.blocks.has(first));
+        assert!(parsed.blocks.has(second));
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn reads_car_written_incrementally() -> Result<()> {
+        let mut blocks = BlockMap::new();
+        let first = blocks.add(String::from("first"))?;
+        let second = blocks.add(String::from("second"))?;
+
+        let mut car = CarWriter::new(Some(&first))?;
+        let mut bytes = car.take();
+        for block in blocks.entries()? {
+            car.write_block(&block.cid, &block.bytes);
+            bytes.extend(car.take());
+        }
+
+        let parsed = read_car_with_root(&bytes)?;
+        assert_eq!(parsed.root, first);
+        assert!(parsed.blocks.has(first));
+        assert!(parsed.blocks.has(second));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_truncated_car() {
+        assert!(read_car(&[0x0a, 0xa2]).is_err());
+    }
+}
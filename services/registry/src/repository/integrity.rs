@@ -0,0 +1,384 @@
+//! Checks that a stored repo is self-consistent: every block reachable from the root is in
+//! `repo_block`, and the `record` and `record_blob` indexes agree with what the MST holds.
+//! The MST is treated as the source of truth, so the indexes can be rebuilt from it.
+use crate::database::establish_connection;
+use crate::repository::mst::walker::{MstWalker, WalkerStatus};
+use crate::repository::mst::NodeEntry;
+use crate::repository::storage::RepoReader;
+use crate::repository::{ActorStore, Repo};
+use anyhow::Result;
+use diesel::*;
+use lexicon_cid::Cid;
+use rsky_pds::repo::types::WriteOpAction;
+use rsky_pds::repo::{prepare_create, util, PrepareCreateOpts};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+    pub did: String,
+    pub commit: Option<String>,
+    pub rev: Option<String>,
+    /// `None` if the repo has no commit to check or the DID's signing key couldn't be resolved.
+    #[serde(rename = "validSignature")]
+    pub valid_signature: Option<bool>,
+    /// Commit, MST node and record blocks that are reachable from the root but not stored.
+    #[serde(rename = "missingBlocks")]
+    pub missing_blocks: Vec<String>,
+    /// Records in the MST without a row in `record`.
+    #[serde(rename = "unindexedRecords")]
+    pub unindexed_records: Vec<String>,
+    /// Rows in `record` for records that aren't in the MST.
+    #[serde(rename = "staleRecords")]
+    pub stale_records: Vec<String>,
+    /// Rows in `record` whose CID differs from the one in the MST.
+    #[serde(rename = "mismatchedRecords")]
+    pub mismatched_records: Vec<String>,
+    /// Blob refs inside records without a row in `record_blob`, as `<record uri>#<blob cid>`.
+    #[serde(rename = "unindexedRecordBlobs")]
+    pub unindexed_record_blobs: Vec<String>,
+    /// Rows in `record_blob` that no record refers to, as `<record uri>#<blob cid>`.
+    #[serde(rename = "staleRecordBlobs")]
+    pub stale_record_blobs: Vec<String>,
+    /// Whether the `record` and `backlink` indexes were rebuilt from the MST.
+    pub repaired: bool,
+}
+
+impl IntegrityReport {
+    pub fn is_consistent(&self) -> bool {
+        self.valid_signature != Some(false)
+            && self.missing_blocks.is_empty()
+            && self.unindexed_records.is_empty()
+            && self.stale_records.is_empty()
+            && self.mismatched_records.is_empty()
+            && self.unindexed_record_blobs.is_empty()
+            && self.stale_record_blobs.is_empty()
+    }
+}
+
+/// Walks the repo of `actor_store` and reports where the stored blocks and indexes disagree
+/// with it. The commit signature is checked against `did_key` when given. With `repair`, every
+/// record that could be read is re-indexed (recreating its backlinks) and rows for records that
+/// aren't in the MST are removed; blocks that are missing can't be recovered this way. Rows that
+/// are re-indexed keep their `repoRev`, and only records without a row get the head rev.
+pub async fn verify_repo(
+    actor_store: &ActorStore,
+    did_key: Option<&String>,
+    repair: bool,
+) -> Result<IntegrityReport> {
+    let did = actor_store.did.clone();
+    let mut report = IntegrityReport {
+        did: did.clone(),
+        ..Default::default()
+    };
+    let root = match actor_store.storage.get_root().await {
+        None => return Ok(report),
+        Some(root) => root,
+    };
+    report.commit = Some(root.to_string());
+
+    let mut storage = RepoReader::new(None, did.clone(), None);
+    if !storage.has(root)? {
+        report.missing_blocks.push(root.to_string());
+        return Ok(report);
+    }
+    let repo = Repo::load(&mut storage, Some(root)).await?;
+    let commit = repo.commit();
+    report.rev = Some(commit.rev.clone());
+    if let Some(did_key) = did_key {
+        report.valid_signature = Some(util::verify_commit_sig(commit.clone(), did_key)?);
+    }
+
+    // Record URI to its CID and the blobs it refers to, for every record the MST holds
+    let mut leaves: BTreeMap<String, Cid> = BTreeMap::new();
+    let mut blob_refs: BTreeSet<(String, String)> = BTreeSet::new();
+    let mut readable: Vec<(String, Cid)> = Vec::new();
+    let mut walker = MstWalker::new(repo.data.clone());
+    while let WalkerStatus::WalkerStatusProgress(ref progress) = walker.status {
+        match progress.curr {
+            NodeEntry::MST(ref node) => {
+                if !storage.has(node.pointer)? {
+                    report.missing_blocks.push(node.pointer.to_string());
+                    walker.step_over()?;
+                    continue;
+                }
+            }
+            NodeEntry::Leaf(ref leaf) => {
+                let uri = format!("at://{did}/{0}", leaf.key);
+                leaves.insert(uri.clone(), leaf.value);
+                if !storage.has(leaf.value)? {
+                    report.missing_blocks.push(leaf.value.to_string());
+                } else {
+                    let path = util::parse_data_key(&leaf.key)?;
+                    let write = prepare_create(PrepareCreateOpts {
+                        did: did.clone(),
+                        collection: path.collection,
+                        rkey: Some(path.rkey),
+                        swap_cid: None,
+                        record: storage.read_record(&leaf.value)?,
+                        validate: Some(false),
+                    })
+                    .await?;
+                    for blob in write.blobs {
+                        blob_refs.insert((uri.clone(), blob.cid.to_string()));
+                    }
+                    readable.push((uri, leaf.value));
+                }
+            }
+        }
+        walker.advance()?;
+    }
+
+    let indexed = indexed_records(&did)?;
+    for (uri, cid) in &leaves {
+        match indexed.get(uri) {
+            None => report.unindexed_records.push(uri.clone()),
+            Some((indexed_cid, _)) if indexed_cid != &cid.to_string() => {
+                report.mismatched_records.push(uri.clone())
+            }
+            Some(_) => (),
+        }
+    }
+    report.stale_records = indexed
+        .keys()
+        .filter(|uri| !leaves.contains_key(*uri))
+        .cloned()
+        .collect();
+
+    let indexed_blobs = indexed_record_blobs(&did)?;
+    report.unindexed_record_blobs = blob_refs
+        .difference(&indexed_blobs)
+        .map(|(uri, cid)| format!("{uri}#{cid}"))
+        .collect();
+    report.stale_record_blobs = indexed_blobs
+        .difference(&blob_refs)
+        .map(|(uri, cid)| format!("{uri}#{cid}"))
+        .collect();
+
+    if repair {
+        for (uri, cid) in readable {
+            let record = storage.read_record(&cid)?;
+            let repo_rev = match indexed.get(&uri) {
+                Some((_, Some(repo_rev))) => repo_rev.clone(),
+                _ => commit.rev.clone(),
+            };
+            actor_store
+                .record
+                .index_record(
+                    uri,
+                    cid,
+                    Some(record),
+                    Some(WriteOpAction::Update),
+                    repo_rev,
+                    None,
+                )
+                .await?;
+        }
+        for uri in &report.stale_records {
+            actor_store.record.delete_record(uri.clone()).await?;
+        }
+        report.repaired = true;
+    }
+    Ok(report)
+}
+
+/// Record URI to the CID and `repoRev` of its row in `record`.
+fn indexed_records(did: &String) -> Result<BTreeMap<String, (String, Option<String>)>> {
+    use crate::schema::registry::record::dsl as RecordSchema;
+    let conn = &mut establish_connection()?;
+
+    let rows: Vec<(String, String, Option<String>)> = RecordSchema::record
+        .filter(RecordSchema::did.eq(did))
+        .select((RecordSchema::uri, RecordSchema::cid, RecordSchema::repoRev))
+        .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|(uri, cid, repo_rev)| (uri, (cid, repo_rev)))
+        .collect())
+}
+
+fn indexed_record_blobs(did: &String) -> Result<BTreeSet<(String, String)>> {
+    use crate::schema::registry::record_blob::dsl as RecordBlobSchema;
+    let conn = &mut establish_connection()?;
+
+    let rows: Vec<(String, String)> = RecordBlobSchema::record_blob
+        .filter(RecordBlobSchema::did.eq(did))
+        .select((RecordBlobSchema::recordUri, RecordBlobSchema::blobCid))
+        .load(conn)?;
+    Ok(rows.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::blobstore::BlobStoreCreator;
+    use crate::signer::{LocalSigner, Signer, REPO_SIGNER};
+    use rsky_pds::common::get_random_str;
+    use rsky_pds::repo::types::PreparedWrite;
+    use secp256k1::SecretKey;
+
+    #[test]
+    fn reports_any_discrepancy_as_inconsistent() {
+        let report = IntegrityReport::default();
+        assert!(report.is_consistent());
+        // A signature that couldn't be checked isn't a discrepancy
+        assert!(IntegrityReport {
+            valid_signature: Some(true),
+            ..report.clone()
+        }
+        .is_consistent());
+        assert!(!IntegrityReport {
+            valid_signature: Some(false),
+            ..report.clone()
+        }
+        .is_consistent());
+
+        let found = vec!["at://did:example:alice/gg.example.note/a".to_string()];
+        for report in [
+            IntegrityReport {
+                missing_blocks: found.clone(),
+                ..report.clone()
+            },
+            IntegrityReport {
+                unindexed_records: found.clone(),
+                ..report.clone()
+            },
+            IntegrityReport {
+                stale_records: found.clone(),
+                ..report.clone()
+            },
+            IntegrityReport {
+                mismatched_records: found.clone(),
+                ..report.clone()
+            },
+            IntegrityReport {
+                unindexed_record_blobs: found.clone(),
+                ..report.clone()
+            },
+            IntegrityReport {
+                stale_record_blobs: found.clone(),
+                ..report.clone()
+            },
+        ] {
+            assert!(!report.is_consistent(), "{report:?}");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn finds_and_repairs_discrepancies() {
+        use crate::schema::registry::record::dsl as RecordSchema;
+        use crate::schema::registry::repo_block::dsl as RepoBlockSchema;
+        let conn = &mut establish_connection().unwrap();
+        let location =
+            std::env::temp_dir().join(format!("registry-integrity-{}", get_random_str()));
+        let did = format!("did:example:{}", get_random_str());
+        let mut actor_store = ActorStore::new(
+            did.clone(),
+            BlobStoreCreator::Disk(location).create(did.clone()),
+        );
+        actor_store
+            .create_repo(&**REPO_SIGNER, vec![])
+            .await
+            .unwrap();
+        let mut writes = Vec::new();
+        for rkey in ["a", "b", "c", "d"] {
+            let record = serde_json::json!({ "$type": "gg.example.note", "text": rkey });
+            let write = prepare_create(PrepareCreateOpts {
+                did: did.clone(),
+                collection: "gg.example.note".to_string(),
+                rkey: Some(rkey.to_string()),
+                swap_cid: None,
+                record: serde_json::from_value(record).unwrap(),
+                validate: Some(false),
+            })
+            .await
+            .unwrap();
+            writes.push(PreparedWrite::Create(write));
+        }
+        let commit = actor_store.process_writes(writes, None).await.unwrap();
+        let uri = |rkey: &str| format!("at://{did}/gg.example.note/{rkey}");
+
+        let report = verify_repo(&actor_store, Some(&REPO_SIGNER.did_key()), false)
+            .await
+            .unwrap();
+        assert!(report.is_consistent(), "{report:?}");
+        assert_eq!(report.valid_signature, Some(true));
+        assert_eq!(report.rev, Some(commit.rev.clone()));
+
+        // Drop one row, point another at the wrong CID, give a third an older rev, and index
+        // a record that isn't in the MST
+        delete(RecordSchema::record)
+            .filter(RecordSchema::uri.eq(uri("a")))
+            .execute(conn)
+            .unwrap();
+        let c_cid: String = RecordSchema::record
+            .filter(RecordSchema::uri.eq(uri("c")))
+            .select(RecordSchema::cid)
+            .first(conn)
+            .unwrap();
+        update(RecordSchema::record)
+            .filter(RecordSchema::uri.eq(uri("b")))
+            .set(RecordSchema::cid.eq(&c_cid))
+            .execute(conn)
+            .unwrap();
+        update(RecordSchema::record)
+            .filter(RecordSchema::uri.eq(uri("c")))
+            .set(RecordSchema::repoRev.eq("older"))
+            .execute(conn)
+            .unwrap();
+        insert_into(RecordSchema::record)
+            .values((
+                RecordSchema::uri.eq(uri("z")),
+                RecordSchema::cid.eq(&c_cid),
+                RecordSchema::did.eq(&did),
+                RecordSchema::collection.eq("gg.example.note"),
+                RecordSchema::rkey.eq("z"),
+                RecordSchema::repoRev.eq(&commit.rev),
+                RecordSchema::indexedAt.eq(crate::common::now()),
+            ))
+            .execute(conn)
+            .unwrap();
+
+        let other_key = LocalSigner::new(SecretKey::new(&mut rand::thread_rng())).did_key();
+        let report = verify_repo(&actor_store, Some(&other_key), false)
+            .await
+            .unwrap();
+        assert_eq!(report.valid_signature, Some(false));
+        assert_eq!(report.unindexed_records, vec![uri("a")]);
+        assert_eq!(report.mismatched_records, vec![uri("b")]);
+        assert_eq!(report.stale_records, vec![uri("z")]);
+        assert!(report.missing_blocks.is_empty());
+        assert!(!report.repaired);
+
+        let report = verify_repo(&actor_store, None, true).await.unwrap();
+        assert!(report.repaired);
+        let report = verify_repo(&actor_store, None, false).await.unwrap();
+        assert!(report.is_consistent(), "{report:?}");
+        let revs: BTreeMap<String, Option<String>> = RecordSchema::record
+            .filter(RecordSchema::did.eq(&did))
+            .select((RecordSchema::uri, RecordSchema::repoRev))
+            .load::<(String, Option<String>)>(conn)
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(revs.get(&uri("a")), Some(&Some(commit.rev.clone())));
+        assert_eq!(revs.get(&uri("c")), Some(&Some("older".to_string())));
+        assert_eq!(revs.get(&uri("z")), None);
+
+        // A record block that's gone can be reported but not repaired
+        let d_cid: String = RecordSchema::record
+            .filter(RecordSchema::uri.eq(uri("d")))
+            .select(RecordSchema::cid)
+            .first(conn)
+            .unwrap();
+        delete(RepoBlockSchema::repo_block)
+            .filter(RepoBlockSchema::did.eq(&did))
+            .filter(RepoBlockSchema::cid.eq(&d_cid))
+            .execute(conn)
+            .unwrap();
+        let report = verify_repo(&actor_store, None, false).await.unwrap();
+        assert_eq!(report.missing_blocks, vec![d_cid]);
+        assert!(!report.is_consistent());
+    }
+}
@@ -4,6 +4,7 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
+use crate::account_manager::AccountManager;
 use crate::config::SECRET_CONFIG;
 use crate::database::establish_connection;
 use crate::repository::blob::BlobReader;
@@ -12,9 +13,10 @@ use crate::repository::mst::MST;
 use crate::repository::preference::PreferenceReader;
 use crate::repository::record::RecordReader;
 use crate::repository::storage::RepoReader;
+use crate::signer::Signer;
 use rsky_pds::common;
 use rsky_pds::common::tid::{Ticker, TID};
-use crate::repository::aws::s3::S3BlobStore;
+use crate::repository::blobstore::BlobStore;
 use rsky_pds::repo::block_map::BlockMap;
 use rsky_pds::repo::cid_set::CidSet;
 use rsky_pds::repo::error::DataStoreError;
@@ -33,16 +35,70 @@ use lexicon_cid::Cid;
 use libipld::cbor::DagCborCodec;
 use libipld::Ipld as VendorIpld;
 use libipld::{Block, DefaultParams};
-use secp256k1::{Keypair, Secp256k1, SecretKey};
 use serde_cbor::Value as CborValue;
 use std::collections::BTreeMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
 pub struct CommitRecord {
-    collection: String,
-    rkey: String,
-    cid: Cid,
-    record: RepoRecord,
+    pub collection: String,
+    pub rkey: String,
+    pub cid: Cid,
+    pub record: RepoRecord,
+}
+
+/// A write as it applied to the MST, with the record's CID before and after.
+#[derive(Debug, Clone)]
+pub struct CommitOp {
+    pub action: WriteOpAction,
+    pub path: String,
+    pub cid: Option<Cid>,
+    pub prev: Option<Cid>,
+}
+
+/// `CommitData` plus what's needed to sequence the commit so it can be checked against the
+/// previous one: its ops, the previous MST root, and the MST nodes covering each op in the new
+/// tree.
+#[derive(Debug, Clone)]
+pub struct CommitDataWithOps {
+    pub cid: Cid,
+    pub rev: String,
+    pub since: Option<String>,
+    pub prev: Option<Cid>,
+    pub prev_data: Option<Cid>,
+    pub new_blocks: BlockMap,
+    pub relevant_blocks: BlockMap,
+    pub removed_cids: CidSet,
+    pub ops: Vec<CommitOp>,
+}
+
+impl From<CommitData> for CommitDataWithOps {
+    fn from(commit: CommitData) -> Self {
+        CommitDataWithOps {
+            cid: commit.cid,
+            rev: commit.rev,
+            since: commit.since,
+            prev: commit.prev,
+            prev_data: None,
+            new_blocks: commit.new_blocks,
+            relevant_blocks: BlockMap::new(),
+            removed_cids: commit.removed_cids,
+            ops: vec![],
+        }
+    }
+}
+
+impl From<CommitDataWithOps> for CommitData {
+    fn from(commit: CommitDataWithOps) -> Self {
+        CommitData {
+            cid: commit.cid,
+            rev: commit.rev,
+            since: commit.since,
+            prev: commit.prev,
+            new_blocks: commit.new_blocks,
+            removed_cids: commit.removed_cids,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -64,14 +120,14 @@ pub struct ActorStore {
 
 // Combination of RepoReader/Transactor, BlobReader/Transactor, RepoReader/Transactor
 impl ActorStore {
-    /// Concrete reader of an individual repo (hence S3BlobStore which takes `did` param)
-    pub fn new(did: String, blobstore: S3BlobStore) -> Self {
+    /// Concrete reader of an individual repo (hence a blobstore scoped to the same `did`)
+    pub fn new(did: String, blobstore: Arc<dyn BlobStore>) -> Self {
         ActorStore {
             storage: RepoReader::new(None, did.clone(), None),
             record: RecordReader::new(did.clone()),
             pref: PreferenceReader::new(did.clone()),
+            blob: BlobReader::new(did.clone(), blobstore), // Unlike TS impl, just use blob reader vs generator
             did,
-            blob: BlobReader::new(blobstore), // Unlike TS impl, just use blob reader vs generator
         }
     }
 
@@ -81,9 +137,9 @@ impl ActorStore {
     // @TODO: Update to use AtUri
     pub async fn create_repo(
         &mut self,
-        keypair: Keypair,
+        signer: &dyn Signer,
         writes: Vec<PreparedCreateOrUpdate>,
-    ) -> Result<CommitData> {
+    ) -> Result<CommitDataWithOps> {
         let write_ops = writes
             .clone()
             .into_iter()
@@ -101,13 +157,27 @@ impl ActorStore {
                 }
             })
             .collect::<Vec<RecordCreateOrUpdateOp>>();
+        // Everything is new in the first commit, so there's no previous tree to prove against
+        let ops = writes
+            .iter()
+            .zip(&write_ops)
+            .map(|(write, op)| CommitOp {
+                action: WriteOpAction::Create,
+                path: util::format_data_key(op.collection.clone(), op.rkey.clone()),
+                cid: Some(write.cid),
+                prev: None,
+            })
+            .collect::<Vec<CommitOp>>();
         let commit = Repo::format_init_commit(
             self.storage.clone(),
             self.did.clone(),
-            keypair,
+            signer,
             Some(write_ops),
-        )?;
+        )
+        .await?;
         self.storage.apply_commit(commit.clone(), None).await?;
+        let mut commit = CommitDataWithOps::from(commit);
+        commit.ops = ops;
         let writes = writes
             .into_iter()
             .map(|w| PreparedWrite::Create(w))
@@ -120,7 +190,7 @@ impl ActorStore {
         &mut self,
         writes: Vec<PreparedWrite>,
         swap_commit_cid: Option<Cid>,
-    ) -> Result<CommitData> {
+    ) -> Result<CommitDataWithOps> {
         let commit = self.format_commit(writes.clone(), swap_commit_cid).await?;
         {
             let immutable_borrow = &self;
@@ -131,7 +201,7 @@ impl ActorStore {
         }
         try_join!(
             // persist the commit to repo storage
-            self.storage.apply_commit(commit.clone(), None),
+            self.storage.apply_commit(commit.clone().into(), None),
             // process blobs
             self.blob.process_write_blobs(writes)
         )?;
@@ -142,7 +212,7 @@ impl ActorStore {
         &mut self,
         writes: Vec<PreparedWrite>,
         swap_commit: Option<Cid>,
-    ) -> Result<CommitData> {
+    ) -> Result<CommitDataWithOps> {
         let current_root = self.storage.get_root_detailed().await;
         if let Ok(current_root) = current_root {
             if let Some(swap_commit) = swap_commit {
@@ -203,15 +273,10 @@ impl ActorStore {
                 .into_iter()
                 .map(|write| write_to_op(write))
                 .collect::<Vec<RecordWriteOp>>();
-            // @TODO: Use repo signing key global config
-            let secp = Secp256k1::new();
-            let repo_private_key = SECRET_CONFIG.repo_signing_key.clone();
-            let repo_secret_key =
-                SecretKey::from_slice(&hex::decode(repo_private_key.as_bytes()).unwrap()).unwrap();
-            let repo_signing_key = Keypair::from_secret_key(&secp, &repo_secret_key);
+            let signer = AccountManager::get_signer(&self.did).await?;
 
             let mut commit = repo
-                .format_commit(RecordWriteEnum::List(write_ops), repo_signing_key)
+                .format_commit(RecordWriteEnum::List(write_ops), &*signer)
                 .await?;
 
             // find blocks that would be deleted but are referenced by another record
@@ -324,6 +389,19 @@ impl ActorStore {
     }
 }
 
+/// Like `util::sign_commit`, but with a signer that may be held outside the process.
+pub async fn sign_commit(unsigned: UnsignedCommit, signer: &dyn Signer) -> Result<Commit> {
+    let sig = signer.sign(&serde_ipld_dagcbor::to_vec(&unsigned)?).await?;
+    Ok(Commit {
+        did: unsigned.did,
+        version: unsigned.version,
+        data: unsigned.data,
+        rev: unsigned.rev,
+        prev: unsigned.prev,
+        sig: sig.to_vec(),
+    })
+}
+
 impl Repo {
     // static
     pub fn new(storage: RepoReader, data: MST, commit: Commit, cid: Cid) -> Self {
@@ -426,6 +504,14 @@ impl Repo {
         self.commit.did.clone()
     }
 
+    pub fn commit(&self) -> Commit {
+        self.commit.clone()
+    }
+
+    pub fn cid(&self) -> Cid {
+        self.cid
+    }
+
     pub fn version(self) -> u8 {
         self.commit.version
     }
@@ -486,10 +572,10 @@ impl Repo {
     }
 
     // static
-    pub fn format_init_commit(
+    pub async fn format_init_commit(
         storage: RepoReader,
         did: String,
-        keypair: Keypair,
+        signer: &dyn Signer,
         initial_writes: Option<Vec<RecordCreateOrUpdateOp>>,
     ) -> Result<CommitData> {
         let mut new_blocks = BlockMap::new();
@@ -503,7 +589,7 @@ impl Repo {
         let diff = DataDiff::of(&mut data, None)?;
         new_blocks.add_map(diff.new_mst_blocks)?;
         let rev = Ticker::new().next(None);
-        let commit = util::sign_commit(
+        let commit = sign_commit(
             UnsignedCommit {
                 did,
                 version: 3,
@@ -511,8 +597,9 @@ impl Repo {
                 prev: None, // added for backwards compatibility with v2
                 data: data_cid,
             },
-            keypair,
-        )?;
+            signer,
+        )
+        .await?;
         let commit_cid = new_blocks.add(commit)?;
         Ok(CommitData {
             cid: commit_cid,
@@ -537,23 +624,24 @@ impl Repo {
     pub async fn create(
         mut storage: RepoReader,
         did: String,
-        keypair: Keypair,
+        signer: &dyn Signer,
         initial_writes: Option<Vec<RecordCreateOrUpdateOp>>,
     ) -> Result<Self> {
-        let commit = Repo::format_init_commit(storage.clone(), did, keypair, initial_writes)?;
+        let commit = Repo::format_init_commit(storage.clone(), did, signer, initial_writes).await?;
         Repo::create_from_commit(&mut storage, commit).await
     }
 
     pub async fn format_commit(
         &mut self,
         to_write: RecordWriteEnum,
-        keypair: Keypair,
-    ) -> Result<CommitData> {
+        signer: &dyn Signer,
+    ) -> Result<CommitDataWithOps> {
         let writes = match to_write {
             RecordWriteEnum::List(to_write) => to_write,
             RecordWriteEnum::Single(to_write) => vec![to_write],
         };
         let mut leaves = BlockMap::new();
+        let mut ops: Vec<CommitOp> = Vec::new();
 
         let mut data = self.data.clone();
         for write in writes {
@@ -562,19 +650,44 @@ impl Repo {
                     let cid = leaves.add(write.record)?;
                     let data_key = util::format_data_key(write.collection, write.rkey);
                     data = data.add(&data_key, cid, None)?;
+                    ops.push(CommitOp {
+                        action: WriteOpAction::Create,
+                        path: data_key,
+                        cid: Some(cid),
+                        prev: None,
+                    });
                 }
                 RecordWriteOp::Update(write) => {
                     let cid = leaves.add(write.record)?;
                     let data_key = util::format_data_key(write.collection, write.rkey);
+                    let prev = data.get(&data_key)?;
                     data = data.update(&data_key, cid)?;
+                    ops.push(CommitOp {
+                        action: WriteOpAction::Update,
+                        path: data_key,
+                        cid: Some(cid),
+                        prev,
+                    });
                 }
                 RecordWriteOp::Delete(write) => {
                     let data_key = util::format_data_key(write.collection, write.rkey);
+                    let prev = data.get(&data_key)?;
                     data = data.delete(&data_key)?;
+                    ops.push(CommitOp {
+                        action: WriteOpAction::Delete,
+                        path: data_key,
+                        cid: None,
+                        prev,
+                    });
                 }
             }
         }
 
+        let mut relevant_blocks = BlockMap::new();
+        for op in &ops {
+            relevant_blocks.add_map(data.get_covering_proof(&op.path)?)?;
+        }
+
         let data_cid = data.get_pointer()?;
         let diff = DataDiff::of(&mut data, Some(&mut self.data.clone()))?;
 
@@ -589,7 +702,7 @@ impl Repo {
 
         let rev = Ticker::new().next(Some(TID::new(self.commit.rev.clone())?));
 
-        let commit = util::sign_commit(
+        let commit = sign_commit(
             UnsignedCommit {
                 did: self.did(),
                 version: 3,
@@ -597,8 +710,9 @@ impl Repo {
                 prev: None, // added for backwards compatibility with v2
                 data: data_cid,
             },
-            keypair,
-        )?;
+            signer,
+        )
+        .await?;
         let commit_cid = new_blocks.add(commit)?;
 
         // ensure the commit cid actually changed
@@ -608,13 +722,16 @@ impl Repo {
             removed_cids.add(self.cid);
         }
 
-        Ok(CommitData {
+        Ok(CommitDataWithOps {
             cid: commit_cid,
             rev: rev.clone().to_string(),
             since: Some(self.commit.rev.clone()),
             prev: Some(self.cid),
+            prev_data: Some(self.commit.data),
             new_blocks,
+            relevant_blocks,
             removed_cids,
+            ops,
         })
     }
 
@@ -627,14 +744,18 @@ impl Repo {
     pub async fn apply_writes(
         &mut self,
         to_write: RecordWriteEnum,
-        keypair: Keypair,
+        signer: &dyn Signer,
     ) -> Result<Self> {
-        let commit = self.format_commit(to_write, keypair).await?;
-        self.apply_commit(commit).await
+        let commit = self.format_commit(to_write, signer).await?;
+        self.apply_commit(commit.into()).await
     }
 
-    pub fn format_resign_commit(&self, rev: String, keypair: Keypair) -> Result<CommitData> {
-        let commit = util::sign_commit(
+    pub async fn format_resign_commit(
+        &self,
+        rev: String,
+        signer: &dyn Signer,
+    ) -> Result<CommitData> {
+        let commit = sign_commit(
             UnsignedCommit {
                 did: self.did(),
                 version: 3,
@@ -642,8 +763,9 @@ impl Repo {
                 prev: None, // added for backwards compatibility with v2
                 data: self.commit.data,
             },
-            keypair,
-        )?;
+            signer,
+        )
+        .await?;
         let mut new_blocks = BlockMap::new();
         let commit_cid = new_blocks.add(commit)?;
         Ok(CommitData {
@@ -656,8 +778,8 @@ impl Repo {
         })
     }
 
-    pub async fn resign_commit(&mut self, rev: String, keypair: Keypair) -> Result<Self> {
-        let formatted = self.format_resign_commit(rev, keypair)?;
+    pub async fn resign_commit(&mut self, rev: String, signer: &dyn Signer) -> Result<Self> {
+        let formatted = self.format_resign_commit(rev, signer).await?;
         self.apply_commit(formatted).await
     }
 }
@@ -669,4 +791,8 @@ pub mod record;
 pub mod sync;
 pub mod blob;
 pub mod mst;
-pub mod aws;
\ No newline at end of file
+pub mod aws;
+pub mod blobstore;
+pub mod car;
+pub mod video;
+pub mod integrity;
\ No newline at end of file
@@ -25,8 +25,9 @@ use rsky_pds::repo::block_map::BlockMap;
 use rsky_pds::repo::cid_set::CidSet;
 use rsky_pds::repo::error::DataStoreError;
 use rsky_pds::repo::parse;
-use rsky_pds::repo::types::{BlockWriter, CidAndBytes};
+use rsky_pds::repo::types::CidAndBytes;
 use rsky_pds::storage::ObjAndBytes;
+use crate::repository::car::CarWriter;
 use crate::repository::storage::RepoReader;
 use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
@@ -1179,8 +1180,7 @@ impl MST {
     }
 
     /// Sync Protocol
-    /// @TODO: This needs to implement an actual CarWriter
-    pub async fn write_to_car_stream(&mut self, car: &mut BlockWriter) -> Result<()> {
+    pub async fn write_to_car_stream(&mut self, car: &mut CarWriter) -> Result<()> {
         let mut leaves = CidSet::new(None);
         let mut to_fetch = CidSet::new(None);
         to_fetch.add(self.get_pointer()?);
@@ -1201,10 +1201,7 @@ impl MST {
                             Err(_) => false,
                         }
                     })?;
-                car.push(CidAndBytes {
-                    cid,
-                    bytes: found.bytes,
-                });
+                car.write_block(&cid, &found.bytes);
                 let node_data: NodeData = serde_cbor::value::from_value(found.obj)?;
                 let entries = util::deserialize_node_data(&self.storage, node_data.clone(), None)?;
 
@@ -1225,7 +1222,7 @@ impl MST {
             )));
         }
         for leaf in leaf_data.blocks.entries()? {
-            car.push(leaf);
+            car.write_block(&leaf.cid, &leaf.bytes);
         }
         Ok(())
     }
@@ -1247,6 +1244,62 @@ impl MST {
         }
         Ok(cids)
     }
+
+    /// The nodes needed to show `key`'s presence or absence in this tree and to insert or remove
+    /// it again: the path to the key plus the paths to its neighbouring leaves. This lets a
+    /// firehose consumer invert a commit's ops to check it against the previous commit.
+    pub fn get_covering_proof(&mut self, key: &String) -> Result<BlockMap> {
+        let mut blocks = self.proof_for_key(key)?;
+        blocks.add_map(self.proof_for_left_sib(key)?)?;
+        blocks.add_map(self.proof_for_right_sib(key)?)?;
+        Ok(blocks)
+    }
+
+    fn proof_for_key(&mut self, key: &String) -> Result<BlockMap> {
+        let index = self.find_gt_or_equal_leaf_index(key)?;
+        let mut blocks = match self.at_index(index)? {
+            Some(NodeEntry::Leaf(l)) if l.key == *key => BlockMap::new(),
+            _ => match self.at_index(index - 1)? {
+                Some(NodeEntry::MST(mut prev)) => prev.proof_for_key(key)?,
+                _ => BlockMap::new(),
+            },
+        };
+        let CidAndBytes { cid, bytes } = self.serialize()?;
+        blocks.set(cid, bytes);
+        Ok(blocks)
+    }
+
+    fn proof_for_left_sib(&mut self, key: &String) -> Result<BlockMap> {
+        let index = self.find_gt_or_equal_leaf_index(key)?;
+        // whether or not the key is here, anything before it is in the entry to its left
+        let mut blocks = match self.at_index(index - 1)? {
+            Some(NodeEntry::MST(mut prev)) => prev.proof_for_left_sib(key)?,
+            _ => BlockMap::new(),
+        };
+        let CidAndBytes { cid, bytes } = self.serialize()?;
+        blocks.set(cid, bytes);
+        Ok(blocks)
+    }
+
+    fn proof_for_right_sib(&mut self, key: &String) -> Result<BlockMap> {
+        let index = self.find_gt_or_equal_leaf_index(key)?;
+        let mut blocks = match self.at_index(index)? {
+            // the key itself is here, so its right sibling is the next entry along
+            Some(NodeEntry::Leaf(l)) if l.key == *key => match self.at_index(index + 1)? {
+                Some(NodeEntry::MST(mut next)) => next.proof_for_right_sib(key)?,
+                _ => BlockMap::new(),
+            },
+            // otherwise the key falls in the subtree before that entry, which may also hold the
+            // sibling
+            _ => match self.at_index(index - 1)? {
+                Some(NodeEntry::MST(mut prev)) => prev.proof_for_right_sib(key)?,
+                _ => BlockMap::new(),
+            },
+        };
+        let CidAndBytes { cid, bytes } = self.serialize()?;
+        blocks.set(cid, bytes);
+        Ok(blocks)
+    }
 }
 
 impl PartialEq for MST {
@@ -1839,4 +1892,37 @@ mod tests {
 
         Ok(())
     }
+
+    #[actix_rt::test]
+    async fn covering_proof_inverts_ops() -> Result<()> {
+        let mut storage =
+            RepoReader::new(None, "did:example:123456789abcdefghi".to_string(), None);
+        let mapping = generate_bulk_data_keys(254, Some(&mut storage))?;
+        let mut mst = MST::create(storage, None, None)?;
+        for entry in &mapping {
+            mst = mst.add(entry.0, *entry.1, None)?;
+        }
+        let (key, cid) = mapping.iter().nth(127).unwrap();
+        let mut without = mst.delete(key)?;
+        let mut with = without.add(key, *cid, None)?;
+
+        // Only the proof is available, so inverting in either direction mustn't need any
+        // other node
+        for (mut tree, invert_add) in [(with.clone(), true), (without.clone(), false)] {
+            let mut partial =
+                RepoReader::new(None, "did:example:123456789abcdefghi".to_string(), None);
+            partial.cache.add_map(tree.get_covering_proof(key)?)?;
+            let mut partial = MST::load(partial, tree.get_pointer()?, None)?;
+            let mut inverted = match invert_add {
+                true => partial.delete(key)?,
+                false => partial.add(key, *cid, None)?,
+            };
+            let expected = match invert_add {
+                true => without.get_pointer()?,
+                false => with.get_pointer()?,
+            };
+            assert_eq!(inverted.get_pointer()?, expected);
+        }
+        Ok(())
+    }
 }
\ No newline at end of file
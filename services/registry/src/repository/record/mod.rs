@@ -108,6 +108,17 @@ impl RecordReader {
         Ok(collections)
     }
 
+    pub async fn list_all_uris(&self) -> Result<Vec<String>> {
+        use crate::schema::registry::record::dsl::*;
+        let conn = &mut establish_connection()?;
+
+        let uris = record
+            .filter(did.eq(&self.did))
+            .select(uri)
+            .load::<String>(conn)?;
+        Ok(uris)
+    }
+
     pub async fn list_records_for_collection(
         &mut self,
         collection: String,
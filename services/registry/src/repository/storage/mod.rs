@@ -10,10 +10,11 @@ use rsky_pds::repo::types::{CommitData, RepoRecord};
 use serde_cbor::Value as CborValue;
 use std::str::FromStr;
 use anyhow::{bail, Result};
+use futures::stream::Stream;
 use futures::try_join;
 use lexicon_cid::Cid;
 use crate::database::{establish_connection, RepoBlock, RepoRoot, models};
-use rsky_pds::car::read_car_bytes;
+use crate::repository::car::{CarStream, CarWriter};
 use rsky_pds::common;
 use rsky_pds::repo::error::DataStoreError;
 use rsky_pds::storage::RepoRootError::RepoRootNotFoundError;
@@ -24,6 +25,7 @@ use diesel::dsl::sql;
 use diesel::prelude::*;
 use diesel::sql_types::{Bool, Text};
 use diesel::*;
+use rocket::async_stream::try_stream;
 use serde::{Serialize, Deserialize};
 
 #[allow(missing_debug_implementations)]
@@ -96,31 +98,37 @@ impl RepoReader {
         })
     }
 
-    pub async fn get_car_stream(&self, since: Option<String>) -> Result<Vec<u8>> {
+    /// Exports the repo as a CAR. Blocks are read a page at a time using the same cursor as
+    /// `get_block_range`, so an export never holds more than one page in memory.
+    pub async fn get_car_stream(&self, since: Option<String>) -> Result<CarStream> {
         match self.get_root().await {
-            None => return Err(anyhow::Error::new(RepoRootNotFoundError)),
-            Some(root) => {
-                let mut car = BlockMap::new();
-                let mut cursor: Option<CidAndRev> = None;
-                let mut write_rows = |rows: Vec<RepoBlock>| -> Result<()> {
-                    for row in rows {
-                        car.set(Cid::from_str(&row.cid)?, row.content);
-                    }
-                    Ok(())
+            None => Err(anyhow::Error::new(RepoRootNotFoundError)),
+            Some(root) => Ok(Box::pin(Self::car_chunks(self.clone(), root, since))),
+        }
+    }
+
+    fn car_chunks(
+        reader: RepoReader,
+        root: Cid,
+        since: Option<String>,
+    ) -> impl Stream<Item = Result<Vec<u8>>> + Send {
+        try_stream! {
+            let mut car = CarWriter::new(Some(&root))?;
+            yield car.take();
+            let mut cursor: Option<CidAndRev> = None;
+            loop {
+                let rows = reader.get_block_range(&since, &cursor)?;
+                let Some(last_row) = rows.last() else {
+                    break;
                 };
-                loop {
-                    let res = self.get_block_range(&since, &cursor)?;
-                    write_rows(res.clone())?;
-                    if let Some(last_row) = res.last() {
-                        cursor = Some(CidAndRev {
-                            cid: Cid::from_str(&last_row.cid)?,
-                            rev: last_row.repo_rev.clone(),
-                        });
-                    } else {
-                        break;
-                    }
+                cursor = Some(CidAndRev {
+                    cid: Cid::from_str(&last_row.cid)?,
+                    rev: last_row.repo_rev.clone(),
+                });
+                for row in rows {
+                    car.write_block(&Cid::from_str(&row.cid)?, &row.content);
                 }
-                read_car_bytes(Some(&root), car).await
+                yield car.take();
             }
         }
     }
@@ -319,6 +327,7 @@ impl RepoReader {
                 .execute(conn)?;
         } else {
             update(RepoRootSchema::repo_root)
+                .filter(RepoRootSchema::did.eq(&self.did))
                 .set((
                     RepoRootSchema::cid.eq(cid.to_string()),
                     RepoRootSchema::rev.eq(rev),
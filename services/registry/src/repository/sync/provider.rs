@@ -4,12 +4,12 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
-use rsky_pds::car::read_car_bytes;
 use rsky_pds::repo::block_map::BlockMap;
 use rsky_pds::repo::cid_set::CidSet;
 use rsky_pds::repo::error::DataStoreError;
 use rsky_pds::repo::util;
 use rsky_pds::repo::types::{Commit, RecordPath};
+use crate::repository::car::{blocks_to_car_stream, CarStream};
 use crate::repository::mst::MST;
 use crate::repository::storage::RepoReader;
 use anyhow::Result;
@@ -20,7 +20,7 @@ pub async fn get_records(
     storage: &mut RepoReader,
     commit_cid: Cid,
     paths: Vec<RecordPath>,
-) -> Result<Vec<u8>> {
+) -> Result<CarStream> {
     let mut car = BlockMap::new();
     let commit = storage.read_obj_and_bytes(&commit_cid, |obj: &CborValue| {
         match serde_cbor::value::from_value::<Commit>(obj.clone()) {
@@ -52,5 +52,5 @@ pub async fn get_records(
     for block in found.blocks.entries()? {
         car.set(block.cid, block.bytes)
     }
-    read_car_bytes(Some(&commit_cid), car).await
+    blocks_to_car_stream(Some(commit_cid), car)
 }
\ No newline at end of file
@@ -0,0 +1,538 @@
+//! Reads video metadata out of uploads without decoding them. MP4/QuickTime (ISO-BMFF) files are
+//! walked box by box and WebM/Matroska (EBML) files element by element, buffering only the header
+//! structures and skipping over media data, so probing a long clip doesn't load it into memory.
+use anyhow::{bail, Context, Result};
+use std::io::ErrorKind;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Largest header box or element that's buffered while probing. The `moov` box of a long clip
+/// can run to a few MB, but nothing we'd accept comes close to this.
+const MAX_HEADER_LEN: u64 = 16 * 1024 * 1024;
+
+const EBML_HEADER: u32 = 0x1A45_DFA3;
+const EBML_DOC_TYPE: u32 = 0x4282;
+const SEGMENT: u32 = 0x1853_8067;
+const SEGMENT_INFO: u32 = 0x1549_A966;
+const TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const SEGMENT_DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const TRACK_VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const CLUSTER: u32 = 0x1F43_B675;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VideoInfo {
+    /// `mp4`, `mov`, `webm` or `mkv`.
+    pub container: String,
+    /// Codec of the first video track, e.g. `h264` or `vp9`.
+    pub codec: Option<String>,
+    /// Milliseconds.
+    pub duration: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+/// Probes `stream` as the container implied by `mime_type`. Returns `None` for mime types that
+/// aren't a supported container.
+pub async fn probe<R>(mime_type: &str, stream: &mut R) -> Result<Option<VideoInfo>>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    match mime_type {
+        "video/mp4" | "video/quicktime" => Ok(Some(probe_mp4(stream).await?)),
+        "video/webm" | "video/x-matroska" => Ok(Some(probe_matroska(stream).await?)),
+        _ => Ok(None),
+    }
+}
+
+async fn skip<R>(stream: &mut R, len: u64) -> Result<()>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let skipped = tokio::io::copy(&mut (&mut *stream).take(len), &mut tokio::io::sink()).await?;
+    if skipped < len {
+        bail!("video ended unexpectedly");
+    }
+    Ok(())
+}
+
+async fn read_body<R>(stream: &mut R, len: u64) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    if len > MAX_HEADER_LEN {
+        bail!("video header of {len} bytes is too large");
+    }
+    let mut body = vec![0; len as usize];
+    stream.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+/// Reads into `buf`, returning false if the stream was already at its end.
+async fn read_or_eof<R>(stream: &mut R, buf: &mut [u8]) -> Result<bool>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    match stream.read_exact(buf).await {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes[..4].try_into().unwrap_or_default())
+}
+
+fn be_u64(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[..8].try_into().unwrap_or_default())
+}
+
+fn to_millis(duration: f64, units_per_second: f64) -> Option<i32> {
+    let millis = duration * 1000.0 / units_per_second;
+    match millis.is_finite() && millis >= 0.0 && millis <= i32::MAX as f64 {
+        true => Some(millis as i32),
+        false => None,
+    }
+}
+
+fn dimension(value: u64) -> Option<i32> {
+    i32::try_from(value).ok().filter(|value| *value > 0)
+}
+
+// ISO-BMFF
+// ---------
+
+async fn probe_mp4<R>(stream: &mut R) -> Result<VideoInfo>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut info = VideoInfo {
+        container: "mp4".to_string(),
+        ..Default::default()
+    };
+    let mut header = [0; 8];
+    while read_or_eof(stream, &mut header).await? {
+        let body_len = match be_u32(&header) {
+            // The box runs to the end of the file
+            0 => None,
+            1 => {
+                let mut large_size = [0; 8];
+                stream.read_exact(&mut large_size).await?;
+                Some(be_u64(&large_size).checked_sub(16).context("invalid box size")?)
+            }
+            size => Some((size as u64).checked_sub(8).context("invalid box size")?),
+        };
+        match (&header[4..8], body_len) {
+            (b"ftyp", Some(len)) => {
+                if read_body(stream, len).await?.starts_with(b"qt  ") {
+                    info.container = "mov".to_string();
+                }
+            }
+            (b"moov", Some(len)) => {
+                parse_moov(&read_body(stream, len).await?, &mut info);
+                return Ok(info);
+            }
+            (_, Some(len)) => skip(stream, len).await?,
+            (_, None) => break,
+        }
+    }
+    bail!("no moov box found")
+}
+
+/// Splits the body of a container box into its children's types and bodies, stopping at the
+/// first malformed child.
+fn child_boxes(mut data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut children = Vec::new();
+    while data.len() >= 8 {
+        let (header_len, size) = match be_u32(data) {
+            0 => (8, data.len()),
+            1 if data.len() >= 16 => (16, be_u64(&data[8..]) as usize),
+            1 => break,
+            size => (8, size as usize),
+        };
+        if size < header_len || size > data.len() {
+            break;
+        }
+        children.push((&data[4..8], &data[header_len..size]));
+        data = &data[size..];
+    }
+    children
+}
+
+fn find_box<'a>(children: &[(&[u8], &'a [u8])], kind: &[u8]) -> Option<&'a [u8]> {
+    children
+        .iter()
+        .find(|(child, _)| *child == kind)
+        .map(|(_, body)| *body)
+}
+
+/// `mvhd` and `mdhd` share the layout of their timescale and duration.
+fn header_duration(body: &[u8]) -> Option<i32> {
+    let (timescale, duration) = match body.first()? {
+        1 => (be_u32(body.get(20..24)?), be_u64(body.get(24..32)?)),
+        _ => (be_u32(body.get(12..16)?), be_u32(body.get(16..20)?) as u64),
+    };
+    match timescale {
+        0 => None,
+        timescale => to_millis(duration as f64, timescale as f64),
+    }
+}
+
+fn parse_moov(moov: &[u8], info: &mut VideoInfo) {
+    let children = child_boxes(moov);
+    info.duration = find_box(&children, b"mvhd").and_then(header_duration);
+    for (kind, trak) in children {
+        if kind == b"trak" && parse_trak(trak, info) {
+            break;
+        }
+    }
+}
+
+/// Fills in `info` from `trak` if it's a video track.
+fn parse_trak(trak: &[u8], info: &mut VideoInfo) -> bool {
+    let children = child_boxes(trak);
+    let Some(mdia) = find_box(&children, b"mdia").map(child_boxes) else {
+        return false;
+    };
+    let handler = find_box(&mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12));
+    if handler != Some(&b"vide"[..]) {
+        return false;
+    }
+    if info.duration.is_none() {
+        info.duration = find_box(&mdia, b"mdhd").and_then(header_duration);
+    }
+
+    // The first sample description holds the codec, and the coded size at a fixed offset
+    let sample_entry = find_box(&mdia, b"minf")
+        .map(child_boxes)
+        .and_then(|minf| find_box(&minf, b"stbl").map(child_boxes))
+        .and_then(|stbl| find_box(&stbl, b"stsd"))
+        .and_then(|stsd| stsd.get(8..));
+    if let Some(entry) = sample_entry.filter(|entry| entry.len() >= 36) {
+        info.codec = Some(mp4_codec(&entry[4..8]));
+        info.width = dimension(u16::from_be_bytes([entry[32], entry[33]]) as u64);
+        info.height = dimension(u16::from_be_bytes([entry[34], entry[35]]) as u64);
+    }
+    // Prefer the display size from the track header, which accounts for non-square pixels. It's
+    // the last 8 bytes, as 16.16 fixed point.
+    if let Some(tkhd) = find_box(&children, b"tkhd").filter(|tkhd| tkhd.len() >= 84) {
+        let len = tkhd.len();
+        if let (Some(width), Some(height)) = (
+            dimension((be_u32(&tkhd[len - 8..]) >> 16) as u64),
+            dimension((be_u32(&tkhd[len - 4..]) >> 16) as u64),
+        ) {
+            info.width = Some(width);
+            info.height = Some(height);
+        }
+    }
+    true
+}
+
+fn mp4_codec(fourcc: &[u8]) -> String {
+    match fourcc {
+        b"avc1" | b"avc3" => "h264".to_string(),
+        b"hvc1" | b"hev1" => "hevc".to_string(),
+        b"av01" => "av1".to_string(),
+        b"vp08" => "vp8".to_string(),
+        b"vp09" => "vp9".to_string(),
+        b"mp4v" => "mpeg4".to_string(),
+        fourcc => String::from_utf8_lossy(fourcc).trim().to_string(),
+    }
+}
+
+// EBML
+// ---------
+
+/// Length of a variable size integer from its first byte.
+fn vint_len(first: u8) -> Result<usize> {
+    match first.leading_zeros() {
+        zeros @ 0..=7 => Ok(zeros as usize + 1),
+        _ => bail!("invalid EBML variable size integer"),
+    }
+}
+
+/// Decodes an element size, where all value bits set means the size is unknown.
+fn vint_size(bytes: &[u8]) -> Option<u64> {
+    let len = bytes.len();
+    let mut value = (bytes[0] & (0xFFu16 >> len) as u8) as u64;
+    for byte in &bytes[1..] {
+        value = (value << 8) | *byte as u64;
+    }
+    match value == (1 << (7 * len)) - 1 {
+        true => None,
+        false => Some(value),
+    }
+}
+
+/// Element IDs keep their length marker bits.
+fn vint_id(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |id, byte| (id << 8) | *byte as u32)
+}
+
+/// Reads an element's ID and size. `Ok(None)` means the stream had ended.
+async fn read_element_header<R>(stream: &mut R) -> Result<Option<(u32, Option<u64>)>>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut buf = [0; 8];
+    if !read_or_eof(stream, &mut buf[..1]).await? {
+        return Ok(None);
+    }
+    let id_len = vint_len(buf[0])?;
+    if id_len > 4 {
+        bail!("invalid EBML element id");
+    }
+    stream.read_exact(&mut buf[1..id_len]).await?;
+    let id = vint_id(&buf[..id_len]);
+    stream.read_exact(&mut buf[..1]).await?;
+    let size_len = vint_len(buf[0])?;
+    stream.read_exact(&mut buf[1..size_len]).await?;
+    Ok(Some((id, vint_size(&buf[..size_len]))))
+}
+
+/// Splits the body of a master element into its children's IDs and bodies, stopping at the
+/// first malformed or unknown size child.
+fn child_elements(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut children = Vec::new();
+    while let Some(first) = data.first() {
+        let Ok(id_len) = vint_len(*first) else { break };
+        let Some(size_first) = data.get(id_len) else {
+            break;
+        };
+        let Ok(size_len) = vint_len(*size_first) else {
+            break;
+        };
+        let header_len = id_len + size_len;
+        let Some(size) = data.get(id_len..header_len).and_then(vint_size) else {
+            break;
+        };
+        let Some(body) = data.get(header_len..header_len.saturating_add(size as usize)) else {
+            break;
+        };
+        children.push((vint_id(&data[..id_len]), body));
+        data = &data[header_len + body.len()..];
+    }
+    children
+}
+
+fn ebml_uint(body: &[u8]) -> u64 {
+    body.iter().take(8).fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+fn ebml_float(body: &[u8]) -> Option<f64> {
+    match body.len() {
+        4 => Some(f32::from_be_bytes(body.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(body.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+fn ebml_string(body: &[u8]) -> String {
+    String::from_utf8_lossy(body)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+async fn probe_matroska<R>(stream: &mut R) -> Result<VideoInfo>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let Some((EBML_HEADER, Some(len))) = read_element_header(stream).await? else {
+        bail!("missing EBML header");
+    };
+    let header = read_body(stream, len).await?;
+    let doc_type = child_elements(&header)
+        .into_iter()
+        .find(|(id, _)| *id == EBML_DOC_TYPE)
+        .map(|(_, body)| ebml_string(body));
+    let mut info = VideoInfo {
+        container: match doc_type.as_deref() {
+            Some("webm") => "webm".to_string(),
+            _ => "mkv".to_string(),
+        },
+        ..Default::default()
+    };
+    let Some((SEGMENT, _)) = read_element_header(stream).await? else {
+        bail!("missing matroska segment");
+    };
+
+    // Segment info and tracks come before the first cluster of media data
+    let mut timecode_scale = 1_000_000.0;
+    let mut duration = None;
+    let (mut seen_info, mut seen_tracks) = (false, false);
+    while !(seen_info && seen_tracks) {
+        let Some((id, Some(len))) = read_element_header(stream).await? else {
+            break;
+        };
+        match id {
+            SEGMENT_INFO => {
+                for (id, body) in child_elements(&read_body(stream, len).await?) {
+                    match id {
+                        TIMECODE_SCALE => timecode_scale = ebml_uint(body) as f64,
+                        SEGMENT_DURATION => duration = ebml_float(body),
+                        _ => (),
+                    }
+                }
+                seen_info = true;
+            }
+            TRACKS => {
+                parse_tracks(&read_body(stream, len).await?, &mut info);
+                seen_tracks = true;
+            }
+            CLUSTER => break,
+            _ => skip(stream, len).await?,
+        }
+    }
+    if !seen_tracks {
+        bail!("no matroska tracks found");
+    }
+    // Durations are in units of the timecode scale, which is in nanoseconds
+    info.duration = duration.and_then(|duration| to_millis(duration * timecode_scale, 1e9));
+    Ok(info)
+}
+
+fn parse_tracks(tracks: &[u8], info: &mut VideoInfo) {
+    for (id, entry) in child_elements(tracks) {
+        if id != TRACK_ENTRY {
+            continue;
+        }
+        let children = child_elements(entry);
+        let is_video = children
+            .iter()
+            .any(|(id, body)| *id == TRACK_TYPE && ebml_uint(body) == 1);
+        if !is_video {
+            continue;
+        }
+        for (id, body) in children {
+            match id {
+                CODEC_ID => info.codec = Some(matroska_codec(&ebml_string(body))),
+                TRACK_VIDEO => {
+                    for (id, body) in child_elements(body) {
+                        match id {
+                            PIXEL_WIDTH => info.width = dimension(ebml_uint(body)),
+                            PIXEL_HEIGHT => info.height = dimension(ebml_uint(body)),
+                            _ => (),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        return;
+    }
+}
+
+fn matroska_codec(codec_id: &str) -> String {
+    match codec_id {
+        "V_MPEG4/ISO/AVC" => "h264".to_string(),
+        "V_MPEGH/ISO/HEVC" => "hevc".to_string(),
+        "V_AV1" => "av1".to_string(),
+        "V_VP8" => "vp8".to_string(),
+        "V_VP9" => "vp9".to_string(),
+        codec_id => codec_id.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn element(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend_from_slice(&(0x0100_0000_0000_0000 | body.len() as u64).to_be_bytes());
+        out.extend_from_slice(body);
+        out
+    }
+
+    #[tokio::test]
+    async fn probes_mp4() {
+        let mut mvhd = vec![0; 20];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&12_345u32.to_be_bytes());
+        let mut tkhd = vec![0; 84];
+        tkhd[76..80].copy_from_slice(&(1080u32 << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(1920u32 << 16).to_be_bytes());
+        let mut hdlr = vec![0; 12];
+        hdlr[8..12].copy_from_slice(b"vide");
+        let mut avc1 = vec![0; 78];
+        avc1[24..26].copy_from_slice(&1080u16.to_be_bytes());
+        avc1[26..28].copy_from_slice(&1920u16.to_be_bytes());
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"avc1", &avc1));
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let mdia = [mp4_box(b"hdlr", &hdlr), mp4_box(b"minf", &stbl)].concat();
+        let trak = [mp4_box(b"tkhd", &tkhd), mp4_box(b"mdia", &mdia)].concat();
+        let moov = [mp4_box(b"mvhd", &mvhd), mp4_box(b"trak", &trak)].concat();
+        let file = [
+            mp4_box(b"ftyp", b"isom\0\0\x02\0"),
+            mp4_box(b"mdat", &[7; 4096]),
+            mp4_box(b"moov", &moov),
+        ]
+        .concat();
+
+        let info = probe("video/mp4", &mut file.as_slice()).await.unwrap();
+        assert_eq!(
+            info,
+            Some(VideoInfo {
+                container: "mp4".to_string(),
+                codec: Some("h264".to_string()),
+                duration: Some(12_345),
+                width: Some(1080),
+                height: Some(1920),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn probes_webm() {
+        let header = element(&[0x42, 0x82], b"webm");
+        let info = [
+            element(&[0x2A, 0xD7, 0xB1], &1_000_000u32.to_be_bytes()),
+            element(&[0x44, 0x89], &2500.0f64.to_be_bytes()),
+        ]
+        .concat();
+        let video = [element(&[0xB0], &[0x05, 0x00]), element(&[0xBA], &[0x02, 0xD0])].concat();
+        let entry = [
+            element(&[0x83], &[1]),
+            element(&[0x86], b"V_VP9"),
+            element(&[0xE0], &video),
+        ]
+        .concat();
+        let segment = [
+            element(&[0x11, 0x4D, 0x9B, 0x74], &[0; 16]),
+            element(&[0x15, 0x49, 0xA9, 0x66], &info),
+            element(&[0x16, 0x54, 0xAE, 0x6B], &element(&[0xAE], &entry)),
+            element(&[0x1F, 0x43, 0xB6, 0x75], &[0; 64]),
+        ]
+        .concat();
+        let file = [
+            element(&[0x1A, 0x45, 0xDF, 0xA3], &header),
+            element(&[0x18, 0x53, 0x80, 0x67], &segment),
+        ]
+        .concat();
+
+        let info = probe("video/webm", &mut file.as_slice()).await.unwrap();
+        assert_eq!(
+            info,
+            Some(VideoInfo {
+                container: "webm".to_string(),
+                codec: Some("vp9".to_string()),
+                duration: Some(2500),
+                width: Some(1280),
+                height: Some(720),
+            })
+        );
+    }
+}
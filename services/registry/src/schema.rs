@@ -9,6 +9,9 @@ pub mod registry {
             password -> Varchar,
             createdAt -> Varchar,
             emailConfirmedAt -> Nullable<Varchar>,
+            invitesDisabled -> Int2,
+            inviteNote -> Nullable<Varchar>,
+            emailAuthFactor -> Int2,
         }
     }
 
@@ -38,6 +41,11 @@ pub mod registry {
             name -> Varchar,
             password -> Varchar,
             createdAt -> Varchar,
+            privileged -> Int2,
+            readOnly -> Int2,
+            collections -> Nullable<Varchar>,
+            expiresAt -> Nullable<Varchar>,
+            lastUsedAt -> Nullable<Varchar>,
         }
     }
 
@@ -60,6 +68,9 @@ pub mod registry {
             height -> Nullable<Int4>,
             createdAt -> Varchar,
             takedownRef -> Nullable<Varchar>,
+            duration -> Nullable<Int4>,
+            container -> Nullable<Varchar>,
+            codec -> Nullable<Varchar>,
         }
     }
 
@@ -80,6 +91,61 @@ pub mod registry {
         }
     }
 
+    diesel::table! {
+        registry.invite_code (code) {
+            code -> Varchar,
+            availableUses -> Int4,
+            disabled -> Int2,
+            forAccount -> Varchar,
+            createdBy -> Varchar,
+            createdAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        registry.invite_code_use (code, usedBy) {
+            code -> Varchar,
+            usedBy -> Varchar,
+            usedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        registry.oauth_request (id) {
+            id -> Varchar,
+            clientId -> Varchar,
+            clientAuth -> Varchar,
+            parameters -> Text,
+            dpopJkt -> Varchar,
+            did -> Nullable<Varchar>,
+            code -> Nullable<Varchar>,
+            expiresAt -> Varchar,
+            createdAt -> Varchar,
+            csrfToken -> Nullable<Varchar>,
+        }
+    }
+
+    diesel::table! {
+        registry.oauth_token (id) {
+            id -> Varchar,
+            did -> Varchar,
+            clientId -> Varchar,
+            clientAuth -> Varchar,
+            scope -> Varchar,
+            dpopJkt -> Varchar,
+            refreshToken -> Varchar,
+            createdAt -> Varchar,
+            expiresAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        registry.oauth_used_jti (jti) {
+            jti -> Varchar,
+            expiresAt -> Varchar,
+        }
+    }
+
     diesel::table! {
         registry.record (uri) {
             uri -> Varchar,
@@ -101,6 +167,14 @@ pub mod registry {
         }
     }
 
+    diesel::table! {
+        registry.recovery_code (did, code) {
+            did -> Varchar,
+            code -> Varchar,
+            usedAt -> Nullable<Varchar>,
+        }
+    }
+
     diesel::table! {
         registry.refresh_token (id) {
             id -> Varchar,
@@ -108,6 +182,7 @@ pub mod registry {
             expiresAt -> Varchar,
             nextId -> Nullable<Varchar>,
             appPasswordName -> Nullable<Varchar>,
+            sessionId -> Nullable<Varchar>,
         }
     }
 
@@ -141,6 +216,40 @@ pub mod registry {
         }
     }
 
+    diesel::table! {
+        registry.session (id) {
+            id -> Varchar,
+            did -> Varchar,
+            appPasswordName -> Nullable<Varchar>,
+            userAgent -> Nullable<Varchar>,
+            ipAddress -> Nullable<Varchar>,
+            createdAt -> Varchar,
+            lastUsedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        registry.signing_key (did) {
+            did -> Varchar,
+            keyDid -> Varchar,
+            privateKey -> Varchar,
+            createdAt -> Varchar,
+            keyNonce -> Nullable<Varchar>,
+            pendingFor -> Nullable<Varchar>,
+        }
+    }
+
+    diesel::table! {
+        registry.totp (did) {
+            did -> Varchar,
+            secret -> Varchar,
+            secretNonce -> Varchar,
+            lastUsedStep -> Nullable<Int8>,
+            confirmedAt -> Nullable<Varchar>,
+            createdAt -> Varchar,
+        }
+    }
+
     diesel::allow_tables_to_appear_in_same_query!(
         account,
         account_pref,
@@ -150,11 +259,20 @@ pub mod registry {
         blob,
         did_doc,
         email_token,
+        invite_code,
+        invite_code_use,
+        oauth_request,
+        oauth_token,
+        oauth_used_jti,
         record,
         record_blob,
+        recovery_code,
         refresh_token,
         repo_block,
         repo_root,
         repo_seq,
+        session,
+        signing_key,
+        totp,
     );
 }
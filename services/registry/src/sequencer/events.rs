@@ -5,17 +5,16 @@
  */
 use crate::account_manager::helpers::account::AccountStatus;
 use crate::database::models;
+use crate::repository::CommitDataWithOps;
 use rsky_pds::car::read_car_bytes;
 use rsky_pds::common;
 use rsky_pds::common::struct_to_cbor;
 use rsky_pds::repo::block_map::BlockMap;
 use rsky_pds::repo::cid_set::CidSet;
-use rsky_pds::repo::types::{CommitData, PreparedWrite};
-use rsky_pds::repo::util::format_data_key;
-use anyhow::Result;
+use rsky_pds::repo::types::{PreparedWrite, WriteOpAction};
+use anyhow::{bail, Result};
 use lexicon_cid::Cid;
 use rsky_lexicon::com::atproto::sync::AccountStatus as LexiconAccountStatus;
-use rsky_syntax::aturi::AtUri;
 use serde::de::Error as DeserializerError;
 use serde::{Deserialize, Serialize, Deserializer};
 
@@ -41,6 +40,10 @@ pub struct CommitEvtOp {
     pub action: CommitEvtOpAction,
     pub path: String,
     pub cid: Option<Cid>,
+    /// The record's CID before an update or delete. Missing from events sequenced before it was
+    /// added.
+    #[serde(default)]
+    pub prev: Option<Cid>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -50,6 +53,10 @@ pub struct CommitEvt {
     pub repo: String,
     pub commit: Cid,
     pub prev: Option<Cid>,
+    /// The previous commit's MST root, which inverting `ops` against `blocks` should reproduce.
+    /// Missing from events sequenced before it was added.
+    #[serde(default)]
+    pub prev_data: Option<Cid>,
     pub rev: String,
     pub since: Option<String>,
     pub blocks: Vec<u8>,
@@ -57,6 +64,16 @@ pub struct CommitEvt {
     pub blobs: Vec<Cid>,
 }
 
+/// Declares the current state of a repo without a diff, for when consumers can't follow on from
+/// its previous commit (an account being activated or its repo imported). `blocks` holds just
+/// the signed commit.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SyncEvt {
+    pub did: String,
+    pub blocks: Vec<u8>,
+    pub rev: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct HandleEvt {
     pub did: String,
@@ -102,6 +119,7 @@ impl Default for TypedCommitEvt {
                 repo: "".to_string(),
                 commit: Default::default(),
                 prev: None,
+                prev_data: None,
                 rev: "".to_string(),
                 since: None,
                 blocks: vec![],
@@ -112,6 +130,14 @@ impl Default for TypedCommitEvt {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TypedSyncEvt {
+    pub r#type: String, // 'sync'
+    pub seq: i64,
+    pub time: String,
+    pub evt: SyncEvt,
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct TypedHandleEvt {
     pub r#type: String, // 'handle'
@@ -162,6 +188,7 @@ pub struct TypedTombstoneEvt {
 #[serde(untagged)]
 pub enum SeqEvt {
     TypedCommitEvt(TypedCommitEvt),
+    TypedSyncEvt(TypedSyncEvt),
     TypedHandleEvt(TypedHandleEvt),
     TypedIdentityEvt(TypedIdentityEvt),
     TypedAccountEvt(TypedAccountEvt),
@@ -183,6 +210,9 @@ impl<'de> Deserialize<'de> for SeqEvt {
                 Some("commit") => Ok(SeqEvt::TypedCommitEvt(
                     serde_json::from_value(value).map_err(DeserializerError::custom)?,
                 )),
+                Some("sync") => Ok(SeqEvt::TypedSyncEvt(
+                    serde_json::from_value(value).map_err(DeserializerError::custom)?,
+                )),
                 Some("handle") => Ok(SeqEvt::TypedHandleEvt(
                     serde_json::from_value(value).map_err(DeserializerError::custom)?,
                 )),
@@ -207,6 +237,7 @@ impl SeqEvt {
     pub fn seq(&self) -> i64 {
         match self {
             SeqEvt::TypedCommitEvt(this) => this.seq,
+            SeqEvt::TypedSyncEvt(this) => this.seq,
             SeqEvt::TypedHandleEvt(this) => this.seq,
             SeqEvt::TypedIdentityEvt(this) => this.seq,
             SeqEvt::TypedAccountEvt(this) => this.seq,
@@ -217,57 +248,49 @@ impl SeqEvt {
 
 pub async fn format_seq_commit(
     did: String,
-    commit_data: CommitData,
+    commit_data: CommitDataWithOps,
     writes: Vec<PreparedWrite>,
 ) -> Result<models::RepoSeq> {
-    let too_big: bool;
-    let mut ops: Vec<CommitEvtOp> = Vec::new();
     let mut blobs = CidSet::new(None);
-    let car_slice: Vec<u8>;
-
-    if writes.len() > 200 || commit_data.new_blocks.byte_size()? > 1000000 {
-        too_big = true;
-        let mut just_root = BlockMap::new();
-        just_root.add(commit_data.new_blocks.get(commit_data.cid))?;
-        car_slice = read_car_bytes(Some(&commit_data.cid), just_root).await?;
-    } else {
-        too_big = false;
-        for w in writes {
-            let uri = AtUri::new(w.uri().clone(), None)?;
-            let path = format_data_key(uri.get_collection(), uri.get_rkey());
-            let cid: Option<Cid>;
-            let action: CommitEvtOpAction;
-            match w {
-                PreparedWrite::Create(w) => {
-                    cid = Some(w.cid);
-                    for blob in w.blobs {
-                        blobs.add(blob.cid);
-                    }
-                    action = CommitEvtOpAction::Create;
-                }
-                PreparedWrite::Update(w) => {
-                    cid = Some(w.cid);
-                    for blob in w.blobs {
-                        blobs.add(blob.cid);
-                    }
-                    action = CommitEvtOpAction::Update;
-                }
-                PreparedWrite::Delete(_) => {
-                    cid = None;
-                    action = CommitEvtOpAction::Delete;
+    for w in writes {
+        match w {
+            PreparedWrite::Create(w) | PreparedWrite::Update(w) => {
+                for blob in w.blobs {
+                    blobs.add(blob.cid);
                 }
             }
-            ops.push(CommitEvtOp { action, path, cid });
+            PreparedWrite::Delete(_) => (),
         }
-        car_slice = read_car_bytes(Some(&commit_data.cid), commit_data.new_blocks).await?;
     }
+    let ops = commit_data
+        .ops
+        .into_iter()
+        .map(|op| CommitEvtOp {
+            action: match op.action {
+                WriteOpAction::Create => CommitEvtOpAction::Create,
+                WriteOpAction::Update => CommitEvtOpAction::Update,
+                WriteOpAction::Delete => CommitEvtOpAction::Delete,
+            },
+            path: op.path,
+            cid: op.cid,
+            prev: op.prev,
+        })
+        .collect::<Vec<CommitEvtOp>>();
+
+    // Consumers need the covering proofs as well as the new blocks to invert the ops, so the
+    // whole commit is always sent rather than falling back to `tooBig`. Writes are capped at 200
+    // per commit, which keeps this bounded.
+    let mut blocks = commit_data.new_blocks;
+    blocks.add_map(commit_data.relevant_blocks)?;
+    let car_slice = read_car_bytes(Some(&commit_data.cid), blocks).await?;
 
     let evt = CommitEvt {
         rebase: false,
-        too_big,
+        too_big: false,
         repo: did.clone(),
         commit: commit_data.cid,
         prev: commit_data.prev,
+        prev_data: commit_data.prev_data,
         rev: commit_data.rev,
         since: commit_data.since,
         ops,
@@ -282,6 +305,31 @@ pub async fn format_seq_commit(
     ))
 }
 
+/// `blocks` must contain the signed commit `commit`; only that block is sent.
+pub async fn format_seq_sync_evt(
+    did: String,
+    commit: Cid,
+    rev: String,
+    blocks: &BlockMap,
+) -> Result<models::RepoSeq> {
+    let Some(commit_block) = blocks.get(commit) else {
+        bail!("Missing commit block `{commit}` for sync event")
+    };
+    let mut just_root = BlockMap::new();
+    just_root.set(commit, commit_block.clone());
+    let evt = SyncEvt {
+        did: did.clone(),
+        blocks: read_car_bytes(Some(&commit), just_root).await?,
+        rev,
+    };
+    Ok(models::RepoSeq::new(
+        did,
+        "sync".to_string(),
+        struct_to_cbor(evt)?,
+        common::now(),
+    ))
+}
+
 pub async fn format_seq_handle_update(did: String, handle: String) -> Result<models::RepoSeq> {
     let evt = HandleEvt {
         did: did.clone(),
@@ -346,4 +394,56 @@ pub async fn format_seq_tombstone(did: String) -> Result<models::RepoSeq> {
         struct_to_cbor(evt)?,
         common::now(),
     ))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_pds::common::cbor_to_struct;
+
+    /// `CommitEvtOp` and `CommitEvt` as stored before `prev` and `prev_data` were added.
+    #[derive(Serialize)]
+    struct OldCommitEvtOp {
+        action: CommitEvtOpAction,
+        path: String,
+        cid: Option<Cid>,
+    }
+
+    #[derive(Serialize)]
+    struct OldCommitEvt {
+        rebase: bool,
+        too_big: bool,
+        repo: String,
+        commit: Cid,
+        prev: Option<Cid>,
+        rev: String,
+        since: Option<String>,
+        blocks: Vec<u8>,
+        ops: Vec<OldCommitEvtOp>,
+        blobs: Vec<Cid>,
+    }
+
+    #[test]
+    fn decodes_commits_stored_in_old_format() {
+        let old = OldCommitEvt {
+            rebase: false,
+            too_big: true,
+            repo: "did:plc:example".to_string(),
+            commit: Cid::default(),
+            prev: None,
+            rev: "3jzfcijpj2z2a".to_string(),
+            since: None,
+            blocks: vec![],
+            ops: vec![OldCommitEvtOp {
+                action: CommitEvtOpAction::Delete,
+                path: "app.bsky.feed.post/3jzfcijpj2z2a".to_string(),
+                cid: None,
+            }],
+            blobs: vec![],
+        };
+        let evt: CommitEvt = cbor_to_struct(struct_to_cbor(old).unwrap()).unwrap();
+        assert!(evt.too_big);
+        assert_eq!(evt.prev_data, None);
+        assert_eq!(evt.ops[0].prev, None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Wakes the sequencer when an event is sequenced, by any registry node sharing the database.
+//! `Sequencer::sequence_evt` sends a `NOTIFY` on `CHANNEL` and this holds a dedicated connection
+//! that `LISTEN`s for it. Diesel can't receive notifications, so that connection is made with
+//! `tokio-postgres` instead of coming from the pool.
+use crate::config::DATABASE_CONFIG;
+use anyhow::Result;
+use futures::{stream, StreamExt};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_postgres::AsyncMessage;
+
+pub const CHANNEL: &str = "repo_seq";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Listens for as long as the process runs, reconnecting whenever the connection drops. While
+/// it's down the sequencer still finds new events by polling, just more slowly.
+pub async fn listen(wakeup: Arc<Notify>) {
+    loop {
+        if let Err(error) = listen_once(&wakeup).await {
+            eprintln!(
+                "@LOG: ERROR: sequencer can't LISTEN for new events, polling for them until it \
+                 can: {error}"
+            );
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn listen_once(wakeup: &Arc<Notify>) -> Result<()> {
+    let (url, tls) = connect_options(&DATABASE_CONFIG.url)?;
+    let (client, mut connection) = tokio_postgres::connect(&url, tls).await?;
+    // The connection only makes progress while it's polled, which has to happen alongside the
+    // LISTEN below. Notifications come through it as messages.
+    let notified = Arc::clone(wakeup);
+    let messages = tokio::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if let AsyncMessage::Notification(_) = message? {
+                notified.notify_one();
+            }
+        }
+        Ok::<_, tokio_postgres::Error>(())
+    });
+    client.batch_execute(&format!("LISTEN {CHANNEL}")).await?;
+    // Anything sequenced while we weren't listening was missed, so check for it now.
+    wakeup.notify_one();
+
+    // `client` has to outlive the connection, which closes once it's dropped.
+    messages.await??;
+    drop(client);
+    Ok(())
+}
+
+/// Connects the way libpq, and so the diesel pool, would with the same URL: `prefer` and
+/// `require` encrypt without checking the server's certificate, while `verify-ca` and
+/// `verify-full` check it. tokio-postgres doesn't know the verify modes, so it's told `require`.
+fn connect_options(url: &str) -> Result<(String, MakeTlsConnector)> {
+    let mut builder = TlsConnector::builder();
+    let url = match ssl_mode(url) {
+        Some(mode @ ("verify-ca" | "verify-full")) => {
+            builder.danger_accept_invalid_hostnames(mode == "verify-ca");
+            url.replace(&format!("sslmode={mode}"), "sslmode=require")
+        }
+        _ => {
+            builder.danger_accept_invalid_certs(true);
+            url.to_string()
+        }
+    };
+    Ok((url, MakeTlsConnector::new(builder.build()?)))
+}
+
+/// The `sslmode` of a URL or key/value connection string.
+fn ssl_mode(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("sslmode=")?;
+    rest.split(['&', ' ']).next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::establish_connection;
+    use diesel::sql_query;
+    use diesel::sql_types::Text;
+    use diesel::RunQueryDsl;
+    use tokio::time::timeout;
+
+    #[test]
+    fn reads_the_ssl_mode() {
+        assert_eq!(
+            ssl_mode("postgresql://registry@db/registry?sslmode=verify-full&application_name=pds"),
+            Some("verify-full")
+        );
+        assert_eq!(
+            ssl_mode("host=db user=registry sslmode=require"),
+            Some("require")
+        );
+        assert_eq!(ssl_mode("postgresql://registry@db/registry"), None);
+
+        let (url, _) =
+            connect_options("postgresql://registry@db/registry?sslmode=verify-ca").unwrap();
+        assert_eq!(url, "postgresql://registry@db/registry?sslmode=require");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn wakes_on_notify() {
+        let wakeup = Arc::new(Notify::new());
+        tokio::spawn(listen(Arc::clone(&wakeup)));
+        // Connecting wakes the sequencer too, to catch anything sequenced beforehand
+        timeout(Duration::from_secs(5), wakeup.notified())
+            .await
+            .expect("Listener didn't connect");
+
+        sql_query("SELECT pg_notify($1, '1')")
+            .bind::<Text, _>(CHANNEL)
+            .execute(&mut establish_connection().unwrap())
+            .unwrap();
+        timeout(Duration::from_secs(5), wakeup.notified())
+            .await
+            .expect("Listener wasn't woken by NOTIFY");
+    }
+}
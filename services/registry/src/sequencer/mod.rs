@@ -4,24 +4,42 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::account_manager::helpers::account::AccountStatus;
-use rsky_pds::common::time::SECOND;
-use rsky_pds::common::{cbor_to_struct, wait};
+use rsky_pds::common::cbor_to_struct;
 use rsky_pds::crawlers::Crawlers;
 use crate::database::establish_connection;
 use crate::database::models;
-use rsky_pds::repo::types::{CommitData, PreparedWrite};
+use crate::repository::CommitDataWithOps;
+use rsky_pds::repo::block_map::BlockMap;
+use rsky_pds::repo::types::PreparedWrite;
 use crate::sequencer::events::{
     format_seq_account_evt, format_seq_commit, format_seq_handle_update, format_seq_identity_evt,
-    format_seq_tombstone, SeqEvt, TypedAccountEvt, TypedCommitEvt, TypedHandleEvt,
-    TypedIdentityEvt, TypedTombstoneEvt,
+    format_seq_sync_evt, format_seq_tombstone, SeqEvt, TypedAccountEvt, TypedCommitEvt,
+    TypedHandleEvt, TypedIdentityEvt, TypedSyncEvt, TypedTombstoneEvt,
 };
-use crate::EVENT_EMITTER;
 use anyhow::Result;
+use diesel::sql_types::Text;
 use diesel::*;
-use futures::{Stream, StreamExt};
-use std::cmp;
-use std::pin::Pin;
-use std::task::{Context, Poll, Waker};
+use lexicon_cid::Cid;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tokio::time::timeout;
+
+/// Events read from `repo_seq` per query.
+const POLL_LIMIT: i64 = 1000;
+
+/// How far a subscriber can fall behind the live events before it has to catch up from the
+/// database instead.
+const EVENT_BUFFER_SIZE: usize = 1000;
+
+/// The sequencer is normally woken by `NOTIFY`. This is how long it waits without one before
+/// checking anyway, in case the listener is disconnected.
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Taken by tests that invalidate or remove events, since every test shares `repo_seq`.
+#[cfg(test)]
+pub static TEST_LOCK: std::sync::LazyLock<tokio::sync::Mutex<()>> =
+    std::sync::LazyLock::new(Default::default);
 
 #[derive(Debug, Clone)]
 pub struct RequestSeqRangeOpts {
@@ -31,45 +49,75 @@ pub struct RequestSeqRangeOpts {
     pub limit: Option<i64>,
 }
 
+/// Writes events to `repo_seq` and publishes them to subscribers as they're sequenced.
+///
+/// Clones share the subscriber channel, so one clone runs `start` in the background while the
+/// others sequence events and hand out subscriptions.
 #[derive(Debug, Clone)]
 pub struct Sequencer {
-    pub destroyed: bool,
-    pub tries_with_no_results: u32,
-    pub waker: Option<Waker>,
     pub crawlers: Crawlers,
     pub last_seen: Option<i64>,
+    events: broadcast::Sender<SeqEvt>,
+    wakeup: Arc<Notify>,
 }
 
 impl Sequencer {
     pub fn new(crawlers: Crawlers, last_seen: Option<i64>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER_SIZE);
         Sequencer {
-            destroyed: false,
-            tries_with_no_results: 0,
             last_seen: Some(last_seen.unwrap_or(0)),
-            waker: None,
             crawlers,
+            events,
+            wakeup: Arc::new(Notify::new()),
         }
     }
 
+    /// Publishes events as they're sequenced, from the current head onwards. Runs until the
+    /// process exits.
     pub async fn start(&mut self) -> Result<()> {
         let curr = self.curr().await?;
         self.last_seen = Some(curr.unwrap_or(0));
-        if self.waker.is_none() {
-            loop {
-                while let Some(_) = self.next().await {
-                    ()
-                }
+        tokio::spawn(listener::listen(Arc::clone(&self.wakeup)));
+        loop {
+            match self.poll().await {
+                // A full page means there's probably more waiting.
+                Ok(count) if count as i64 == POLL_LIMIT => continue,
+                Ok(_) => (),
+                Err(err) => eprintln!(
+                    "@LOG: sequencer failed to poll db, err: {}, last_seen: {:?}",
+                    err.to_string(),
+                    self.last_seen
+                ),
             }
+            let _ = timeout(FALLBACK_POLL_INTERVAL, self.wakeup.notified()).await;
         }
-        Ok(())
     }
 
-    pub async fn destroy(&mut self) {
-        self.destroyed = true;
-        if let Some(waker) = self.waker.take() {
-            waker.wake();
+    /// Publishes any events sequenced since `last_seen`, returning how many there were.
+    async fn poll(&mut self) -> Result<usize> {
+        let evts = self
+            .request_seq_range(RequestSeqRangeOpts {
+                earliest_seq: self.last_seen,
+                latest_seq: None,
+                earliest_time: None,
+                limit: Some(POLL_LIMIT),
+            })
+            .await?;
+        if let Some(last_evt) = evts.last() {
+            self.last_seen = Some(last_evt.seq());
+        }
+        let count = evts.len();
+        for evt in evts {
+            // Sending only fails when nobody is subscribed.
+            let _ = self.events.send(evt);
         }
-        EVENT_EMITTER.write().await.emit("close", ());
+        Ok(count)
+    }
+
+    /// Live events from now on. Receivers that fall more than `EVENT_BUFFER_SIZE` events behind
+    /// get `RecvError::Lagged` and miss the events in between.
+    pub fn subscribe(&self) -> broadcast::Receiver<SeqEvt> {
+        self.events.subscribe()
     }
 
     pub async fn curr(&self) -> Result<Option<i64>> {
@@ -87,6 +135,19 @@ impl Sequencer {
         }
     }
 
+    /// The oldest event still kept. Anything before it has been removed by the janitor.
+    pub async fn earliest(&self) -> Result<Option<i64>> {
+        use crate::schema::registry::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection()?;
+
+        let got = RepoSeqSchema::repo_seq
+            .select(RepoSeqSchema::seq)
+            .order_by(RepoSeqSchema::seq.asc())
+            .first(conn)
+            .optional()?;
+        Ok(got)
+    }
+
     pub async fn next_seq(&self, cursor: i64) -> Result<Option<models::RepoSeq>> {
         use crate::schema::registry::repo_seq::dsl as RepoSeqSchema;
         let conn = &mut establish_connection()?;
@@ -159,6 +220,13 @@ impl Sequencer {
                             time: row.sequenced_at,
                             evt: cbor_to_struct(row.event)?,
                         }));
+                    } else if row.event_type == "sync" {
+                        seq_evts.push(SeqEvt::TypedSyncEvt(TypedSyncEvt {
+                            r#type: "sync".to_string(),
+                            seq,
+                            time: row.sequenced_at,
+                            evt: cbor_to_struct(row.event)?,
+                        }));
                     } else if row.event_type == "handle" {
                         seq_evts.push(SeqEvt::TypedHandleEvt(TypedHandleEvt {
                             r#type: "handle".to_string(),
@@ -195,18 +263,6 @@ impl Sequencer {
         Ok(seq_evts)
     }
 
-    async fn exponential_backoff(&mut self) -> () {
-        self.tries_with_no_results += 1;
-        let wait_time = cmp::min(
-            2u64.checked_pow(self.tries_with_no_results).unwrap_or(2),
-            SECOND as u64,
-        );
-        wait(wait_time);
-        if let Some(waker) = self.waker.take() {
-            waker.wake();
-        }
-    }
-
     pub async fn sequence_evt(&mut self, evt: models::RepoSeq) -> Result<i64> {
         use crate::schema::registry::repo_seq::dsl as RepoSeqSchema;
         let conn = &mut establish_connection()?;
@@ -219,20 +275,39 @@ impl Sequencer {
                 RepoSeqSchema::sequencedAt.eq(evt.sequenced_at),
             ))
             .get_result::<models::RepoSeq>(conn)?;
+        let seq = res.seq.expect("Sequence number wasn't updated on insert.");
+        sql_query("SELECT pg_notify($1, $2)")
+            .bind::<Text, _>(listener::CHANNEL)
+            .bind::<Text, _>(seq.to_string())
+            .execute(conn)?;
+        // The listener passes the notification on too, but there's no need to wait for the
+        // round trip when the event was sequenced here.
+        self.wakeup.notify_one();
         self.crawlers.notify_of_update().await?;
-        Ok(res.seq.expect("Sequence number wasn't updated on insert."))
+        Ok(seq)
     }
 
     pub async fn sequence_commit(
         &mut self,
         did: String,
-        commit_data: CommitData,
+        commit_data: CommitDataWithOps,
         writes: Vec<PreparedWrite>,
     ) -> Result<i64> {
         let evt = format_seq_commit(did, commit_data, writes).await?;
         self.sequence_evt(evt).await
     }
 
+    pub async fn sequence_sync_evt(
+        &mut self,
+        did: String,
+        commit: Cid,
+        rev: String,
+        blocks: &BlockMap,
+    ) -> Result<i64> {
+        let evt = format_seq_sync_evt(did, commit, rev, blocks).await?;
+        self.sequence_evt(evt).await
+    }
+
     pub async fn sequence_handle_update(&mut self, did: String, handle: String) -> Result<i64> {
         let evt = format_seq_handle_update(did, handle).await?;
         self.sequence_evt(evt).await
@@ -262,69 +337,110 @@ impl Sequencer {
     }
 }
 
-impl Stream for Sequencer {
-    type Item = Result<(), anyhow::Error>;
+/// Marks all of a user's events other than `excluding_seqs` as invalidated, so they're no longer
+/// served. Used when an account is deleted; the janitor removes the rows later.
+pub async fn invalidate_all_for_user(did: &String, excluding_seqs: Option<Vec<i64>>) -> Result<()> {
+    invalidate_for_user(did, None, excluding_seqs)
+}
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if self.destroyed {
-            return Poll::Ready(None);
-        }
-        // if already polling, do not start another poll
-        return match futures::executor::block_on(self.request_seq_range(RequestSeqRangeOpts {
-            earliest_seq: self.last_seen,
-            latest_seq: None,
-            earliest_time: None,
-            limit: Some(1000),
-        })) {
-            Err(err) => {
-                eprintln!(
-                    "@LOG: sequencer failed to poll db, err: {}, last_seen: {:?}",
-                    err.to_string(),
-                    self.last_seen
-                );
-                self.waker = Some(cx.waker().clone());
-                futures::executor::block_on(self.exponential_backoff());
-                Poll::Ready(Some(Err(err)))
-            }
-            Ok(evts) => {
-                if evts.len() > 0 {
-                    self.tries_with_no_results = 0;
-                    futures::executor::block_on(EVENT_EMITTER.write()).emit(
-                        "events",
-                        evts.iter()
-                            .map(|evt| serde_json::to_string(evt).unwrap())
-                            .collect::<Vec<String>>(),
-                    );
-                    self.last_seen = match evts.last() {
-                        None => self.last_seen,
-                        Some(last_evt) => Some(last_evt.seq()),
-                    };
-                    self.waker = Some(cx.waker().clone());
-                    Poll::Ready(Some(Ok(())))
-                } else {
-                    self.waker = Some(cx.waker().clone());
-                    futures::executor::block_on(self.exponential_backoff());
-                    Poll::Pending
-                }
-            }
-        };
-    }
+/// Marks a user's commit and sync events other than `excluding_seqs` as invalidated. Used when
+/// their repo is re-synced, since the earlier events describe history that's been replaced.
+pub async fn invalidate_commits_for_user(
+    did: &String,
+    excluding_seqs: Option<Vec<i64>>,
+) -> Result<()> {
+    invalidate_for_user(did, Some(vec!["append", "rebase", "sync"]), excluding_seqs)
 }
 
-pub async fn delete_all_for_user(did: &String, excluding_seqs: Option<Vec<i64>>) -> Result<()> {
+fn invalidate_for_user(
+    did: &String,
+    event_types: Option<Vec<&str>>,
+    excluding_seqs: Option<Vec<i64>>,
+) -> Result<()> {
     use crate::schema::registry::repo_seq::dsl as RepoSeqSchema;
     let conn = &mut establish_connection()?;
     let excluding_seqs = excluding_seqs.unwrap_or_else(|| vec![]);
 
-    let mut builder = delete(RepoSeqSchema::repo_seq)
+    let mut builder = update(RepoSeqSchema::repo_seq)
         .filter(RepoSeqSchema::did.eq(did))
+        .filter(RepoSeqSchema::invalidated.eq(0))
         .into_boxed();
+    if let Some(event_types) = event_types {
+        builder = builder.filter(RepoSeqSchema::eventType.eq_any(event_types));
+    }
     if excluding_seqs.len() > 0 {
         builder = builder.filter(RepoSeqSchema::seq.ne_all(excluding_seqs));
     }
-    builder.execute(conn)?;
+    builder
+        .set(RepoSeqSchema::invalidated.eq(1))
+        .execute(conn)?;
     Ok(())
 }
 
 pub mod events;
-pub mod outbox;
\ No newline at end of file
+pub mod listener;
+pub mod outbox;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn invalidates_a_users_events() {
+        use crate::schema::registry::repo_seq::dsl as RepoSeqSchema;
+        let _lock = TEST_LOCK.lock().await;
+        let mut sequencer = Sequencer::new(Crawlers::new("localhost".to_string(), vec![]), None);
+        let did = format!("did:example:{}", rsky_pds::common::get_random_str());
+        let other = format!("did:example:{}", rsky_pds::common::get_random_str());
+        let mut seqs = Vec::new();
+        for (did, event_type) in [
+            (&did, "identity"),
+            (&did, "sync"),
+            (&did, "append"),
+            (&did, "sync"),
+            (&other, "append"),
+        ] {
+            let mut evt = format_seq_identity_evt(did.clone(), None).await.unwrap();
+            evt.event_type = event_type.to_string();
+            seqs.push(sequencer.sequence_evt(evt).await.unwrap());
+        }
+        let invalidated = || -> BTreeMap<i64, i16> {
+            RepoSeqSchema::repo_seq
+                .filter(RepoSeqSchema::did.eq_any([&did, &other]))
+                .select((RepoSeqSchema::seq, RepoSeqSchema::invalidated))
+                .load::<(i64, i16)>(&mut establish_connection().unwrap())
+                .unwrap()
+                .into_iter()
+                .collect()
+        };
+
+        // Commits and syncs go, apart from the excluded one; other events and users are kept
+        invalidate_commits_for_user(&did, Some(vec![seqs[3]]))
+            .await
+            .unwrap();
+        assert_eq!(
+            invalidated(),
+            BTreeMap::from([
+                (seqs[0], 0),
+                (seqs[1], 1),
+                (seqs[2], 1),
+                (seqs[3], 0),
+                (seqs[4], 0)
+            ])
+        );
+
+        invalidate_all_for_user(&did, None).await.unwrap();
+        assert_eq!(
+            invalidated(),
+            BTreeMap::from([
+                (seqs[0], 1),
+                (seqs[1], 1),
+                (seqs[2], 1),
+                (seqs[3], 1),
+                (seqs[4], 0)
+            ])
+        );
+    }
+}
\ No newline at end of file
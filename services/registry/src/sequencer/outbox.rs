@@ -3,48 +3,29 @@
  * Modified to work with our own DB
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
-use rsky_pds::common::r#async::{AsyncBuffer, AsyncBufferFullError};
 use crate::sequencer::events::SeqEvt;
 use crate::sequencer::{RequestSeqRangeOpts, Sequencer};
-use crate::EVENT_EMITTER;
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use futures::stream::Stream;
 use futures::{pin_mut, StreamExt};
 use rocket::async_stream::try_stream;
-use std::sync::Arc;
-use tokio::runtime::Runtime;
-use tokio::sync::{Mutex, RwLock};
-use tokio::time::{timeout, Duration};
+use tokio::sync::broadcast::error::RecvError;
 
-#[derive(Debug, Clone)]
-pub struct OutboxOpts {
-    pub max_buffer_size: usize,
-}
-
-#[allow(dead_code)]
+/// A single subscriber's view of the event stream: a backfill from its cursor, followed by live
+/// events from the sequencer.
+#[derive(Debug)]
 pub struct Outbox {
-    caught_up: Arc<Mutex<bool>>,
     pub last_seen: i64,
-    pub cutover_buffer: Arc<Mutex<Vec<SeqEvt>>>,
-    pub out_buffer: Arc<RwLock<AsyncBuffer<SeqEvt>>>,
     pub sequencer: Sequencer,
-    pub backfill_cursor: Option<i64>,
 }
 
 const PAGE_SIZE: i64 = 500;
 
 impl Outbox {
-    pub fn new(sequencer: Sequencer, opts: Option<OutboxOpts>) -> Self {
-        let OutboxOpts { max_buffer_size } = opts.unwrap_or(OutboxOpts {
-            max_buffer_size: 500,
-        });
+    pub fn new(sequencer: Sequencer) -> Self {
         Self {
             sequencer,
-            caught_up: Arc::new(Mutex::new(false)),
             last_seen: -1,
-            cutover_buffer: Arc::new(Mutex::new(vec![])),
-            out_buffer: Arc::new(RwLock::new(AsyncBuffer::new(Some(max_buffer_size)))),
-            backfill_cursor: None,
         }
     }
 
@@ -53,122 +34,118 @@ impl Outbox {
         backfill_cursor: Option<i64>,
     ) -> impl Stream<Item = Result<SeqEvt>> + 'a {
         try_stream! {
-            if let Some(cursor) = backfill_cursor {
-                let backfill_stream = self.get_backfill(cursor).await;
-                pin_mut!(backfill_stream);
-                while let Some(Ok(evt)) = backfill_stream.next().await {
-                    yield evt;
-                }
-            } else {
-                let mut bool_lock = self.caught_up.lock().await;
-                *bool_lock = true;
-            }
-
-            let caught_up = Arc::clone(&self.caught_up);
-            let out_buffer = Arc::clone(&self.out_buffer);
-            let cutover_buffer = Arc::clone(&self.cutover_buffer);
-
-            let add_to_buffer = move |evts: Vec<String>| {
-                let evts = evts
-                    .into_iter()
-                    .map(|evt| serde_json::from_str(evt.as_str()).unwrap())
-                    .collect::<Vec<SeqEvt>>();
-                let caught_up = Arc::clone(&caught_up);
-                let out_buffer = Arc::clone(&out_buffer);
-                let cutover_buffer = Arc::clone(&cutover_buffer);
-
-                async move {
-                    if *caught_up.lock().await {
-                        out_buffer.read().await.push_many(evts);
-                    } else {
-                        cutover_buffer.lock().await.extend(evts);
-                    }
-                }
+            // Where to catch up from if we fall behind before yielding anything. Without a cursor
+            // that's the head as we subscribe, not the start of the table.
+            let start = match backfill_cursor {
+                Some(cursor) => cursor,
+                None => self.sequencer.curr().await?.unwrap_or(-1),
             };
-
-            EVENT_EMITTER.write().await.on("events", move |evts| {
-                let rt = Runtime::new().unwrap();
-                // By entering the context, we tie `tokio::spawn` to this executor.
-                let _guard = rt.enter();
-                rt.block_on(tokio::spawn(
-                    add_to_buffer(evts)
-                )).unwrap();
-            });
-
-            if let Some(cursor) = backfill_cursor {
-                let earliest_seq = if self.last_seen > -1 {
-                    Some(self.last_seen)
-                } else {
-                    Some(cursor)
-                };
-                let cutover_evts = self.sequencer.request_seq_range(RequestSeqRangeOpts {
-                    earliest_seq,
-                    latest_seq: None,
-                    earliest_time: None,
-                    limit: Some(PAGE_SIZE),
-                }).await?;
-                {
-                    let out_buffer_lock = self.out_buffer.read().await;
-                    let mut cutover_lock = self.cutover_buffer.lock().await;
-                    out_buffer_lock.push_many(cutover_evts);
-                    out_buffer_lock.push_many(cutover_lock.drain(..).collect());
+            // Subscribing before the backfill means events sequenced while it runs are held for
+            // us rather than missed. Those that the backfill also returns are skipped by seq.
+            let mut live = self.sequencer.subscribe();
+            if backfill_cursor.is_some() {
+                let backfill_stream = self.get_backfill(start).await;
+                pin_mut!(backfill_stream);
+                while let Some(evt) = backfill_stream.next().await {
+                    yield evt?;
                 }
-                let mut bool_lock = self.caught_up.lock().await;
-                *bool_lock = true;
-            } else {
-                let mut bool_lock = self.caught_up.lock().await;
-                *bool_lock = true;
             }
 
             loop {
-                while let Ok(Some(res)) = timeout(Duration::from_secs(2),self.out_buffer.write().await.next()).await {
-                    let evt = res.map_err(|error| {
-                        match error.downcast_ref() {
-                            Some(AsyncBufferFullError(_)) => anyhow!("Stream consumer too slow.".to_string()),
-                            _ => anyhow!(error.to_string())
+                let evt = match live.recv().await {
+                    Ok(evt) => evt,
+                    Err(RecvError::Closed) => break,
+                    // Too far behind for the live buffer; whatever was dropped is still in the
+                    // database, so catch up from there and carry on.
+                    Err(RecvError::Lagged(_)) => {
+                        let backfill_stream = self.get_backfill(start).await;
+                        pin_mut!(backfill_stream);
+                        while let Some(evt) = backfill_stream.next().await {
+                            yield evt?;
                         }
-                    })?;
-                    if evt.seq() > self.last_seen {
-                        self.last_seen = evt.seq();
-                        yield evt;
+                        continue;
                     }
+                };
+                if evt.seq() > self.last_seen {
+                    self.last_seen = evt.seq();
+                    yield evt;
                 }
             }
         }
     }
 
+    /// Pages through the events after `backfill_cursor` (or after the last one yielded, if that's
+    /// later) until the database has nothing more.
     pub async fn get_backfill<'a>(
         &'a mut self,
         backfill_cursor: i64,
     ) -> impl Stream<Item = Result<SeqEvt>> + 'a {
         try_stream! {
             loop {
-                let earliest_seq = if self.last_seen > -1 {
-                    Some(self.last_seen)
-                } else {
-                    Some(backfill_cursor)
-                };
-                let evts = match self.sequencer.request_seq_range(RequestSeqRangeOpts {
+                let earliest_seq = Some(self.last_seen.max(backfill_cursor));
+                let evts = self.sequencer.request_seq_range(RequestSeqRangeOpts {
                     earliest_seq,
                     latest_seq: None,
                     earliest_time: None,
                     limit: Some(PAGE_SIZE),
-                }).await {
-                    Ok(res) => res,
-                    Err(_) => break
-                };
-                for evt in evts.iter() {
+                }).await?;
+                let done = (evts.len() as i64) < PAGE_SIZE;
+                for evt in evts {
                     self.last_seen = evt.seq();
-                    yield evt.clone();
-                }
-                let seq_cursor = self.sequencer.last_seen.unwrap_or(-1);
-                if seq_cursor - self.last_seen < (PAGE_SIZE / 2)  {
-                    break;
+                    yield evt;
                 }
-                if evts.is_empty() {
+                if done {
                     break;
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::{EVENT_BUFFER_SIZE, TEST_LOCK};
+    use rsky_pds::common;
+    use rsky_pds::crawlers::Crawlers;
+    use std::time::Duration;
+
+    #[tokio::test]
+    #[ignore = "needs the test database"]
+    async fn lagging_without_a_cursor_catches_up_from_the_head() {
+        let _lock = TEST_LOCK.lock().await;
+        let mut sequencer = Sequencer::new(Crawlers::new("localhost".to_string(), vec![]), None);
+        let did = format!("did:example:{}", common::get_random_str());
+        // Sequenced before the subscription, so it isn't sent
+        let before = sequencer
+            .sequence_identity_evt(did.clone(), None)
+            .await
+            .unwrap();
+        sequencer.last_seen = Some(before);
+
+        let mut outbox = Outbox::new(sequencer.clone());
+        let events = outbox.events(None).await;
+        pin_mut!(events);
+        // The first poll subscribes and then waits for live events
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), events.next())
+                .await
+                .is_err()
+        );
+
+        // One more than the live buffer holds
+        let mut seqs = Vec::new();
+        for _ in 0..=EVENT_BUFFER_SIZE {
+            seqs.push(
+                sequencer
+                    .sequence_identity_evt(did.clone(), None)
+                    .await
+                    .unwrap(),
+            );
+        }
+        while sequencer.poll().await.unwrap() > 0 {}
+        for seq in &seqs {
+            assert_eq!(events.next().await.unwrap().unwrap().seq(), *seq);
+        }
+    }
+}
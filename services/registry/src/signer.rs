@@ -0,0 +1,227 @@
+//! Signing with the server's secp256k1 keys. A key can be held in process, read from a key
+//! file, or left with a separate signer process reached over a Unix socket, so keys like the PLC
+//! rotation key don't have to live on the web host at all.
+//!
+//! The socket protocol is one JSON line each way per connection. The request is
+//! `{"key": "<did:key>", "msg": "<hex>"}`, and the signer answers with
+//! `{"sig": "<hex compact signature>"}` or `{"error": "<reason>"}`. The signature is ECDSA over
+//! the SHA-256 hash of `msg`; it's normalized to low-S and checked against the configured public
+//! key before it's used.
+use crate::api::com::atproto::server::{decode_did_key, encode_did_key};
+use crate::config::{KeyConfig, KeyProviderConfig, SECRET_CONFIG};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use secp256k1::ecdsa::Signature;
+use secp256k1::{Message, PublicKey, SecretKey, SECP256K1};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+/// Signs access and refresh tokens.
+pub static PDS_SIGNER: LazyLock<Arc<dyn Signer>> = LazyLock::new(|| {
+    from_config(&SECRET_CONFIG.pds_private_key).expect("Failed to load pds_private_key")
+});
+/// Signs PLC operations for the DIDs this server manages.
+pub static ROTATION_SIGNER: LazyLock<Arc<dyn Signer>> = LazyLock::new(|| {
+    from_config(&SECRET_CONFIG.pds_rotation_key).expect("Failed to load pds_rotation_key")
+});
+/// Signs commits and service auth for accounts without a signing key of their own.
+pub static REPO_SIGNER: LazyLock<Arc<dyn Signer>> = LazyLock::new(|| {
+    from_config(&SECRET_CONFIG.repo_signing_key).expect("Failed to load repo_signing_key")
+});
+
+/// How long to wait on a remote signer before giving up on a signature.
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[async_trait]
+pub trait Signer: Debug + Send + Sync {
+    fn public_key(&self) -> PublicKey;
+
+    /// Signs the SHA-256 hash of `msg`, returning the compact low-S signature atproto uses.
+    async fn sign(&self, msg: &[u8]) -> Result<[u8; 64]>;
+
+    fn did_key(&self) -> String {
+        encode_did_key(&self.public_key())
+    }
+}
+
+pub fn from_config(config: &KeyConfig) -> Result<Arc<dyn Signer>> {
+    match config {
+        KeyConfig::Hex(private_key) => Ok(Arc::new(LocalSigner::from_hex(private_key)?)),
+        KeyConfig::Provider(KeyProviderConfig::File { path }) => {
+            Ok(Arc::new(LocalSigner::from_file(path)?))
+        }
+        KeyConfig::Provider(KeyProviderConfig::Remote { socket, public_key }) => Ok(Arc::new(
+            RemoteSigner::new(PathBuf::from(socket), decode_did_key(public_key)?),
+        )),
+    }
+}
+
+fn digest(msg: &[u8]) -> Result<Message> {
+    Ok(Message::from_digest_slice(Sha256::digest(msg).as_ref())?)
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalSigner {
+    secret_key: SecretKey,
+}
+
+impl LocalSigner {
+    pub fn new(secret_key: SecretKey) -> Self {
+        LocalSigner { secret_key }
+    }
+
+    /// Errors don't include `private_key`, as they end up in logs.
+    pub fn from_hex(private_key: &str) -> Result<Self> {
+        let bytes = hex::decode(private_key.trim().as_bytes())
+            .map_err(|_| anyhow!("Private key isn't valid hex"))?;
+        let secret_key = SecretKey::from_slice(&bytes)
+            .map_err(|_| anyhow!("Private key isn't a valid secp256k1 key"))?;
+        Ok(Self::new(secret_key))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let private_key = std::fs::read_to_string(path)
+            .map_err(|error| anyhow!("Failed to read key file `{path}`: {error}"))?;
+        Self::from_hex(&private_key).map_err(|error| anyhow!("{error} in `{path}`"))
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key_global(&self.secret_key)
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<[u8; 64]> {
+        let mut sig = SECP256K1.sign_ecdsa(&digest(msg)?, &self.secret_key);
+        sig.normalize_s();
+        Ok(sig.serialize_compact())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SignRequest {
+    key: String,
+    msg: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignResponse {
+    sig: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    socket: PathBuf,
+    public_key: PublicKey,
+}
+
+impl RemoteSigner {
+    pub fn new(socket: PathBuf, public_key: PublicKey) -> Self {
+        RemoteSigner { socket, public_key }
+    }
+
+    async fn request(&self, request: &SignRequest) -> Result<SignResponse> {
+        let mut stream = UnixStream::connect(&self.socket).await.map_err(|error| {
+            anyhow!(
+                "Failed to reach signer at `{0}`: {error}",
+                self.socket.display()
+            )
+        })?;
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        stream.write_all(&line).await?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).await?;
+        Ok(serde_json::from_str(&response)?)
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<[u8; 64]> {
+        let request = SignRequest {
+            key: self.did_key(),
+            msg: hex::encode(msg),
+        };
+        let response = tokio::time::timeout(REMOTE_SIGNER_TIMEOUT, self.request(&request))
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for signer `{}`", request.key))??;
+        let sig = match response {
+            SignResponse { sig: Some(sig), .. } => sig,
+            SignResponse {
+                error: Some(error), ..
+            } => bail!("Signer refused to sign with `{0}`: {error}", request.key),
+            _ => bail!("Signer sent neither a signature nor an error"),
+        };
+        let mut sig = Signature::from_compact(&hex::decode(sig.as_bytes())?)?;
+        sig.normalize_s();
+        // Catches a socket that's serving some other key before anything signed with it
+        // gets published
+        SECP256K1
+            .verify_ecdsa(&digest(msg)?, &sig, &self.public_key)
+            .map_err(|_| anyhow!("Signer returned a bad signature for `{}`", request.key))?;
+        Ok(sig.serialize_compact())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_pds::common::get_random_str;
+    use tokio::net::UnixListener;
+
+    /// Answers sign requests with `signer` the way a real signer process would.
+    async fn serve(listener: UnixListener, signer: LocalSigner) {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut line = String::new();
+            BufReader::new(reader).read_line(&mut line).await.unwrap();
+            let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+            let response = if request["key"] == signer.did_key() {
+                let msg = hex::decode(request["msg"].as_str().unwrap()).unwrap();
+                let sig = signer.sign(&msg).await.unwrap();
+                serde_json::json!({ "sig": hex::encode(sig) })
+            } else {
+                serde_json::json!({ "error": "Unknown key" })
+            };
+            writer
+                .write_all(format!("{response}\n").as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn signs_through_remote_signer() {
+        let socket = std::env::temp_dir().join(format!("registry-signer-{}", get_random_str()));
+        let stub = LocalSigner::new(SecretKey::new(&mut rand::thread_rng()));
+        let public_key = stub.public_key();
+        tokio::spawn(serve(UnixListener::bind(&socket).unwrap(), stub));
+
+        let remote = RemoteSigner::new(socket.clone(), public_key);
+        let sig = remote.sign(b"hello").await.unwrap();
+        let sig = Signature::from_compact(&sig).unwrap();
+        assert!(SECP256K1
+            .verify_ecdsa(&digest(b"hello").unwrap(), &sig, &public_key)
+            .is_ok());
+
+        let other_key = PublicKey::from_secret_key_global(&SecretKey::new(&mut rand::thread_rng()));
+        let misconfigured = RemoteSigner::new(socket.clone(), other_key);
+        assert!(misconfigured.sign(b"hello").await.is_err());
+        std::fs::remove_file(socket).unwrap();
+    }
+}
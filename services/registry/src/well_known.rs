@@ -4,10 +4,13 @@
  * License: https://github.com/blacksky-algorithms/rsky/blob/main/LICENSE
  */
 use crate::account_manager::AccountManager;
+use crate::oauth;
 use rocket::request::{FromRequest, Outcome};
 use rocket::response::status;
 use rocket::Request;
 use rocket::http::Status;
+use rocket::serde::json::Json;
+use serde_json::Value;
 use anyhow::Result;
 
 use crate::config::IDENTITY_CONFIG;
@@ -63,6 +66,16 @@ async fn did(
     }
 }
 
+#[get("/oauth-protected-resource")]
+async fn oauth_protected_resource() -> Json<Value> {
+    Json(oauth::protected_resource_metadata())
+}
+
+#[get("/oauth-authorization-server")]
+async fn oauth_authorization_server() -> Json<Value> {
+    Json(oauth::authorization_server_metadata())
+}
+
 pub fn routes() -> Vec<rocket::Route> {
-    routes![did]
+    routes![did, oauth_protected_resource, oauth_authorization_server]
 }
\ No newline at end of file